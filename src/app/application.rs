@@ -1,31 +1,88 @@
-use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
 
 use kea;
 use kea::Ptr;
 use kea::comm::{channel, duplex, Duplex, Sender};
+use kea::lsp::{EventCallback, Location, LspEvent, Position, Response, TextEdit};
 use log::{debug, error, info};
+use ropey::Rope;
 
 // use crate::euclid::{default::Vector2D, vec2};
-use crate::core::{self, Core, Edit, KeaCore, Update};
+use crate::core::{
+    self, BufferId, Core, Edit, FileChange, FileWatcher, KeaCore, Resolution, SwapFile,
+    SwapFiles, Update,
+};
 use crate::font::{Font, FontCollection};
 use crate::glutin::{
     event::{KeyboardInput, VirtualKeyCode, ModifiersState, ElementState},
     event_loop::EventLoop,
     PossiblyCurrent,
 };
+use crate::pathfinder_geometry::rect::RectF;
 use crate::pathfinder_geometry::vector::{vec2f, Vector2F};
+use crate::terminal::{self, Terminal, TerminalEvent};
 use crate::renderer::{
-    window::LogicalSize, Color, Glyph, Rect, RenderContext, Renderable, Renderer, TextLine, Window,
+    window::LogicalSize, Color, Glyph, Rect, RenderContext, Renderable, Renderer, TextLine, Theme,
+    Window,
 };
+use crate::ui::completion::{CompletionItem, CompletionKind, CompletionPopup};
+use crate::ui::key_bindings::{BindingCollection, Key};
 use crate::ui::*;
 
+use super::clipboard::{ClipboardState, ClipboardType, SystemClipboard};
+use super::commands::{self, CommandCallback, CommandResult, CommandTarget, Commands, Outcome};
+use super::completion::{
+    is_word_char, BufferWords, Completer, CompletionQuery, CompletionSource, FilePaths, Snippets,
+};
+use super::diff_view::{self, DiffView};
+use super::event_handler::ClickState;
+use super::language_servers::{LanguageServers, Request};
+use super::git::{self, Git, GitCallback, GitResult};
+use super::linters::{LintCallback, LintResult, Linters};
+use super::session::{FrameState, LayoutNode, Session};
+use super::snippets::{self, SnippetSession, Variables};
+use crate::ui::registers::Register;
 use super::{AppError, AppEvent, Config};
 use crate::renderer::window::event::WindowEvent::{CursorEntered, CursorMoved};
 use std::path::Component::CurDir;
 
+/// how many points the font size changes for each zoom step.
+const ZOOM_STEP: f32 = 1.0;
+pub(super) const MIN_FONT_SIZE: f32 = 4.0;
+pub(super) const MAX_FONT_SIZE: f32 = 96.0;
+/// the source of the diagnostics of language servers.
+const LSP_SOURCE: &str = "lsp";
+/// completions are requested once typing pauses for this long.
+const COMPLETION_DELAY: Duration = Duration::from_millis(150);
+/// how long the word before the cursor has to be before completions are shown while typing.
+const COMPLETION_MIN_PREFIX: usize = 2;
+/// the part of the height of the window the terminal takes.
+const TERMINAL_HEIGHT: f32 = 0.35;
+
+/// reports the output of the terminal shown in a frame.
+pub type TerminalEvents = Arc<dyn Fn(FrameId, TerminalEvent) + Send + Sync>;
+
+/// the font size after a zoom operation, clamped to the sizes allowed. None if the operation
+/// doesn't zoom.
+pub(super) fn zoomed_font_size(
+    operation: Operation,
+    font_size: f32,
+    config: &Config,
+) -> Option<f32> {
+    let font_size = match operation {
+        Operation::ZoomIn => font_size + ZOOM_STEP,
+        Operation::ZoomOut => font_size - ZOOM_STEP,
+        Operation::ZoomReset => config.font_size(),
+        _ => return None,
+    };
+    Some(font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE))
+}
+
 pub struct App(Arc<Mutex<Application>>);
 
 impl App {
@@ -53,15 +110,48 @@ impl WeakApp {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum EditMode {
     Normal,
     Insert,
 }
 
+/// A question for the user, answered with a prompt at the bottom of the window.
+enum Question {
+    /// the file of a buffer changed on disk while the buffer had unsaved changes.
+    FileChanged(BufferId, Prompt<Resolution>),
+    /// a swap file with unsaved changes was left behind.
+    Recover(SwapFile, Prompt<bool>),
+    /// the new name of the symbol at a position, typed until enter is pressed.
+    Rename {
+        path: PathBuf,
+        position: Position,
+        name: String,
+    },
+    /// a shell command, typed until enter is pressed. A filter is given the selection.
+    Command { command: String, filter: bool },
+}
+
+impl Question {
+    fn text(&self) -> String {
+        match self {
+            Question::FileChanged(_, prompt) => prompt.text(),
+            Question::Recover(_, prompt) => prompt.text(),
+            Question::Rename { name, .. } => format!("Rename to: {}", name),
+            Question::Command { command, filter } => {
+                format!("{}{}", if *filter { "|" } else { "!" }, command)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct EditState {
     mode: EditMode,
+    /// the register of the next yank, delete or put in normal mode.
+    register: Option<Register>,
+    /// `"` was typed, the next character names a register.
+    awaiting_register: bool,
 }
 
 pub struct Application {
@@ -87,6 +177,57 @@ pub struct Application {
     context: RenderContext,
     /// state of the editor
     state: EditState,
+    /// maps keys to operations.
+    bindings: BindingCollection,
+    /// the font size or dpi changed, the glyphs need to be rasterized again.
+    glyphs_invalidated: bool,
+    /// a selection is being dragged with the mouse in the active frame.
+    dragging: bool,
+    /// the system clipboard and the registers.
+    clipboard: ClipboardState,
+    /// questions waiting for an answer, the first one is prompted.
+    questions: VecDeque<Question>,
+    /// the file given with `--config`, the last layer of the config.
+    config_file: Option<PathBuf>,
+    /// reports changes to the files of the config and the theme.
+    config_watcher: Option<FileWatcher>,
+    /// shown at the bottom of the window until the next key is pressed, e.g. a config error.
+    message: Option<String>,
+    /// the language servers of the languages of the buffers.
+    language_servers: LanguageServers,
+    /// the linters of the languages of the buffers.
+    linters: Linters,
+    /// the frame the first file of the files being dropped was opened in, the other files
+    /// are opened in frames of their own.
+    drop_frame: Option<FrameId>,
+    /// the scratch buffer of the last list of diagnostics or locations, the next list
+    /// replaces its text while it is open.
+    list_buffer: Option<BufferId>,
+    /// computes how the buffers differ from their files in git.
+    git: Git,
+    /// collects the completions of the word before the cursor in the background.
+    completer: Option<Completer>,
+    /// the completions shown at the cursor of the active frame.
+    completion: Option<CompletionPopup>,
+    /// when completions are requested, typing moves it back.
+    completion_due: Option<Instant>,
+    /// the id of the latest completion query, the results of older queries are ignored.
+    completion_query: u64,
+    /// the completion request sent to a language server.
+    lsp_completion: Option<u64>,
+    /// the revisions of the buffers when a rename or a format was requested, by request. The
+    /// edits of buffers edited since are dropped.
+    edit_requests: HashMap<u64, Vec<(BufferId, usize)>>,
+    /// the tab stops of the snippet being filled.
+    snippet: Option<SnippetSession>,
+    /// the two frames of a diff, shown side by side.
+    diff: Option<DiffView>,
+    /// the frame of the terminal, shown below the other frames.
+    terminal: Option<FrameId>,
+    /// reports the output of terminals, no terminal is opened without it.
+    on_terminal_event: Option<TerminalEvents>,
+    /// runs the shell commands typed by the user.
+    commands: Commands,
 }
 
 impl Application {
@@ -100,7 +241,26 @@ impl Application {
         let core = Core::new();
 
         let font_size = config.font_size();
+        let ligatures = config.ligatures();
         let dpi_factor = window.dpi_factor();
+        let bindings = BindingCollection::new(&config);
+
+        let dpi_factor = dpi_factor as f32;
+        let mut context = RenderContext::new(font_collection, font_size, dpi_factor, ligatures);
+        context.set_tab_width(config.tab_width());
+        match config.load_theme() {
+            Ok(theme) => context.set_theme(theme),
+            Err(e) => error!("{}", e),
+        }
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let wayland_display = {
+            use glutin::platform::unix::WindowExtUnix;
+            window.window().wayland_display()
+        };
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        let wayland_display = None;
+        let clipboard = ClipboardState::new(Box::new(SystemClipboard::new(wayland_display)));
 
         Ok(Self {
             // renderer: context,
@@ -112,122 +272,2426 @@ impl Application {
             layout: FrameLayout::new(),
             active_frame: None,
             core,
-            context: RenderContext::new(font_collection, font_size, dpi_factor as f32),
+            context,
             state: EditState {
                 mode: EditMode::Normal,
+                register: None,
+                awaiting_register: false,
             },
+            bindings,
+            glyphs_invalidated: false,
+            dragging: false,
+            clipboard,
+            questions: VecDeque::new(),
+            config_file: None,
+            config_watcher: None,
+            message: None,
+            language_servers: LanguageServers::new(),
+            linters: Linters::new(),
+            drop_frame: None,
+            list_buffer: None,
+            git: Git::new(),
+            completer: None,
+            completion: None,
+            completion_due: None,
+            completion_query: 0,
+            lsp_completion: None,
+            edit_requests: HashMap::new(),
+            snippet: None,
+            diff: None,
+            terminal: None,
+            on_terminal_event: None,
+            commands: Commands::new(),
         })
     }
 
-    pub fn update_size(&mut self, width: u32, height: u32) {
-        self.draw_requested = true;
+    /// reports changes to the files of the config and its theme as `AppEvent::ConfigChanged`.
+    pub fn watch_config(&mut self, watcher: FileWatcher, config_file: Option<PathBuf>) {
+        self.config_file = config_file;
+        self.config_watcher = Some(watcher);
+        let files = Config::files(self.config_file.as_deref());
+        for path in files.iter().chain(self.config.theme_file().iter()) {
+            self.watch_config_file(path);
+        }
     }
 
-    pub fn draw_requested(&self) -> bool {
-        self.draw_requested
+    fn watch_config_file(&mut self, path: &Path) {
+        if let Some(watcher) = self.config_watcher.as_mut() {
+            // the config directory of the user may not exist.
+            if let Err(e) = watcher.watch(path) {
+                debug!("{}", e);
+            }
+        }
     }
 
-    pub fn swap_buffer(&self) {
-        self.window.swap_buffers()
+    /// loads the config again after one of its files changed. A config with an error is
+    /// reported and the previous config is kept.
+    pub fn reload_config(&mut self) {
+        let (config, errors) = Config::load_config(self.config_file.clone());
+        if let Some(e) = errors.first() {
+            for e in errors.iter() {
+                error!("{}", e);
+            }
+            self.show_message(e.to_string());
+            return;
+        }
+
+        let theme = match config.load_theme() {
+            Ok(theme) => theme,
+            Err(e) => {
+                error!("{}", e);
+                self.show_message(e.to_string());
+                return;
+            }
+        };
+
+        let fonts = if config.font_name() != self.config.font_name() {
+            let mut fonts = match FontCollection::new(self.context.dpi_factor()) {
+                Ok(fonts) => fonts,
+                Err(e) => {
+                    error!("{}", e);
+                    self.show_message(format!("fonts: {}", e));
+                    return;
+                }
+            };
+            match fonts.add_font_by_name(config.font_name()) {
+                Ok(_) => Some(fonts),
+                Err(e) => {
+                    error!("{}", e);
+                    self.show_message(format!("font '{}': {}", config.font_name(), e));
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+
+        info!("Reloaded Config: {:#?}", config);
+        self.apply_config(config, theme, fonts);
     }
 
-    pub fn on_init(&mut self) {
-        let window_size = self.window.get_size();
-        let size = vec2f(window_size.width as f32, window_size.height as f32);
-        let origin = Vector2F::zero();
+    /// applies the settings of a config that changed, the buffers and frames are kept.
+    fn apply_config(&mut self, config: Config, theme: Theme, fonts: Option<FontCollection>) {
+        if let Some(fonts) = fonts {
+            self.context.set_fonts(fonts);
+        }
+        if config.ligatures() != self.config.ligatures() {
+            self.context.set_ligatures(config.ligatures());
+        }
+        if let Some(path) = config.theme_file() {
+            self.watch_config_file(&path);
+        }
+        self.context.set_theme(theme);
+        self.context.set_tab_width(config.tab_width());
+        self.bindings = BindingCollection::new(&config);
+        for frame in self.frames.values_mut() {
+            frame.set_options(config.frame_options());
+        }
 
-        let metrics = self
-            .context
-            .fonts()
-            .default_font()
-            .metrics()
-            .scale_with(self.config.font_size(), self.window.dpi_factor() as f32);
+        // a zoomed font size is kept unless the size of the config changed.
+        let font_size = if config.font_size() != self.config.font_size() {
+            config.font_size()
+        } else {
+            self.context.font_size()
+        };
+        self.config = config;
+        self.message = None;
+        self.context.set_font_size(font_size);
+        self.glyphs_invalidated = true;
+        let window_size = self.window.get_size();
+        self.update_size(window_size.width, window_size.height);
+    }
 
-        let buffer_id = self
-            .core
-            .open_file(core::BufferInfo {
-                path: Some(PathBuf::from_str("src/main.rs").unwrap()),
-            })
-            .unwrap();
-        let buffer = self.core.get_buffer_ptr(&buffer_id).unwrap();
+    fn show_message(&mut self, message: String) {
+        self.message = Some(message);
+        self.draw_requested = true;
+    }
 
-        let lines = Frame::compute_lines(size.y(), &metrics);
-        let mut frame = Frame::new(buffer, size, origin, lines);
-        frame.update_line_cache(Invalidation::Init);
+    /// reports how shell commands ended, commands aren't run without it.
+    pub fn start_commands(&mut self, on_result: CommandCallback) {
+        self.commands.set_callback(on_result);
+    }
 
-        frame.set_active(true);
+    /// reports what language servers send through on_event, servers aren't started without it.
+    pub fn start_language_servers(&mut self, on_event: EventCallback) {
+        self.language_servers.set_callback(on_event);
+    }
 
-        let frame_id = frame.id();
+    pub fn start_linters(&mut self, on_result: LintCallback) {
+        self.linters.set_callback(on_result);
+    }
 
-        self.active_frame = Some(frame_id);
-        self.frames.insert(frame_id, frame);
-        self.layout.push_frame(FrameInfo { frame: frame_id });
+    /// lints new buffers, called after every event.
+    pub fn sync_linters(&mut self) {
+        self.linters.sync(&self.core, &self.config);
     }
 
-    pub fn handle_keyboard_input(&mut self, input: KeyboardInput, modifiers: &ModifiersState) {
-        // let edit_mode = self.state.mode;
-        // if let Some(operation) =
-        debug!(
-            "Keyboard Input: {:?} Modifier State: {:?}",
-            input, modifiers
-        );
+    pub fn start_git(&mut self, on_result: GitCallback) {
+        self.git.set_callback(on_result);
+    }
 
+    /// computes the changes against git of the edited buffers, called after every event.
+    pub fn sync_git(&mut self) {
+        self.git.sync(&self.core);
+    }
 
-		// for now I do not want to handle release.
-		if input.state == ElementState::Released {
-    		return;
-		}
+    /// sends the changes of the buffers to their language servers, called after every event.
+    pub fn sync_language_servers(&mut self) {
+        for e in self.language_servers.sync(&self.core, &self.config) {
+            error!("{}", e);
+            self.show_message(e.to_string());
+        }
+    }
 
-        if let Some(key) = input.virtual_keycode {
-            match key {
-                VirtualKeyCode::Up => {
-                    if let Some(frame) = self.active_frame_mut() {
+    pub fn handle_lsp_event(&mut self, event: LspEvent) {
+        match event {
+            LspEvent::Diagnostics { path, diagnostics } => {
+                for id in self.core.buffers_with_path(&path) {
+                    if let Some(mut buffer) = self.core.get_buffer_mut(&id) {
+                        let diagnostics = diagnostics
+                            .iter()
+                            .map(|diagnostic| core::Diagnostic {
+                                range: buffer.lsp_char_index(diagnostic.range.start)
+                                    ..buffer.lsp_char_index(diagnostic.range.end),
+                                severity: diagnostic.severity,
+                                message: diagnostic.message.clone(),
+                                source: LSP_SOURCE.to_string(),
+                            })
+                            .collect();
+                        buffer.set_diagnostics(LSP_SOURCE, diagnostics);
                     }
+                    self.buffer_reloaded(id);
                 }
-                VirtualKeyCode::Down => {
-                    println!("Handling Down");
-                    if let Some(frame) = self.active_frame_mut() {
-                    }
+            }
+            LspEvent::Response { id, response } => {
+                if let Some(request) = self.language_servers.take_request(id) {
+                    self.handle_lsp_response(id, request, response);
                 }
-                VirtualKeyCode::Left => {
-                    if let Some(frame) = self.active_frame_mut() {
-                    }
+            }
+            LspEvent::Message(message) => self.show_message(message),
+            LspEvent::Exited(language) => {
+                self.language_servers.exited(&language);
+                self.show_message(format!("the {} language server exited", language));
+            }
+        }
+    }
+
+    fn handle_lsp_response(&mut self, id: u64, request: Request, response: Response) {
+        match response {
+            Response::Hover(text) => {
+                let text = text.unwrap_or_default();
+                // the documentation is shown on one line, after the diagnostics of the line.
+                let text = self
+                    .cursor_diagnostics()
+                    .into_iter()
+                    .chain(text.lines().map(str::trim).map(String::from))
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                if !text.is_empty() {
+                    self.show_message(text);
                 }
-                VirtualKeyCode::Right => {
-                    if let Some(frame) = self.active_frame_mut() {
-                    }
+            }
+            Response::Locations(locations) => match locations.as_slice() {
+                [] => self.show_message("no locations found".to_string()),
+                [location] => self.goto_location(location),
+                _ if request == Request::Definition => self.goto_location(&locations[0]),
+                _ => self.show_locations(&locations),
+            },
+            Response::Edits(files) => {
+                let revisions = self.edit_requests.remove(&id).unwrap_or_default();
+                for (path, edits) in files {
+                    self.apply_edits(&path, edits, &revisions);
+                }
+            }
+            Response::Completions(completions) => {
+                if self.lsp_completion != Some(id) {
+                    return;
                 }
-                _ => {}
+                self.lsp_completion = None;
+                let items = completions
+                    .into_iter()
+                    .map(|completion| {
+                        let mut item =
+                            CompletionItem::new(completion.label, CompletionKind::Lsp)
+                                .with_text(completion.text);
+                        if let Some(detail) = completion.detail {
+                            item = item.with_detail(detail);
+                        }
+                        if let Some(range) = completion.range {
+                            item = item.with_range(range);
+                        }
+                        item
+                    })
+                    .collect();
+                self.add_completions(items);
+            }
+            Response::Failed(message) => {
+                error!("{:?} failed: {}", request, message);
+                self.show_message(message);
             }
         }
     }
 
-    pub fn active_frame(&self) -> Option<&Frame> {
-        if let Some(id) = self.active_frame.as_ref() {
-            self.frames.get(id)
+    /// the path and position of the primary cursor of the active frame.
+    fn cursor_position(&self) -> Option<(PathBuf, Position)> {
+        let frame = self.active_frame()?;
+        let buffer = frame.buffer();
+        let cursor = frame.cursor();
+        let char_idx = buffer.char_index(cursor.line(), cursor.column());
+        Some((buffer.path()?.to_path_buf(), buffer.lsp_position(char_idx)))
+    }
+
+    /// the messages of the diagnostics on the line of the cursor.
+    fn cursor_diagnostics(&self) -> Vec<String> {
+        let frame = match self.active_frame() {
+            Some(frame) => frame,
+            None => return Vec::new(),
+        };
+        let buffer = frame.buffer();
+        let line = frame.cursor().line();
+        let start = buffer.char_index(line, 0);
+        let end = start + buffer.line_len(line);
+        buffer
+            .diagnostics()
+            .overlapping(start..end)
+            .map(|diagnostic| format!("{:?}: {}", diagnostic.severity, diagnostic.message))
+            .collect()
+    }
+
+    /// the diagnostics of the linter of a file replace its earlier diagnostics. The linter
+    /// checks the file on disk, so they are dropped for buffers with unsaved changes.
+    pub fn handle_lint_result(&mut self, result: LintResult) {
+        for id in self.core.buffers_with_path(&result.path) {
+            if let Some(mut buffer) = self.core.get_buffer_mut(&id) {
+                if buffer.is_dirty() {
+                    continue;
+                }
+                let diagnostics = result
+                    .lints
+                    .iter()
+                    .map(|lint| {
+                        let char_idx = buffer.char_index(lint.line, lint.column);
+                        core::Diagnostic {
+                            range: char_idx..char_idx,
+                            severity: lint.severity,
+                            message: lint.message.clone(),
+                            source: result.linter.clone(),
+                        }
+                    })
+                    .collect();
+                buffer.set_diagnostics(&result.linter, diagnostics);
+            }
+            self.buffer_reloaded(id);
+        }
+    }
+
+    /// the changes of a buffer computed in the background, they are dropped if the buffer was
+    /// edited since and computed again.
+    pub fn handle_git_result(&mut self, result: GitResult) {
+        self.git.finished(&result);
+        match self.core.get_buffer_mut(&result.buffer) {
+            Some(mut buffer) if buffer.revision() == result.revision => {
+                buffer.set_git_changes(result.changes)
+            }
+            _ => return,
+        }
+        for frame in self.frames.values_mut() {
+            if frame.buffer_id() == result.buffer {
+                frame.update_line_cache(Invalidation::Init);
+            }
+        }
+        self.draw_requested = true;
+    }
+
+    /// moves the cursor of the active frame to the next or previous changed lines of its
+    /// buffer.
+    fn goto_hunk(&mut self, next: bool) {
+        let frame = match self.active_frame_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let found = {
+            let buffer = frame.buffer();
+            let changes = buffer.git_changes();
+            let line = frame.cursor().line();
+            if next {
+                changes.next(line)
+            } else {
+                changes.previous(line)
+            }
+        };
+        match found {
+            Some(line) => frame.place_cursor(line, 0, false),
+            None => self.show_message("no changes".to_string()),
+        }
+    }
+
+    /// the buffer of the active frame, its path and text and the line of the primary cursor.
+    fn cursor_line(&self) -> Option<(BufferId, PathBuf, String, usize)> {
+        let frame = self.active_frame()?;
+        let buffer = frame.buffer();
+        let path = core::absolute(buffer.path()?);
+        Some((buffer.id(), path, buffer.content().to_string(), frame.cursor().line()))
+    }
+
+    /// writes the changed lines at the cursor of the active frame to the index.
+    fn stage_hunk(&mut self) {
+        let (id, path, text, line) = match self.cursor_line() {
+            Some(target) => target,
+            None => return,
+        };
+        match git::stage_hunk(&path, &text, line) {
+            Ok(true) => {
+                self.git.refresh(id);
+                self.show_message("staged the hunk".to_string());
+            }
+            Ok(false) => self.show_message("no changes at the cursor".to_string()),
+            Err(e) => self.show_message(e.to_string()),
+        }
+    }
+
+    /// replaces the changed lines at the cursor of the active frame with the lines of the
+    /// index, the revert can be undone.
+    fn revert_hunk(&mut self) {
+        let (id, path, text, line) = match self.cursor_line() {
+            Some(target) => target,
+            None => return,
+        };
+        let (range, old) = match git::revert_hunk(&path, &text, line) {
+            Ok(Some(hunk)) => hunk,
+            Ok(None) => return self.show_message("no changes at the cursor".to_string()),
+            Err(e) => return self.show_message(e.to_string()),
+        };
+        let position = match self.core.get_buffer_mut(&id) {
+            Some(mut buffer) => {
+                buffer.begin_edit();
+                buffer.remove(range.clone());
+                buffer.insert(range.start, &old);
+                buffer.end_edit();
+                buffer.position(range.start)
+            }
+            None => return,
+        };
+        self.buffer_reloaded(id);
+        if let Some(frame) = self.active_frame_mut() {
+            frame.place_cursor(position.0, position.1, false);
+        }
+    }
+
+    /// compares the buffer of the active frame with another buffer side by side. The other
+    /// buffer is the one of another frame, or else the buffer opened last.
+    fn diff_buffers(&mut self) {
+        let active = match self.active_frame() {
+            Some(frame) => frame.buffer_id(),
+            None => return,
+        };
+        let shown = self
+            .layout
+            .frame_iter()
+            .filter_map(|info| self.frames.get(&info.frame))
+            .map(Frame::buffer_id)
+            .find(|id| *id != active);
+        let last = self.core.buffer_ids().into_iter().rev().find(|id| *id != active);
+        match shown.or(last) {
+            Some(other) => self.open_diff(other, None),
+            None => self.show_message("no other buffer to compare with".to_string()),
+        }
+    }
+
+    /// compares the buffer of the active frame with its file on disk side by side.
+    fn diff_disk(&mut self) {
+        let disk = match self.active_frame() {
+            Some(frame) => frame.buffer().read_disk(),
+            None => return,
+        };
+        match disk {
+            Ok(disk) => {
+                let disk = self.core.scratch(&disk.to_string());
+                self.open_diff(disk, Some(disk));
+            }
+            Err(e) => self.show_message(e.to_string()),
+        }
+    }
+
+    /// shows a buffer in a new frame on the left of the active frame, the differences between
+    /// them are marked.
+    fn open_diff(&mut self, other: BufferId, disk: Option<BufferId>) {
+        let right = match self.active_frame {
+            Some(id) => id,
+            None => return,
+        };
+        let left = self.add_frame(other);
+        if let Some(frame) = self.frames.get_mut(&left) {
+            frame.set_read_only(disk.is_some());
+        }
+        self.layout.push_frame(FrameInfo { frame: left });
+        self.diff = Some(DiffView::new(left, right, disk));
+        let size = self.window.get_size();
+        self.resize_frames(vec2f(size.width as f32, size.height as f32));
+        self.sync_diff();
+    }
+
+    /// closes the frame added for a diff, the other frame fills the window again.
+    fn close_diff(&mut self) {
+        let diff = match self.diff.take() {
+            Some(diff) => diff,
+            None => return,
+        };
+        self.layout.remove_frame(diff.left);
+        self.frames.remove(&diff.left);
+        if let Some(disk) = diff.disk {
+            self.core.remove_buffer(&disk);
+        }
+        if self.active_frame == Some(diff.left) {
+            self.active_frame = None;
+            self.focus_frame(diff.right);
+        }
+        if let Some(frame) = self.frames.get_mut(&diff.right) {
+            frame.set_diff(None);
+        }
+        let size = self.window.get_size();
+        self.resize_frames(vec2f(size.width as f32, size.height as f32));
+        self.draw_requested = true;
+    }
+
+    /// keeps the diff up to date with its buffers and both of its frames scrolled together.
+    pub fn sync_diff(&mut self) {
+        let changed = match self.diff.as_mut() {
+            Some(diff) => diff.sync(&mut self.frames),
+            None => return,
+        };
+        match changed {
+            Some(changed) => self.draw_requested |= changed,
+            None => self.close_diff(),
+        }
+    }
+
+    /// the diff and whether the active frame is its left side, None if the active frame isn't a
+    /// side of a diff.
+    fn diff_side(&self) -> Option<(&DiffView, bool)> {
+        let diff = self.diff.as_ref()?;
+        let active = self.active_frame?;
+        if active == diff.left || active == diff.right {
+            Some((diff, active == diff.left))
         } else {
-            info!("No Active Frame");
             None
         }
     }
 
-    pub fn active_frame_mut(&mut self) -> Option<&mut Frame> {
-        if let Some(id) = self.active_frame.as_ref() {
-            self.frames.get_mut(id)
+    /// moves the cursor of the active side of a diff to the next or previous hunk.
+    fn goto_diff_hunk(&mut self, next: bool) {
+        let found = match (self.diff_side(), self.active_frame()) {
+            (Some((diff, left)), Some(frame)) => {
+                diff.goto_hunk(frame.cursor().line(), left, next)
+            }
+            _ => return,
+        };
+        match found {
+            Some(line) => {
+                if let Some(frame) = self.active_frame_mut() {
+                    let last_line = frame.buffer().num_lines().saturating_sub(1);
+                    frame.place_cursor(line.min(last_line), 0, false);
+                }
+            }
+            None => self.show_message("no more differences".to_string()),
+        }
+    }
+
+    /// replaces the lines of the hunk at the cursor of the active side of a diff with the lines
+    /// of the other side, or the lines of the other side with the lines of the active side.
+    fn copy_diff_hunk(&mut self, to_active: bool) {
+        let (hunk, left, active, other) = match (self.diff_side(), self.active_frame) {
+            (Some((diff, left)), Some(active)) => {
+                let line = self.frames[&active].cursor().line();
+                match diff.hunk_at(line, left) {
+                    Some(hunk) => (hunk.clone(), left, active, diff.other(active)),
+                    None => return self.show_message("no difference at the cursor".to_string()),
+                }
+            }
+            _ => return,
+        };
+        let (from, to) = if to_active { (other, active) } else { (active, other) };
+        // the old lines of the hunk are on the left.
+        let lines = |frame: FrameId| {
+            if (frame == active) == left {
+                hunk.old.clone()
+            } else {
+                hunk.new.clone()
+            }
+        };
+
+        let text = {
+            let buffer = self.frames[&from].buffer();
+            buffer.text(diff_view::line_chars(&buffer, &lines(from)))
+        };
+        let id = {
+            if self.frames[&to].is_read_only() {
+                return self.show_message("the file on disk can't be changed".to_string());
+            }
+            let mut buffer = self.frames[&to].buffer_mut();
+            let range = diff_view::line_chars(&buffer, &lines(to));
+            // the last line of a buffer may not end in a line ending.
+            let mut text = text;
+            if range.end < buffer.len_chars() && !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            buffer.begin_edit();
+            buffer.remove(range.clone());
+            buffer.insert(range.start, &text);
+            buffer.end_edit();
+            buffer.id()
+        };
+        self.buffer_reloaded(id);
+    }
+
+    /// opens a terminal below the frames, or moves the focus between it and the frames.
+    fn toggle_terminal(&mut self) {
+        match self.terminal {
+            Some(id) if self.active_frame == Some(id) => {
+                let other = self.drawn_frames().into_iter().rev().find(|frame| *frame != id);
+                if let Some(other) = other {
+                    self.focus_frame(other);
+                }
+            }
+            Some(id) => self.focus_frame(id),
+            None => self.open_terminal(),
+        }
+    }
+
+    /// runs the shell of the user in a frame below the other frames.
+    fn open_terminal(&mut self) {
+        let on_event = match self.on_terminal_event.clone() {
+            Some(on_event) => on_event,
+            None => return,
+        };
+        let buffer = self.core.scratch("");
+        let id = self.add_frame(buffer);
+        let terminal = Terminal::spawn(80, 24, Arc::new(move |event| on_event(id, event)));
+        match (terminal, self.frames.get_mut(&id)) {
+            (Ok(terminal), Some(frame)) => frame.set_terminal(terminal),
+            (Err(e), _) => {
+                self.frames.remove(&id);
+                self.core.remove_buffer(&buffer);
+                return self.show_message(e.to_string());
+            }
+            _ => return,
+        }
+        self.terminal = Some(id);
+        let size = self.window.get_size();
+        self.resize_frames(vec2f(size.width as f32, size.height as f32));
+        self.focus_frame(id);
+    }
+
+    /// closes the terminal, the shell is killed if it's still running.
+    fn close_terminal(&mut self) {
+        let id = match self.terminal.take() {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(frame) = self.frames.remove(&id) {
+            self.core.remove_buffer(&frame.buffer_id());
+        }
+        if self.active_frame == Some(id) {
+            self.active_frame = None;
+            if let Some(other) = self.drawn_frames().last().copied() {
+                self.focus_frame(other);
+            }
+        }
+        let size = self.window.get_size();
+        self.resize_frames(vec2f(size.width as f32, size.height as f32));
+        self.draw_requested = true;
+    }
+
+    /// the output of the shell is shown, the terminal closes when the shell exits.
+    pub fn handle_terminal_event(&mut self, id: FrameId, event: TerminalEvent) {
+        if self.terminal != Some(id) {
+            return;
+        }
+        match event {
+            TerminalEvent::Output(bytes) => {
+                if let Some(terminal) = self.frames.get_mut(&id).and_then(Frame::terminal_mut) {
+                    terminal.feed(&bytes);
+                    self.draw_requested = true;
+                }
+            }
+            TerminalEvent::Exited => self.close_terminal(),
+        }
+    }
+
+    /// the terminal if it's the active frame.
+    fn active_terminal(&mut self) -> Option<&mut Terminal> {
+        match self.active_frame {
+            Some(id) if self.terminal == Some(id) => {
+                self.frames.get_mut(&id).and_then(Frame::terminal_mut)
+            }
+            _ => None,
+        }
+    }
+
+    /// sends a key to the active terminal. Shift with page up and down scrolls through the
+    /// scrollback, ctrl+shift+c copies the selection and ctrl+shift+v pastes. Returns false
+    /// if the key is an operation of the editor instead, e.g. zooming.
+    fn terminal_key(&mut self, key: VirtualKeyCode, modifiers: &ModifiersState) -> bool {
+        let operation = self.bindings.lookup(&Key::new(key, modifiers)).cloned();
+        match operation {
+            Some(Operation::Terminal)
+            | Some(Operation::ZoomIn)
+            | Some(Operation::ZoomOut)
+            | Some(Operation::ZoomReset) => return false,
+            _ => {}
+        }
+        let shift = modifiers.shift();
+        let ctrl_shift = modifiers.ctrl() && shift;
+        let copy = match self.active_terminal() {
+            Some(_) if ctrl_shift && key == VirtualKeyCode::C => true,
+            Some(_) if ctrl_shift && key == VirtualKeyCode::V => false,
+            Some(terminal) => {
+                let rows = terminal.grid().rows() as isize;
+                match key {
+                    VirtualKeyCode::PageUp if shift => terminal.scroll(-rows),
+                    VirtualKeyCode::PageDown if shift => terminal.scroll(rows),
+                    key => {
+                        let application = terminal.application_cursor();
+                        let bytes = terminal::key_bytes(key, modifiers, application);
+                        if let Some(Err(e)) = bytes.map(|bytes| terminal.write(&bytes)) {
+                            error!("{}", e);
+                        }
+                    }
+                }
+                self.draw_requested = true;
+                return true;
+            }
+            None => return false,
+        };
+
+        if copy {
+            let frames = &self.frames;
+            let text = self.terminal.and_then(|id| frames.get(&id)?.selected_text());
+            if let Some(text) = text {
+                self.clipboard
+                    .clipboard_mut()
+                    .store(ClipboardType::Clipboard, text);
+            }
         } else {
-            info!("No Active Frame");
-            None
+            let text = self.clipboard.clipboard_mut().load(ClipboardType::Clipboard);
+            self.paste_in_terminal(&text);
         }
+        true
     }
-}
 
-impl Renderable for Application {
-    fn render(&mut self, renderer: &mut Renderer) {
-        for frame in self.layout.frame_iter() {
-            // println!("{:?}", frame);
-            if let Some(frame) = self.frames.get_mut(&frame.frame) {
-                renderer.render_frame(&self.context, frame);
+    /// sends pasted text to the active terminal.
+    fn paste_in_terminal(&mut self, text: &str) {
+        if let Some(Err(e)) = self.active_terminal().map(|terminal| terminal.paste(text)) {
+            error!("{}", e);
+        }
+    }
+
+    /// sends a typed character to the active terminal, with alt it's prefixed by escape.
+    fn type_in_terminal(&mut self, ch: char, modifiers: &ModifiersState) {
+        // control characters are sent with their keys.
+        if modifiers.ctrl() || modifiers.logo() || ch == '\u{1b}' {
+            return;
+        }
+        let mut bytes = terminal::char_bytes(ch);
+        if modifiers.alt() {
+            bytes.insert(0, 0x1b);
+        }
+        if let Some(Err(e)) = self.active_terminal().map(|terminal| terminal.write(&bytes)) {
+            error!("{}", e);
+        }
+    }
+
+    /// moves the cursor of the active frame to the next or previous diagnostic of its buffer
+    /// and shows its message. The search wraps around the buffer.
+    fn goto_diagnostic(&mut self, next: bool) {
+        let frame = match self.active_frame_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let found = {
+            let buffer = frame.buffer();
+            let cursor = frame.cursor();
+            let char_idx = buffer.char_index(cursor.line(), cursor.column());
+            let diagnostics = buffer.diagnostics();
+            let diagnostic = if next {
+                diagnostics.next(char_idx)
+            } else {
+                diagnostics.previous(char_idx)
+            };
+            diagnostic.map(|diagnostic| {
+                let message = format!("{:?}: {}", diagnostic.severity, diagnostic.message);
+                (buffer.position(diagnostic.range.start), message)
+            })
+        };
+        match found {
+            Some(((line, column), message)) => {
+                frame.place_cursor(line, column, false);
+                self.show_message(message);
+            }
+            None => self.show_message("no diagnostics".to_string()),
+        }
+        self.draw_requested = true;
+    }
+
+    /// lists the diagnostics of the buffers with a file in the list buffer shown in the active
+    /// frame, a line for each diagnostic.
+    fn list_diagnostics(&mut self) {
+        let mut list = String::new();
+        for id in self.core.buffer_ids() {
+            let buffer = match self.core.get_buffer(&id) {
+                Some(buffer) => buffer,
+                None => continue,
+            };
+            let path = match buffer.path() {
+                Some(path) => path,
+                None => continue,
+            };
+            for diagnostic in buffer.diagnostics().iter() {
+                let (line, column) = buffer.position(diagnostic.range.start);
+                list.push_str(&format!(
+                    "{}:{}:{}: {:?}: {}\n",
+                    path.display(),
+                    line + 1,
+                    column + 1,
+                    diagnostic.severity,
+                    diagnostic.message.lines().next().unwrap_or("")
+                ));
+            }
+        }
+        if list.is_empty() {
+            return self.show_message("no diagnostics".to_string());
+        }
+        self.show_list(&list);
+    }
+
+    /// runs the linter of the buffer of the active frame, unless the buffer has unsaved changes.
+    fn lint(&mut self) {
+        let path = match self.active_frame() {
+            Some(frame) if frame.buffer().is_dirty() => {
+                let message = "the buffer has unsaved changes, the linter checks the file";
+                self.show_message(message.to_string());
+                return;
+            }
+            Some(frame) => frame.buffer().path().map(Path::to_path_buf),
+            None => None,
+        };
+        let linted = path.map_or(false, |path| self.linters.lint(&path, &self.config));
+        if !linted {
+            self.show_message("the buffer has no linter".to_string());
+        }
+    }
+
+    /// asks for a shell command to run, or to filter the selection of the active frame
+    /// through.
+    fn ask_command(&mut self, filter: bool) {
+        if let Some(command) = self.commands.running() {
+            let message = format!("'{}' is still running, ctrl+alt+c cancels it", command);
+            return self.show_message(message);
+        }
+        let command = String::new();
+        self.questions.push_front(Question::Command { command, filter });
+        self.draw_requested = true;
+    }
+
+    /// runs a shell command in the background. A filter is given the selection of the active
+    /// frame, or its whole buffer without a selection.
+    fn run_command(&mut self, command: &str, filter: bool) {
+        if filter && self.read_only() {
+            return;
+        }
+        let (input, target) = match self.active_frame().filter(|_| filter) {
+            Some(frame) => {
+                let buffer = frame.buffer();
+                let range = match frame.selection() {
+                    Some((start, end)) => {
+                        buffer.char_index(start.line(), start.column())
+                            ..buffer.char_index(end.line(), end.column())
+                    }
+                    None => 0..buffer.len_chars(),
+                };
+                let target = CommandTarget::Filter {
+                    buffer: buffer.id(),
+                    range: range.clone(),
+                    revision: buffer.revision(),
+                };
+                (Some(buffer.text(range)), target)
+            }
+            None if filter => return,
+            None => (None, CommandTarget::Output),
+        };
+        let timeout = self.config.command_timeout();
+        if self.commands.run(command, input, target, timeout) {
+            self.show_message(format!("running '{}'", command));
+        }
+    }
+
+    /// shows the output of a command that ended, or replaces the text given to a filter with
+    /// it as a single edit. The error of a failed command is shown instead.
+    pub fn handle_command_result(&mut self, result: CommandResult) {
+        let (command, target) = match self.commands.finished(&result) {
+            Some(finished) => finished,
+            None => return,
+        };
+        let output = match result.outcome {
+            Outcome::Success(output) => output,
+            Outcome::Failed(code, stderr) => {
+                let code = code.map_or_else(|| "a signal".to_string(), |code| code.to_string());
+                let message = format!("'{}' exited with {}: {}", command, code, stderr.trim());
+                return self.show_message(message);
+            }
+            Outcome::TimedOut => {
+                let timeout = self.config.command_timeout().as_secs();
+                return self.show_message(format!("'{}' timed out after {}s", command, timeout));
+            }
+            Outcome::Cancelled => return self.show_message(format!("'{}' was cancelled", command)),
+            Outcome::Error(e) => return self.show_message(e),
+        };
+        self.message = None;
+        self.draw_requested = true;
+
+        let (id, range, revision) = match target {
+            CommandTarget::Output => {
+                let buffer_id = self.core.scratch(&output);
+                if let Some(id) = self.active_frame {
+                    self.show_buffer(id, buffer_id);
+                }
+                return;
+            }
+            CommandTarget::Filter {
+                buffer,
+                range,
+                revision,
+            } => (buffer, range, revision),
+        };
+        let position = self.core.get_buffer_mut(&id).map(|mut buffer| {
+            commands::apply_filter(&mut buffer, range, revision, &output)
+                .map(|start| buffer.position(start))
+        });
+        let position = match position {
+            Some(Some(position)) => position,
+            Some(None) => {
+                let message = format!("the buffer was edited while '{}' ran", command);
+                return self.show_message(message);
+            }
+            None => return self.show_message(format!("the buffer of '{}' was closed", command)),
+        };
+        self.buffer_reloaded(id);
+        if let Some(frame) = self.active_frame_mut().filter(|frame| frame.buffer_id() == id) {
+            frame.place_cursor(position.0, position.1, false);
+        }
+    }
+
+    fn language_operation(&mut self, operation: Operation) {
+        let (path, position) = match self.cursor_position() {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        let sent = match operation {
+            Operation::Hover => self.language_servers.request(&path, position, Request::Hover),
+            Operation::GotoDefinition => {
+                self.language_servers
+                    .request(&path, position, Request::Definition)
+            }
+            Operation::FindReferences => {
+                self.language_servers
+                    .request(&path, position, Request::References)
+            }
+            Operation::Format => {
+                let id = self.language_servers.format(&path, self.config.tab_width());
+                self.edits_requested(id)
+            }
+            Operation::Rename => {
+                self.questions.push_front(Question::Rename {
+                    path,
+                    position,
+                    name: String::new(),
+                });
+                self.draw_requested = true;
+                return;
+            }
+            _ => return,
+        };
+        if !sent {
+            self.show_message(format!("no language server for '{}'", path.display()));
+        }
+    }
+
+    /// opens the file of a location in the active frame with the cursor at the location.
+    fn goto_location(&mut self, location: &Location) {
+        let buffer_id = match self.core.buffers_with_path(&location.path).first() {
+            Some(id) => *id,
+            None => match self.core.open_file(core::BufferInfo {
+                path: Some(location.path.clone()),
+            }) {
+                Ok(id) => id,
+                Err(e) => {
+                    error!("{}", e);
+                    self.show_message(e.to_string());
+                    return;
+                }
+            },
+        };
+        let (line, column) = match self.core.get_buffer(&buffer_id) {
+            Some(buffer) => buffer.position(buffer.lsp_char_index(location.range.start)),
+            None => return,
+        };
+
+        if let Some(id) = self.active_frame {
+            self.show_buffer(id, buffer_id);
+        }
+        if let Some(frame) = self.active_frame_mut() {
+            frame.place_cursor(line, column, false);
+        }
+        self.draw_requested = true;
+    }
+
+    /// lists locations in the list buffer shown in the active frame, a line for each location.
+    fn show_locations(&mut self, locations: &[Location]) {
+        let mut list = String::new();
+        for location in locations {
+            let start = location.range.start;
+            list.push_str(&format!(
+                "{}:{}:{}\n",
+                location.path.display(),
+                start.line + 1,
+                start.character + 1
+            ));
+        }
+        self.show_list(&list);
+    }
+
+    /// shows a list in the active frame, in the list buffer if it is still open or else in a
+    /// new scratch buffer.
+    fn show_list(&mut self, list: &str) {
+        let open = self.list_buffer.and_then(|id| {
+            let mut buffer = self.core.get_buffer_mut(&id)?;
+            buffer.reload(Rope::from_str(list));
+            Some(id)
+        });
+        let buffer_id = match open {
+            Some(id) => {
+                self.buffer_reloaded(id);
+                id
             }
+            None => self.core.scratch(list),
+        };
+        self.list_buffer = Some(buffer_id);
+        if let Some(id) = self.active_frame {
+            self.show_buffer(id, buffer_id);
+        }
+    }
+
+    /// remembers the revisions of the buffers the edits of a request are computed for, returns
+    /// whether the request was sent.
+    fn edits_requested(&mut self, id: Option<u64>) -> bool {
+        let id = match id {
+            Some(id) => id,
+            None => return false,
+        };
+        let revisions = self
+            .core
+            .buffer_ids()
+            .into_iter()
+            .filter_map(|buffer| Some((buffer, self.core.get_buffer(&buffer)?.revision())))
+            .collect();
+        self.edit_requests.insert(id, revisions);
+        true
+    }
+
+    /// applies the edits of a language server to the buffers of a file, the file is opened if
+    /// it has no buffer. Buffers edited since the edits were requested are left as they are,
+    /// the ranges of the edits don't fit them anymore.
+    fn apply_edits(
+        &mut self,
+        path: &Path,
+        mut edits: Vec<TextEdit>,
+        revisions: &[(BufferId, usize)],
+    ) {
+        let mut buffer_ids = self.core.buffers_with_path(path);
+        // a file opened only to apply the edits is saved, nothing else shows its changes.
+        let opened = buffer_ids.is_empty();
+        if opened {
+            match self.core.open_file(core::BufferInfo {
+                path: Some(path.to_path_buf()),
+            }) {
+                Ok(id) => buffer_ids.push(id),
+                Err(e) => {
+                    error!("{}", e);
+                    self.show_message(e.to_string());
+                    return;
+                }
+            }
+        }
+
+        // the ranges refer to the text before the edits, the last edit is applied first so
+        // the ranges of the others stay the same.
+        edits.sort_by_key(|edit| edit.range.start);
+        for id in buffer_ids {
+            let requested = revisions.iter().find(|(buffer, _)| *buffer == id);
+            let current = self.core.get_buffer(&id).map(|buffer| buffer.revision());
+            if matches!(requested, Some((_, revision)) if Some(*revision) != current) {
+                self.show_message(format!("'{}' changed, the edits were dropped", path.display()));
+                continue;
+            }
+            let mut saved = Ok(());
+            if let Some(mut buffer) = self.core.get_buffer_mut(&id) {
+                buffer.begin_edit();
+                for edit in edits.iter().rev() {
+                    let start = buffer.lsp_char_index(edit.range.start);
+                    let end = buffer.lsp_char_index(edit.range.end);
+                    buffer.remove(start..end);
+                    buffer.insert(start, &edit.text);
+                }
+                buffer.end_edit();
+                if opened {
+                    saved = buffer.save();
+                }
+            }
+            if let Err(e) = saved {
+                error!("{}", e);
+                self.show_message(format!("'{}' was edited but not saved: {}", path.display(), e));
+            }
+            self.buffer_reloaded(id);
+        }
+    }
+
+    /// lets terminals be opened, the output of their shells is reported through on_event.
+    pub fn start_terminals(&mut self, on_event: TerminalEvents) {
+        self.on_terminal_event = Some(on_event);
+    }
+
+    /// runs the sources of completions, the candidates are reported through on_items.
+    pub fn start_completion<F>(&mut self, on_items: F)
+    where
+        F: Fn(u64, Vec<CompletionItem>) + Send + 'static,
+    {
+        let sources: Vec<Box<dyn CompletionSource>> = vec![
+            Box::new(Snippets::new(Config::snippet_dirs())),
+            Box::new(FilePaths),
+            Box::new(BufferWords),
+        ];
+        self.completer = Some(Completer::new(sources, on_items));
+    }
+
+    /// when completions should be requested, None if they shouldn't.
+    pub fn completion_due(&self) -> Option<Instant> {
+        self.completion_due
+    }
+
+    /// the word before the primary cursor of the active frame with the line and column it
+    /// starts at, and the directory of a path the word is part of.
+    fn word_before_cursor(&self) -> Option<(usize, usize, String, Option<PathBuf>)> {
+        let frame = self.active_frame()?;
+        let cursor = frame.cursor();
+        let (line, column) = (cursor.line(), cursor.column());
+        let buffer = frame.buffer();
+        let line_start = buffer.char_index(line, 0);
+        let before: Vec<char> = buffer.text(line_start..line_start + column).chars().collect();
+
+        let start = before
+            .iter()
+            .rposition(|c| !is_word_char(*c))
+            .map_or(0, |idx| idx + 1);
+        let prefix: String = before[start..].iter().collect();
+        // a path is the text between the last whitespace and the last slash before the word.
+        let token_start = before[..start]
+            .iter()
+            .rposition(|c| c.is_whitespace() || *c == '"' || *c == '\'')
+            .map_or(0, |idx| idx + 1);
+        let token: String = before[token_start..start].iter().collect();
+        let directory = token.rfind('/').map(|idx| {
+            let directory = &token[..=idx];
+            match directory.strip_prefix("~/") {
+                Some(rest) => match std::env::var_os("HOME") {
+                    Some(home) => PathBuf::from(home).join(rest),
+                    None => PathBuf::from(directory),
+                },
+                None => PathBuf::from(directory),
+            }
+        });
+        Some((line, start, prefix, directory))
+    }
+
+    /// the text before the cursor changed. An open popup is filtered with the new word and
+    /// the completions are requested again once typing pauses. While typing completions are
+    /// requested for long enough words and for paths.
+    fn update_completion(&mut self, typed: bool) {
+        let word = match self.word_before_cursor() {
+            Some(word) => word,
+            None => return self.close_completion(),
+        };
+        let (line, column, prefix, directory) = word;
+        if let Some(popup) = self.completion.as_mut() {
+            if popup.anchor() == (line, column) && (!prefix.is_empty() || directory.is_some()) {
+                popup.set_prefix(&prefix);
+                self.completion_due = Some(Instant::now() + COMPLETION_DELAY);
+                self.draw_requested = true;
+                return;
+            }
+        }
+        self.close_completion();
+        if typed && (prefix.chars().count() >= COMPLETION_MIN_PREFIX || directory.is_some()) {
+            self.completion_due = Some(Instant::now() + COMPLETION_DELAY);
+        }
+    }
+
+    /// asks the sources and the language server for the completions of the word before the
+    /// cursor. A request of a language server for an earlier word is cancelled.
+    pub fn request_completion(&mut self) {
+        self.completion_due = None;
+        if self.state.mode != EditMode::Insert {
+            return;
+        }
+        let (line, column, prefix, directory) = match self.word_before_cursor() {
+            Some(word) => word,
+            None => return,
+        };
+        let reuse = self
+            .completion
+            .as_ref()
+            .map_or(false, |popup| popup.anchor() == (line, column));
+        if !reuse {
+            self.completion = Some(CompletionPopup::new(line, column, &prefix));
+        }
+
+        if let Some(id) = self.lsp_completion.take() {
+            self.language_servers.cancel(id);
+        }
+        if let Some((path, position)) = self.cursor_position() {
+            self.lsp_completion = self.language_servers.completion(&path, position);
+        }
+
+        self.completion_query += 1;
+        let mut texts = Vec::new();
+        let mut language = None;
+        if let Some(frame) = self.active_frame() {
+            let buffer = frame.buffer();
+            texts.push(buffer.content().clone());
+            language = buffer.path().and_then(|path| self.config.language(path));
+        }
+        let active = self.active_frame().map(Frame::buffer_id);
+        for id in self.core.buffer_ids() {
+            if Some(id) != active {
+                if let Some(buffer) = self.core.get_buffer(&id) {
+                    texts.push(buffer.content().clone());
+                }
+            }
+        }
+        let query = CompletionQuery {
+            id: self.completion_query,
+            prefix,
+            directory,
+            language,
+            texts,
+        };
+        if let Some(completer) = self.completer.as_ref() {
+            completer.query(query);
+        }
+    }
+
+    /// the candidates of a completion query.
+    pub fn handle_completions(&mut self, id: u64, items: Vec<CompletionItem>) {
+        if id == self.completion_query {
+            self.add_completions(items);
+        }
+    }
+
+    fn add_completions(&mut self, items: Vec<CompletionItem>) {
+        if let Some(popup) = self.completion.as_mut() {
+            popup.add_items(items);
+            self.draw_requested = true;
+        }
+    }
+
+    fn close_completion(&mut self) {
+        if self.completion.take().is_some() {
+            self.draw_requested = true;
+        }
+        self.completion_due = None;
+        if let Some(id) = self.lsp_completion.take() {
+            self.language_servers.cancel(id);
+        }
+    }
+
+    /// is the completion popup shown.
+    fn completing(&self) -> bool {
+        self.completion.as_ref().map_or(false, |popup| !popup.is_empty())
+    }
+
+    /// replaces the word before the cursors with the selected completion. A snippet is
+    /// expanded, a choice replaces the text of the active tab stop.
+    fn accept_completion(&mut self) {
+        let (prefix, text, kind, range) = match self.completion.as_ref().and_then(|popup| {
+            let item = popup.selected()?;
+            let prefix = popup.prefix().chars().count();
+            Some((prefix, item.text.clone(), item.kind, item.range))
+        }) {
+            Some(completion) => completion,
+            None => return,
+        };
+        self.close_completion();
+        match kind {
+            CompletionKind::Snippet => return self.expand_snippet(prefix, &text),
+            CompletionKind::Choice => return self.replace_snippet_stop(&text),
+            _ => {}
+        }
+        if let Some(frame) = self.active_frame_mut() {
+            // the range of a language server is replaced, it may start before the word or
+            // reach past the cursor. Its end was the cursor if the word was typed further.
+            let (before, after) = match range {
+                Some(range) => {
+                    let buffer = frame.buffer();
+                    let cursor = frame.cursor();
+                    let cursor = buffer.char_index(cursor.line(), cursor.column());
+                    let start = buffer.lsp_char_index(range.start).min(cursor);
+                    let end = buffer.lsp_char_index(range.end).max(cursor);
+                    (cursor - start, end - cursor)
+                }
+                None => (prefix, 0),
+            };
+            frame.delete_backward(before);
+            frame.delete_forward(after);
+            frame.insert(&text);
+        }
+        self.draw_requested = true;
+    }
+
+    /// replaces the word before the cursor with a snippet and selects its first tab stop. The
+    /// expansion is undone at once.
+    fn expand_snippet(&mut self, prefix: usize, body: &str) {
+        self.end_snippet();
+        let frame = match self.active_frame_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        frame.collapse_cursors();
+        frame.buffer_mut().begin_edit();
+        frame.delete_backward(prefix);
+
+        let (char_idx, indent, path) = {
+            let buffer = frame.buffer();
+            let cursor = frame.cursor();
+            let line_start = buffer.char_index(cursor.line(), 0);
+            let indent: String = buffer
+                .text(line_start..line_start + cursor.column())
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let path = buffer.path().map(Path::to_path_buf);
+            (line_start + cursor.column(), indent, path)
+        };
+        let expansion = snippets::expand(body, &indent, &Variables::new(path));
+        frame.insert(&expansion.text);
+        frame.buffer_mut().end_edit();
+
+        let (session, regions) = SnippetSession::new(frame.buffer_id(), &expansion, char_idx);
+        frame.buffer_mut().set_regions(regions);
+        self.snippet = Some(session);
+        self.activate_snippet_stop(0);
+    }
+
+    /// the current ranges of the regions of a tab stop.
+    fn snippet_ranges(&self, stop: usize) -> Vec<Range<usize>> {
+        let (session, frame) = match (self.snippet.as_ref(), self.active_frame()) {
+            (Some(session), Some(frame)) => (session, frame),
+            _ => return Vec::new(),
+        };
+        let buffer = frame.buffer();
+        session.stops.get(stop).map_or(Vec::new(), |(regions, _)| {
+            regions.iter().filter_map(|idx| buffer.region(*idx)).collect()
+        })
+    }
+
+    /// selects the placeholder of a tab stop with a cursor at each of its mirrors, a stop with
+    /// choices offers them. The session ends at the last stop.
+    fn activate_snippet_stop(&mut self, stop: usize) {
+        let ranges = self.snippet_ranges(stop);
+        let choices = match self.snippet.as_mut() {
+            Some(session) => {
+                session.current = stop;
+                session.fresh = true;
+                session.stops[stop].1.clone()
+            }
+            None => return,
+        };
+        if let Some(frame) = self.active_frame_mut() {
+            frame.select_ranges(&ranges);
+        }
+        self.draw_requested = true;
+        if self.snippet.as_ref().map_or(true, SnippetSession::at_end) {
+            return self.end_snippet();
+        }
+
+        if let (false, Some(range)) = (choices.is_empty(), ranges.first()) {
+            let (line, column) = match self.active_frame() {
+                Some(frame) => frame.buffer().position(range.start),
+                None => return,
+            };
+            let mut popup = CompletionPopup::new(line, column, "");
+            popup.add_items(
+                choices
+                    .iter()
+                    .map(|choice| CompletionItem::new(choice.as_str(), CompletionKind::Choice))
+                    .collect(),
+            );
+            self.completion = Some(popup);
+        }
+    }
+
+    /// Tab moves to the next tab stop and Shift+Tab to the previous one, returns false if no
+    /// snippet is being filled in the active frame.
+    fn goto_snippet_stop(&mut self, forward: bool) -> bool {
+        let active = self.active_frame().map(Frame::buffer_id);
+        let current = match self.snippet.as_ref() {
+            Some(session) if Some(session.buffer) == active => session.current,
+            Some(_) => {
+                self.end_snippet();
+                return false;
+            }
+            None => return false,
+        };
+        let stop = if forward {
+            current + 1
+        } else {
+            current.saturating_sub(1)
+        };
+        self.activate_snippet_stop(stop);
+        true
+    }
+
+    /// replaces the text of the active tab stop and its mirrors, the cursors are placed after
+    /// the text.
+    fn replace_snippet_stop(&mut self, text: &str) {
+        let current = match self.snippet.as_mut() {
+            Some(session) => {
+                session.fresh = false;
+                session.current
+            }
+            None => return,
+        };
+        let regions = match self.snippet.as_ref() {
+            Some(session) => session.stops[current].0.clone(),
+            None => return,
+        };
+        let frame = match self.active_frame_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let ranges = {
+            let mut buffer = frame.buffer_mut();
+            buffer.begin_edit();
+            // from the end so the regions before stay where they are.
+            let mut ranges: Vec<Range<usize>> =
+                regions.iter().filter_map(|idx| buffer.region(*idx)).collect();
+            ranges.sort_by_key(|range| std::cmp::Reverse(range.start));
+            for range in ranges {
+                buffer.remove(range);
+            }
+            regions
+                .iter()
+                .filter_map(|idx| buffer.region(*idx))
+                .collect::<Vec<_>>()
+        };
+        frame.select_ranges(&ranges);
+        frame.insert(text);
+        frame.buffer_mut().end_edit();
+        self.draw_requested = true;
+    }
+
+    /// typing while the placeholder of a tab stop is selected replaces it in the stop and its
+    /// mirrors, returns false if no placeholder is selected.
+    fn type_over_placeholder(&mut self, ch: char) -> bool {
+        let fresh = self.snippet.as_ref().map_or(false, |session| session.fresh);
+        let selected = self
+            .active_frame()
+            .map_or(false, |frame| frame.selection().is_some());
+        if !fresh || !selected {
+            return false;
+        }
+        let text = match ch {
+            '\u{8}' | '\u{7f}' => String::new(),
+            '\r' | '\n' => "\n".to_string(),
+            ch if ch.is_control() => return false,
+            ch => ch.to_string(),
+        };
+        self.replace_snippet_stop(&text);
+        true
+    }
+
+    /// stops filling the tab stops of the snippet.
+    fn end_snippet(&mut self) {
+        if let Some(session) = self.snippet.take() {
+            if let Some(mut buffer) = self.core.get_buffer_mut(&session.buffer) {
+                buffer.set_regions(Vec::new());
+            }
+        }
+    }
+
+    /// reports changes to the files of the buffers as `AppEvent::FileChanged`.
+    pub fn watch_files(&mut self, watcher: FileWatcher) {
+        self.core.watch_files(watcher);
+    }
+
+    pub fn set_swap_files(&mut self, swap_files: SwapFiles) {
+        self.core.set_swap_files(swap_files);
+    }
+
+    /// writes the changes of modified buffers to their swap files, called periodically.
+    pub fn write_swap_files(&mut self) {
+        self.core.write_swap_files(false);
+    }
+
+    pub fn update_size(&mut self, width: u32, height: u32) {
+        self.resize_frames(vec2f(width as f32, height as f32));
+        self.draw_requested = true;
+    }
+
+    /// the window moved to a monitor with a different dpi.
+    pub fn update_dpi_factor(&mut self, dpi_factor: f32, width: u32, height: u32) {
+        info!("DPI factor changed to {}", dpi_factor);
+        self.context.set_dpi_factor(dpi_factor);
+        self.glyphs_invalidated = true;
+        self.update_size(width, height);
+    }
+
+    pub fn set_font_size(&mut self, font_size: f32) {
+        let font_size = font_size.max(MIN_FONT_SIZE).min(MAX_FONT_SIZE);
+        if (font_size - self.context.font_size()).abs() < std::f32::EPSILON {
+            return;
+        }
+
+        info!("Font size changed to {}", font_size);
+        self.context.set_font_size(font_size);
+        self.glyphs_invalidated = true;
+
+        let window_size = self.window.get_size();
+        self.update_size(window_size.width, window_size.height);
+    }
+
+    /// places the frames and recomputes the number of lines of each. The terminal takes the
+    /// bottom of the window and the frames of the layout share the rest, except for the frames
+    /// of a diff which split it into a left and a right half.
+    fn resize_frames(&mut self, size: Vector2F) {
+        let metrics = self.context.font_metrics();
+        let terminal_height = match self.terminal {
+            Some(_) => (size.y() * TERMINAL_HEIGHT).floor(),
+            None => 0.0,
+        };
+        let (window, size) = (size, vec2f(size.x(), size.y() - terminal_height));
+        let half = (size.x() / 2.0).floor();
+        let mut rects: HashMap<FrameId, RectF> = self
+            .layout
+            .rects(RectF::new(Vector2F::zero(), size))
+            .into_iter()
+            .collect();
+        if let Some(diff) = self.diff.as_ref() {
+            rects.insert(diff.left, RectF::new(Vector2F::zero(), vec2f(half, size.y())));
+            let right = RectF::new(vec2f(half, 0.0), vec2f(size.x() - half, size.y()));
+            rects.insert(diff.right, right);
+        }
+        for (id, frame) in self.frames.iter_mut() {
+            if self.terminal == Some(*id) {
+                let lines = Frame::compute_lines(terminal_height, &metrics);
+                let columns = (window.x() / self.context.cell_width()) as usize;
+                frame.set_origin(vec2f(0.0, size.y()));
+                frame.resize(vec2f(window.x(), terminal_height), lines);
+                if let Some(Err(e)) = frame.terminal_mut().map(|t| t.resize(columns, lines)) {
+                    error!("{}", e);
+                }
+                continue;
+            }
+            // a frame that isn't part of the layout fills the window when it is shown.
+            let rect = rects
+                .get(id)
+                .copied()
+                .unwrap_or_else(|| RectF::new(Vector2F::zero(), size));
+            frame.set_origin(rect.origin());
+            frame.resize(rect.size(), Frame::compute_lines(rect.height(), &metrics));
+        }
+    }
+
+    /// the frames of the layout in the order they are drawn, the frames of a diff are drawn
+    /// last from left to right followed by the terminal.
+    fn drawn_frames(&self) -> Vec<FrameId> {
+        let mut frames: Vec<FrameId> = self.layout.frame_iter().map(|info| info.frame).collect();
+        if let Some(diff) = self.diff.as_ref() {
+            frames.retain(|id| *id != diff.left && *id != diff.right);
+            frames.push(diff.left);
+            frames.push(diff.right);
+        }
+        frames.extend(self.terminal);
+        frames
+    }
+
+    pub fn draw_requested(&self) -> bool {
+        self.draw_requested
+    }
+
+    pub fn swap_buffer(&self) {
+        self.window.swap_buffers()
+    }
+
+    /// restores the last session, without one src/main.rs is opened. The user is asked about
+    /// swap files left by editors that didn't exit cleanly.
+    pub fn on_init(&mut self) {
+        let session_path = PathBuf::from(kea::utils::session_file_path());
+        if session_path.exists() {
+            match Session::load(&session_path) {
+                Ok(session) => self.restore_session(&session),
+                Err(e) => error!("{}", e),
+            }
+        }
+
+        if self.frames.is_empty() {
+            let path = PathBuf::from("src/main.rs");
+            let buffer_id = match self.core.open_file(core::BufferInfo { path: Some(path) }) {
+                Ok(buffer_id) => buffer_id,
+                Err(e) => {
+                    error!("{}", e);
+                    self.core.scratch("")
+                }
+            };
+            let frame_id = self.add_frame(buffer_id);
+            self.layout.push_frame(FrameInfo { frame: frame_id });
+            self.focus_frame(frame_id);
+        }
+        let size = self.window.get_size();
+        self.resize_frames(vec2f(size.width as f32, size.height as f32));
+
+        for swap in self.core.orphaned_swap_files() {
+            let name = swap.path.as_ref().map_or_else(
+                || "an unnamed buffer".to_string(),
+                |path| format!("'{}'", path.display()),
+            );
+            let prompt = Prompt::new(format!("Recover unsaved changes to {}?", name))
+                .with_choice('r', "recover", true)
+                .with_choice('d', "discard", false);
+            self.questions.push_back(Question::Recover(swap, prompt));
+        }
+    }
+
+    /// saves the session and writes the swap files of buffers with unsaved changes.
+    pub fn on_exit(&mut self) {
+        // the frame added for a diff and the terminal aren't part of the session.
+        self.close_diff();
+        self.close_terminal();
+        if let Err(e) = self.session().save(kea::utils::session_file_path()) {
+            error!("{}", e);
+        }
+        self.core.write_swap_files(true);
+        self.language_servers.shutdown();
+    }
+
+    /// creates a frame showing a buffer, it isn't part of the layout yet.
+    fn add_frame(&mut self, buffer_id: BufferId) -> FrameId {
+        let window_size = self.window.get_size();
+        let size = vec2f(window_size.width as f32, window_size.height as f32);
+        let metrics = self.context.font_metrics();
+        let lines = Frame::compute_lines(size.y(), &metrics);
+
+        let buffer = self.core.get_buffer_ptr(&buffer_id).unwrap();
+        let mut frame = Frame::new(buffer, size, Vector2F::zero(), lines);
+        frame.set_options(self.config.frame_options());
+        frame.update_line_cache(Invalidation::Init);
+
+        let frame_id = frame.id();
+        self.frames.insert(frame_id, frame);
+        frame_id
+    }
+
+    fn session(&self) -> Session {
+        let frame_ids: Vec<FrameId> = self.layout.frame_iter().map(|info| info.frame).collect();
+        let frames = frame_ids
+            .iter()
+            .filter_map(|id| self.frames.get(id))
+            .map(|frame| FrameState {
+                // the session is shared by every working directory.
+                path: frame.buffer().path().map(|path| match std::env::current_dir() {
+                    Ok(dir) => dir.join(path),
+                    Err(_) => PathBuf::from(path),
+                }),
+                cursors: frame
+                    .cursors()
+                    .iter()
+                    .map(|cursor| (cursor.line(), cursor.column()))
+                    .collect(),
+                scroll: frame.view().start,
+            })
+            .collect();
+        let index = |id: &FrameId| frame_ids.iter().position(|frame| frame == id);
+        let layout = self
+            .layout
+            .nodes()
+            .iter()
+            .filter_map(|node| match node {
+                Layout::Vertical => Some(LayoutNode::Vertical),
+                Layout::Horizontal => Some(LayoutNode::Horizontal),
+                Layout::Frame(info) => index(&info.frame).map(LayoutNode::Frame),
+            })
+            .collect();
+
+        Session {
+            frames,
+            layout,
+            active: self.active_frame.as_ref().and_then(index),
+        }
+    }
+
+    /// opens the files of a session in frames laid out as they were. Files that can't be
+    /// opened are replaced by empty buffers so the layout stays the same.
+    fn restore_session(&mut self, session: &Session) {
+        let mut frame_ids = Vec::new();
+        for (state, buffer_id) in session.frames.iter().zip(session.open_buffers(&mut self.core)) {
+            let frame_id = self.add_frame(buffer_id);
+            if let Some(frame) = self.frames.get_mut(&frame_id) {
+                frame.restore(&state.cursors, state.scroll);
+            }
+            frame_ids.push(frame_id);
+        }
+        session.restore_layout(&mut self.layout, &frame_ids);
+
+        let active = session.active.and_then(|idx| frame_ids.get(idx));
+        if let Some(frame_id) = active.or_else(|| frame_ids.first()).copied() {
+            self.focus_frame(frame_id);
+        }
+    }
+
+    pub fn handle_keyboard_input(&mut self, input: KeyboardInput, modifiers: &ModifiersState) {
+        // let edit_mode = self.state.mode;
+        // if let Some(operation) =
+        debug!(
+            "Keyboard Input: {:?} Modifier State: {:?}",
+            input, modifiers
+        );
+
+
+		// for now I do not want to handle release.
+		if input.state == ElementState::Released {
+    		return;
+		}
+
+        if self.message.take().is_some() {
+            self.draw_requested = true;
+        }
+
+        if let Some(key) = input.virtual_keycode {
+            if self.terminal_key(key, modifiers) {
+                return;
+            }
+            let key = Key::new(key, modifiers);
+            if let Some(operation) = self.bindings.lookup(&key).cloned() {
+                self.handle_operation(operation);
+            }
+        }
+    }
+
+    pub fn handle_operation(&mut self, operation: Operation) {
+        if operation != Operation::Cut {
+            self.clipboard.end_cuts();
+        }
+
+        if self.completing() {
+            match operation {
+                Operation::CursorUp | Operation::CursorDown => {
+                    let offset = if operation == Operation::CursorUp { -1 } else { 1 };
+                    if let Some(popup) = self.completion.as_mut() {
+                        popup.select(offset);
+                    }
+                    self.draw_requested = true;
+                    return;
+                }
+                Operation::NormalMode => return self.close_completion(),
+                Operation::Complete => {}
+                _ => self.close_completion(),
+            }
+        }
+
+        let font_size = self.context.font_size();
+        if let Some(font_size) = zoomed_font_size(operation, font_size, &self.config) {
+            return self.set_font_size(font_size);
+        }
+
+        match operation {
+            Operation::Copy => {
+                let frames = &self.frames;
+                if let Some(frame) = self.active_frame.and_then(|id| frames.get(&id)) {
+                    self.clipboard.copy(frame);
+                }
+            }
+            Operation::Cut => self.edit_active_frame(|clipboard, frame| clipboard.cut(frame)),
+            Operation::Paste => self.edit_active_frame(|clipboard, frame| {
+                clipboard.paste(frame, ClipboardType::Clipboard)
+            }),
+            Operation::CycleKillRing => self.clipboard.cycle_kill_ring(),
+            Operation::Hover
+            | Operation::GotoDefinition
+            | Operation::FindReferences
+            | Operation::Rename
+            | Operation::Format => self.language_operation(operation),
+            Operation::Complete => self.request_completion(),
+            Operation::NextDiagnostic => self.goto_diagnostic(true),
+            Operation::PreviousDiagnostic => self.goto_diagnostic(false),
+            Operation::ListDiagnostics => self.list_diagnostics(),
+            Operation::Lint => self.lint(),
+            Operation::NextHunk if self.diff_side().is_some() => self.goto_diff_hunk(true),
+            Operation::PreviousHunk if self.diff_side().is_some() => self.goto_diff_hunk(false),
+            Operation::NextHunk => self.goto_hunk(true),
+            Operation::PreviousHunk => self.goto_hunk(false),
+            Operation::StageHunk => self.stage_hunk(),
+            Operation::RevertHunk => self.revert_hunk(),
+            Operation::DiffBuffers | Operation::DiffDisk if self.diff.is_some() => {
+                self.close_diff()
+            }
+            Operation::DiffBuffers => self.diff_buffers(),
+            Operation::DiffDisk => self.diff_disk(),
+            Operation::DiffGet => self.copy_diff_hunk(true),
+            Operation::DiffPut => self.copy_diff_hunk(false),
+            Operation::Terminal => self.toggle_terminal(),
+            Operation::RunCommand | Operation::FilterCommand => {
+                let filter = operation == Operation::FilterCommand;
+                self.ask_command(filter);
+            }
+            Operation::CancelCommand => {
+                if !self.commands.cancel() {
+                    self.show_message("no command is running".to_string());
+                }
+            }
+            Operation::Undo | Operation::Redo => self.undo(operation == Operation::Redo),
+            Operation::NormalMode if !self.questions.is_empty() => self.dismiss_question(),
+            Operation::NormalMode => {
+                self.end_snippet();
+                self.state.register = None;
+                self.state.awaiting_register = false;
+                if self.state.mode == EditMode::Insert {
+                    self.state.mode = EditMode::Normal;
+                } else if let Some(frame) = self.active_frame_mut() {
+                    frame.collapse_cursors();
+                    self.draw_requested = true;
+                }
+            }
+            operation => {
+                if let Some(frame) = self.active_frame_mut() {
+                    if frame.handle_operation(operation) {
+                        self.draw_requested = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// reverts the last edit of the active buffer, or applies it again.
+    fn undo(&mut self, redo: bool) {
+        if self.read_only() {
+            return;
+        }
+        let frame = match self.active_frame_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        if !frame.undo(redo) {
+            let message = if redo { "nothing to redo" } else { "nothing to undo" };
+            return self.show_message(message.to_string());
+        }
+        let id = frame.buffer_id();
+        self.buffer_reloaded(id);
+    }
+
+    /// whether the active frame can't be edited, the user is told so.
+    fn read_only(&mut self) -> bool {
+        let read_only = matches!(self.active_frame(), Some(frame) if frame.is_read_only());
+        if read_only {
+            self.show_message("the frame is read-only".to_string());
+        }
+        read_only
+    }
+
+    /// applies an edit that uses the clipboard to the active frame, the edit returns true
+    /// if the frame changed.
+    fn edit_active_frame<F>(&mut self, edit: F)
+    where
+        F: FnOnce(&mut ClipboardState, &mut Frame) -> bool,
+    {
+        let frame = match self.active_frame {
+            Some(id) => self.frames.get_mut(&id),
+            None => None,
+        };
+        if let Some(frame) = frame {
+            if edit(&mut self.clipboard, frame) {
+                self.draw_requested = true;
+            }
+        }
+    }
+
+    /// a character was typed. In insert mode it is inserted at the cursors, in normal mode
+    /// it is a command.
+    pub fn handle_character(&mut self, ch: char, modifiers: &ModifiersState) {
+        if self.active_terminal().is_some() {
+            return self.type_in_terminal(ch, modifiers);
+        }
+        // characters typed with a modifier are handled by the key bindings.
+        if modifiers.ctrl() || modifiers.alt() || modifiers.logo() {
+            return;
+        }
+        self.clipboard.end_cuts();
+
+        if !self.questions.is_empty() {
+            self.answer_question(ch);
+            return;
+        }
+
+        match self.state.mode {
+            EditMode::Insert if self.completing() && (ch == '\t' || ch == '\r') => {
+                self.accept_completion()
+            }
+            EditMode::Insert => {
+                if ch != '\t' || !self.goto_snippet_stop(!modifiers.shift()) {
+                    self.insert_character(ch);
+                }
+            }
+            EditMode::Normal => self.normal_command(ch),
+        }
+    }
+
+    fn insert_character(&mut self, ch: char) {
+        if self.read_only() {
+            return;
+        }
+        if self.type_over_placeholder(ch) {
+            self.update_completion(!ch.is_control());
+            return;
+        }
+        let frame = match self.active_frame_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        match ch {
+            '\u{8}' => frame.delete_backward(1),
+            '\u{7f}' => frame.delete_forward(1),
+            '\r' | '\n' => frame.insert("\n"),
+            '\t' => frame.insert("\t"),
+            ch if ch.is_control() => return,
+            ch => frame.insert(ch.encode_utf8(&mut [0; 4])),
+        }
+        self.draw_requested = true;
+        // a deleted character doesn't start a completion, it only updates an open one.
+        let typed = !ch.is_control();
+        self.update_completion(typed);
+    }
+
+    /// `i` enters insert mode, `y` yanks the selection, `d` deletes it and `p` puts text.
+    /// `"` followed by a register name selects the register of the next yank, delete or put.
+    fn normal_command(&mut self, ch: char) {
+        if self.state.awaiting_register {
+            self.state.awaiting_register = false;
+            self.state.register = Register::from_char(ch);
+            return;
+        }
+
+        let register = self.state.register.take().unwrap_or(Register::Unnamed);
+        match ch {
+            '"' => self.state.awaiting_register = true,
+            'i' => self.state.mode = EditMode::Insert,
+            '!' => self.ask_command(false),
+            '|' => self.ask_command(true),
+            'y' => {
+                let frames = &self.frames;
+                if let Some(frame) = self.active_frame.and_then(|id| frames.get(&id)) {
+                    self.clipboard.yank(frame, register);
+                }
+            }
+            'd' => self.edit_active_frame(|clipboard, frame| clipboard.delete(frame, register)),
+            'p' => self.edit_active_frame(|clipboard, frame| clipboard.put(frame, register)),
+            _ => {}
+        }
+    }
+
+    /// the middle mouse button pastes the primary selection at the point.
+    pub fn handle_middle_click(&mut self, point: Vector2F) {
+        let frame_id = match self.frame_at(point) {
+            Some(id) => id,
+            None => return,
+        };
+        if self.active_frame != Some(frame_id) {
+            self.focus_frame(frame_id);
+        }
+        if self.terminal == Some(frame_id) {
+            let text = self.clipboard.clipboard_mut().load(ClipboardType::Selection);
+            return self.paste_in_terminal(&text);
+        }
+
+        if let Some(frame) = self.frames.get_mut(&frame_id) {
+            let (line, column) = frame.position_at(point, &mut self.context);
+            frame.place_cursor(line, column, false);
+            self.clipboard.paste(frame, ClipboardType::Selection);
+            self.draw_requested = true;
+        }
+    }
+
+    /// the frame under a point, frames later in the layout are drawn on top of earlier ones.
+    fn frame_at(&self, point: Vector2F) -> Option<FrameId> {
+        self.drawn_frames().into_iter().rev().find(|id| {
+            self.frames
+                .get(id)
+                .map_or(false, |frame| frame.contains(point))
+        })
+    }
+
+    /// makes a frame the target of input.
+    pub fn focus_frame(&mut self, id: FrameId) {
+        if let Some(frame) = self.active_frame_mut() {
+            frame.set_active(false);
+        }
+        if let Some(frame) = self.frames.get_mut(&id) {
+            frame.set_active(true);
+            self.active_frame = Some(id);
+            self.draw_requested = true;
+        }
+    }
+
+    /// the left mouse button was pressed. A single click places the cursor, or extends the
+    /// selection with shift held, a double click selects a word and a triple click a line.
+    pub fn handle_mouse_press(
+        &mut self,
+        point: Vector2F,
+        click: ClickState,
+        modifiers: &ModifiersState,
+    ) {
+        let frame_id = match self.frame_at(point) {
+            Some(id) => id,
+            None => return,
+        };
+        if self.active_frame != Some(frame_id) {
+            self.focus_frame(frame_id);
+        }
+        self.clipboard.end_cuts();
+        self.close_completion();
+        self.end_snippet();
+
+        if let Some(frame) = self.frames.get_mut(&frame_id) {
+            // text in a terminal is selected by cells.
+            if frame.terminal().is_some() {
+                let cell = frame.cell_at(point, &self.context);
+                if let Some(terminal) = frame.terminal_mut() {
+                    terminal.select_start(cell);
+                }
+                self.dragging = true;
+                self.draw_requested = true;
+                return;
+            }
+            let (line, column) = frame.position_at(point, &mut self.context);
+            match click {
+                ClickState::Double => frame.select_word(line, column),
+                ClickState::Triple => frame.select_line(line),
+                _ => frame.place_cursor(line, column, modifiers.shift()),
+            }
+            self.dragging = true;
+            self.draw_requested = true;
+        }
+    }
+
+    /// the selection made with the mouse becomes the primary selection.
+    pub fn handle_mouse_release(&mut self) {
+        if !self.dragging {
+            return;
+        }
+        self.dragging = false;
+
+        if let Some(text) = self.active_frame().and_then(|frame| frame.selected_text()) {
+            self.clipboard
+                .clipboard_mut()
+                .store(ClipboardType::Selection, text);
+        }
+    }
+
+    /// extends the selection of the active frame to the point while dragging.
+    pub fn handle_mouse_drag(&mut self, point: Vector2F) {
+        if !self.dragging {
+            return;
+        }
+
+        let frame = match self.active_frame {
+            Some(id) => self.frames.get_mut(&id),
+            None => None,
+        };
+        if let Some(frame) = frame {
+            if frame.terminal().is_some() {
+                let cell = frame.cell_at(point, &self.context);
+                if let Some(terminal) = frame.terminal_mut() {
+                    terminal.select_to(cell);
+                }
+                self.draw_requested = true;
+                return;
+            }
+            let (line, column) = frame.position_at(point, &mut self.context);
+            frame.place_cursor(line, column, true);
+            self.draw_requested = true;
+        }
+    }
+
+    /// scrolls the active frame by a line if a selection is dragged above or below it.
+    /// Returns true if the frame was scrolled.
+    pub fn auto_scroll(&mut self, point: Vector2F) -> bool {
+        if !self.dragging {
+            return false;
+        }
+
+        let scrolled = match self.active_frame_mut() {
+            Some(frame) if frame.terminal().is_some() => false,
+            Some(frame) if point.y() < frame.rect().min_y() => {
+                frame.scroll(-1);
+                true
+            }
+            Some(frame) if point.y() > frame.rect().max_y() => {
+                frame.scroll(1);
+                true
+            }
+            _ => false,
+        };
+
+        if scrolled {
+            self.handle_mouse_drag(point);
+        }
+        scrolled
+    }
+
+    /// highlights the frame a file is dragged over, the active frame when the point isn't
+    /// known. A hover starts a new drop.
+    pub fn handle_file_hover(&mut self, point: Option<Vector2F>) {
+        self.drop_frame = None;
+        let hovered = self.drop_target(point);
+        for (id, frame) in self.frames.iter_mut() {
+            frame.set_hovered(Some(*id) == hovered);
+        }
+        self.draw_requested = true;
+    }
+
+    pub fn handle_file_hover_cancelled(&mut self) {
+        self.end_drop();
+        self.clear_hover();
+    }
+
+    /// the next dropped file starts a new drop instead of joining the files of the last one.
+    pub fn end_drop(&mut self) {
+        self.drop_frame = None;
+    }
+
+    fn clear_hover(&mut self) {
+        for frame in self.frames.values_mut() {
+            frame.set_hovered(false);
+        }
+        self.draw_requested = true;
+    }
+
+    /// opens a dropped file, or a listing of a dropped directory, in the frame under the
+    /// point. When several files are dropped the first one is opened there and each of the
+    /// others in a new frame on top of it. The frame is focused.
+    pub fn handle_dropped_file(&mut self, path: PathBuf, point: Option<Vector2F>) {
+        self.clear_hover();
+
+        // a file that is already open is shown instead of being opened again.
+        let open = self.core.buffers_with_path(&path).first().copied();
+        let buffer_id = match open {
+            Some(id) => id,
+            None => match self.core.open_file(core::BufferInfo { path: Some(path) }) {
+                Ok(id) => id,
+                Err(e) => {
+                    error!("Failed to open dropped file: {}", e);
+                    return;
+                }
+            },
+        };
+        let frame_id = match (self.drop_frame, self.drop_target(point)) {
+            (Some(_), _) => {
+                // the frames of a diff are drawn over the others.
+                self.close_diff();
+                let id = self.add_frame(buffer_id);
+                self.layout.push_frame(FrameInfo { frame: id });
+                let size = self.window.get_size();
+                self.resize_frames(vec2f(size.width as f32, size.height as f32));
+                id
+            }
+            (None, Some(id)) => {
+                self.show_buffer(id, buffer_id);
+                id
+            }
+            (None, None) => {
+                if open.is_none() {
+                    self.core.remove_buffer(&buffer_id);
+                }
+                return;
+            }
+        };
+        self.drop_frame = Some(frame_id);
+        self.focus_frame(frame_id);
+    }
+
+    /// the frame under the point of a drop, the active frame when the point isn't known.
+    fn drop_target(&self, point: Option<Vector2F>) -> Option<FrameId> {
+        point
+            .and_then(|point| self.frame_at(point))
+            .or(self.active_frame)
+    }
+
+    /// a file was changed by another program. Buffers without unsaved changes are reloaded,
+    /// the user is asked what to do with the others.
+    pub fn handle_file_changed(&mut self, path: PathBuf) {
+        for id in self.core.buffers_with_path(&path) {
+            match self.core.check_file(&id) {
+                Ok(FileChange::Unchanged) => {}
+                Ok(FileChange::Reloaded) => self.buffer_reloaded(id),
+                Ok(FileChange::Conflict) => {
+                    let asked = self.questions.iter().any(|question| match question {
+                        Question::FileChanged(buffer, _) => *buffer == id,
+                        _ => false,
+                    });
+                    if asked {
+                        continue;
+                    }
+                    let prompt = Prompt::new(format!("'{}' changed on disk.", path.display()))
+                        .with_choice('k', "keep", Resolution::Keep)
+                        .with_choice('r', "reload", Resolution::Reload)
+                        .with_choice('d', "diff", Resolution::Diff);
+                    self.questions.push_back(Question::FileChanged(id, prompt));
+                    self.draw_requested = true;
+                }
+                Err(e) => error!("Failed to check '{}': {}", path.display(), e),
+            }
+        }
+        if !self.core.buffers_with_path(&path).is_empty() {
+            self.linters.lint(&path, &self.config);
+        }
+    }
+
+    /// answers the first question, characters that aren't one of its choices are ignored. The
+    /// characters of a rename are the new name, it is sent when enter is pressed.
+    fn answer_question(&mut self, ch: char) {
+        let question = match self.questions.pop_front() {
+            Some(question) => question,
+            None => return,
+        };
+
+        match question {
+            Question::FileChanged(id, prompt) => match prompt.answer(ch) {
+                Some(resolution) => self.resolve_conflict(id, resolution),
+                None => self
+                    .questions
+                    .push_front(Question::FileChanged(id, prompt)),
+            },
+            Question::Recover(swap, prompt) => match prompt.answer(ch) {
+                Some(true) => self.recover(&swap),
+                Some(false) => {
+                    if let Err(e) = swap.remove() {
+                        error!("{}", e);
+                    }
+                }
+                None => self.questions.push_front(Question::Recover(swap, prompt)),
+            },
+            Question::Rename {
+                path,
+                position,
+                mut name,
+            } => match ch {
+                '\r' | '\n' if !name.is_empty() => {
+                    let id = self.language_servers.rename(&path, position, &name);
+                    if !self.edits_requested(id) {
+                        self.show_message(format!("no language server for '{}'", path.display()));
+                    }
+                }
+                '\u{8}' => {
+                    name.pop();
+                    self.questions.push_front(Question::Rename { path, position, name });
+                }
+                ch => {
+                    if !ch.is_control() {
+                        name.push(ch);
+                    }
+                    self.questions.push_front(Question::Rename { path, position, name });
+                }
+            },
+            Question::Command {
+                mut command,
+                filter,
+            } => match ch {
+                '\r' | '\n' if !command.trim().is_empty() => self.run_command(&command, filter),
+                '\u{8}' => {
+                    command.pop();
+                    self.questions.push_front(Question::Command { command, filter });
+                }
+                ch => {
+                    if !ch.is_control() {
+                        command.push(ch);
+                    }
+                    self.questions.push_front(Question::Command { command, filter });
+                }
+            },
+        }
+        self.draw_requested = true;
+    }
+
+    /// escape keeps a buffer whose file changed and cancels a rename or a command. A swap file
+    /// is kept and offered again the next time the editor starts.
+    fn dismiss_question(&mut self) {
+        if let Some(Question::FileChanged(id, _)) = self.questions.pop_front() {
+            self.resolve_conflict(id, Resolution::Keep);
+        }
+        self.draw_requested = true;
+    }
+
+    /// shows the changes of a swap file in the active frame.
+    fn recover(&mut self, swap: &SwapFile) {
+        let buffer_id = match self.core.recover(swap) {
+            Ok(id) => id,
+            Err(e) => {
+                error!("Failed to recover '{}': {}", swap.swap.display(), e);
+                return;
+            }
+        };
+
+        if let Some(id) = self.active_frame {
+            self.show_buffer(id, buffer_id);
+        }
+        self.buffer_reloaded(buffer_id);
+    }
+
+    fn resolve_conflict(&mut self, id: BufferId, resolution: Resolution) {
+        let conflicts = match self.core.resolve_conflict(&id, resolution) {
+            Ok(conflicts) => conflicts,
+            Err(e) => {
+                error!("Failed to resolve a file change: {}", e);
+                return self.show_message(e.to_string());
+            }
+        };
+        self.buffer_reloaded(id);
+        if resolution != Resolution::Diff {
+            return;
+        }
+
+        // what is left to resolve is shown next to the file on disk, in a frame of the buffer.
+        let shown = self.active_frame.filter(|frame| self.frames[frame].buffer_id() == id);
+        let frame = shown.or_else(|| self.layout_frame_of(id));
+        let frame = match frame {
+            Some(frame) => frame,
+            None => return,
+        };
+        self.close_diff();
+        self.focus_frame(frame);
+        self.diff_disk();
+        self.show_message(format!("merged the file, {} conflicts left", conflicts));
+    }
+
+    /// a frame of the layout showing a buffer.
+    fn layout_frame_of(&self, id: BufferId) -> Option<FrameId> {
+        self.layout
+            .frame_iter()
+            .map(|info| info.frame)
+            .find(|frame| self.frames.get(frame).map(Frame::buffer_id) == Some(id))
+    }
+
+    /// shows a buffer in a frame. The buffer shown before is closed if it has no unsaved
+    /// changes and no other frame shows it.
+    fn show_buffer(&mut self, frame_id: FrameId, buffer_id: BufferId) {
+        let buffer = match self.core.get_buffer_ptr(&buffer_id) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let old = match self.frames.get_mut(&frame_id) {
+            Some(frame) if frame.buffer_id() != buffer_id => {
+                let old = frame.buffer_id();
+                frame.set_buffer(buffer);
+                old
+            }
+            _ => return,
+        };
+        self.close_buffer(old);
+        self.draw_requested = true;
+    }
+
+    /// removes a buffer that isn't shown and has no unsaved changes, its file is no longer
+    /// watched.
+    fn close_buffer(&mut self, id: BufferId) {
+        let shown = self.frames.values().any(|frame| frame.buffer_id() == id);
+        let dirty = matches!(self.core.get_buffer(&id), Some(buffer) if buffer.is_dirty());
+        if !shown && !dirty {
+            self.core.remove_buffer(&id);
+        }
+    }
+
+    /// moves the cursors of the frames showing a buffer into its new contents.
+    fn buffer_reloaded(&mut self, id: BufferId) {
+        if self.snippet.as_ref().map_or(false, |session| session.buffer == id) {
+            self.end_snippet();
+        }
+        for frame in self.frames.values_mut() {
+            if frame.buffer_id() == id {
+                frame.buffer_reloaded();
+            }
+        }
+        self.draw_requested = true;
+    }
+
+    pub fn active_frame(&self) -> Option<&Frame> {
+        if let Some(id) = self.active_frame.as_ref() {
+            self.frames.get(id)
+        } else {
+            info!("No Active Frame");
+            None
+        }
+    }
+
+    pub fn active_frame_mut(&mut self) -> Option<&mut Frame> {
+        if let Some(id) = self.active_frame.as_ref() {
+            self.frames.get_mut(id)
+        } else {
+            info!("No Active Frame");
+            None
+        }
+    }
+}
+
+impl Renderable for Application {
+    fn render(&mut self, renderer: &mut Renderer) {
+        if self.glyphs_invalidated {
+            renderer.reset_glyphs(self.context.dpi_factor());
+            self.glyphs_invalidated = false;
+        }
+
+        // the theme changed since the window was cleared.
+        if renderer.clear_color() != self.context.background() {
+            renderer.set_clear_color(self.context.background());
+            renderer.clear();
+        }
+
+        for id in self.drawn_frames() {
+            if let Some(frame) = self.frames.get_mut(&id) {
+                renderer.render_frame(&mut self.context, frame);
+            }
+        }
+
+        let size = self.window.get_size();
+        let window = vec2f(size.width as f32, size.height as f32);
+        if let Some(popup) = self.completion.as_ref().filter(|popup| !popup.is_empty()) {
+            let (line, column) = popup.anchor();
+            let context = &mut self.context;
+            let frames = &mut self.frames;
+            let point = match self.active_frame.and_then(|id| frames.get_mut(&id)) {
+                Some(frame) => frame.point_of(line, column, context),
+                None => None,
+            };
+            if let Some(point) = point {
+                renderer.render_completion(context, popup, point, window);
+            }
+        }
+
+        let text = match self.questions.front() {
+            Some(question) => Some(question.text()),
+            None => self.message.clone(),
+        };
+        if let Some(text) = text {
+            let size = self.window.get_size();
+            let (width, height) = (size.width as f32, size.height as f32);
+            renderer.render_prompt(&mut self.context, &text, Vector2F::zero(), width, height);
         }
 
         self.draw_requested = false;
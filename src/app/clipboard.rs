@@ -0,0 +1,399 @@
+// Clipboard.
+// Access to the system clipboard hidden behind a trait so the editor can be run with an in-memory
+// clipboard in tests. On X11 and Wayland there is also the primary selection, the text that was
+// last selected, which is pasted with the middle mouse button.
+
+use std::ffi::c_void;
+
+use copypasta::nop_clipboard::NopClipboardContext;
+use copypasta::ClipboardProvider;
+use log::error;
+
+use crate::ui::registers::{distribute, Register, Registers};
+use crate::ui::Frame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardType {
+    Clipboard,
+    /// the primary selection, platforms without it use the clipboard instead.
+    Selection,
+}
+
+pub trait Clipboard {
+    fn store(&mut self, ty: ClipboardType, text: String);
+
+    /// the contents of the clipboard, empty if it can't be read.
+    fn load(&mut self, ty: ClipboardType) -> String;
+}
+
+pub struct SystemClipboard {
+    clipboard: Box<dyn ClipboardProvider>,
+    selection: Option<Box<dyn ClipboardProvider>>,
+}
+
+impl SystemClipboard {
+    /// the clipboard of the platform. On Wayland the display of the window is needed, without
+    /// it the X11 clipboard is used.
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "ios"))
+    ))]
+    pub fn new(wayland_display: Option<*mut c_void>) -> Self {
+        use copypasta::x11_clipboard::{Clipboard, Primary, X11ClipboardContext};
+
+        if let Some(display) = wayland_display {
+            let (selection, clipboard) =
+                unsafe { copypasta::wayland_clipboard::create_clipboards_from_external(display) };
+            return Self {
+                clipboard: Box::new(clipboard),
+                selection: Some(Box::new(selection)),
+            };
+        }
+
+        let clipboard = X11ClipboardContext::<Clipboard>::new()
+            .map(|ctx| Box::new(ctx) as Box<dyn ClipboardProvider>)
+            .unwrap_or_else(|e| {
+                error!("Failed to open the clipboard: {}", e);
+                Box::new(NopClipboardContext::new().unwrap())
+            });
+        let selection = X11ClipboardContext::<Primary>::new()
+            .map(|ctx| Box::new(ctx) as Box<dyn ClipboardProvider>)
+            .ok();
+
+        Self {
+            clipboard,
+            selection,
+        }
+    }
+
+    #[cfg(not(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "ios"))
+    )))]
+    pub fn new(_wayland_display: Option<*mut c_void>) -> Self {
+        let clipboard = copypasta::ClipboardContext::new()
+            .map(|ctx| Box::new(ctx) as Box<dyn ClipboardProvider>)
+            .unwrap_or_else(|e| {
+                error!("Failed to open the clipboard: {}", e);
+                Box::new(NopClipboardContext::new().unwrap())
+            });
+
+        Self {
+            clipboard,
+            selection: None,
+        }
+    }
+
+    fn provider(&mut self, ty: ClipboardType) -> &mut Box<dyn ClipboardProvider> {
+        match (ty, self.selection.as_mut()) {
+            (ClipboardType::Selection, Some(selection)) => selection,
+            _ => &mut self.clipboard,
+        }
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn store(&mut self, ty: ClipboardType, text: String) {
+        if let Err(e) = self.provider(ty).set_contents(text) {
+            error!("Failed to store {:?} contents: {}", ty, e);
+        }
+    }
+
+    fn load(&mut self, ty: ClipboardType) -> String {
+        self.provider(ty).get_contents().unwrap_or_else(|e| {
+            error!("Failed to load {:?} contents: {}", ty, e);
+            String::new()
+        })
+    }
+}
+
+/// A clipboard that isn't shared with other applications.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryClipboard {
+    clipboard: String,
+    selection: String,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn store(&mut self, ty: ClipboardType, text: String) {
+        match ty {
+            ClipboardType::Clipboard => self.clipboard = text,
+            ClipboardType::Selection => self.selection = text,
+        }
+    }
+
+    fn load(&mut self, ty: ClipboardType) -> String {
+        match ty {
+            ClipboardType::Clipboard => self.clipboard.clone(),
+            ClipboardType::Selection => self.selection.clone(),
+        }
+    }
+}
+
+/// The clipboard and the registers, the copy and paste operations of frames go through it.
+pub struct ClipboardState {
+    clipboard: Box<dyn Clipboard>,
+    registers: Registers,
+    /// the last operation was a cut, the next cut is appended to it.
+    last_cut: bool,
+}
+
+impl ClipboardState {
+    pub fn new(clipboard: Box<dyn Clipboard>) -> Self {
+        Self {
+            clipboard,
+            registers: Registers::new(),
+            last_cut: false,
+        }
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn clipboard_mut(&mut self) -> &mut dyn Clipboard {
+        self.clipboard.as_mut()
+    }
+
+    /// any operation other than a cut ends a sequence of cuts.
+    pub fn end_cuts(&mut self) {
+        self.last_cut = false;
+    }
+
+    /// copies the selections to the clipboard. Returns false if nothing is selected.
+    pub fn copy(&mut self, frame: &Frame) -> bool {
+        match frame.selected_texts() {
+            Some(texts) => {
+                self.store(Register::Clipboard, texts);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// removes the selections and adds them to the kill ring and the clipboard. Cuts made one
+    /// after another are joined.
+    pub fn cut(&mut self, frame: &mut Frame) -> bool {
+        let texts = match frame.delete_selections() {
+            Some(texts) => texts,
+            None => return false,
+        };
+
+        self.registers.kill(texts, self.last_cut);
+        self.last_cut = true;
+        if let Some(texts) = self.registers.load(Register::Unnamed) {
+            let text = texts.join("\n");
+            self.clipboard.store(ClipboardType::Clipboard, text);
+        }
+        true
+    }
+
+    /// replaces the selection with the contents of the clipboard.
+    pub fn paste(&mut self, frame: &mut Frame, ty: ClipboardType) -> bool {
+        let register = match ty {
+            ClipboardType::Clipboard => Register::Clipboard,
+            ClipboardType::Selection => Register::Selection,
+        };
+        self.put(frame, register)
+    }
+
+    /// copies the selections into a register, one entry for each cursor.
+    pub fn yank(&mut self, frame: &Frame, register: Register) -> bool {
+        match frame.selected_texts() {
+            Some(texts) => {
+                self.store(register, texts);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// removes the selections and stores them in a register, the cut is added to the kill
+    /// ring.
+    pub fn delete(&mut self, frame: &mut Frame, register: Register) -> bool {
+        let texts = match frame.delete_selections() {
+            Some(texts) => texts,
+            None => return false,
+        };
+        self.registers.kill(texts.clone(), false);
+        if register != Register::Unnamed {
+            self.store(register, texts);
+        }
+        true
+    }
+
+    /// replaces the selection with the contents of a register. With several cursors each
+    /// cursor puts one line when the number of lines and cursors match.
+    pub fn put(&mut self, frame: &mut Frame, register: Register) -> bool {
+        let texts = match self.load(register) {
+            Some(texts) if !texts.iter().all(String::is_empty) => texts,
+            _ => return false,
+        };
+
+        frame.delete_selections();
+        frame.insert_each(&distribute(&texts, frame.cursors().len()));
+        true
+    }
+
+    pub fn cycle_kill_ring(&mut self) {
+        self.registers.rotate_kill_ring();
+    }
+
+    fn store(&mut self, register: Register, texts: Vec<String>) {
+        match register {
+            Register::Clipboard => {
+                self.clipboard
+                    .store(ClipboardType::Clipboard, texts.join("\n"));
+            }
+            Register::Selection => {
+                self.clipboard
+                    .store(ClipboardType::Selection, texts.join("\n"));
+            }
+            _ => {}
+        }
+        self.registers.store(register, texts);
+    }
+
+    fn load(&mut self, register: Register) -> Option<Vec<String>> {
+        let ty = match register {
+            Register::Clipboard => ClipboardType::Clipboard,
+            Register::Selection => ClipboardType::Selection,
+            register => return self.registers.load(register).cloned(),
+        };
+
+        // the unnamed register keeps the text of each cursor, if the clipboard still has what
+        // was copied last it is used so the cursors get their own text back.
+        let text = self.clipboard.load(ty);
+        match self.registers.load(Register::Unnamed) {
+            Some(texts) if texts.join("\n") == text => Some(texts.clone()),
+            _ => Some(vec![text]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::Buffer;
+    use crate::pathfinder_geometry::vector::Vector2F;
+    use crate::ui::{Invalidation, Operation};
+    use kea::ptr;
+
+    fn frame(text: &str) -> Frame {
+        let mut buffer = Buffer::empty(0).unwrap();
+        buffer.insert(0, text);
+        let mut frame = Frame::new(ptr(buffer), Vector2F::zero(), Vector2F::zero(), 10);
+        frame.update_line_cache(Invalidation::Init);
+        frame
+    }
+
+    fn text(frame: &Frame) -> String {
+        frame.buffer().content().to_string()
+    }
+
+    fn select(frame: &mut Frame, start: (usize, usize), end: (usize, usize)) {
+        frame.place_cursor(start.0, start.1, false);
+        frame.place_cursor(end.0, end.1, true);
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let mut state = ClipboardState::new(Box::new(MemoryClipboard::new()));
+        let mut frame = frame("hello world\n");
+
+        select(&mut frame, (0, 0), (0, 5));
+        assert!(state.copy(&frame));
+        assert_eq!(
+            state.clipboard_mut().load(ClipboardType::Clipboard),
+            "hello"
+        );
+
+        frame.place_cursor(0, 11, false);
+        assert!(state.paste(&mut frame, ClipboardType::Clipboard));
+        assert_eq!(text(&frame), "hello worldhello\n");
+
+        // nothing selected.
+        assert!(!state.copy(&frame));
+    }
+
+    #[test]
+    fn repeated_cuts() {
+        let mut state = ClipboardState::new(Box::new(MemoryClipboard::new()));
+        let mut frame = frame("one two three\n");
+
+        select(&mut frame, (0, 0), (0, 4));
+        state.cut(&mut frame);
+        select(&mut frame, (0, 0), (0, 4));
+        state.cut(&mut frame);
+        assert_eq!(text(&frame), "three\n");
+        assert_eq!(
+            state.clipboard_mut().load(ClipboardType::Clipboard),
+            "one two "
+        );
+
+        state.end_cuts();
+        select(&mut frame, (0, 0), (0, 5));
+        state.cut(&mut frame);
+        assert_eq!(state.registers().kill_ring().len(), 2);
+
+        state.cycle_kill_ring();
+        assert!(state.put(&mut frame, Register::Unnamed));
+        assert_eq!(text(&frame), "one two \n");
+    }
+
+    #[test]
+    fn registers_and_cursors() {
+        let mut state = ClipboardState::new(Box::new(MemoryClipboard::new()));
+        let mut frame = frame("a\nb\nc\n");
+
+        select(&mut frame, (0, 0), (2, 1));
+        assert!(state.yank(&frame, Register::Named('q')));
+        assert_eq!(state.clipboard_mut().load(ClipboardType::Clipboard), "");
+
+        // one line for each cursor.
+        frame.place_cursor(0, 1, false);
+        frame.add_cursor(1, 1);
+        frame.add_cursor(2, 1);
+        assert!(state.put(&mut frame, Register::Named('q')));
+        assert_eq!(text(&frame), "aa\nbb\ncc\n");
+
+        // more lines than cursors, each cursor puts all of it.
+        state
+            .clipboard_mut()
+            .store(ClipboardType::Clipboard, "x\ny\nz".to_string());
+        frame.collapse_cursors();
+        frame.place_cursor(0, 0, false);
+        frame.add_cursor(1, 0);
+        assert!(state.paste(&mut frame, ClipboardType::Clipboard));
+        assert_eq!(text(&frame), "x\ny\nzaa\nx\ny\nzbb\ncc\n");
+    }
+
+    #[test]
+    fn selection_of_each_cursor() {
+        let mut state = ClipboardState::new(Box::new(MemoryClipboard::new()));
+        let mut frame = frame("one\ntwo\nsix\n");
+
+        // each cursor yanks its own selection and puts it back.
+        select(&mut frame, (0, 0), (0, 2));
+        frame.handle_operation(Operation::AddCursorBelow);
+        frame.handle_operation(Operation::AddCursorBelow);
+        assert!(state.yank(&frame, Register::Named('a')));
+        assert_eq!(
+            state.registers().load(Register::Named('a')),
+            Some(&vec!["on".to_string(), "tw".to_string(), "si".to_string()])
+        );
+        assert_eq!(state.registers().kill_ring().len(), 0);
+
+        assert!(state.delete(&mut frame, Register::Unnamed));
+        assert_eq!(text(&frame), "e\no\nx\n");
+        assert_eq!(state.registers().kill_ring().len(), 1);
+        assert!(state.put(&mut frame, Register::Named('a')));
+        assert_eq!(text(&frame), "one\ntwo\nsix\n");
+    }
+}
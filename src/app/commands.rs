@@ -0,0 +1,337 @@
+// Commands.
+// Shell commands typed by the user, run in the background with `sh -c`, `cmd /C` on windows. The
+// output of a command is shown in a scratch buffer, a filter is given the selection or the whole
+// buffer on stdin and its output replaces it. A command is killed when it runs longer than the
+// timeout or when it's cancelled, only one command runs at a time.
+
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::core::{Buffer, BufferId};
+
+/// how often a running command is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// what the output of a command is for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandTarget {
+    /// shown in a scratch buffer.
+    Output,
+    /// replaces characters of a buffer, unless the buffer was edited at another revision.
+    Filter {
+        buffer: BufferId,
+        range: Range<usize>,
+        revision: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// the command succeeded, with its stdout.
+    Success(String),
+    /// the command exited with an error, with its exit code and stderr.
+    Failed(Option<i32>, String),
+    TimedOut,
+    Cancelled,
+    /// the command couldn't be run.
+    Error(String),
+}
+
+/// how a command ended.
+#[derive(Debug, Clone)]
+pub struct CommandResult {
+    pub id: u64,
+    pub outcome: Outcome,
+}
+
+pub type CommandCallback = Arc<dyn Fn(CommandResult) + Send + Sync>;
+
+/// the command that is running.
+struct Running {
+    id: u64,
+    command: String,
+    target: CommandTarget,
+    cancelled: Arc<AtomicBool>,
+}
+
+pub struct Commands {
+    next_id: u64,
+    running: Option<Running>,
+    /// reports how commands ended, commands aren't run without it.
+    on_result: Option<CommandCallback>,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            running: None,
+            on_result: None,
+        }
+    }
+
+    pub fn set_callback(&mut self, on_result: CommandCallback) {
+        self.on_result = Some(on_result);
+    }
+
+    /// the command that is running.
+    pub fn running(&self) -> Option<&str> {
+        self.running
+            .as_ref()
+            .map(|running| running.command.as_str())
+    }
+
+    /// runs a command in the background, the input is written to its stdin. Returns false if
+    /// another command is running.
+    pub fn run(
+        &mut self,
+        command: &str,
+        input: Option<String>,
+        target: CommandTarget,
+        timeout: Duration,
+    ) -> bool {
+        let on_result = match self.on_result.as_ref() {
+            Some(on_result) if self.running.is_none() => on_result.clone(),
+            _ => return false,
+        };
+        self.next_id += 1;
+        let id = self.next_id;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.running = Some(Running {
+            id,
+            command: command.to_string(),
+            target,
+            cancelled: cancelled.clone(),
+        });
+
+        let command = command.to_string();
+        kea::utils::spawn_thread("command", move || {
+            let outcome = execute(&command, input.as_deref(), timeout, &cancelled);
+            on_result(CommandResult { id, outcome });
+        });
+        true
+    }
+
+    /// kills the running command, returns false if none is running.
+    pub fn cancel(&mut self) -> bool {
+        match self.running.as_ref() {
+            Some(running) => {
+                running.cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// the command that ended and what its output is for, None if the result is of a command
+    /// that isn't running.
+    pub fn finished(&mut self, result: &CommandResult) -> Option<(String, CommandTarget)> {
+        match self.running.take() {
+            Some(running) if running.id == result.id => Some((running.command, running.target)),
+            running => {
+                self.running = running;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+    let mut shell = Command::new("sh");
+    // the command gets a process group of its own, the processes it starts are killed with it.
+    shell.arg("-c").arg(command).process_group(0);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// kills a command and the processes it started, they keep its pipes open otherwise.
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // SAFETY: kill only sends a signal, the negative pid is the process group of the command.
+    // The group id isn't reused while a process of the group is left.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    child.wait().ok();
+}
+
+#[cfg(windows)]
+fn kill(child: &mut Child) {
+    child.kill().ok();
+    child.wait().ok();
+}
+
+/// reads a pipe of a command on a thread of its own, the output is sent when the pipe closes.
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    kea::utils::spawn_thread("command output", move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut output).ok();
+        }
+        sender.send(output).ok();
+    });
+    receiver
+}
+
+/// runs a command in the current directory and waits until it exits, is cancelled or times
+/// out.
+pub fn execute(
+    command: &str,
+    input: Option<&str>,
+    timeout: Duration,
+    cancelled: &AtomicBool,
+) -> Outcome {
+    let mut child = match shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return Outcome::Error(format!("failed to run '{}': {}", command, e)),
+    };
+
+    // the pipes are written and read on threads of their own, a command blocks when a pipe is
+    // full. Without input stdin is closed right away.
+    let stdin = child.stdin.take();
+    if let (Some(mut stdin), Some(input)) = (stdin, input) {
+        let input = input.to_string();
+        kea::utils::spawn_thread("command input", move || {
+            stdin.write_all(input.as_bytes()).ok();
+        });
+    }
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    // the command is done when it exited and its pipes are closed, a process it started in
+    // the background can keep them open after it exited.
+    let deadline = Instant::now() + timeout;
+    let (mut status, mut out, mut err) = (None, None, None);
+    loop {
+        if status.is_none() {
+            match child.try_wait() {
+                Ok(exited) => status = exited,
+                Err(e) => {
+                    kill(&mut child);
+                    return Outcome::Error(format!("failed to wait for '{}': {}", command, e));
+                }
+            }
+        }
+        if out.is_none() {
+            out = stdout.try_recv().ok();
+        }
+        if err.is_none() {
+            err = stderr.try_recv().ok();
+        }
+        if let (Some(status), Some(out), Some(err)) = (status, out.as_ref(), err.as_ref()) {
+            let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+            return if status.success() {
+                Outcome::Success(text(out))
+            } else {
+                Outcome::Failed(status.code(), text(err))
+            };
+        }
+        if cancelled.load(Ordering::SeqCst) {
+            kill(&mut child);
+            return Outcome::Cancelled;
+        }
+        if Instant::now() >= deadline {
+            kill(&mut child);
+            return Outcome::TimedOut;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// replaces the input of a filter with its output as a single edit, returns where the output
+/// starts. Commands end their output with a line break, input without one gets none. None if
+/// the buffer was edited since the input was taken.
+pub fn apply_filter(
+    buffer: &mut Buffer,
+    range: Range<usize>,
+    revision: usize,
+    output: &str,
+) -> Option<usize> {
+    if buffer.revision() != revision {
+        return None;
+    }
+    let mut output = output;
+    if !buffer.text(range.clone()).ends_with('\n') {
+        output = output.strip_suffix('\n').unwrap_or(output);
+    }
+    buffer.begin_edit();
+    buffer.remove(range.clone());
+    buffer.insert(range.start, output);
+    buffer.end_edit();
+    Some(range.start)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn outcomes() {
+        let run = |command: &str, input: Option<&str>, timeout: u64| {
+            let cancelled = AtomicBool::new(false);
+            execute(command, input, Duration::from_millis(timeout), &cancelled)
+        };
+        assert_eq!(
+            run("sort", Some("b\nc\na\n"), 5000),
+            Outcome::Success("a\nb\nc\n".to_string())
+        );
+        assert_eq!(
+            run("echo oops >&2; exit 3", None, 5000),
+            Outcome::Failed(Some(3), "oops\n".to_string())
+        );
+        assert_eq!(run("sleep 5", None, 50), Outcome::TimedOut);
+
+        let cancelled = AtomicBool::new(true);
+        let outcome = execute("sleep 5", None, Duration::from_secs(5), &cancelled);
+        assert_eq!(outcome, Outcome::Cancelled);
+
+        // a process left in the background holds the output open, it's killed with the command.
+        let started = Instant::now();
+        assert_eq!(run("sleep 5 & echo started", None, 200), Outcome::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn filters() {
+        let mut buffer = Buffer::scratch("b\na\nc", 0);
+        let revision = buffer.revision();
+        // the selection doesn't end with a line break, the one of the output is dropped.
+        assert_eq!(apply_filter(&mut buffer, 0..3, revision, "a\nb\n"), Some(0));
+        assert_eq!(buffer.content().to_string(), "a\nb\nc");
+
+        let revision = buffer.revision();
+        assert_eq!(
+            apply_filter(&mut buffer, 0..6, revision, "C\nB\nA\n"),
+            Some(0)
+        );
+        assert_eq!(buffer.content().to_string(), "C\nB\nA");
+        // the filter is a single edit.
+        buffer.undo();
+        assert_eq!(buffer.content().to_string(), "a\nb\nc");
+
+        // a buffer edited while the filter ran is left as it is.
+        let revision = buffer.revision();
+        buffer.insert(0, "x");
+        assert_eq!(apply_filter(&mut buffer, 0..2, revision, "y"), None);
+        assert_eq!(buffer.content().to_string(), "xa\nb\nc");
+    }
+}
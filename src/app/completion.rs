@@ -0,0 +1,236 @@
+// Completion Sources.
+// The candidates of a completion are collected by sources on a background thread so a slow source
+// never delays drawing. Queries are numbered, a query waiting behind a newer one is dropped and
+// the results of a query that was replaced while it ran are ignored by the application. The
+// language server is asked separately, its answer arrives with the other lsp events.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+
+use log::error;
+use ropey::Rope;
+
+use super::snippets::{read_snippets, SnippetDef};
+use crate::ui::completion::{fuzzy_score, CompletionItem, CompletionKind};
+
+/// the most candidates a source returns for a query.
+const MAX_ITEMS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct CompletionQuery {
+    pub id: u64,
+    /// the word before the cursor.
+    pub prefix: String,
+    /// the directory of a path before the word, e.g. `src/` of `src/ma`.
+    pub directory: Option<PathBuf>,
+    /// the language of the buffer, used to find its snippets.
+    pub language: Option<String>,
+    /// the contents of the open buffers, the active buffer first.
+    pub texts: Vec<Rope>,
+}
+
+/// A source of completion candidates, sources run on the completion thread.
+pub trait CompletionSource: Send {
+    fn complete(&mut self, query: &CompletionQuery) -> Vec<CompletionItem>;
+}
+
+/// the words of the open buffers.
+pub struct BufferWords;
+
+impl CompletionSource for BufferWords {
+    fn complete(&mut self, query: &CompletionQuery) -> Vec<CompletionItem> {
+        // every word would match an empty prefix.
+        if query.prefix.is_empty() {
+            return Vec::new();
+        }
+
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for text in query.texts.iter() {
+            for line in text.lines() {
+                let line = line.to_string();
+                for word in line.split(|c: char| !is_word_char(c)) {
+                    if word.chars().count() > 1
+                        && word != query.prefix
+                        && fuzzy_score(&query.prefix, word).is_some()
+                        && seen.insert(word.to_string())
+                    {
+                        items.push(CompletionItem::new(word, CompletionKind::Word));
+                    }
+                }
+            }
+        }
+        best(&query.prefix, items)
+    }
+}
+
+/// the entries of the directory of a path before the cursor.
+pub struct FilePaths;
+
+impl CompletionSource for FilePaths {
+    fn complete(&mut self, query: &CompletionQuery) -> Vec<CompletionItem> {
+        let directory = match query.directory.as_ref() {
+            Some(directory) => directory,
+            None => return Vec::new(),
+        };
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let items = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let mut name = entry.file_name().into_string().ok()?;
+                if entry.file_type().map_or(false, |ty| ty.is_dir()) {
+                    name.push('/');
+                }
+                fuzzy_score(&query.prefix, &name)?;
+                Some(CompletionItem::new(name, CompletionKind::Path))
+            })
+            .collect();
+        best(&query.prefix, items)
+    }
+}
+
+/// the snippets of the language of the buffer, a snippet completes its name.
+pub struct Snippets {
+    dirs: Vec<PathBuf>,
+    /// the snippets of each language, read when the language is first queried.
+    languages: HashMap<String, Vec<SnippetDef>>,
+}
+
+impl Snippets {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self {
+            dirs,
+            languages: HashMap::new(),
+        }
+    }
+
+    /// the snippets of a language, the files of later directories replace snippets of the
+    /// same name.
+    fn load(dirs: &[PathBuf], language: &str) -> Vec<SnippetDef> {
+        let mut snippets: Vec<SnippetDef> = Vec::new();
+        for dir in dirs {
+            let path = dir.join(format!("{}.yml", language));
+            match read_snippets(&path) {
+                Ok(file) => {
+                    for snippet in file {
+                        snippets.retain(|other| other.name != snippet.name);
+                        snippets.push(snippet);
+                    }
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
+        snippets
+    }
+}
+
+impl CompletionSource for Snippets {
+    fn complete(&mut self, query: &CompletionQuery) -> Vec<CompletionItem> {
+        let language = match query.language.as_ref() {
+            Some(language) => language,
+            None => return Vec::new(),
+        };
+        let dirs = &self.dirs;
+        let snippets = self
+            .languages
+            .entry(language.clone())
+            .or_insert_with(|| Self::load(dirs, language));
+
+        let items = snippets
+            .iter()
+            .filter(|snippet| fuzzy_score(&query.prefix, &snippet.name).is_some())
+            .map(|snippet| {
+                let item = CompletionItem::new(snippet.name.as_str(), CompletionKind::Snippet)
+                    .with_text(snippet.body.as_str());
+                match snippet.description.as_ref() {
+                    Some(description) => item.with_detail(description.as_str()),
+                    None => item,
+                }
+            })
+            .collect();
+        best(&query.prefix, items)
+    }
+}
+
+/// the items that match the prefix best, at most MAX_ITEMS.
+fn best(prefix: &str, mut items: Vec<CompletionItem>) -> Vec<CompletionItem> {
+    items.sort_by_cached_key(|item| std::cmp::Reverse(fuzzy_score(prefix, &item.label)));
+    items.truncate(MAX_ITEMS);
+    items
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Runs the sources of completions on a thread.
+pub struct Completer {
+    sender: Sender<CompletionQuery>,
+}
+
+impl Completer {
+    /// on_items is called on the completion thread with the id of a query and its candidates.
+    pub fn new<F>(mut sources: Vec<Box<dyn CompletionSource>>, on_items: F) -> Self
+    where
+        F: Fn(u64, Vec<CompletionItem>) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<CompletionQuery>();
+        kea::utils::spawn_thread("completion", move || {
+            while let Ok(mut query) = receiver.recv() {
+                // only the latest query matters, the others were replaced while waiting.
+                while let Ok(newer) = receiver.try_recv() {
+                    query = newer;
+                }
+                let items = sources
+                    .iter_mut()
+                    .flat_map(|source| source.complete(&query))
+                    .collect();
+                on_items(query.id, items);
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn query(&self, query: CompletionQuery) {
+        self.sender.send(query).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sources() {
+        let mut query = CompletionQuery {
+            id: 1,
+            prefix: "ren".to_string(),
+            directory: None,
+            language: Some("rust".to_string()),
+            texts: vec![Rope::from_str(
+                "fn render(renderer: &mut Renderer) {}\nren ren",
+            )],
+        };
+        let labels = |items: Vec<CompletionItem>| -> Vec<String> {
+            items.into_iter().map(|item| item.label).collect()
+        };
+        assert_eq!(
+            labels(BufferWords.complete(&query)),
+            vec!["render", "renderer", "Renderer"]
+        );
+
+        query.prefix = "ma".to_string();
+        query.directory = Some(PathBuf::from("src"));
+        assert!(labels(FilePaths.complete(&query)).contains(&"main.rs".to_string()));
+
+        let mut snippets = Snippets::new(vec![PathBuf::from("config/snippets")]);
+        let items = snippets.complete(&query);
+        assert_eq!(items[0].label, "match");
+        assert!(items[0].text.starts_with("match ${1:value} {"));
+    }
+}
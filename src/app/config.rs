@@ -1,43 +1,277 @@
+// Config.
+// The settings are read from several files, each layer overrides the settings it sets and keeps
+// the others, lowest first:
+//
+//     the defaults of every setting
+//     ./config/kea.yml                  the defaults shipped with the editor
+//     $XDG_CONFIG_HOME/kea/kea.yml      the settings of the user, ~/.config without XDG_CONFIG_HOME
+//     ./.kea.yml                        the settings of a project
+//     --config <path>                   a file given on the command line
+//
+// A file with an error is skipped, the error names the file and the line.
+//
+// Language servers are configured by language, the server of a file is the first one listing the
+// extension of the file.
+//
+// Linters are configured by language too, see linters.rs for how their output is read.
+//
+// The font family is the name of an installed family, the path of a font file, or the path of a
+// directory with the font files of a family, e.g. `./fonts`. Relative paths are relative to the
+// working directory.
+//
+// Snippets are read from snippets/<language>.yml in the config directory of the user and from
+// ./config/snippets/<language>.yml.
+//
+// The color scheme names a theme, it is read from kea/themes/<name>.yml in the config directory
+// of the user or from ./config/themes/<name>.yml.
+
 extern crate serde;
 extern crate serde_yaml;
 
 use crate::font::{FontDesc, Properties};
+use crate::renderer::Theme;
+use crate::ui::key_bindings::Key;
+use crate::ui::{FrameOptions, Operation};
+use serde::{Deserialize, Deserializer};
+use serde_yaml::Value;
 use std::collections::BTreeMap;
-use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::application::{MAX_FONT_SIZE, MIN_FONT_SIZE};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    #[error("failed to read config '{0}': {1}")]
+    ReadFailed(PathBuf, String),
+    /// the message of the parser includes the line and column.
+    #[error("invalid config '{path}': {message}")]
+    Invalid {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct FontConfig {
     family: String,
+    #[serde(deserialize_with = "font_size")]
     size: f32,
+    /// should the shaper combine characters into ligatures.
+    ligatures: bool,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            family: "monospace".to_string(),
+            size: 14.0,
+            ligatures: true,
+        }
+    }
+}
+
+fn font_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let size = f32::deserialize(deserializer)?;
+    if size < MIN_FONT_SIZE || size > MAX_FONT_SIZE {
+        return Err(serde::de::Error::custom(format!(
+            "font size must be between {} and {}",
+            MIN_FONT_SIZE, MAX_FONT_SIZE
+        )));
+    }
+    Ok(size)
+}
+
+const MAX_TAB_WIDTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorConfig {
+    #[serde(deserialize_with = "tab_width")]
+    tab_width: usize,
+    /// should long lines continue on the next row.
+    wrap: bool,
+    /// should line numbers be shown.
+    gutter: bool,
+    /// should the messages of diagnostics be shown after the text of their line.
+    virtual_text: bool,
+    /// should the commit that last changed the line of the cursor be shown after it.
+    blame: bool,
+    /// the seconds a shell command may run before it's killed.
+    command_timeout: u64,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            tab_width: crate::renderer::DEFAULT_TAB_WIDTH,
+            wrap: false,
+            gutter: false,
+            virtual_text: true,
+            blame: true,
+            command_timeout: 10,
+        }
+    }
+}
+
+fn tab_width<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let width = usize::deserialize(deserializer)?;
+    if width == 0 || width > MAX_TAB_WIDTH {
+        return Err(serde::de::Error::custom(format!(
+            "tab width must be between 1 and {}",
+            MAX_TAB_WIDTH
+        )));
+    }
+    Ok(width)
+}
+
+/// a language server, started when the first file with one of its extensions is opened.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LanguageServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// the extensions of the files of the language, without the dot.
+    pub extensions: Vec<String>,
+}
+
+/// a command that checks the files of a language, its output is read as diagnostics.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinterConfig {
+    pub command: String,
+    /// `{file}` is replaced with the path of the file, it is the last argument otherwise.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// the languages of common extensions, the extensions of language servers are added to them.
+const LANGUAGES: [(&str, &str); 16] = [
+    ("rs", "rust"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("hpp", "cpp"),
+    ("py", "python"),
+    ("js", "javascript"),
+    ("ts", "typescript"),
+    ("go", "go"),
+    ("sh", "shell"),
+    ("md", "markdown"),
+    ("toml", "toml"),
+    ("yml", "yaml"),
+    ("yaml", "yaml"),
+    ("json", "json"),
+];
+
+/// bindings are written as `ctrl+alt+up: add_cursor_above`.
+fn key_bindings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Key, Operation>, D::Error> {
+    let bindings = BTreeMap::<String, String>::deserialize(deserializer)?;
+    let mut parsed = BTreeMap::new();
+    for (key, operation) in bindings {
+        let key = key.parse().map_err(serde::de::Error::custom)?;
+        let operation = operation
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("unknown operation '{}'", operation)))?;
+        parsed.insert(key, operation);
+    }
+    Ok(parsed)
 }
 
 /// user defined and default settings to be used by the editor
 /// Used to set: theme, font, font size, key bindings
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     font: FontConfig,
-    theme_scheme: String,
+    color_scheme: String,
+    editor: EditorConfig,
+    #[serde(deserialize_with = "key_bindings")]
+    key_bindings: BTreeMap<Key, Operation>,
+    /// the language servers by the name of their language, e.g. `rust`.
+    language_servers: BTreeMap<String, LanguageServerConfig>,
+    /// the linters by the name of their language.
+    linters: BTreeMap<String, LinterConfig>,
 }
 
 impl Config {
-    pub fn load_config() -> Self {
-        let mut f = std::fs::File::open("./config/kea.yml").unwrap();
-        let mut content = String::new();
-        f.read_to_string(&mut content).unwrap();
-        let config: BTreeMap<String, BTreeMap<String, String>> =
-            serde_yaml::from_str(&content).unwrap();
-        let font_name = config["font"]["family"].as_str();
-        let size = config["font"]["size"]
-            .as_str()
-            .parse::<f32>()
-            .unwrap_or(14f32);
-        Self {
-            font: FontConfig {
-                family: font_name.to_string(),
-                size,
-            },
-            theme_scheme: "".to_string(),
+    /// loads the layers of the config, the file of `--config` is the last layer. The errors of
+    /// skipped files are returned so they can be reported once logging is set up.
+    pub fn load_config(config_file: Option<PathBuf>) -> (Self, Vec<ConfigError>) {
+        let mut errors = Vec::new();
+        let mut config = Value::Mapping(Default::default());
+        for path in Self::files(config_file.as_deref()).iter() {
+            // only the file given on the command line has to exist.
+            if !path.exists() && Some(path) != config_file.as_ref() {
+                continue;
+            }
+            match read_layer(path) {
+                Ok(layer) => merge(&mut config, layer),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        // every layer is valid on its own so the merged layers are too.
+        let config = serde_yaml::from_value(config).unwrap_or_else(|e| {
+            errors.push(ConfigError::Invalid {
+                path: PathBuf::new(),
+                line: None,
+                message: e.to_string(),
+            });
+            Self::default()
+        });
+        (config, errors)
+    }
+
+    /// the files of each layer, lowest first. They don't have to exist.
+    pub fn files(config_file: Option<&Path>) -> Vec<PathBuf> {
+        let mut layers = vec![PathBuf::from("./config/kea.yml")];
+        if let Some(dir) = user_config_dir() {
+            layers.push(dir.join("kea").join("kea.yml"));
+        }
+        layers.push(PathBuf::from("./.kea.yml"));
+        layers.extend(config_file.map(Path::to_path_buf));
+        layers
+    }
+
+    /// the file of the theme of the color scheme, None without a color scheme.
+    pub fn theme_file(&self) -> Option<PathBuf> {
+        if self.color_scheme.is_empty() {
+            return None;
+        }
+        let name = format!("{}.yml", self.color_scheme);
+        let user_theme = user_config_dir().map(|dir| dir.join("kea").join("themes").join(&name));
+        match user_theme {
+            Some(path) if path.exists() => Some(path),
+            _ => Some(Path::new("./config/themes").join(name)),
+        }
+    }
+
+    /// the theme of the color scheme, the default colors are used without a color scheme.
+    pub fn load_theme(&self) -> Result<Theme, ConfigError> {
+        let path = match self.theme_file() {
+            Some(path) => path,
+            None => return Ok(Theme::default()),
+        };
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| ConfigError::ReadFailed(path.clone(), e.to_string()))?;
+        if is_empty(&content) {
+            return Ok(Theme::default());
         }
+        serde_yaml::from_str(&content).map_err(|e| invalid(&path, e))
+    }
+
+    /// the settings of a single file, settings it doesn't have are the defaults.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let layer = read_layer(path)?;
+        serde_yaml::from_value(layer).map_err(|e| invalid(path, e))
     }
 
     pub fn font_name(&self) -> &str {
@@ -51,4 +285,210 @@ impl Config {
     pub fn font_size(&self) -> f32 {
         self.font.size
     }
+
+    pub fn ligatures(&self) -> bool {
+        self.font.ligatures
+    }
+
+    pub fn color_scheme(&self) -> &str {
+        self.color_scheme.as_str()
+    }
+
+    pub fn tab_width(&self) -> usize {
+        self.editor.tab_width
+    }
+
+    /// how long a shell command may run before it's killed.
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_secs(self.editor.command_timeout.max(1))
+    }
+
+    pub fn frame_options(&self) -> FrameOptions {
+        FrameOptions {
+            wrap: self.editor.wrap,
+            gutter: self.editor.gutter,
+            virtual_text: self.editor.virtual_text,
+            blame: self.editor.blame,
+        }
+    }
+
+    /// the bindings that replace or add to the default key bindings.
+    pub fn key_bindings(&self) -> &BTreeMap<Key, Operation> {
+        &self.key_bindings
+    }
+
+    /// the language of a file by its extension.
+    pub fn language(&self, path: &Path) -> Option<String> {
+        if let Some((language, _)) = self.language_server(path) {
+            return Some(language.to_string());
+        }
+        let extension = path.extension()?.to_str()?;
+        LANGUAGES
+            .iter()
+            .find(|(e, _)| *e == extension)
+            .map(|(_, language)| language.to_string())
+    }
+
+    /// the directories of the snippet files, lowest first.
+    pub fn snippet_dirs() -> Vec<PathBuf> {
+        let mut dirs = vec![PathBuf::from("./config/snippets")];
+        if let Some(dir) = user_config_dir() {
+            dirs.push(dir.join("kea").join("snippets"));
+        }
+        dirs
+    }
+
+    /// the language and the language server of a file, None if no server handles the file.
+    pub fn language_server(&self, path: &Path) -> Option<(&str, &LanguageServerConfig)> {
+        let extension = path.extension()?.to_str()?;
+        self.language_servers
+            .iter()
+            .find(|(_, server)| server.extensions.iter().any(|e| e == extension))
+            .map(|(language, server)| (language.as_str(), server))
+    }
+
+    /// the linter of the language of a file.
+    pub fn linter(&self, path: &Path) -> Option<&LinterConfig> {
+        self.linters.get(&self.language(path)?)
+    }
+}
+
+/// $XDG_CONFIG_HOME, or ~/.config when it isn't set.
+fn user_config_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+}
+
+/// reads a file of the config. The file is checked on its own so an error can point at its
+/// line, the merged layers don't know where a value came from.
+fn read_layer(path: &Path) -> Result<Value, ConfigError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError::ReadFailed(path.to_path_buf(), e.to_string()))?;
+    if is_empty(&content) {
+        return Ok(Value::Mapping(Default::default()));
+    }
+
+    serde_yaml::from_str::<Config>(&content).map_err(|e| invalid(path, e))?;
+    serde_yaml::from_str(&content).map_err(|e| invalid(path, e))
+}
+
+/// an empty file or one with only comments sets nothing.
+fn is_empty(content: &str) -> bool {
+    content.lines().all(|line| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with('#')
+    })
+}
+
+fn invalid(path: &Path, e: serde_yaml::Error) -> ConfigError {
+    ConfigError::Invalid {
+        path: path.to_path_buf(),
+        line: e.location().map(|location| location.line()),
+        message: e.to_string(),
+    }
+}
+
+/// overrides the settings of config with the settings of layer, nested settings are merged.
+fn merge(config: &mut Value, layer: Value) {
+    match (config, layer) {
+        (Value::Mapping(config), Value::Mapping(layer)) => {
+            for (key, value) in layer {
+                match config.get_mut(&key) {
+                    Some(setting) => merge(setting, value),
+                    None => {
+                        config.insert(key, value);
+                    }
+                }
+            }
+        }
+        (config, layer) => *config = layer,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn defaults_and_layers() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let base = dir.join("base.yml");
+        let project = dir.join("project.yml");
+        fs::write(
+            &base,
+            "font:\n  family: Fira Code\n  size: 12\neditor:\n  tab_width: 8\n  gutter: true\n",
+        )
+        .unwrap();
+        fs::write(
+            &project,
+            "# only the size\nfont:\n  size: 16\nkey_bindings:\n  ctrl+shift+c: copy\n\
+             language_servers:\n  rust:\n    command: rust-analyzer\n    extensions: [rs]\n\
+             linters:\n  python:\n    command: flake8\n",
+        )
+        .unwrap();
+
+        let mut config = Value::Mapping(Default::default());
+        merge(&mut config, read_layer(&base).unwrap());
+        merge(&mut config, read_layer(&project).unwrap());
+        let config: Config = serde_yaml::from_value(config).unwrap();
+        assert_eq!(config.font_name(), "Fira Code");
+        assert_eq!(config.font_size(), 16.0);
+        assert!(config.ligatures());
+        assert_eq!(config.tab_width(), 8);
+        assert!(config.frame_options().gutter && !config.frame_options().wrap);
+        let key: Key = "ctrl+shift+c".parse().unwrap();
+        assert_eq!(config.key_bindings().get(&key), Some(&Operation::Copy));
+        let (language, server) = config.language_server(Path::new("src/main.rs")).unwrap();
+        assert_eq!(
+            (language, server.command.as_str()),
+            ("rust", "rust-analyzer")
+        );
+        assert!(config.language_server(Path::new("README.md")).is_none());
+        assert_eq!(config.linter(Path::new("a.py")).unwrap().command, "flake8");
+        assert!(config.linter(Path::new("a.rs")).is_none());
+
+        fs::write(&base, "").unwrap();
+        assert_eq!(Config::from_file(&base).unwrap(), Config::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("kea.yml");
+
+        fs::write(&path, "# too large\nfont:\n  size: 1000\n").unwrap();
+        match Config::from_file(&path) {
+            Err(ConfigError::Invalid { line, message, .. }) => {
+                assert_eq!(line, Some(3));
+                assert!(message.contains("font size must be between"));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        fs::write(&path, "font:\n  famly: Fira Code\n").unwrap();
+        assert!(matches!(
+            Config::from_file(&path),
+            Err(ConfigError::Invalid { line: Some(2), .. })
+        ));
+
+        fs::write(&path, "key_bindings:\n  ctrl+s: save_everything\n").unwrap();
+        match Config::from_file(&path) {
+            Err(ConfigError::Invalid { message, .. }) => {
+                assert!(message.contains("unknown operation 'save_everything'"));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // the file given on the command line has an error, the other layers are used.
+        let (config, errors) = Config::load_config(Some(path.clone()));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(config, Config::load_config(None).0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,133 @@
+// Diff View.
+// Two frames side by side showing the differences between their buffers, e.g. a buffer and its
+// file on disk. Changed lines and their changed characters are marked and the shorter side of
+// each hunk is padded with filler rows, so matching lines stay on the same row while either
+// side is scrolled. The diff is computed again whenever one of the buffers was edited.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::core::diff::{self, Hunk};
+use crate::core::{Buffer, BufferId};
+use crate::ui::{Frame, FrameId};
+
+pub struct DiffView {
+    pub left: FrameId,
+    pub right: FrameId,
+    /// the buffer holding the file on disk, when a buffer is compared with its file.
+    pub disk: Option<BufferId>,
+    /// the hunks turning the left buffer into the right one.
+    hunks: Vec<Hunk>,
+    /// the revisions of the buffers the hunks were computed for.
+    revisions: Option<(usize, usize)>,
+    /// the row of the diff at the top of both frames.
+    top: usize,
+}
+
+impl DiffView {
+    pub fn new(left: FrameId, right: FrameId, disk: Option<BufferId>) -> Self {
+        Self {
+            left,
+            right,
+            disk,
+            hunks: Vec::new(),
+            revisions: None,
+            top: 0,
+        }
+    }
+
+    /// the frame on the other side.
+    pub fn other(&self, id: FrameId) -> FrameId {
+        if id == self.left {
+            self.right
+        } else {
+            self.left
+        }
+    }
+
+    /// computes the diff again if either buffer changed and scrolls the side that didn't
+    /// scroll to the row the other one scrolled to. Returns whether the frames changed, None if
+    /// a frame was closed.
+    pub fn sync(&mut self, frames: &mut BTreeMap<FrameId, Frame>) -> Option<bool> {
+        let (left, right) = (frames.get(&self.left)?, frames.get(&self.right)?);
+        let revisions = (left.buffer().revision(), right.buffer().revision());
+        let mut changed = false;
+        if self.revisions != Some(revisions) {
+            changed = true;
+            let old = lines(&left.buffer());
+            let new = lines(&right.buffer());
+            self.hunks = diff::diff(&old, &new);
+            let (left, right) = diff::sides(&old, &new, &self.hunks);
+            self.revisions = Some(revisions);
+            if let Some(frame) = frames.get_mut(&self.left) {
+                frame.set_diff(Some(left));
+            }
+            if let Some(frame) = frames.get_mut(&self.right) {
+                frame.set_diff(Some(right));
+            }
+        }
+
+        let left_top = frames.get(&self.left).map_or(0, Frame::top_row);
+        let right_top = frames.get(&self.right).map_or(0, Frame::top_row);
+        let (top, follower) = if left_top != self.top {
+            (left_top, self.right)
+        } else {
+            (right_top, self.left)
+        };
+        self.top = top;
+        if let Some(frame) = frames.get_mut(&follower) {
+            if frame.top_row() != top {
+                frame.scroll_to_row(top);
+                changed = true;
+            }
+        }
+        Some(changed)
+    }
+
+    /// the lines of a side changed by a hunk.
+    fn lines_of<'a>(&self, hunk: &'a Hunk, left: bool) -> &'a Range<usize> {
+        if left {
+            &hunk.old
+        } else {
+            &hunk.new
+        }
+    }
+
+    /// the hunk changing a line of a side, lines removed on one side are at the line after them.
+    pub fn hunk_at(&self, line: usize, left: bool) -> Option<&Hunk> {
+        self.hunks.iter().find(|hunk| {
+            let lines = self.lines_of(hunk, left);
+            lines.contains(&line) || (lines.is_empty() && lines.start == line)
+        })
+    }
+
+    /// the first line of the next or previous hunk of a side.
+    pub fn goto_hunk(&self, line: usize, left: bool, next: bool) -> Option<usize> {
+        let mut starts = self
+            .hunks
+            .iter()
+            .map(|hunk| self.lines_of(hunk, left).start);
+        if next {
+            starts.find(|&start| start > line)
+        } else {
+            starts.rev().find(|&start| start < line)
+        }
+    }
+}
+
+/// the lines of a buffer without their line endings.
+fn lines(buffer: &Buffer) -> Vec<String> {
+    buffer.request_lines(0, buffer.num_lines())
+}
+
+/// the characters of a range of lines of a buffer, including their line endings.
+pub fn line_chars(buffer: &Buffer, lines: &Range<usize>) -> Range<usize> {
+    let char_index = |line: usize| {
+        if line < buffer.num_lines() {
+            buffer.char_index(line, 0)
+        } else {
+            buffer.len_chars()
+        }
+    };
+    char_index(lines.start)..char_index(lines.end)
+}
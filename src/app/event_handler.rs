@@ -8,11 +8,23 @@ use crate::glutin::{
 use std::sync::mpsc::Receiver;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use super::{App, Application};
 use crate::core::Update;
+use crate::pathfinder_geometry::vector::{vec2f, Vector2F};
 use crate::renderer::{Renderable, Renderer, Window};
 
+/// clicks within this interval of the previous click count as a double or triple click.
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+/// how far in pixels the mouse can move between the clicks of a double or triple click.
+const MULTI_CLICK_DISTANCE: f32 = 4.0;
+/// how often the view scrolls while a selection is dragged past the edge of a frame.
+const AUTO_SCROLL_INTERVAL: Duration = Duration::from_millis(50);
+/// how often the changes of modified buffers are written to swap files.
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClickState {
     None,
     Single,
@@ -20,33 +32,78 @@ pub enum ClickState {
     Triple,
 }
 
-// pub struct Mouse {
-//     pub x: f32,
-//     pub y: f32,
-//     pub left_button: ElementState,
-//     pub right_button: ElementState,
-//     pub middle_button: ElementState,
-//     pub click_state: ClickState,
-// }
-
-// impl Default for Mouse {
-//     fn default() -> Self {
-//         Self {
-//             x: 0.0,
-//             y: 0.0,
-//             left_button: ElementState::Released,
-//             right_button: ElementState::Released,
-//             middle_button: ElementState::Released,
-//             click_state: ClickState::None,
-//         }
-//     }
-// }
+pub struct Mouse {
+    pub x: f32,
+    pub y: f32,
+    pub left_button: ElementState,
+    pub right_button: ElementState,
+    pub middle_button: ElementState,
+    pub click_state: ClickState,
+    /// the cursor moved in the window since it last left it. No moves are reported while a
+    /// file is dragged from another program, the position is then stale.
+    inside: bool,
+    /// when and where the left button was last pressed.
+    last_click: Option<(Instant, Vector2F)>,
+    /// the next time the view can be scrolled while dragging.
+    next_scroll: Instant,
+}
+
+impl Default for Mouse {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            left_button: ElementState::Released,
+            right_button: ElementState::Released,
+            middle_button: ElementState::Released,
+            click_state: ClickState::None,
+            inside: false,
+            last_click: None,
+            next_scroll: Instant::now(),
+        }
+    }
+}
+
+impl Mouse {
+    pub fn position(&self) -> Vector2F {
+        vec2f(self.x, self.y)
+    }
+
+    /// the position of the cursor if it is known to be in the window.
+    pub fn position_inside(&self) -> Option<Vector2F> {
+        if self.inside {
+            Some(self.position())
+        } else {
+            None
+        }
+    }
+
+    /// registers a press of the left button. A press soon after the previous one and close to
+    /// it advances the click state, a fourth click starts again as a single click.
+    pub fn click(&mut self, now: Instant) -> ClickState {
+        let position = self.position();
+        let repeated = self.last_click.map_or(false, |(time, last)| {
+            now.duration_since(time) <= MULTI_CLICK_INTERVAL
+                && (position - last).length() <= MULTI_CLICK_DISTANCE
+        });
+
+        self.click_state = match self.click_state {
+            ClickState::Single if repeated => ClickState::Double,
+            ClickState::Double if repeated => ClickState::Triple,
+            _ => ClickState::Single,
+        };
+        self.last_click = Some((now, position));
+        self.click_state
+    }
+}
 
 pub struct EventHandler {
     pub renderer: Renderer,
     pub elp: event_loop::EventLoopProxy<AppEvent>,
     pub modifiers: event::ModifiersState,
-    // pub mouse: Mouse,
+    pub mouse: Mouse,
+    /// when the swap files are written next.
+    pub next_swap: Instant,
 }
 
 impl EventHandler {
@@ -55,6 +112,8 @@ impl EventHandler {
             renderer,
             elp,
             modifiers: event::ModifiersState::empty(),
+            mouse: Mouse::default(),
+            next_swap: Instant::now() + SWAP_INTERVAL,
         }
     }
 
@@ -69,9 +128,7 @@ impl EventHandler {
                     | TouchpadPressure { .. }
                     | CursorEntered { .. }
                     | AxisMotion { .. }
-                    | HoveredFileCancelled
                     | Destroyed
-                    | HoveredFile(_)
                     | Touch(_)
                     | Moved(_) => true,
                     _ => false,
@@ -104,9 +161,7 @@ impl EventHandler {
                     handler.elp.send_event(AppEvent::Exit).ok();
                 }
                 Focused(focus) => {}
-                ReceivedCharacter(ch) => {
-                    // app.handle_character(ch, &handler.modifiers);
-                }
+                ReceivedCharacter(ch) => app.handle_character(ch, &handler.modifiers),
                 KeyboardInput {
                     device_id,
                     input,
@@ -117,12 +172,16 @@ impl EventHandler {
                     position,
                     ..
                 } => {
-                    // let pos = position.cast::<f32>();
-                    // handler.mouse.x = pos.x;
-                    // handler.mouse.y = pos.y;
+                    let pos = position.cast::<f32>();
+                    handler.mouse.x = pos.x;
+                    handler.mouse.y = pos.y;
+                    handler.mouse.inside = true;
+                    if handler.mouse.left_button == ElementState::Pressed {
+                        app.handle_mouse_drag(handler.mouse.position());
+                    }
                 }
                 CursorEntered { device_id } => {}
-                CursorLeft { device_id } => {}
+                CursorLeft { device_id } => handler.mouse.inside = false,
                 MouseWheel {
                     device_id,
                     delta,
@@ -136,35 +195,45 @@ impl EventHandler {
                     state,
                     button,
                     ..
-                } => {
-                    // match button {
-                    //     glutin::event::MouseButton::Left => {
-                    //         handler.mouse.left_button = state;
-                    //     }
-                    //     glutin::event::MouseButton::Right => {
-                    //         handler.mouse.right_button = state;
-                    //     }
-                    //     glutin::event::MouseButton::Middle => {
-                    //         handler.mouse.middle_button = state;
-                    //     }
-                    //     glutin::event::MouseButton::Other(i) => {
-                    //         // other mouse buttons are not supported at the moment.
-                    //     }
-                    // }
-                    // app.handle_mouse_input(&handler.mouse,&modifiers);
-                }
+                } => match button {
+                    glutin::event::MouseButton::Left => {
+                        handler.mouse.left_button = state;
+                        match state {
+                            ElementState::Pressed => {
+                                let click = handler.mouse.click(Instant::now());
+                                let position = handler.mouse.position();
+                                app.handle_mouse_press(position, click, &handler.modifiers);
+                            }
+                            ElementState::Released => app.handle_mouse_release(),
+                        }
+                    }
+                    glutin::event::MouseButton::Right => {
+                        handler.mouse.right_button = state;
+                    }
+                    glutin::event::MouseButton::Middle => {
+                        handler.mouse.middle_button = state;
+                        if state == ElementState::Pressed {
+                            app.handle_middle_click(handler.mouse.position());
+                        }
+                    }
+                    glutin::event::MouseButton::Other(i) => {
+                        // other mouse buttons are not supported at the moment.
+                    }
+                },
                 ModifiersChanged(mods) => {
                     handler.modifiers = mods;
                 }
                 ThemeChanged(theme) => {}
                 // Destroyed => {},
-                // i might what to handle this eventually.
-                // DroppedFile(path) => {},
-                // HoveredFile(path) => {},
-                // HoveredFileCancelled => {},
+                // an event is sent for each file when several files are dragged.
+                HoveredFile(path) => app.handle_file_hover(handler.mouse.position_inside()),
+                HoveredFileCancelled => app.handle_file_hover_cancelled(),
+                DroppedFile(path) => app.handle_dropped_file(path, handler.mouse.position_inside()),
                 _ => {}
             },
             event::Event::NewEvents(start_cause) => {
+                // the files of a drop arrive in one batch of events.
+                app.end_drop();
                 use event::StartCause::*;
                 match start_cause {
                     Init => app.on_init(),
@@ -190,16 +259,36 @@ impl EventHandler {
 
             match event {
                 event::Event::UserEvent(e) => match e {
-                    AppEvent::Exit => *cf = event_loop::ControlFlow::Exit,
+                    AppEvent::Exit => {
+                        app.inner().on_exit();
+                        *cf = event_loop::ControlFlow::Exit;
+                        // nothing else runs, e.g. the stopped language servers aren't synced.
+                        return;
+                    }
+                    AppEvent::FileChanged(path) => app.inner().handle_file_changed(path),
+                    AppEvent::ConfigChanged => app.inner().reload_config(),
+                    AppEvent::Lsp(event) => app.inner().handle_lsp_event(event),
+                    AppEvent::Completions(id, items) => app.inner().handle_completions(id, items),
+                    AppEvent::Lint(result) => app.inner().handle_lint_result(result),
+                    AppEvent::Git(result) => app.inner().handle_git_result(result),
+                    AppEvent::Terminal(id, event) => app.inner().handle_terminal_event(id, event),
+                    AppEvent::Command(result) => app.inner().handle_command_result(result),
                 },
                 event::Event::WindowEvent {
                     event:
                         WindowEvent::ScaleFactorChanged {
-                            scale_factor: _,
-                            new_inner_size: _,
+                            scale_factor,
+                            new_inner_size,
                         },
                     ..
-                } => {}
+                } => {
+                    // handled here because the event borrows the new size and can't be queued.
+                    let size = *new_inner_size;
+                    self.renderer
+                        .update_perspective(size.width as i32, size.height as i32);
+                    app.inner()
+                        .update_dpi_factor(scale_factor as f32, size.width, size.height);
+                }
                 event => unsafe {
                     *cf = event_loop::ControlFlow::Poll;
                     event_queue.push(std::mem::transmute(event));
@@ -211,6 +300,38 @@ impl EventHandler {
                 Self::handle_event(event, self, &mut guard);
             }
 
+            // keep scrolling while a selection is dragged past the edge of the frame, even
+            // if the mouse doesn't move.
+            if self.mouse.left_button == ElementState::Pressed {
+                let now = Instant::now();
+                if now >= self.mouse.next_scroll && guard.auto_scroll(self.mouse.position()) {
+                    self.mouse.next_scroll = now + AUTO_SCROLL_INTERVAL;
+                }
+                if now < self.mouse.next_scroll && *cf != event_loop::ControlFlow::Exit {
+                    *cf = event_loop::ControlFlow::WaitUntil(self.mouse.next_scroll);
+                }
+            }
+
+            guard.sync_language_servers();
+            guard.sync_linters();
+            guard.sync_git();
+            guard.sync_diff();
+
+            // completions are requested once typing pauses.
+            if let Some(due) = guard.completion_due() {
+                if Instant::now() >= due {
+                    guard.request_completion();
+                } else if *cf != event_loop::ControlFlow::Exit {
+                    *cf = event_loop::ControlFlow::WaitUntil(due);
+                }
+            }
+
+            let now = Instant::now();
+            if now >= self.next_swap {
+                guard.write_swap_files();
+                self.next_swap = now + SWAP_INTERVAL;
+            }
+
             if guard.draw_requested() {
                 self.renderer.clear();
 
@@ -222,3 +343,27 @@ impl EventHandler {
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn click_counting() {
+        let mut mouse = Mouse::default();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert_eq!(mouse.click(at(0)), ClickState::Single);
+        assert_eq!(mouse.click(at(100)), ClickState::Double);
+        assert_eq!(mouse.click(at(200)), ClickState::Triple);
+        assert_eq!(mouse.click(at(300)), ClickState::Single);
+
+        // too slow.
+        assert_eq!(mouse.click(at(1000)), ClickState::Single);
+
+        // too far away.
+        mouse.x = 20.0;
+        assert_eq!(mouse.click(at(1100)), ClickState::Single);
+    }
+}
@@ -0,0 +1,452 @@
+// Git.
+// The changes of the buffers whose files are in a git repository, against the version of the
+// file in the index or, when it isn't staged, in HEAD. They are computed in the background
+// whenever a buffer was edited, together with the commit that last changed each line. A hunk
+// can be staged, its lines are written to the index, or reverted, the lines of the index are put
+// back into the buffer. The repository is read with libgit2, no git binary is needed.
+//
+// Blaming a file walks its history, so the blame of the lines in HEAD is kept until HEAD moves
+// and the lines of the buffer are mapped to them by the diff against HEAD.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{Local, TimeZone};
+use git2::{IndexEntry, IndexTime, Oid, Repository};
+use log::error;
+
+use crate::core::diff;
+use crate::core::{self, BufferId, Changes, Core};
+
+#[derive(thiserror::Error, Debug)]
+pub enum GitError {
+    #[error("'{0}' isn't in a git repository")]
+    NoRepository(PathBuf),
+    #[error("git: {0}")]
+    Git(String),
+}
+
+fn git_error(e: git2::Error) -> GitError {
+    GitError::Git(e.message().to_string())
+}
+
+/// the changes of a buffer when it was at a revision.
+#[derive(Debug, Clone)]
+pub struct GitResult {
+    pub buffer: BufferId,
+    pub revision: usize,
+    pub changes: Changes,
+}
+
+pub type GitCallback = Arc<dyn Fn(GitResult) + Send + Sync>;
+
+/// the blame of each line of a file in HEAD, with the commit HEAD was at.
+type Blamed = (Oid, Arc<Vec<String>>);
+
+/// the blame of files in HEAD by the path of the file in its repository.
+#[derive(Clone, Default)]
+pub struct BlameCache(Arc<Mutex<HashMap<PathBuf, Blamed>>>);
+
+pub struct Git {
+    /// the revision of each buffer its changes were last computed for.
+    computed: HashMap<BufferId, usize>,
+    /// the buffers whose changes are being computed, a buffer waits for its last result
+    /// before it is computed again.
+    pending: HashSet<BufferId>,
+    /// reports the results, nothing is computed without it.
+    on_result: Option<GitCallback>,
+    blame: BlameCache,
+}
+
+impl Git {
+    pub fn new() -> Self {
+        Self {
+            computed: HashMap::new(),
+            pending: HashSet::new(),
+            on_result: None,
+            blame: BlameCache::default(),
+        }
+    }
+
+    pub fn set_callback(&mut self, on_result: GitCallback) {
+        self.on_result = Some(on_result);
+    }
+
+    /// computes the changes of the buffers that were edited since their changes were computed.
+    pub fn sync(&mut self, core: &Core) {
+        let on_result = match self.on_result.as_ref() {
+            Some(on_result) => on_result,
+            None => return,
+        };
+        for id in core.buffer_ids() {
+            if self.pending.contains(&id) {
+                continue;
+            }
+            let buffer = match core.get_buffer(&id) {
+                Some(buffer) => buffer,
+                None => continue,
+            };
+            let path = match buffer.path() {
+                Some(path) => core::absolute(path),
+                None => continue,
+            };
+            let revision = buffer.revision();
+            if self.computed.get(&id) == Some(&revision) {
+                continue;
+            }
+            self.computed.insert(id, revision);
+            self.pending.insert(id);
+
+            let text = buffer.content().to_string();
+            let on_result = on_result.clone();
+            let blame = self.blame.clone();
+            kea::utils::spawn_thread("git", move || {
+                let changes = match changes(&path, &text, &blame) {
+                    Ok(changes) => changes,
+                    Err(GitError::NoRepository(_)) => Changes::default(),
+                    Err(e) => {
+                        error!("{}", e);
+                        Changes::default()
+                    }
+                };
+                on_result(GitResult {
+                    buffer: id,
+                    revision,
+                    changes,
+                })
+            });
+        }
+        let buffers: HashSet<BufferId> = core.buffer_ids().into_iter().collect();
+        self.computed.retain(|id, _| buffers.contains(id));
+    }
+
+    /// the changes of a buffer were computed, they can be computed again.
+    pub fn finished(&mut self, result: &GitResult) {
+        self.pending.remove(&result.buffer);
+    }
+
+    /// computes the changes of a buffer again even if it wasn't edited, e.g. when the index
+    /// changed.
+    pub fn refresh(&mut self, id: BufferId) {
+        self.computed.remove(&id);
+    }
+}
+
+/// the hunks and the blame of the lines of a file whose buffer has the text.
+pub fn changes(path: &Path, text: &str, cache: &BlameCache) -> Result<Changes, GitError> {
+    let (repository, relative) = open(path)?;
+    let base = match base_text(&repository, &relative)? {
+        Some(base) => base,
+        None => return Ok(Changes::default()),
+    };
+    let new = lines(text);
+    let hunks = diff::diff(&lines(&base), &new);
+    // a file can be staged before it was ever committed.
+    let blame = blame(&repository, &relative, &new, cache).unwrap_or_default();
+    Ok(Changes::new(hunks, blame))
+}
+
+/// writes the hunk changing a line of the text of a buffer to the index, the rest of the file
+/// in the index stays the same. Returns false if the line isn't changed.
+pub fn stage_hunk(path: &Path, text: &str, line: usize) -> Result<bool, GitError> {
+    let (repository, relative) = open(path)?;
+    let base = base_text(&repository, &relative)?.unwrap_or_default();
+    let (old, new) = (lines(&base), lines(text));
+    let hunks = diff::diff(&old, &new);
+    let hunk = match Changes::new(hunks, Vec::new()).hunk_at(line) {
+        Some(hunk) => hunk.clone(),
+        None => return Ok(false),
+    };
+
+    let mut staged = old[..hunk.old.start].concat();
+    staged.push_str(&new[hunk.new.clone()].concat());
+    staged.push_str(&old[hunk.old.end..].concat());
+
+    let mut index = repository.index().map_err(git_error)?;
+    let mut entry = index
+        .get_path(&relative, 0)
+        .unwrap_or_else(|| new_entry(&relative));
+    // git compares the contents again when the time doesn't match the file.
+    entry.mtime = IndexTime::new(0, 0);
+    entry.file_size = staged.len() as u32;
+    index
+        .add_frombuffer(&entry, staged.as_bytes())
+        .map_err(git_error)?;
+    index.write().map_err(git_error)?;
+    Ok(true)
+}
+
+/// the characters of the text of a buffer changed by the hunk at a line and the lines of the
+/// index they replaced, None if the line isn't changed.
+pub fn revert_hunk(
+    path: &Path,
+    text: &str,
+    line: usize,
+) -> Result<Option<(Range<usize>, String)>, GitError> {
+    let (repository, relative) = open(path)?;
+    let base = match base_text(&repository, &relative)? {
+        Some(base) => base,
+        None => return Ok(None),
+    };
+    let (old, new) = (lines(&base), lines(text));
+    let hunks = diff::diff(&old, &new);
+    let hunk = match Changes::new(hunks, Vec::new()).hunk_at(line) {
+        Some(hunk) => hunk.clone(),
+        None => return Ok(None),
+    };
+    let chars = |lines: &[&str]| lines.iter().map(|line| line.chars().count()).sum::<usize>();
+    let start = chars(&new[..hunk.new.start]);
+    let end = start + chars(&new[hunk.new.clone()]);
+    Ok(Some((start..end, old[hunk.old].concat())))
+}
+
+/// the repository of a file and the path of the file in it.
+fn open(path: &Path) -> Result<(Repository, PathBuf), GitError> {
+    let no_repository = || GitError::NoRepository(path.to_path_buf());
+    let directory = path.parent().ok_or_else(no_repository)?;
+    let repository = Repository::discover(directory).map_err(|_| no_repository())?;
+    // the file may not exist yet, its directory does.
+    let directory = directory.canonicalize().map_err(|_| no_repository())?;
+    let workdir = repository
+        .workdir()
+        .and_then(|workdir| workdir.canonicalize().ok())
+        .ok_or_else(no_repository)?;
+    let relative = directory
+        .strip_prefix(&workdir)
+        .map_err(|_| no_repository())?
+        .join(path.file_name().ok_or_else(no_repository)?);
+    Ok((repository, relative))
+}
+
+/// the text of a file in the index, or in HEAD if it isn't in the index. None if the file
+/// isn't tracked.
+fn base_text(repository: &Repository, relative: &Path) -> Result<Option<String>, GitError> {
+    let index = repository.index().map_err(git_error)?;
+    match index.get_path(relative, 0) {
+        Some(entry) => blob_text(repository, entry.id).map(Some),
+        None => head_text(repository, relative),
+    }
+}
+
+fn head_text(repository: &Repository, relative: &Path) -> Result<Option<String>, GitError> {
+    // a repository without commits has no HEAD.
+    let head = match repository.head() {
+        Ok(head) => head,
+        Err(_) => return Ok(None),
+    };
+    let tree = head.peel_to_tree().map_err(git_error)?;
+    match tree.get_path(relative) {
+        Ok(entry) => blob_text(repository, entry.id()).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+fn blob_text(repository: &Repository, id: Oid) -> Result<String, GitError> {
+    let blob = repository.find_blob(id).map_err(git_error)?;
+    Ok(String::from_utf8_lossy(blob.content()).into_owned())
+}
+
+/// an index entry for a file that isn't in the index.
+fn new_entry(relative: &Path) -> IndexEntry {
+    IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode: 0o100644,
+        uid: 0,
+        gid: 0,
+        file_size: 0,
+        id: Oid::zero(),
+        flags: 0,
+        flags_extended: 0,
+        path: relative.to_string_lossy().replace('\\', "/").into_bytes(),
+    }
+}
+
+/// the lines of a text with their line breaks.
+fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// the author, the date and the summary of the commit that last changed each line of the text.
+/// The lines that were changed since HEAD are marked as not committed.
+fn blame(
+    repository: &Repository,
+    relative: &Path,
+    text: &[&str],
+    cache: &BlameCache,
+) -> Result<Vec<String>, GitError> {
+    let head = match head_text(repository, relative)? {
+        Some(head) => head,
+        None => return Ok(Vec::new()),
+    };
+    let head_lines = lines(&head);
+    let head_id = repository
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(git_error)?
+        .id();
+    let key = repository.path().join(relative);
+    let cached = match cache.0.lock().unwrap().get(&key) {
+        Some((id, blamed)) if *id == head_id => Some(blamed.clone()),
+        _ => None,
+    };
+    let blamed = match cached {
+        Some(blamed) => blamed,
+        None => {
+            let blamed = Arc::new(blame_head(repository, relative, head_lines.len())?);
+            let mut cache = cache.0.lock().unwrap();
+            cache.insert(key, (head_id, blamed.clone()));
+            blamed
+        }
+    };
+
+    // the line of HEAD each line of the text was, None if it was changed.
+    let mut committed = Vec::with_capacity(text.len());
+    let (mut old, mut new) = (0, 0);
+    for hunk in diff::diff(&head_lines, text) {
+        while new < hunk.new.start {
+            committed.push(Some(old));
+            old += 1;
+            new += 1;
+        }
+        committed.extend(hunk.new.clone().map(|_| None));
+        old = hunk.old.end;
+        new = hunk.new.end;
+    }
+    committed.extend((new..text.len()).map(|line| Some(old + line - new)));
+
+    Ok(committed
+        .into_iter()
+        .map(|line| match line.and_then(|line| blamed.get(line)) {
+            Some(summary) => summary.clone(),
+            None => NOT_COMMITTED.to_string(),
+        })
+        .collect())
+}
+
+const NOT_COMMITTED: &str = "not committed yet";
+
+/// the author, the date and the summary of the commit that last changed each line of a file
+/// in HEAD.
+fn blame_head(
+    repository: &Repository,
+    relative: &Path,
+    lines: usize,
+) -> Result<Vec<String>, GitError> {
+    let blame = repository.blame_file(relative, None).map_err(git_error)?;
+    let mut summaries: HashMap<Oid, String> = HashMap::new();
+    let mut blamed = Vec::with_capacity(lines);
+    for line in 0..lines {
+        let hunk = match blame.get_line(line + 1) {
+            Some(hunk) => hunk,
+            None => {
+                blamed.push(NOT_COMMITTED.to_string());
+                continue;
+            }
+        };
+        let id = hunk.final_commit_id();
+        if let Entry::Vacant(entry) = summaries.entry(id) {
+            let commit = repository.find_commit(id).map_err(git_error)?;
+            let date = Local
+                .timestamp_opt(commit.time().seconds(), 0)
+                .single()
+                .map_or(String::new(), |date| date.format("%Y-%m-%d").to_string());
+            let author = commit.author().name().unwrap_or("unknown").to_string();
+            let summary = commit.summary().unwrap_or("").to_string();
+            entry.insert(format!("{}, {} - {}", author, date, summary));
+        }
+        blamed.push(summaries[&id].clone());
+    }
+    Ok(blamed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use git2::Signature;
+    use std::fs;
+
+    /// commits the file in the repository with a message.
+    fn commit(repository: &Repository, message: &str) {
+        let mut index = repository.index().unwrap();
+        index.add_path(Path::new("notes.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Ada", "ada@example.com").unwrap();
+        let parent = repository
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .ok();
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn stage_and_revert() {
+        let dir = tempfile::tempdir().unwrap();
+        let repository = Repository::init(dir.path()).unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        commit(&repository, "first");
+
+        let cache = BlameCache::default();
+        let text = "zero\none\n2\nthree\n";
+        let changes = changes(&path, text, &cache).unwrap();
+        assert_eq!(changes.hunks().len(), 2);
+        assert_eq!(changes.change(0), Some(core::LineChange::Added));
+        assert_eq!(changes.change(2), Some(core::LineChange::Modified));
+        assert_eq!(changes.blame(0), Some("not committed yet"));
+        assert!(changes.blame(1).unwrap().starts_with("Ada, "));
+        assert!(changes.blame(1).unwrap().ends_with(" - first"));
+
+        assert!(stage_hunk(&path, text, 2).unwrap());
+        // the changes are against the index now, only the added line is left.
+        let changes = super::changes(&path, text, &cache).unwrap();
+        assert_eq!(changes.hunks().len(), 1);
+        assert_eq!(changes.change(2), None);
+        let reverted = revert_hunk(&path, text, 0).unwrap();
+        assert_eq!(reverted, Some((0..5, String::new())));
+        assert!(revert_hunk(&path, text, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn blame_follows_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let repository = Repository::init(dir.path()).unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        commit(&repository, "first");
+
+        let cache = BlameCache::default();
+        let changes = super::changes(&path, "one\ntwo\n", &cache).unwrap();
+        assert!(changes.blame(1).unwrap().ends_with(" - first"));
+        assert_eq!(cache.0.lock().unwrap().len(), 1);
+
+        // the cached blame is mapped to the lines of the edited text.
+        let changes = super::changes(&path, "zero\none\n2\n", &cache).unwrap();
+        assert_eq!(changes.blame(0), Some("not committed yet"));
+        assert!(changes.blame(1).unwrap().ends_with(" - first"));
+        assert_eq!(changes.blame(2), Some("not committed yet"));
+
+        // a new commit moves HEAD, the file is blamed again.
+        fs::write(&path, "one\n2\n").unwrap();
+        commit(&repository, "second");
+        let changes = super::changes(&path, "one\n2\n", &cache).unwrap();
+        assert!(changes.blame(0).unwrap().ends_with(" - first"));
+        assert!(changes.blame(1).unwrap().ends_with(" - second"));
+    }
+}
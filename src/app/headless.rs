@@ -0,0 +1,361 @@
+// Headless Mode.
+// Drives the core and frames without a window, the edits are read from a script with one
+// command per line:
+//
+//     open [path]         opens a file in a new frame, a missing file is created when saved.
+//                         Without a path an unnamed buffer is opened.
+//     move <line> <col>   moves the cursor, both are one-indexed.
+//     select <line> <col> extends the selection to a position.
+//     insert <text>       inserts text at the cursor, \n, \t and \\ are unescaped.
+//     backspace [count]   deletes characters before the cursor.
+//     delete [count]      deletes characters after the cursor.
+//     save [path]         saves the buffer, optionally to a new path.
+//     print               writes the buffer to the output.
+//     <operation> [count] any operation of a key binding, e.g. `cursor_down 3`. Copy and paste
+//                         use a clipboard that isn't shared with other applications.
+//
+// Empty lines and lines starting with `#` are ignored. When the script is finished modified
+// buffers are saved and unnamed buffers are written to the output.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::clipboard::{ClipboardState, ClipboardType, MemoryClipboard};
+use crate::core::{BufferInfo, Core, CoreError};
+use crate::pathfinder_geometry::vector::Vector2F;
+use crate::ui::{Frame, FrameOptions, Invalidation, Operation};
+
+/// the number of lines a frame shows, used when scrolling by a page.
+const HEADLESS_LINES: usize = 50;
+
+#[derive(thiserror::Error, Debug)]
+pub enum HeadlessError {
+    #[error("failed to read script: {0}")]
+    ReadError(io::Error),
+    #[error("failed to write output: {0}")]
+    WriteError(io::Error),
+    #[error("{0}")]
+    CoreError(CoreError),
+    #[error("line {line}: {message}")]
+    ParseError { line: usize, message: String },
+    #[error("line {0}: no buffer is open")]
+    NoBuffer(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Open(Option<PathBuf>),
+    /// zero-indexed line and column.
+    Move(usize, usize),
+    Select(usize, usize),
+    Insert(String),
+    Backspace(usize),
+    Delete(usize),
+    Save(Option<PathBuf>),
+    Print,
+    Operation(Operation, usize),
+}
+
+impl Command {
+    /// parses a line of a script, returns None for empty lines and comments.
+    fn parse(line_number: usize, line: &str) -> Result<Option<Self>, HeadlessError> {
+        let err = |message: &str| HeadlessError::ParseError {
+            line: line_number,
+            message: message.to_string(),
+        };
+
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            return Ok(None);
+        }
+
+        let line = line.trim_start();
+        let (name, rest) = match line.find(' ') {
+            Some(idx) => (&line[..idx], &line[idx + 1..]),
+            None => (line, ""),
+        };
+        let args: Vec<&str> = rest.split_whitespace().collect();
+
+        let count = |args: &[&str]| match args {
+            [] => Ok(1),
+            [count] => count.parse().map_err(|_| err("invalid count")),
+            _ => Err(err("too many arguments")),
+        };
+        let path = |rest: &str| match rest.trim() {
+            "" => None,
+            path => Some(PathBuf::from(path)),
+        };
+
+        let command = match name {
+            "open" => Command::Open(path(rest)),
+            "move" | "select" => match args.as_slice() {
+                [line, column] => {
+                    let line: usize = line.parse().map_err(|_| err("invalid line"))?;
+                    let column: usize = column.parse().map_err(|_| err("invalid column"))?;
+                    let (line, column) = (line.saturating_sub(1), column.saturating_sub(1));
+                    if name == "move" {
+                        Command::Move(line, column)
+                    } else {
+                        Command::Select(line, column)
+                    }
+                }
+                _ => return Err(err(&format!("expected `{} <line> <column>`", name))),
+            },
+            "insert" => Command::Insert(unescape(rest)),
+            "backspace" => Command::Backspace(count(&args)?),
+            "delete" => Command::Delete(count(&args)?),
+            "save" => Command::Save(path(rest)),
+            "print" => Command::Print,
+            name => match Operation::from_str(name) {
+                Ok(operation) => Command::Operation(operation, count(&args)?),
+                Err(_) => return Err(err(&format!("unknown command `{}`", name))),
+            },
+        };
+
+        Ok(Some(command))
+    }
+}
+
+/// replaces the escape sequences \n, \t and \\, other backslashes are kept.
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some(ch) => {
+                result.push('\\');
+                result.push(ch);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Runs scripts against a core, printed buffers are written to out.
+pub struct Headless<W: Write> {
+    core: Core,
+    frames: Vec<Frame>,
+    /// the options of the opened frames.
+    options: FrameOptions,
+    clipboard: ClipboardState,
+    out: W,
+}
+
+impl<W: Write> Headless<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            core: Core::new(),
+            frames: Vec::new(),
+            options: FrameOptions::default(),
+            clipboard: ClipboardState::new(Box::new(MemoryClipboard::new())),
+            out,
+        }
+    }
+
+    pub fn with_options(mut self, options: FrameOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn run(&mut self, script: &str) -> Result<(), HeadlessError> {
+        for (idx, line) in script.lines().enumerate() {
+            if let Some(command) = Command::parse(idx + 1, line)? {
+                self.execute(idx + 1, command)?;
+            }
+        }
+        self.finish()
+    }
+
+    fn execute(&mut self, line: usize, command: Command) -> Result<(), HeadlessError> {
+        if let Command::Open(path) = command {
+            return self.open(path);
+        }
+
+        let frame = self
+            .frames
+            .last_mut()
+            .ok_or(HeadlessError::NoBuffer(line))?;
+        match command {
+            Command::Open(_) => unreachable!(),
+            Command::Move(line, column) => frame.place_cursor(line, column, false),
+            Command::Select(line, column) => frame.place_cursor(line, column, true),
+            Command::Insert(text) => frame.insert(&text),
+            Command::Backspace(count) => frame.delete_backward(count),
+            Command::Delete(count) => frame.delete_forward(count),
+            Command::Save(path) => {
+                let mut buffer = frame.buffer_mut();
+                match path {
+                    Some(path) => buffer.save_as(path),
+                    None => buffer.save(),
+                }
+                .map_err(HeadlessError::CoreError)?;
+            }
+            Command::Print => Self::print(&mut self.out, frame)?,
+            Command::Operation(operation, count) => {
+                for _ in 0..count {
+                    if operation != Operation::Cut {
+                        self.clipboard.end_cuts();
+                    }
+                    match operation {
+                        Operation::Copy => {
+                            self.clipboard.copy(frame);
+                        }
+                        Operation::Cut => {
+                            self.clipboard.cut(frame);
+                        }
+                        Operation::Paste => {
+                            self.clipboard.paste(frame, ClipboardType::Clipboard);
+                        }
+                        Operation::CycleKillRing => self.clipboard.cycle_kill_ring(),
+                        operation => {
+                            frame.handle_operation(operation);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, path: Option<PathBuf>) -> Result<(), HeadlessError> {
+        let buffer_id = match path {
+            Some(path) if !path.exists() => self.core.new_file(path),
+            path => self
+                .core
+                .open_file(BufferInfo { path })
+                .map_err(HeadlessError::CoreError)?,
+        };
+
+        let buffer = self.core.get_buffer_ptr(&buffer_id).unwrap();
+        let mut frame = Frame::new(buffer, Vector2F::zero(), Vector2F::zero(), HEADLESS_LINES);
+        frame.set_options(self.options);
+        frame.update_line_cache(Invalidation::Init);
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    /// saves the modified buffers and prints the unnamed ones.
+    fn finish(&mut self) -> Result<(), HeadlessError> {
+        for frame in &self.frames {
+            let has_path = frame.buffer().path().is_some();
+            if !has_path {
+                Self::print(&mut self.out, frame)?;
+            } else if frame.buffer().is_dirty() {
+                frame
+                    .buffer_mut()
+                    .save()
+                    .map_err(HeadlessError::CoreError)?;
+            }
+        }
+        self.out.flush().map_err(HeadlessError::WriteError)
+    }
+
+    fn print(out: &mut W, frame: &Frame) -> Result<(), HeadlessError> {
+        write!(out, "{}", frame.buffer().content()).map_err(HeadlessError::WriteError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn run(script: &str) -> String {
+        let mut headless = Headless::new(Vec::new());
+        headless.run(script).unwrap();
+        String::from_utf8(headless.out).unwrap()
+    }
+
+    #[test]
+    fn edits_unnamed_buffer() {
+        let output = run("open\n\
+             insert fn main() {\\n}\\n\n\
+             move 1 12\n\
+             insert \\n    println!();\n\
+             cursor_up\n\
+             backspace 2\n");
+        assert_eq!(output, "fn main()\n    println!();\n}\n");
+    }
+
+    #[test]
+    fn saves_modified_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("modified.txt");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let script = format!(
+            "# replace the second line\nopen {}\nmove 2 1\ndelete 3\ninsert 2\n",
+            path.display()
+        );
+        assert_eq!(run(&script), "");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\n2\nthree\n");
+    }
+
+    #[test]
+    fn creates_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("created.txt");
+        let copy = dir.path().join("copy.txt");
+        let script = format!(
+            "open {}\ninsert a\\tb\nsave {}\ninsert c\nprint\n",
+            path.display(),
+            copy.display()
+        );
+        assert_eq!(run(&script), "a\tbc");
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "a\tbc");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn frames_use_the_options() {
+        let options = FrameOptions {
+            wrap: true,
+            ..FrameOptions::default()
+        };
+        let mut headless = Headless::new(Vec::new()).with_options(options);
+        headless.run("open\nopen\n").unwrap();
+        assert!(headless.frames.iter().all(|frame| frame.options().wrap));
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let script = r"open
+insert one two\nthree
+move 1 1
+select 1 5
+cut
+move 1 4
+paste
+move 2 1
+add_cursor_above
+insert !
+";
+        assert_eq!(run(script), "!twoone \n!three");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut headless = Headless::new(Vec::new());
+        match headless.run("insert a") {
+            Err(HeadlessError::NoBuffer(1)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match headless.run("open\n\nmove 1") {
+            Err(HeadlessError::ParseError { line: 3, .. }) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match headless.run("jump 4") {
+            Err(HeadlessError::ParseError { line: 1, .. }) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Language Servers.
+// Starts the language server of a language when a buffer of the language is first seen and keeps
+// the servers up to date with the buffers. A server that fails to start or exits isn't started
+// again until the editor restarts. The answers to requests arrive as `AppEvent::Lsp` events, the
+// manager remembers what each request was for.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use kea::lsp::{EventCallback, LspClient, LspError, Position};
+use log::{error, info};
+
+use super::Config;
+use crate::core::{self, Core};
+
+/// what a request was for, it decides what is done with the answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Hover,
+    Definition,
+    References,
+    Rename,
+    Format,
+    Completion,
+}
+
+pub struct LanguageServers {
+    /// the running servers by language.
+    clients: HashMap<String, LspClient>,
+    /// languages whose server failed to start or exited.
+    failed: HashSet<String>,
+    /// reports what servers send, servers aren't started without it.
+    on_event: Option<EventCallback>,
+    /// the requests waiting for an answer.
+    requests: HashMap<u64, Request>,
+    /// the servers were shut down, no server is started again.
+    stopped: bool,
+}
+
+impl LanguageServers {
+    pub fn new() -> Self {
+        Self {
+            clients: HashMap::new(),
+            failed: HashSet::new(),
+            on_event: None,
+            requests: HashMap::new(),
+            stopped: false,
+        }
+    }
+
+    pub fn set_callback(&mut self, on_event: EventCallback) {
+        self.on_event = Some(on_event);
+    }
+
+    /// opens new buffers on their servers and sends the changes of open buffers. Servers are
+    /// started for buffers of languages without one. Returns the errors of servers that
+    /// couldn't be started.
+    pub fn sync(&mut self, core: &Core, config: &Config) -> Vec<LspError> {
+        let mut errors = Vec::new();
+        if self.on_event.is_none() || self.stopped {
+            return errors;
+        }
+
+        for id in core.buffer_ids() {
+            let buffer = match core.get_buffer_ptr(&id) {
+                Some(buffer) => buffer,
+                None => continue,
+            };
+            let mut buffer = buffer.deref().borrow_mut();
+            let path = match buffer.path() {
+                Some(path) => core::absolute(path),
+                None => continue,
+            };
+            let (language, server) = match config.language_server(&path) {
+                Some(server) => server,
+                None => continue,
+            };
+
+            if !self.clients.contains_key(language) {
+                if self.failed.contains(language) {
+                    continue;
+                }
+                match self.start(language, &server.command, &server.args) {
+                    Ok(client) => {
+                        self.clients.insert(language.to_string(), client);
+                    }
+                    Err(e) => {
+                        self.failed.insert(language.to_string());
+                        errors.push(e);
+                        continue;
+                    }
+                }
+            }
+
+            let client = self.clients.get_mut(language).unwrap();
+            if client.is_open(&path) {
+                let changes = buffer.take_changes();
+                if !changes.is_empty() {
+                    client.did_change(&path, changes, || buffer.content().to_string());
+                }
+            } else {
+                buffer.track_changes();
+                buffer.take_changes();
+                client.did_open(&path, language, buffer.content().to_string());
+            }
+        }
+        errors
+    }
+
+    fn start(&self, language: &str, command: &str, args: &[String]) -> Result<LspClient, LspError> {
+        let on_event = self.on_event.clone().unwrap();
+        let root = std::env::current_dir().unwrap_or_default();
+        info!("Starting language server '{}' for {}", command, language);
+        LspClient::start(language, command, args, &root, on_event)
+    }
+
+    /// the server of a language exited, it isn't started again.
+    pub fn exited(&mut self, language: &str) {
+        if let Some(client) = self.clients.remove(language) {
+            error!("Language server '{}' exited", client.name());
+        }
+        self.failed.insert(language.to_string());
+    }
+
+    /// stops every server, e.g. when the editor exits.
+    pub fn shutdown(&mut self) {
+        self.stopped = true;
+        self.clients.clear();
+    }
+
+    /// sends a hover, definition or references request to the server of a file, false if the
+    /// file isn't open on a server.
+    pub fn request(&mut self, path: &Path, position: Position, request: Request) -> bool {
+        let kind = request.clone();
+        self.send(path, request, move |client, path| match kind {
+            Request::Hover => client.hover(path, position),
+            Request::Definition => client.definition(path, position),
+            _ => client.references(path, position),
+        })
+        .is_some()
+    }
+
+    /// asks for the edits renaming the symbol at a position, returns the id of the request.
+    pub fn rename(&mut self, path: &Path, position: Position, new_name: &str) -> Option<u64> {
+        self.send(path, Request::Rename, |client, path| {
+            client.rename(path, position, new_name)
+        })
+    }
+
+    /// asks for the edits formatting a file, returns the id of the request.
+    pub fn format(&mut self, path: &Path, tab_size: usize) -> Option<u64> {
+        self.send(path, Request::Format, |client, path| {
+            client.formatting(path, tab_size, true)
+        })
+    }
+
+    /// asks for the completions of the word at a position, returns the id of the request.
+    pub fn completion(&mut self, path: &Path, position: Position) -> Option<u64> {
+        self.send(path, Request::Completion, |client, path| {
+            client.completion(path, position)
+        })
+    }
+
+    /// forgets a request, its answer is no longer needed.
+    pub fn cancel(&mut self, id: u64) {
+        if self.requests.remove(&id).is_some() {
+            for client in self.clients.values_mut() {
+                client.cancel(id);
+            }
+        }
+    }
+
+    fn send<F>(&mut self, path: &Path, request: Request, send: F) -> Option<u64>
+    where
+        F: FnOnce(&mut LspClient, &Path) -> u64,
+    {
+        let path = core::absolute(path);
+        let client = self
+            .clients
+            .values_mut()
+            .find(|client| client.is_open(&path))?;
+        let id = send(client, &path);
+        self.requests.insert(id, request);
+        Some(id)
+    }
+
+    /// what the request of an answer was for, None if it was forgotten.
+    pub fn take_request(&mut self, id: u64) -> Option<Request> {
+        self.requests.remove(&id)
+    }
+}
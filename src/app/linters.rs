@@ -0,0 +1,199 @@
+// Linters.
+// A linter is a command that checks the file of a buffer, its output is read as diagnostics. It
+// runs when a buffer of its language is opened, when the file changes on disk and when asked to.
+// The file is given where an argument is `{file}`, or after the arguments when none is. Each line
+// of the output, stdout or stderr, of the form
+//
+//     path:line:column: severity: message
+//
+// is a diagnostic of the file, lines and columns count from 1. The column and the severity can be
+// left out, a diagnostic without a severity is an error. Other lines are ignored.
+//
+// The linter checks the file on disk, so its diagnostics are dropped for buffers with unsaved
+// changes, they would be of other text.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use kea::lsp::Severity;
+use log::error;
+
+use super::config::LinterConfig;
+use super::Config;
+use crate::core::{self, BufferId, Core};
+
+/// a diagnostic of a linter, the line and column are zero-indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// the linter that ran and the file it checked, with what it found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintResult {
+    pub linter: String,
+    pub path: PathBuf,
+    pub lints: Vec<Lint>,
+}
+
+pub type LintCallback = Arc<dyn Fn(LintResult) + Send + Sync>;
+
+pub struct Linters {
+    /// the buffers linted since they were opened.
+    linted: HashSet<BufferId>,
+    /// reports the results, linters aren't run without it.
+    on_result: Option<LintCallback>,
+}
+
+impl Linters {
+    pub fn new() -> Self {
+        Self {
+            linted: HashSet::new(),
+            on_result: None,
+        }
+    }
+
+    pub fn set_callback(&mut self, on_result: LintCallback) {
+        self.on_result = Some(on_result);
+    }
+
+    /// lints the files of buffers that weren't linted yet.
+    pub fn sync(&mut self, core: &Core, config: &Config) {
+        for id in core.buffer_ids() {
+            if !self.linted.insert(id) {
+                continue;
+            }
+            let path = core
+                .get_buffer(&id)
+                .and_then(|buffer| buffer.path().map(Path::to_path_buf));
+            if let Some(path) = path {
+                self.lint(&path, config);
+            }
+        }
+        let buffers: HashSet<BufferId> = core.buffer_ids().into_iter().collect();
+        self.linted.retain(|id| buffers.contains(id));
+    }
+
+    /// runs the linter of the language of a file in the background, returns false if the
+    /// language has no linter.
+    pub fn lint(&self, path: &Path, config: &Config) -> bool {
+        let (linter, on_result) = match (config.linter(path), self.on_result.as_ref()) {
+            (Some(linter), Some(on_result)) => (linter.clone(), on_result.clone()),
+            _ => return false,
+        };
+        let path = core::absolute(path);
+        kea::utils::spawn_thread("linter", move || match run(&linter, &path) {
+            Ok(lints) => on_result(LintResult {
+                linter: linter.command.clone(),
+                path,
+                lints,
+            }),
+            Err(e) => error!("{}", e),
+        });
+        true
+    }
+}
+
+/// runs a linter on a file and reads its output.
+fn run(linter: &LinterConfig, path: &Path) -> Result<Vec<Lint>, String> {
+    let file = path.to_string_lossy();
+    let mut args: Vec<String> = linter
+        .args
+        .iter()
+        .map(|arg| arg.replace("{file}", &file))
+        .collect();
+    if !linter.args.iter().any(|arg| arg.contains("{file}")) {
+        args.push(file.to_string());
+    }
+
+    let output = Command::new(&linter.command)
+        .args(&args)
+        .current_dir(path.parent().unwrap_or_else(|| Path::new(".")))
+        .output()
+        .map_err(|e| format!("failed to run the linter '{}': {}", linter.command, e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Ok(stdout
+        .lines()
+        .chain(stderr.lines())
+        .filter_map(|line| parse_line(line, path))
+        .collect())
+}
+
+/// reads a line of the output of a linter, None if it isn't a diagnostic of the file.
+fn parse_line(line: &str, path: &Path) -> Option<Lint> {
+    let mut parts = line.splitn(3, ':');
+    let file = parts.next()?.trim();
+    let line_number: usize = parts.next()?.trim().parse().ok()?;
+    let rest = parts.next()?;
+
+    // a relative path is relative to the directory of the file, the linter runs there.
+    let file = Path::new(file);
+    let same = file == path
+        || path
+            .parent()
+            .map_or(false, |directory| directory.join(file) == path);
+    if !same || line_number == 0 {
+        return None;
+    }
+
+    let (column, rest) = match rest.split_once(':') {
+        Some((column, message)) => match column.trim().parse::<usize>() {
+            Ok(column) => (column.saturating_sub(1), message),
+            Err(_) => (0, rest),
+        },
+        None => (0, rest),
+    };
+    let (severity, message) = match rest.split_once(':') {
+        Some((severity, message)) => match severity.trim().to_lowercase().as_str() {
+            "error" | "fatal error" => (Severity::Error, message),
+            "warning" => (Severity::Warning, message),
+            "note" | "info" => (Severity::Information, message),
+            "help" | "hint" => (Severity::Hint, message),
+            _ => (Severity::Error, rest),
+        },
+        None => (Severity::Error, rest),
+    };
+
+    Some(Lint {
+        line: line_number - 1,
+        column,
+        severity,
+        message: message.trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn output_lines() {
+        let path = Path::new("/project/src/main.c");
+        let lint = |line, column, severity, message: &str| Lint {
+            line,
+            column,
+            severity,
+            message: message.to_string(),
+        };
+        assert_eq!(
+            parse_line("main.c:3:5: warning: unused variable 'x'", path),
+            Some(lint(2, 4, Severity::Warning, "unused variable 'x'"))
+        );
+        assert_eq!(
+            parse_line("/project/src/main.c:10: expected ';'", path),
+            Some(lint(9, 0, Severity::Error, "expected ';'"))
+        );
+        assert_eq!(
+            parse_line("main.c:1:1: note: see: here", path),
+            Some(lint(0, 0, Severity::Information, "see: here"))
+        );
+        assert_eq!(parse_line("other.c:1:1: error: no", path), None);
+        assert_eq!(parse_line("1 warning generated.", path), None);
+    }
+}
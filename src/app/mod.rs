@@ -1,19 +1,41 @@
 mod application;
+mod clipboard;
+mod commands;
+mod completion;
 mod config;
+mod diff_view;
 mod event_handler;
+mod git;
+mod headless;
+mod language_servers;
+mod linters;
+mod session;
+#[cfg(test)]
+mod snapshot;
+mod snippets;
 
 use super::renderer::{
     self,
     window::{LogicalSize, Window},
     Renderer,
 };
-use crate::core::{self, Edit, KeaCore, Update};
+use crate::core::{self, Edit, FileWatcher, KeaCore, SwapFiles, Update};
 use crate::font::{Font, FontCollection, FontMetrics, GlyphId};
-use crate::renderer::platform::atlas::FontAtlas;
-use application::{App, Application, WeakApp};
+use crate::renderer::platform::{atlas::FontAtlas, backend::GlBackend};
+use crate::terminal::TerminalEvent;
+use crate::ui::completion::CompletionItem;
+use crate::ui::FrameId;
+use application::{App, Application, TerminalEvents, WeakApp};
+use commands::{CommandCallback, CommandResult};
 pub use config::Config;
 use event_handler::EventHandler;
+use git::{GitCallback, GitResult};
+use headless::{Headless, HeadlessError};
+use kea::lsp::{EventCallback, LspEvent};
 use kea::{self, comm::Receiver, utils::log_file_path};
+use linters::{LintCallback, LintResult};
+use std::io::{self, Read};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, Weak};
 
 use glutin::event_loop::EventLoop;
@@ -23,6 +45,22 @@ use log::{error, info};
 #[derive(Debug, Clone)]
 pub enum AppEvent {
     Exit,
+    /// a file was changed by another program.
+    FileChanged(PathBuf),
+    /// a file of the config or the theme changed.
+    ConfigChanged,
+    /// a language server sent something.
+    Lsp(LspEvent),
+    /// the candidates of a completion query.
+    Completions(u64, Vec<CompletionItem>),
+    /// a linter checked a file.
+    Lint(LintResult),
+    /// the changes of a buffer against git were computed.
+    Git(GitResult),
+    /// the shell of the terminal in a frame wrote something or exited.
+    Terminal(FrameId, TerminalEvent),
+    /// a shell command ended.
+    Command(CommandResult),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -33,9 +71,30 @@ pub enum AppError {
     FontError(crate::font::FontError),
     #[error("{0}")]
     RenderError(crate::renderer::RenderError),
+    #[error("{0}")]
+    HeadlessError(HeadlessError),
+}
+
+/// runs the editor without a window, the commands are read from the script or stdin when
+/// no script is given. The frames use the options of the config.
+pub fn run_headless(script: Option<PathBuf>, config: &Config) -> Result<(), AppError> {
+    let script = match script {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut script = String::new();
+            io::stdin().read_to_string(&mut script).map(|_| script)
+        }
+    }
+    .map_err(|e| AppError::HeadlessError(HeadlessError::ReadError(e)))?;
+
+    let stdout = io::stdout();
+    let mut headless = Headless::new(stdout.lock()).with_options(config.frame_options());
+    headless.run(&script).map_err(AppError::HeadlessError)
 }
 
-pub fn run(config: Config) -> Result<(), AppError> {
+/// runs the editor, config_file is the file given with `--config` which is read again when the
+/// config changes.
+pub fn run(config: Config, config_file: Option<PathBuf>) -> Result<(), AppError> {
     let event_loop = EventLoop::<AppEvent>::with_user_event();
     let window = Window::<glutin::NotCurrent>::new(
         &event_loop,
@@ -51,7 +110,6 @@ pub fn run(config: Config) -> Result<(), AppError> {
 
     let mut font_collection = FontCollection::new(dpi).unwrap();
     let font_desc = config.font_desc();
-    let font_size = config.font_size();
 
     match font_collection.add_font_by_name(config.font_name()) {
         Ok(_) => {}
@@ -66,17 +124,10 @@ pub fn run(config: Config) -> Result<(), AppError> {
     let window = window.make_current().map_err(AppError::WindowError)?;
     window.init_gl();
 
-    let font_atlas = FontAtlas::from_collection(&mut font_collection, |atlas, font| {
-        // println!("Loading Font: {:?}", font.desc());
-        for c in 32 as u8..127 as u8 {
-            let rglyph = font.rasterize_glyph(c as char, font_size)?;
-            atlas.add_glyph(&rglyph);
-        }
-        Ok(())
-    })
-    .map_err(AppError::FontError)?;
+    // glyphs are rasterized into the atlas the first time they are rendered.
+    let font_atlas = FontAtlas::new(font_collection.dpi_factor());
 
-    let mut renderer = Renderer::new();
+    let mut renderer = Renderer::new(Box::new(GlBackend::new()));
     renderer.init().map_err(|e| {
         error!("Failed to initialize render: {:?}", e);
         AppError::RenderError(e)
@@ -92,7 +143,72 @@ pub fn run(config: Config) -> Result<(), AppError> {
     let elp = event_loop.create_proxy();
     let mut event_handler = EventHandler::new(renderer, elp);
 
-    let app = Application::with_config(window, app_sender, font_collection, config)?;
+    let mut app = Application::with_config(window, app_sender, font_collection, config)?;
+    // changes are sent to the event loop so they are handled on this thread.
+    let proxy = event_loop.create_proxy();
+    match FileWatcher::new(move |path| {
+        proxy.send_event(AppEvent::FileChanged(path)).ok();
+    }) {
+        Ok(watcher) => app.watch_files(watcher),
+        Err(e) => error!("{}", e),
+    }
+    let proxy = event_loop.create_proxy();
+    match FileWatcher::new(move |_| {
+        proxy.send_event(AppEvent::ConfigChanged).ok();
+    }) {
+        Ok(watcher) => app.watch_config(watcher, config_file),
+        Err(e) => error!("{}", e),
+    }
+    // the proxy is shared with the threads reading from the servers.
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let on_event: EventCallback = Arc::new(move |event| {
+        proxy.lock().unwrap().send_event(AppEvent::Lsp(event)).ok();
+    });
+    app.start_language_servers(on_event);
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let on_result: LintCallback = Arc::new(move |result| {
+        proxy
+            .lock()
+            .unwrap()
+            .send_event(AppEvent::Lint(result))
+            .ok();
+    });
+    app.start_linters(on_result);
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let on_result: GitCallback = Arc::new(move |result| {
+        proxy.lock().unwrap().send_event(AppEvent::Git(result)).ok();
+    });
+    app.start_git(on_result);
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let on_event: TerminalEvents = Arc::new(move |id, event| {
+        proxy
+            .lock()
+            .unwrap()
+            .send_event(AppEvent::Terminal(id, event))
+            .ok();
+    });
+    app.start_terminals(on_event);
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let on_result: CommandCallback = Arc::new(move |result| {
+        proxy
+            .lock()
+            .unwrap()
+            .send_event(AppEvent::Command(result))
+            .ok();
+    });
+    app.start_commands(on_result);
+    let proxy = Mutex::new(event_loop.create_proxy());
+    app.start_completion(move |id, items| {
+        proxy
+            .lock()
+            .unwrap()
+            .send_event(AppEvent::Completions(id, items))
+            .ok();
+    });
+    match SwapFiles::new(kea::utils::swap_directory_path()) {
+        Ok(swap_files) => app.set_swap_files(swap_files),
+        Err(e) => error!("{}", e),
+    }
     let app = App::new(app);
     // let _update = application_update_thread(app.weak(), app_receiver);
 
@@ -0,0 +1,219 @@
+// Session.
+// The open files, how the frames are laid out and where their cursors are. The session is saved
+// when the editor exits and restored when it starts again.
+
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{BufferId, BufferInfo, Core};
+use crate::ui::{FrameId, FrameInfo, FrameLayout, Layout};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SessionError {
+    #[error("failed to read session '{0}': {1}")]
+    ReadFailed(PathBuf, String),
+    #[error("failed to write session '{0}': {1}")]
+    WriteFailed(PathBuf, String),
+    #[error("invalid session '{0}': {1}")]
+    Invalid(PathBuf, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameState {
+    /// the file shown in the frame, None for unnamed buffers.
+    pub path: Option<PathBuf>,
+    /// the line and column of each cursor, the primary cursor is first.
+    pub cursors: Vec<(usize, usize)>,
+    /// the first visible line.
+    pub scroll: usize,
+}
+
+/// A node of the frame layout, frames are referred to by their index in the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LayoutNode {
+    Vertical,
+    Horizontal,
+    Frame(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub frames: Vec<FrameState>,
+    pub layout: Vec<LayoutNode>,
+    /// the index of the active frame.
+    pub active: Option<usize>,
+}
+
+impl Session {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SessionError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| SessionError::ReadFailed(path.to_path_buf(), e.to_string()))?;
+        let session: Self = serde_yaml::from_str(&content)
+            .map_err(|e| SessionError::Invalid(path.to_path_buf(), e.to_string()))?;
+
+        // a layout that refers to missing frames can't be restored.
+        let valid = session.layout.iter().all(|node| match node {
+            LayoutNode::Frame(idx) => *idx < session.frames.len(),
+            _ => true,
+        });
+        if !valid {
+            let message = "the layout refers to a missing frame".to_string();
+            return Err(SessionError::Invalid(path.to_path_buf(), message));
+        }
+        Ok(session)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SessionError> {
+        let path = path.as_ref();
+        let failed = |e: String| SessionError::WriteFailed(path.to_path_buf(), e);
+        let content = serde_yaml::to_string(self).map_err(|e| failed(e.to_string()))?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|e| failed(e.to_string()))?;
+        }
+        std::fs::write(path, content).map_err(|e| failed(e.to_string()))
+    }
+
+    /// opens the file of each frame, a file that was never saved is opened as a new file.
+    /// Files that can't be opened are replaced by empty buffers so the layout stays the same.
+    pub fn open_buffers(&self, core: &mut Core) -> Vec<BufferId> {
+        self.frames
+            .iter()
+            .map(|state| match state.path.clone() {
+                Some(path) if !path.exists() => core.new_file(path),
+                path => core.open_file(BufferInfo { path }).unwrap_or_else(|e| {
+                    error!("Failed to restore a frame: {}", e);
+                    core.scratch("")
+                }),
+            })
+            .collect()
+    }
+
+    /// lays the frames out as they were, frames[i] shows the i-th frame of the session.
+    /// Without a layout the frames are placed one after another.
+    pub fn restore_layout(&self, layout: &mut FrameLayout, frames: &[FrameId]) {
+        for node in &self.layout {
+            match *node {
+                LayoutNode::Vertical => layout.push(Layout::Vertical),
+                LayoutNode::Horizontal => layout.push(Layout::Horizontal),
+                LayoutNode::Frame(idx) => layout.push_frame(FrameInfo { frame: frames[idx] }),
+            }
+        }
+        if self.layout.is_empty() {
+            for frame in frames {
+                layout.push_frame(FrameInfo { frame: *frame });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pathfinder_geometry::vector::Vector2F;
+    use crate::ui::Frame;
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("kea-session-{}.yml", std::process::id()));
+        let session = Session {
+            frames: vec![
+                FrameState {
+                    path: Some(PathBuf::from("src/main.rs")),
+                    cursors: vec![(10, 4), (11, 4)],
+                    scroll: 3,
+                },
+                FrameState {
+                    path: None,
+                    cursors: vec![(0, 0)],
+                    scroll: 0,
+                },
+            ],
+            layout: vec![
+                LayoutNode::Vertical,
+                LayoutNode::Frame(0),
+                LayoutNode::Frame(1),
+            ],
+            active: Some(1),
+        };
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session);
+
+        std::fs::write(&path, "frames: []\nlayout: [!Frame 0]\nactive: ~\n").unwrap();
+        assert!(matches!(
+            Session::load(&path),
+            Err(SessionError::Invalid(..))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let saved = dir.path().join("saved.rs");
+        std::fs::write(&saved, "fn main() {}\n").unwrap();
+        let session = Session {
+            frames: vec![
+                FrameState {
+                    path: Some(saved.clone()),
+                    cursors: vec![(0, 3)],
+                    scroll: 0,
+                },
+                FrameState {
+                    path: Some(dir.path().join("new.rs")),
+                    cursors: vec![(0, 0)],
+                    scroll: 0,
+                },
+                FrameState {
+                    path: None,
+                    cursors: vec![(0, 0)],
+                    scroll: 0,
+                },
+            ],
+            layout: vec![
+                LayoutNode::Horizontal,
+                LayoutNode::Frame(2),
+                LayoutNode::Frame(0),
+                LayoutNode::Frame(1),
+            ],
+            active: Some(0),
+        };
+
+        let mut core = Core::new();
+        let buffers = session.open_buffers(&mut core);
+        assert_eq!(buffers.len(), 3);
+        let path = |idx: usize| {
+            core.get_buffer(&buffers[idx])
+                .unwrap()
+                .path()
+                .map(Path::to_owned)
+        };
+        assert_eq!(path(0), Some(saved));
+        let text = core.get_buffer(&buffers[0]).unwrap().content().to_string();
+        assert_eq!(text, "fn main() {}\n");
+        // the file that was never saved is still written to its path.
+        assert_eq!(path(1), Some(dir.path().join("new.rs")));
+        assert_eq!(path(2), None);
+
+        let frames: Vec<FrameId> = buffers
+            .iter()
+            .map(|id| {
+                let buffer = core.get_buffer_ptr(id).unwrap();
+                Frame::new(buffer, Vector2F::zero(), Vector2F::zero(), 10).id()
+            })
+            .collect();
+        let mut layout = FrameLayout::new();
+        session.restore_layout(&mut layout, &frames);
+        let order: Vec<FrameId> = layout.frame_iter().map(|info| info.frame).collect();
+        assert_eq!(order, vec![frames[2], frames[0], frames[1]]);
+        assert!(matches!(layout.nodes()[0], Layout::Horizontal));
+
+        // without a layout the frames are placed in order.
+        let mut layout = FrameLayout::new();
+        Session::default().restore_layout(&mut layout, &frames);
+        let order: Vec<FrameId> = layout.frame_iter().map(|info| info.frame).collect();
+        assert_eq!(order, frames);
+    }
+}
@@ -0,0 +1,372 @@
+// Snapshot Tests.
+// Opens a fixture file in a frame, runs a script of steps (keys, operations, resizes), and
+// renders the frame with the software backend after each step. Each image is compared with a
+// stored png snapshot, when they differ a diff image is written next to the build output.
+//
+// A missing snapshot fails the test. Set KEA_BLESS=1 to write missing snapshots, or to replace
+// them after an intended change to the rendering. The snapshots are rendered with the font file
+// of tests/fixtures/kea.yml so they don't depend on the fonts installed on the system.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::application::zoomed_font_size;
+use super::Config;
+use crate::core::{BufferInfo, Core};
+use crate::font::FontCollection;
+use crate::glutin::event::{ModifiersState, VirtualKeyCode};
+use crate::pathfinder_geometry::vector::{vec2f, Vector2F};
+use crate::renderer::backend::RgbaImage;
+use crate::renderer::platform::atlas::FontAtlas;
+use crate::renderer::software::SoftwareBackend;
+use crate::renderer::{RenderContext, Renderer};
+use crate::ui::key_bindings::{BindingCollection, Key};
+use crate::ui::{Frame, Invalidation, Operation};
+
+const FIXTURES: &str = "tests/fixtures";
+const SNAPSHOTS: &str = "tests/snapshots";
+/// where diff images of failed snapshots are written.
+const DIFFS: &str = "target/snapshot-diffs";
+/// set to replace the stored snapshots with the rendered images.
+const BLESS_VAR: &str = "KEA_BLESS";
+/// largest difference of a channel for two pixels to be considered the same.
+const CHANNEL_TOLERANCE: u8 = 8;
+/// fraction of the pixels that are allowed to differ.
+const PIXEL_TOLERANCE: f32 = 0.001;
+
+/// the snapshots are rendered without dpi scaling.
+const DPI_FACTOR: f32 = 1.0;
+
+#[derive(Debug, Clone)]
+enum Step {
+    Key(VirtualKeyCode, ModifiersState),
+    Operation(Operation),
+    Resize(u32, u32),
+}
+
+impl Step {
+    /// parses a step of a script, steps look like: `key down`, `key ctrl+equals`,
+    /// `scroll_down`, or `resize 320 200`.
+    fn parse(step: &str) -> Self {
+        let parts: Vec<&str> = step.split_whitespace().collect();
+        match parts.as_slice() {
+            ["key", key] => {
+                let mut mods = ModifiersState::empty();
+                let mut keys: Vec<&str> = key.split('+').collect();
+                let key = keys.pop().unwrap();
+                for modifier in keys {
+                    match modifier {
+                        "ctrl" => mods |= ModifiersState::CTRL,
+                        "shift" => mods |= ModifiersState::SHIFT,
+                        "alt" => mods |= ModifiersState::ALT,
+                        _ => panic!("unknown modifier in step: {}", step),
+                    }
+                }
+                Step::Key(Self::parse_key(key), mods)
+            }
+            ["resize", width, height] => Step::Resize(
+                width.parse().expect("invalid width"),
+                height.parse().expect("invalid height"),
+            ),
+            [operation] => match Operation::from_str(operation) {
+                Ok(operation) => Step::Operation(operation),
+                Err(_) => panic!("unknown operation in step: {}", step),
+            },
+            _ => panic!("invalid step: {}", step),
+        }
+    }
+
+    fn parse_key(key: &str) -> VirtualKeyCode {
+        match key {
+            "up" => VirtualKeyCode::Up,
+            "down" => VirtualKeyCode::Down,
+            "left" => VirtualKeyCode::Left,
+            "right" => VirtualKeyCode::Right,
+            "pageup" => VirtualKeyCode::PageUp,
+            "pagedown" => VirtualKeyCode::PageDown,
+            "equals" => VirtualKeyCode::Equals,
+            "minus" => VirtualKeyCode::Minus,
+            "0" => VirtualKeyCode::Key0,
+            _ => panic!("unknown key: {}", key),
+        }
+    }
+}
+
+/// A frame and everything needed to render it, without a window.
+struct Harness {
+    name: String,
+    config: Config,
+    /// owns the buffer of the frame.
+    core: Core,
+    frame: Frame,
+    context: RenderContext,
+    renderer: Renderer,
+    bindings: BindingCollection,
+    size: Vector2F,
+}
+
+impl Harness {
+    fn new(name: &str, fixture: &str, config: &str, width: u32, height: u32) -> Self {
+        let config = Config::from_file(Path::new(FIXTURES).join(config)).unwrap();
+
+        let mut fonts = FontCollection::new(DPI_FACTOR).unwrap();
+        fonts
+            .add_font_by_name(config.font_name())
+            .expect("failed to load the font of the snapshots");
+        let context = RenderContext::new(fonts, config.font_size(), DPI_FACTOR, config.ligatures());
+
+        let mut renderer = Renderer::new(Box::new(SoftwareBackend::new(width, height)));
+        renderer.init().unwrap();
+        renderer.set_atlas(FontAtlas::new(DPI_FACTOR));
+        renderer.update_perspective(width as i32, height as i32);
+
+        let mut core = Core::new();
+        let buffer_id = core
+            .open_file(BufferInfo {
+                path: Some(Path::new(FIXTURES).join(fixture)),
+            })
+            .unwrap();
+        let buffer = core.get_buffer_ptr(&buffer_id).unwrap();
+
+        let size = vec2f(width as f32, height as f32);
+        let lines = Frame::compute_lines(size.y(), &context.font_metrics());
+        let mut frame = Frame::new(buffer, size, Vector2F::zero(), lines);
+        frame.update_line_cache(Invalidation::Init);
+        frame.set_active(true);
+
+        let bindings = BindingCollection::new(&config);
+
+        Self {
+            name: name.to_string(),
+            config,
+            core,
+            frame,
+            context,
+            renderer,
+            bindings,
+            size,
+        }
+    }
+
+    fn apply(&mut self, step: &Step) {
+        match step {
+            Step::Key(key, mods) => {
+                let key = Key::new(*key, mods);
+                if let Some(operation) = self.bindings.lookup(&key).cloned() {
+                    self.operation(operation);
+                }
+            }
+            Step::Operation(operation) => self.operation(*operation),
+            Step::Resize(width, height) => {
+                self.renderer
+                    .update_perspective(*width as i32, *height as i32);
+                self.size = vec2f(*width as f32, *height as f32);
+                self.resize_frame();
+            }
+        }
+    }
+
+    fn operation(&mut self, operation: Operation) {
+        match zoomed_font_size(operation, self.context.font_size(), &self.config) {
+            Some(font_size) => {
+                self.context.set_font_size(font_size);
+                self.renderer.reset_glyphs(DPI_FACTOR);
+                self.resize_frame();
+            }
+            None => {
+                self.frame.handle_operation(operation);
+            }
+        }
+    }
+
+    fn resize_frame(&mut self) {
+        let lines = Frame::compute_lines(self.size.y(), &self.context.font_metrics());
+        self.frame.resize(self.size, lines);
+    }
+
+    fn render(&mut self) -> RgbaImage {
+        self.renderer.clear();
+        self.renderer
+            .render_frame(&mut self.context, &mut self.frame);
+        self.renderer.flush();
+        self.renderer.backend_mut().read_pixels()
+    }
+
+    /// runs the script, comparing the frame with its snapshot before the first step
+    /// and after every step.
+    fn run(mut self, script: &[&str]) {
+        let mut failures = Vec::new();
+        self.check(0, "initial", &mut failures);
+
+        for (idx, step) in script.iter().enumerate() {
+            self.apply(&Step::parse(step));
+            self.check(idx + 1, step, &mut failures);
+        }
+
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+
+    fn check(&mut self, idx: usize, step: &str, failures: &mut Vec<String>) {
+        let image = self.render();
+        let file_name = format!("{}-{:02}.png", self.name, idx);
+        let path = Path::new(SNAPSHOTS).join(&file_name);
+
+        let bless = matches!(std::env::var(BLESS_VAR), Ok(val) if val != "0");
+        if bless {
+            write_png(&path, &image);
+            return;
+        }
+        let expected = match read_png(&path) {
+            Some(expected) => expected,
+            None => {
+                let actual_path = PathBuf::from(DIFFS).join(&file_name);
+                write_png(&actual_path, &image);
+                failures.push(format!(
+                    "{} step {} `{}`: no snapshot {}, the frame was written to {}, set {}=1 to \
+                     write the snapshot",
+                    self.name,
+                    idx,
+                    step,
+                    path.display(),
+                    actual_path.display(),
+                    BLESS_VAR
+                ));
+                return;
+            }
+        };
+
+        if let Some((count, diff)) = compare(&expected, &image) {
+            let diff_path = PathBuf::from(DIFFS).join(file_name.replace(".png", ".diff.png"));
+            write_png(&diff_path, &diff);
+            failures.push(format!(
+                "{} step {} `{}`: {} pixels differ, diff written to {}",
+                self.name,
+                idx,
+                step,
+                count,
+                diff_path.display()
+            ));
+        }
+    }
+}
+
+/// compares two images, if they differ by more than the tolerance the number of differing
+/// pixels and an image highlighting them is returned.
+fn compare(expected: &RgbaImage, actual: &RgbaImage) -> Option<(usize, RgbaImage)> {
+    let width = expected.width().max(actual.width());
+    let height = expected.height().max(actual.height());
+    let mut diff = RgbaImage::new(width, height);
+    let mut count = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let inside = |image: &RgbaImage| x < image.width() && y < image.height();
+            let same = inside(expected)
+                && inside(actual)
+                && expected
+                    .pixel(x, y)
+                    .iter()
+                    .zip(actual.pixel(x, y).iter())
+                    .all(|(a, b)| (*a as i32 - *b as i32).abs() <= CHANNEL_TOLERANCE as i32);
+
+            if same {
+                // matching pixels are faded so the differences stand out.
+                let [r, g, b, _] = actual.pixel(x, y);
+                let luma = (r as u32 + g as u32 + b as u32) / 3;
+                let faded = (170 + luma / 3) as u8;
+                diff.set_pixel(x, y, [faded, faded, faded, 255]);
+            } else {
+                count += 1;
+                diff.set_pixel(x, y, [255, 0, 0, 255]);
+            }
+        }
+    }
+
+    let allowed = (PIXEL_TOLERANCE * (width * height) as f32) as usize;
+    if count > allowed {
+        Some((count, diff))
+    } else {
+        None
+    }
+}
+
+fn read_png(path: &Path) -> Option<RgbaImage> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let (info, mut reader) = decoder.read_info().ok()?;
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return None;
+    }
+
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels).ok()?;
+    RgbaImage::from_pixels(info.width, info.height, pixels)
+}
+
+fn write_png(path: &Path, image: &RgbaImage) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+
+    let file = File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width(), image.height());
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(image.pixels())
+        .unwrap();
+}
+
+#[test]
+fn cursor_movement() {
+    let harness = Harness::new("cursor_movement", "sample.rs", "kea.yml", 320, 200);
+    harness.run(&[
+        "key down",
+        "key down",
+        "key right",
+        "key right",
+        "key up",
+        "key left",
+        "key left",
+        "key left",
+    ]);
+}
+
+#[test]
+fn scroll_and_resize() {
+    let harness = Harness::new("scroll_and_resize", "sample.rs", "kea.yml", 320, 200);
+    harness.run(&[
+        "key pagedown",
+        "resize 240 320",
+        "scroll_up",
+        "resize 320 120",
+    ]);
+}
+
+#[test]
+fn zoom() {
+    let harness = Harness::new("zoom", "sample.rs", "kea.yml", 320, 200);
+    harness.run(&[
+        "key ctrl+equals",
+        "key ctrl+equals",
+        "key ctrl+minus",
+        "key ctrl+0",
+    ]);
+}
+
+#[test]
+fn compare_tolerance() {
+    let mut expected = RgbaImage::new(4, 4);
+    let mut actual = RgbaImage::new(4, 4);
+    expected.set_pixel(0, 0, [100, 100, 100, 255]);
+    actual.set_pixel(0, 0, [104, 100, 96, 255]);
+    assert!(compare(&expected, &actual).is_none());
+
+    actual.set_pixel(1, 1, [255, 255, 255, 255]);
+    let (count, diff) = compare(&expected, &actual).unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(diff.pixel(1, 1), [255, 0, 0, 255]);
+}
@@ -0,0 +1,493 @@
+// Snippets.
+// A snippet is text with tab stops, read from snippets/<language>.yml next to kea.yml. A file maps
+// the name of each snippet to its body, or to a body and a description:
+//
+//     fn: "fn ${1:name}($2) {\n\t$0\n}"
+//     test:
+//       body: ["#[test]", "fn ${1:name}() {", "\t$0", "}"]
+//       description: a test function
+//
+// `$1` and `${1:placeholder}` are tab stops, visited in order with `$0` last. A stop that appears
+// several times is mirrored, every copy shows the text typed into the stop. `${1|one,two|}` offers
+// a choice of texts. `$NAME` and `${NAME:default}` are variables: the file of the buffer
+// (TM_FILENAME, TM_FILENAME_BASE, TM_DIRECTORY, TM_FILEPATH) and the date and time (CURRENT_YEAR,
+// CURRENT_MONTH, CURRENT_DATE, CURRENT_HOUR, ...). A backslash escapes `$`, `}` and `\`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use crate::core::BufferId;
+
+/// the body of a snippet, a list is joined with new lines.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum Body {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum SnippetEntry {
+    Body(Body),
+    Described {
+        body: Body,
+        #[serde(default)]
+        description: Option<String>,
+    },
+}
+
+/// a snippet as written in a snippet file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetDef {
+    pub name: String,
+    pub body: String,
+    pub description: Option<String>,
+}
+
+/// the snippets of a file, a missing file has none.
+pub fn read_snippets(path: &Path) -> Result<Vec<SnippetDef>, String> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let entries: Option<BTreeMap<String, SnippetEntry>> = serde_yaml::from_str(&content)
+        .map_err(|e| format!("invalid snippets '{}': {}", path.display(), e))?;
+    Ok(entries
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, entry)| {
+            let (body, description) = match entry {
+                SnippetEntry::Body(body) => (body, None),
+                SnippetEntry::Described { body, description } => (body, description),
+            };
+            let body = match body {
+                Body::Text(text) => text,
+                Body::Lines(lines) => lines.join("\n"),
+            };
+            SnippetDef {
+                name,
+                body,
+                description,
+            }
+        })
+        .collect())
+}
+
+/// what variables are replaced with.
+pub struct Variables {
+    /// the file of the buffer the snippet is expanded in.
+    pub path: Option<PathBuf>,
+    pub now: DateTime<Local>,
+}
+
+impl Variables {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            now: Local::now(),
+        }
+    }
+
+    /// the value of a variable, None if it is unknown or has no value.
+    fn get(&self, name: &str) -> Option<String> {
+        let path = self.path.as_deref();
+        let file = |f: fn(&Path) -> Option<&std::ffi::OsStr>| {
+            path.and_then(f)
+                .map(|name| name.to_string_lossy().to_string())
+        };
+        let date = |format: &str| Some(self.now.format(format).to_string());
+        match name {
+            "TM_FILENAME" => file(Path::file_name),
+            "TM_FILENAME_BASE" => file(Path::file_stem),
+            "TM_DIRECTORY" => path
+                .and_then(Path::parent)
+                .map(|dir| dir.to_string_lossy().to_string()),
+            "TM_FILEPATH" => path.map(|path| path.to_string_lossy().to_string()),
+            "CURRENT_YEAR" => date("%Y"),
+            "CURRENT_YEAR_SHORT" => date("%y"),
+            "CURRENT_MONTH" => date("%m"),
+            "CURRENT_MONTH_NAME" => date("%B"),
+            "CURRENT_MONTH_NAME_SHORT" => date("%b"),
+            "CURRENT_DATE" => date("%d"),
+            "CURRENT_DAY_NAME" => date("%A"),
+            "CURRENT_DAY_NAME_SHORT" => date("%a"),
+            "CURRENT_HOUR" => date("%H"),
+            "CURRENT_MINUTE" => date("%M"),
+            "CURRENT_SECOND" => date("%S"),
+            "CURRENT_SECONDS_UNIX" => date("%s"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Stop {
+        number: usize,
+        placeholder: Vec<Part>,
+        choices: Vec<String>,
+    },
+    Variable {
+        name: String,
+        default: Vec<Part>,
+    },
+}
+
+/// a tab stop of an expanded snippet, the ranges are character offsets into its text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStop {
+    pub number: usize,
+    /// the first range is the stop, the others are its mirrors.
+    pub ranges: Vec<Range<usize>>,
+    pub choices: Vec<String>,
+}
+
+/// the text of a snippet with its variables replaced and its tab stops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub text: String,
+    /// in the order they are visited, `$0` last. A snippet without `$0` ends after its text.
+    pub stops: Vec<TabStop>,
+}
+
+/// expands the body of a snippet. Lines after the first are indented like the line the snippet
+/// is inserted on.
+pub fn expand(body: &str, indent: &str, variables: &Variables) -> Expansion {
+    let parts = parse(&mut body.chars().peekable(), false);
+    let mut placeholders = HashMap::new();
+    collect_placeholders(&parts, &mut placeholders);
+
+    let mut expansion = Expansion {
+        text: String::new(),
+        stops: Vec::new(),
+    };
+    let mut len = 0;
+    render(
+        &parts,
+        indent,
+        variables,
+        &placeholders,
+        &mut expansion,
+        &mut len,
+    );
+
+    expansion.stops.sort_by_key(|stop| {
+        if stop.number == 0 {
+            usize::MAX
+        } else {
+            stop.number
+        }
+    });
+    if expansion.stops.last().map_or(true, |stop| stop.number != 0) {
+        expansion.stops.push(TabStop {
+            number: 0,
+            ranges: vec![len..len],
+            choices: Vec::new(),
+        });
+    }
+    expansion
+}
+
+/// an expanded snippet whose tab stops are being filled. The stops are regions of the buffer so
+/// they follow the edits, the regions of a stop are its placeholder and its mirrors.
+#[derive(Debug, Clone)]
+pub struct SnippetSession {
+    pub buffer: BufferId,
+    /// the regions and choices of each stop in the order they are visited.
+    pub stops: Vec<(Vec<usize>, Vec<String>)>,
+    /// the index of the active stop.
+    pub current: usize,
+    /// typing replaces the placeholder of the active stop until it was edited.
+    pub fresh: bool,
+}
+
+impl SnippetSession {
+    /// the session of a snippet inserted at char_idx, with the regions of its stops.
+    pub fn new(
+        buffer: BufferId,
+        expansion: &Expansion,
+        char_idx: usize,
+    ) -> (Self, Vec<Range<usize>>) {
+        let mut regions = Vec::new();
+        let stops = expansion
+            .stops
+            .iter()
+            .map(|stop| {
+                let indices = stop
+                    .ranges
+                    .iter()
+                    .map(|range| {
+                        regions.push(char_idx + range.start..char_idx + range.end);
+                        regions.len() - 1
+                    })
+                    .collect();
+                (indices, stop.choices.clone())
+            })
+            .collect();
+        let session = Self {
+            buffer,
+            stops,
+            current: 0,
+            fresh: false,
+        };
+        (session, regions)
+    }
+
+    /// is the active stop the last one, where the cursor ends.
+    pub fn at_end(&self) -> bool {
+        self.current + 1 >= self.stops.len()
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// parses parts until the end of the body, or until the closing `}` when nested.
+fn parse(chars: &mut Chars, nested: bool) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.peek() {
+                Some('$') | Some('}') | Some('\\') => text.push(chars.next().unwrap()),
+                _ => text.push(ch),
+            },
+            '}' if nested => break,
+            '$' => match parse_dollar(chars) {
+                Some(part) => {
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                }
+                None => text.push('$'),
+            },
+            ch => text.push(ch),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    parts
+}
+
+/// parses what follows a `$`, None if it is a plain dollar sign.
+fn parse_dollar(chars: &mut Chars) -> Option<Part> {
+    let braced = chars.peek() == Some(&'{');
+    if braced {
+        chars.next();
+    }
+    let mut name = String::new();
+    while let Some(&ch) = chars.peek() {
+        if !(ch.is_ascii_alphanumeric() || ch == '_') {
+            break;
+        }
+        name.push(ch);
+        chars.next();
+    }
+    if name.is_empty() {
+        return if braced {
+            Some(Part::Text("${".to_string()))
+        } else {
+            None
+        };
+    }
+
+    let number = name.parse::<usize>().ok();
+    let mut inner = Vec::new();
+    let mut choices = Vec::new();
+    if braced {
+        match chars.next() {
+            Some(':') => inner = parse(chars, true),
+            Some('|') if number.is_some() => {
+                choices = parse_choices(chars);
+                // the closing brace.
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    Some(match number {
+        Some(number) => Part::Stop {
+            number,
+            placeholder: inner,
+            choices,
+        },
+        None => Part::Variable {
+            name,
+            default: inner,
+        },
+    })
+}
+
+/// the comma separated choices of `${1|one,two|}`, after the first `|`.
+fn parse_choices(chars: &mut Chars) -> Vec<String> {
+    let mut choices = vec![String::new()];
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    choices.last_mut().unwrap().push(next);
+                }
+            }
+            ',' => choices.push(String::new()),
+            '|' => break,
+            ch => choices.last_mut().unwrap().push(ch),
+        }
+    }
+    choices
+}
+
+/// the first placeholder of each stop, its mirrors show the same text.
+fn collect_placeholders<'a>(parts: &'a [Part], placeholders: &mut HashMap<usize, &'a [Part]>) {
+    for part in parts {
+        match part {
+            Part::Stop {
+                number,
+                placeholder,
+                ..
+            } => {
+                if !placeholder.is_empty() {
+                    placeholders
+                        .entry(*number)
+                        .or_insert(placeholder.as_slice());
+                }
+                collect_placeholders(placeholder, placeholders);
+            }
+            Part::Variable { default, .. } => collect_placeholders(default, placeholders),
+            Part::Text(_) => {}
+        }
+    }
+}
+
+fn render(
+    parts: &[Part],
+    indent: &str,
+    variables: &Variables,
+    placeholders: &HashMap<usize, &[Part]>,
+    expansion: &mut Expansion,
+    len: &mut usize,
+) {
+    let push = |expansion: &mut Expansion, len: &mut usize, text: &str| {
+        for ch in text.chars() {
+            expansion.text.push(ch);
+            *len += 1;
+            if ch == '\n' {
+                expansion.text.push_str(indent);
+                *len += indent.chars().count();
+            }
+        }
+    };
+
+    for part in parts {
+        match part {
+            Part::Text(text) => push(expansion, len, text),
+            Part::Variable { name, default } => match variables.get(name) {
+                Some(value) if !value.is_empty() => push(expansion, len, &value),
+                _ if !default.is_empty() => {
+                    render(default, indent, variables, placeholders, expansion, len)
+                }
+                // an unknown variable is left as a placeholder of its name.
+                _ => push(expansion, len, name),
+            },
+            Part::Stop {
+                number,
+                placeholder,
+                choices,
+            } => {
+                let start = *len;
+                if let Some(choice) = choices.first() {
+                    push(expansion, len, choice);
+                } else {
+                    // a mirror shows the placeholder of the stop, a nested stop of a mirror
+                    // isn't a stop again.
+                    let mirrored = expansion.stops.iter().any(|stop| stop.number == *number);
+                    let placeholder = placeholders
+                        .get(number)
+                        .copied()
+                        .unwrap_or(placeholder.as_slice());
+                    if mirrored {
+                        let mut copy = Expansion {
+                            text: String::new(),
+                            stops: Vec::new(),
+                        };
+                        let mut copy_len = 0;
+                        render(
+                            placeholder,
+                            indent,
+                            variables,
+                            placeholders,
+                            &mut copy,
+                            &mut copy_len,
+                        );
+                        expansion.text.push_str(&copy.text);
+                        *len += copy_len;
+                    } else {
+                        render(placeholder, indent, variables, placeholders, expansion, len);
+                    }
+                }
+                let range = start..*len;
+                match expansion
+                    .stops
+                    .iter_mut()
+                    .find(|stop| stop.number == *number)
+                {
+                    Some(stop) => stop.ranges.push(range),
+                    None => expansion.stops.push(TabStop {
+                        number: *number,
+                        ranges: vec![range],
+                        choices: choices.clone(),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn expansion() {
+        let variables = Variables {
+            path: Some(PathBuf::from("/src/main.rs")),
+            now: Local.with_ymd_and_hms(2021, 3, 4, 5, 6, 7).unwrap(),
+        };
+        let expansion = expand(
+            "fn ${1:name}(${2|a,b|}) -> ${1} {\n\t$0 \\$x $TM_FILENAME_BASE ${NOPE:${3:v}}\n}",
+            "    ",
+            &variables,
+        );
+        assert_eq!(
+            expansion.text,
+            "fn name(a) -> name {\n    \t $x main v\n    }"
+        );
+        let ranges: Vec<(usize, Vec<Range<usize>>)> = expansion
+            .stops
+            .iter()
+            .map(|stop| (stop.number, stop.ranges.clone()))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (1, vec![3..7, 14..18]),
+                (2, vec![8..9]),
+                (3, vec![35..36]),
+                (0, vec![26..26]),
+            ]
+        );
+        assert_eq!(expansion.stops[1].choices, vec!["a", "b"]);
+
+        let date = expand("$CURRENT_YEAR-$CURRENT_MONTH-$CURRENT_DATE", "", &variables);
+        assert_eq!(date.text, "2021-03-04");
+        assert_eq!(date.stops[0].ranges, vec![10..10]);
+    }
+}
@@ -1,23 +1,36 @@
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
+use kea::lsp::{Position, TextChange, TextRange};
 use ropey::Rope;
 
-use super::CoreError;
+use super::changes::Changes;
+use super::diff;
+use super::history::{Change, History};
+use super::regions::Regions;
+use super::{CoreError, Diagnostic, Diagnostics, Syntax};
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub struct BufferId(usize);
+pub struct BufferId(pub(super) usize);
 
 fn map_error(e: std::io::Error, path: &PathBuf) -> CoreError {
     match e.kind() {
         std::io::ErrorKind::NotFound => CoreError::FileNotFound(path.clone()),
         std::io::ErrorKind::PermissionDenied => CoreError::FilePermissions(path.clone()),
         std::io::ErrorKind::Interrupted => panic!("Read interrupted"),
-        _ => unimplemented!("Io errors not mapped: {}", e),
+        _ => CoreError::ReadFailed(path.clone(), e.to_string()),
     }
 }
 
+fn read_file(path: &Path) -> Result<Rope, CoreError> {
+    File::open(path)
+        .and_then(|f| Rope::from_reader(BufReader::new(f)))
+        .map_err(|e| map_error(e, &path.to_path_buf()))
+}
+
 struct Line {
     // line number in line space (starts at 1)
     line: usize,
@@ -96,6 +109,10 @@ impl ShallowCache {
         self.lines.clear()
     }
 
+    fn truncate(&mut self, len: usize) {
+        self.lines.truncate(len)
+    }
+
     fn len(&self) -> usize {
         self.lines.len()
     }
@@ -120,6 +137,28 @@ pub struct Buffer {
     path: Option<PathBuf>,
     content: Rope,
     shallow_cache: ShallowCache,
+    /// the buffer has been modified since it was loaded or saved.
+    dirty: bool,
+    /// the contents of the file when it was last loaded or saved. Used to tell whether the
+    /// file was changed by another program and as the base of a merge.
+    saved: Rope,
+    /// incremented whenever the contents change.
+    revision: usize,
+    /// the changes since they were last taken, None when changes aren't tracked.
+    changes: Option<Vec<TextChange>>,
+    /// the errors and warnings reported for the text.
+    diagnostics: Diagnostics,
+    /// the edits that can be undone and redone.
+    history: History,
+    /// ranges that follow the edits, e.g. the tab stops of a snippet.
+    regions: Regions,
+    /// ranges that follow the edits for the frames showing the buffer, e.g. their folds, by
+    /// the id of the frame.
+    anchored: BTreeMap<usize, Regions>,
+    /// the syntax tree of the text, None if no grammar knows the language of the file.
+    syntax: Option<Syntax>,
+    /// how the text differs from the file in git.
+    git: Changes,
 }
 
 pub type BufferResult = ::std::result::Result<BufferId, CoreError>;
@@ -128,16 +167,24 @@ impl Buffer {
     pub fn from_path<S: AsRef<Path>>(path: S, id: usize) -> Result<Self, CoreError> {
         let id = BufferId(id);
 
-        let content = File::open(path.as_ref())
-            .and_then(|f| Ok(BufReader::new(f)))
-            .and_then(|r| Rope::from_reader(r))
-            .map_err(|e| map_error(e, &path.as_ref().to_path_buf()))?;
+        let content = read_file(path.as_ref())?;
+        let syntax = Syntax::for_path(path.as_ref(), &content);
 
         let mut buffer = Self {
             id,
             path: Some(path.as_ref().to_path_buf()),
+            saved: content.clone(),
             content,
             shallow_cache: ShallowCache::new(),
+            dirty: false,
+            revision: 0,
+            changes: None,
+            diagnostics: Diagnostics::new(),
+            history: History::new(),
+            regions: Regions::default(),
+            anchored: BTreeMap::new(),
+            syntax,
+            git: Changes::default(),
         };
 
         buffer.invalidate_line_cache(0);
@@ -145,15 +192,438 @@ impl Buffer {
         Ok(buffer)
     }
 
+    /// a buffer listing the entries of a directory, one per line. Directories are listed
+    /// first and end with a '/'.
+    pub fn from_directory<S: AsRef<Path>>(path: S, id: usize) -> Result<Self, CoreError> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries: Vec<(bool, String)> = std::fs::read_dir(&path)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| map_error(e, &path))?
+            .into_iter()
+            .map(|entry| {
+                let is_dir = entry.file_type().map_or(false, |ty| ty.is_dir());
+                (!is_dir, entry.file_name().to_string_lossy().into_owned())
+            })
+            .collect();
+        entries.sort();
+
+        let mut listing = String::new();
+        for (is_file, name) in entries {
+            listing.push_str(&name);
+            if !is_file {
+                listing.push('/');
+            }
+            listing.push('\n');
+        }
+
+        Ok(Self::scratch(&listing, id))
+    }
+
+    /// a buffer without a file showing text, e.g. a list of references. It isn't modified.
+    pub fn scratch(text: &str, id: usize) -> Self {
+        let mut buffer = Self {
+            id: BufferId(id),
+            path: None,
+            content: Rope::from_str(text),
+            shallow_cache: ShallowCache::new(),
+            dirty: false,
+            saved: Rope::new(),
+            revision: 0,
+            changes: None,
+            diagnostics: Diagnostics::new(),
+            history: History::new(),
+            regions: Regions::default(),
+            anchored: BTreeMap::new(),
+            syntax: None,
+            git: Changes::default(),
+        };
+        buffer.invalidate_line_cache(0);
+        buffer
+    }
+
     pub fn empty(id: usize) -> Result<Self, CoreError> {
         Ok(Self {
             id: BufferId(id),
             path: None,
             content: Rope::new(),
             shallow_cache: ShallowCache::new(),
+            dirty: false,
+            saved: Rope::new(),
+            revision: 0,
+            changes: None,
+            diagnostics: Diagnostics::new(),
+            history: History::new(),
+            regions: Regions::default(),
+            anchored: BTreeMap::new(),
+            syntax: None,
+            git: Changes::default(),
         })
     }
 
+    /// an empty buffer that will be saved to path. Used when opening a file that doesn't exist.
+    pub fn with_path<S: AsRef<Path>>(path: S, id: usize) -> Self {
+        let content = Rope::new();
+        Self {
+            id: BufferId(id),
+            path: Some(path.as_ref().to_path_buf()),
+            syntax: Syntax::for_path(path.as_ref(), &content),
+            content,
+            shallow_cache: ShallowCache::new(),
+            dirty: false,
+            saved: Rope::new(),
+            revision: 0,
+            changes: None,
+            diagnostics: Diagnostics::new(),
+            history: History::new(),
+            regions: Regions::default(),
+            anchored: BTreeMap::new(),
+            git: Changes::default(),
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// inserts text before the character at char_idx.
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        let char_idx = char_idx.min(self.content.len_chars());
+        if text.is_empty() {
+            return;
+        }
+        self.history.record(Change::Insert {
+            char_idx,
+            text: text.to_string(),
+        });
+        self.insert_text(char_idx, text);
+    }
+
+    /// removes a range of characters.
+    pub fn remove(&mut self, range: Range<usize>) {
+        let len = self.content.len_chars();
+        let range = range.start.min(len)..range.end.min(len);
+        if range.start >= range.end {
+            return;
+        }
+        self.history.record(Change::Remove {
+            char_idx: range.start,
+            text: self.text(range.clone()),
+        });
+        self.remove_text(range);
+    }
+
+    fn insert_text(&mut self, char_idx: usize, text: &str) {
+        let line = self.content.char_to_line(char_idx);
+        if self.changes.is_some() {
+            let position = self.lsp_position(char_idx);
+            self.record_change(Some(TextRange::new(position, position)), text.to_string());
+        }
+        self.content.insert(char_idx, text);
+        if let Some(syntax) = self.syntax.as_mut() {
+            let lines = text.matches('\n').count() + 1;
+            syntax.edited(&self.content, line, 1, lines);
+        }
+        let len = text.chars().count();
+        self.diagnostics.inserted(char_idx, len);
+        self.regions.inserted(char_idx, len);
+        for regions in self.anchored.values_mut() {
+            regions.inserted(char_idx, len);
+        }
+        self.dirty = true;
+        self.revision += 1;
+        self.invalidate_line_cache(line);
+    }
+
+    fn remove_text(&mut self, range: Range<usize>) {
+        let line = self.content.char_to_line(range.start);
+        if self.changes.is_some() {
+            let start = self.lsp_position(range.start);
+            let end = self.lsp_position(range.end);
+            self.record_change(Some(TextRange::new(start, end)), String::new());
+        }
+        let lines = self.content.char_to_line(range.end) - line + 1;
+        self.content.remove(range.clone());
+        if let Some(syntax) = self.syntax.as_mut() {
+            syntax.edited(&self.content, line, lines, 1);
+        }
+        self.diagnostics.removed(range.clone());
+        self.regions.removed(range.clone());
+        for regions in self.anchored.values_mut() {
+            regions.removed(range.clone());
+        }
+        self.dirty = true;
+        self.revision += 1;
+        self.invalidate_line_cache(line);
+    }
+
+    /// the edits until the matching `end_edit` are undone at once.
+    pub fn begin_edit(&mut self) {
+        self.history.begin();
+    }
+
+    pub fn end_edit(&mut self) {
+        self.history.end();
+    }
+
+    /// the next edit is undone on its own even if it continues the typed text.
+    pub fn close_edit(&mut self) {
+        self.history.close();
+    }
+
+    /// reverts the last edit, returns where it happened.
+    pub fn undo(&mut self) -> Option<usize> {
+        let changes = self.history.undo()?;
+        Some(self.apply(changes))
+    }
+
+    /// applies the last reverted edit again, returns where it happened.
+    pub fn redo(&mut self) -> Option<usize> {
+        let changes = self.history.redo()?;
+        Some(self.apply(changes))
+    }
+
+    /// applies changes without recording them, returns the position after the last one.
+    fn apply(&mut self, changes: Vec<Change>) -> usize {
+        let mut position = 0;
+        for change in changes {
+            let range = change.range();
+            match change {
+                Change::Insert { char_idx, text } => {
+                    self.insert_text(char_idx, &text);
+                    position = range.end;
+                }
+                Change::Remove { .. } => {
+                    self.remove_text(range.clone());
+                    position = range.start;
+                }
+            }
+        }
+        position
+    }
+
+    /// replaces the regions of the buffer.
+    pub fn set_regions(&mut self, ranges: Vec<Range<usize>>) {
+        self.regions = Regions::new(ranges);
+    }
+
+    /// replaces the ranges kept for an owner, e.g. the folds of a frame.
+    pub fn set_anchored(&mut self, owner: usize, ranges: Vec<Range<usize>>) {
+        if ranges.is_empty() {
+            self.anchored.remove(&owner);
+        } else {
+            self.anchored.insert(owner, Regions::new(ranges));
+        }
+    }
+
+    /// the ranges kept for an owner as they are now.
+    pub fn anchored(&self, owner: usize) -> &[Range<usize>] {
+        self.anchored.get(&owner).map_or(&[], Regions::ranges)
+    }
+
+    /// a region as it is now, None if there is no region idx.
+    pub fn region(&self, idx: usize) -> Option<Range<usize>> {
+        self.regions.get(idx)
+    }
+
+    /// the text of a range of characters.
+    pub fn text(&self, range: Range<usize>) -> String {
+        let len = self.content.len_chars();
+        String::from(self.content.slice(range.start.min(len)..range.end.min(len)))
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.content.len_chars()
+    }
+
+    /// the character index of a line and column, both zero-indexed. The column is
+    /// clamped to the length of the line.
+    pub fn char_index(&self, line: usize, column: usize) -> usize {
+        if line >= self.content.len_lines() {
+            return self.content.len_chars();
+        }
+        self.content.line_to_char(line) + column.min(self.line_len(line))
+    }
+
+    /// the line and column of a character index.
+    pub fn position(&self, char_idx: usize) -> (usize, usize) {
+        let char_idx = char_idx.min(self.content.len_chars());
+        let line = self.content.char_to_line(char_idx);
+        (line, char_idx - self.content.line_to_char(line))
+    }
+
+    pub fn save(&mut self) -> Result<(), CoreError> {
+        let path = self.path.clone().ok_or(CoreError::NoPath)?;
+        self.save_as(path)
+    }
+
+    /// writes the buffer to path, the buffer is saved to this path from now on.
+    pub fn save_as<S: AsRef<Path>>(&mut self, path: S) -> Result<(), CoreError> {
+        let path = path.as_ref().to_path_buf();
+        File::create(&path)
+            .and_then(|f| self.content.write_to(BufWriter::new(f)))
+            .map_err(|e| CoreError::WriteFailed(path.clone(), e.to_string()))?;
+
+        if self.path.as_ref().map(|old| old.extension()) != Some(path.extension()) {
+            self.syntax = Syntax::for_path(&path, &self.content);
+        }
+        self.path = Some(path);
+        self.dirty = false;
+        self.saved = self.content.clone();
+        Ok(())
+    }
+
+    /// reads the file of the buffer as it is on disk now.
+    pub fn read_disk(&self) -> Result<Rope, CoreError> {
+        read_file(self.path.as_deref().ok_or(CoreError::NoPath)?)
+    }
+
+    /// was the file changed by another program since it was loaded or saved.
+    pub fn changed_on_disk(&self, disk: &Rope) -> bool {
+        *disk != self.saved
+    }
+
+    /// replaces the contents with the file on disk, unsaved changes are lost. The reload is
+    /// undone in one step.
+    pub fn reload(&mut self, disk: Rope) {
+        self.replace_text(&disk.to_string());
+        self.saved = disk;
+        self.dirty = false;
+    }
+
+    /// keeps the contents of the buffer, the change on disk is no longer reported. Saving
+    /// the buffer overwrites the file.
+    pub fn keep(&mut self, disk: Rope) {
+        self.saved = disk;
+    }
+
+    /// applies the changes made to the file on disk to the buffer. Returns the number of
+    /// conflicts, the buffer keeps its own lines there. The merge is undone in one step.
+    pub fn merge(&mut self, disk: Rope) -> usize {
+        let merged = diff::merge(
+            &self.saved.to_string(),
+            &self.content.to_string(),
+            &disk.to_string(),
+        );
+        self.replace_text(&merged.text);
+        self.saved = disk;
+        self.dirty = true;
+        merged.conflicts
+    }
+
+    /// replaces the contents with text recovered from a swap file, the buffer is modified
+    /// until it is saved. The recovery is undone in one step.
+    pub fn recover(&mut self, text: &str) {
+        self.replace_text(text);
+        self.dirty = true;
+    }
+
+    /// replaces the contents with text by editing the lines that differ, so the positions in
+    /// the unchanged lines are kept. The edits are a single step of the history.
+    fn replace_text(&mut self, text: &str) {
+        let current = self.content.to_string();
+        let old: Vec<&str> = current.split_inclusive('\n').collect();
+        let new: Vec<&str> = text.split_inclusive('\n').collect();
+        // the character index of the start of each old line, and of the end of the text.
+        let mut starts = vec![0];
+        for line in old.iter() {
+            starts.push(starts[starts.len() - 1] + line.chars().count());
+        }
+
+        self.close_edit();
+        self.begin_edit();
+        // the last hunk is applied first so the indices of the others stay the same.
+        for hunk in diff::diff(&old, &new).iter().rev() {
+            let start = starts[hunk.old.start];
+            self.remove(start..starts[hunk.old.end]);
+            self.insert(start, &new[hunk.new.clone()].concat());
+        }
+        self.end_edit();
+        self.close_edit();
+    }
+
+    /// starts recording the changes of the buffer, e.g. for a language server.
+    pub fn track_changes(&mut self) {
+        self.changes.get_or_insert_with(Vec::new);
+    }
+
+    /// the changes since they were last taken, in the order they were made.
+    pub fn take_changes(&mut self) -> Vec<TextChange> {
+        self.changes.as_mut().map_or_else(Vec::new, std::mem::take)
+    }
+
+    fn record_change(&mut self, range: Option<TextRange>, text: String) {
+        if let Some(changes) = self.changes.as_mut() {
+            changes.push(TextChange { range, text });
+        }
+    }
+
+    /// the syntax tree of the text, None if the language of the buffer has no grammar.
+    pub fn syntax(&self) -> Option<&Syntax> {
+        self.syntax.as_ref()
+    }
+
+    /// how the text differs from the file in git, computed in the background.
+    pub fn git_changes(&self) -> &Changes {
+        &self.git
+    }
+
+    pub fn set_git_changes(&mut self, changes: Changes) {
+        self.git = changes;
+    }
+
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// replaces the diagnostics reported by a source.
+    pub fn set_diagnostics(&mut self, source: &str, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics.set(source, diagnostics);
+    }
+
+    /// the position of a character the way language servers count, in utf-16 code units.
+    pub fn lsp_position(&self, char_idx: usize) -> Position {
+        let (line, column) = self.position(char_idx);
+        let line_start = self.content.line_to_char(line);
+        let character = self
+            .content
+            .slice(line_start..line_start + column)
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        Position::new(line, character)
+    }
+
+    /// the character index of a position from a language server, it is clamped to its line.
+    pub fn lsp_char_index(&self, position: Position) -> usize {
+        if position.line >= self.content.len_lines() {
+            return self.content.len_chars();
+        }
+        let line_start = self.content.line_to_char(position.line);
+        let mut units = 0;
+        let mut column = 0;
+        for ch in self
+            .content
+            .line(position.line)
+            .chars()
+            .take(self.line_len(position.line))
+        {
+            if units >= position.character {
+                break;
+            }
+            units += ch.len_utf16();
+            column += 1;
+        }
+        line_start + column
+    }
+
     pub fn id(&self) -> BufferId {
         self.id
     }
@@ -164,16 +634,16 @@ impl Buffer {
 
     /// invalids the shallow line cache from start_line to the end of the buffer.
     pub fn invalidate_line_cache(&mut self, start_line: usize) {
-        let mut last_line_byte = self
-            .shallow_cache
-            .get(start_line)
-            .map_or(0, |l| l.start_index());
+        let start_line = start_line.min(self.shallow_cache.len());
+        let mut last_line_byte = self.content.line_to_byte(start_line);
         for (idx, line) in self.content.lines().skip(start_line).enumerate() {
             let idx = idx + start_line;
             let bytes = line.len_bytes();
-            let bytes_end_line = if bytes >= 2 && line.char(bytes - 2) == '\r' {
+            // the line ending is checked by character, lines can have multi-byte characters.
+            let chars = line.len_chars();
+            let bytes_end_line = if chars >= 2 && line.char(chars - 2) == '\r' {
                 bytes - 2
-            } else if bytes >= 1 && line.char(bytes - 1) == '\n' {
+            } else if chars >= 1 && line.char(chars - 1) == '\n' {
                 bytes - 1
             } else {
                 bytes
@@ -201,11 +671,14 @@ impl Buffer {
             last_line_byte += bytes;
         }
 
-        for line in &self.shallow_cache.lines {
-            let start = line.start_index();
-            let end = line.end_line();
-            let l = self.content.slice(start..end);
-        }
+        // lines that were removed.
+        let num_lines = self.content.len_lines();
+        let num_lines = if self.content.line(num_lines - 1).len_bytes() == 0 {
+            num_lines - 1
+        } else {
+            num_lines
+        };
+        self.shallow_cache.truncate(num_lines);
     }
 
     /// if a character was inserted into the buffer then the shallow line cache doesn't
@@ -224,8 +697,24 @@ impl Buffer {
         }
     }
 
+    /// number of lines in the buffer.
+    pub fn num_lines(&self) -> usize {
+        self.shallow_cache.len()
+    }
+
+    /// number of characters in a line, not including the line ending.
+    pub fn line_len(&self, line: usize) -> usize {
+        self.shallow_cache.get(line).map_or(0, |line| {
+            self.content
+                .byte_to_char(line.end_line())
+                .saturating_sub(self.content.byte_to_char(line.start_index()))
+        })
+    }
+
     pub fn request_lines(&self, start: usize, end: usize) -> Vec<String> {
         let mut res = Vec::new();
+        let end = end.min(self.shallow_cache.len());
+        let start = start.min(end);
 
         if let Some(lines) = self.shallow_cache.slice(start..end) {
             for line in lines {
@@ -237,3 +726,106 @@ impl Buffer {
         res
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn directory_listing() {
+        let dir = std::env::temp_dir().join(format!("kea-listing-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("b.txt"), "").unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+
+        let buffer = Buffer::from_directory(&dir, 0).unwrap();
+        assert_eq!(buffer.request_lines(0, 10), vec!["src/", "a.txt", "b.txt"]);
+        assert!(buffer.path().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tracked_changes() {
+        let mut buffer = Buffer::scratch("a\u{1F600}b\nline\n", 0);
+        buffer.insert(0, "x");
+        assert!(buffer.take_changes().is_empty());
+
+        buffer.track_changes();
+        // the emoji is two utf-16 code units.
+        buffer.insert(3, "c");
+        buffer.remove(6..10);
+        let at = |line, character| Position::new(line, character);
+        assert_eq!(
+            buffer.take_changes(),
+            vec![
+                TextChange {
+                    range: Some(TextRange::new(at(0, 4), at(0, 4))),
+                    text: "c".to_string(),
+                },
+                TextChange {
+                    range: Some(TextRange::new(at(1, 0), at(1, 4))),
+                    text: String::new(),
+                },
+            ]
+        );
+        assert_eq!(buffer.lsp_char_index(at(0, 4)), 3);
+        assert_eq!(buffer.lsp_char_index(at(0, 100)), 5);
+        assert!(buffer.take_changes().is_empty());
+
+        // the recovered text replaces the lines that differ.
+        buffer.recover("new");
+        assert_eq!(
+            buffer.take_changes(),
+            vec![
+                TextChange {
+                    range: Some(TextRange::new(at(0, 0), at(2, 0))),
+                    text: String::new(),
+                },
+                TextChange {
+                    range: Some(TextRange::new(at(0, 0), at(0, 0))),
+                    text: "new".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_is_undone_in_one_step() {
+        let mut buffer = Buffer::scratch("a\nb\nc\n", 0);
+        buffer.keep(Rope::from_str("a\nb\nc\n"));
+        buffer.insert(0, "x");
+        assert_eq!(buffer.merge(Rope::from_str("a\nb\nC\n")), 0);
+        assert_eq!(buffer.content().to_string(), "xa\nb\nC\n");
+
+        buffer.undo();
+        assert_eq!(buffer.content().to_string(), "xa\nb\nc\n");
+        buffer.undo();
+        assert_eq!(buffer.content().to_string(), "a\nb\nc\n");
+        buffer.redo();
+        buffer.redo();
+        assert_eq!(buffer.content().to_string(), "xa\nb\nC\n");
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut buffer = Buffer::scratch("one\n", 0);
+        buffer.insert(3, " t");
+        buffer.insert(5, "wo");
+        buffer.begin_edit();
+        buffer.remove(0..4);
+        buffer.insert(0, "1");
+        buffer.end_edit();
+        assert_eq!(buffer.content().to_string(), "1two\n");
+
+        assert_eq!(buffer.undo(), Some(4));
+        assert_eq!(buffer.content().to_string(), "one two\n");
+        assert_eq!(buffer.undo(), Some(3));
+        assert_eq!(buffer.content().to_string(), "one\n");
+        assert_eq!(buffer.undo(), None);
+        assert_eq!(buffer.redo(), Some(7));
+        assert_eq!(buffer.content().to_string(), "one two\n");
+        assert_eq!(buffer.line_len(0), 7);
+    }
+}
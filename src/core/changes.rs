@@ -0,0 +1,83 @@
+// Changes.
+// How the text of a buffer differs from the version of its file in git, as hunks of lines, and
+// who last changed each line. They are computed in the background from a copy of the text, so
+// they can be a little behind the buffer until they are computed again.
+
+use super::diff::Hunk;
+
+/// how a line differs from the version of the file in git.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineChange {
+    Added,
+    Modified,
+    /// lines were removed after the line, or before it if it is the first line.
+    Removed,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    /// the hunks in order, old lines are lines of the file in git and new lines are lines of
+    /// the buffer.
+    hunks: Vec<Hunk>,
+    /// a summary of the commit that last changed each line of the buffer.
+    blame: Vec<String>,
+}
+
+impl Changes {
+    pub fn new(hunks: Vec<Hunk>, blame: Vec<String>) -> Self {
+        Self { hunks, blame }
+    }
+
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// the change of a line, shown in the gutter.
+    pub fn change(&self, line: usize) -> Option<LineChange> {
+        let hunk = self.hunk_at(line)?;
+        Some(if hunk.new.is_empty() {
+            LineChange::Removed
+        } else if hunk.old.is_empty() {
+            LineChange::Added
+        } else {
+            LineChange::Modified
+        })
+    }
+
+    /// the hunk changing a line.
+    pub fn hunk_at(&self, line: usize) -> Option<&Hunk> {
+        self.hunks
+            .iter()
+            .find(|hunk| hunk.new.contains(&line) || marked_line(hunk) == line)
+    }
+
+    /// the line the first hunk after a line is marked on.
+    pub fn next(&self, line: usize) -> Option<usize> {
+        self.hunks
+            .iter()
+            .map(marked_line)
+            .find(|&marked| marked > line)
+    }
+
+    /// the line the last hunk before a line is marked on.
+    pub fn previous(&self, line: usize) -> Option<usize> {
+        self.hunks
+            .iter()
+            .rev()
+            .map(marked_line)
+            .find(|&marked| marked < line)
+    }
+
+    pub fn blame(&self, line: usize) -> Option<&str> {
+        self.blame.get(line).map(String::as_str)
+    }
+}
+
+/// the first line of a hunk in the buffer, removed lines are marked on the line before them.
+fn marked_line(hunk: &Hunk) -> usize {
+    if hunk.new.is_empty() {
+        hunk.new.start.saturating_sub(1)
+    } else {
+        hunk.new.start
+    }
+}
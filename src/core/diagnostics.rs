@@ -0,0 +1,163 @@
+// Diagnostics.
+// The errors and warnings of a buffer, reported by a language server or a linter. A diagnostic
+// covers a range of characters, the range moves with the edits made before it and grows or
+// shrinks with the edits inside it, so the marks stay on the text they were reported for until
+// the source reports again. Each source replaces only its own diagnostics.
+
+use std::ops::Range;
+
+use kea::lsp::Severity;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// the characters the diagnostic is about, an empty range is a position.
+    pub range: Range<usize>,
+    pub severity: Severity,
+    pub message: String,
+    /// the language server or linter that reported the diagnostic.
+    pub source: String,
+}
+
+/// the diagnostics of a buffer ordered by their start.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// replaces the diagnostics of a source.
+    pub fn set(&mut self, source: &str, diagnostics: Vec<Diagnostic>) {
+        self.items.retain(|diagnostic| diagnostic.source != source);
+        self.items.extend(diagnostics);
+        self.items
+            .sort_by_key(|diagnostic| (diagnostic.range.start, diagnostic.range.end));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// the diagnostics touching a range of characters, a diagnostic ending at the start of the
+    /// range doesn't touch it unless it is empty.
+    pub fn overlapping(&self, range: Range<usize>) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter().filter(move |diagnostic| {
+            diagnostic.range.start <= range.end
+                && (diagnostic.range.end > range.start
+                    || (diagnostic.range.start == range.start && diagnostic.range.is_empty()))
+        })
+    }
+
+    /// the first diagnostic starting after a character, the first one when none does.
+    pub fn next(&self, char_idx: usize) -> Option<&Diagnostic> {
+        self.items
+            .iter()
+            .find(|diagnostic| diagnostic.range.start > char_idx)
+            .or_else(|| self.items.first())
+    }
+
+    /// the last diagnostic starting before a character, the last one when none does.
+    pub fn previous(&self, char_idx: usize) -> Option<&Diagnostic> {
+        self.items
+            .iter()
+            .rev()
+            .find(|diagnostic| diagnostic.range.start < char_idx)
+            .or_else(|| self.items.last())
+    }
+
+    /// text of len characters was inserted at char_idx. Text inserted at the start of a
+    /// diagnostic moves it, text inserted at its end doesn't extend it.
+    pub fn inserted(&mut self, char_idx: usize, len: usize) {
+        for diagnostic in self.items.iter_mut() {
+            let range = &mut diagnostic.range;
+            if range.start >= char_idx {
+                range.start += len;
+                range.end += len;
+            } else if range.end > char_idx {
+                range.end += len;
+            }
+        }
+    }
+
+    /// a range of characters was removed. Diagnostics inside the range are removed with it.
+    pub fn removed(&mut self, removed: Range<usize>) {
+        let len = removed.end - removed.start;
+        let shift = |idx: usize| {
+            if idx >= removed.end {
+                idx - len
+            } else {
+                idx.min(removed.start)
+            }
+        };
+        self.items.retain(|diagnostic| {
+            let range = &diagnostic.range;
+            range.is_empty() || range.start < removed.start || range.end > removed.end
+        });
+        for diagnostic in self.items.iter_mut() {
+            diagnostic.range = shift(diagnostic.range.start)..shift(diagnostic.range.end);
+        }
+    }
+
+    /// the text was replaced, the diagnostics are kept inside the new text until they are
+    /// reported again.
+    pub fn clamp(&mut self, len: usize) {
+        for diagnostic in self.items.iter_mut() {
+            let range = &mut diagnostic.range;
+            *range = range.start.min(len)..range.end.min(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn diagnostic(range: Range<usize>, source: &str) -> Diagnostic {
+        Diagnostic {
+            range,
+            severity: Severity::Error,
+            message: String::new(),
+            source: source.to_string(),
+        }
+    }
+
+    fn ranges(diagnostics: &Diagnostics) -> Vec<Range<usize>> {
+        diagnostics.iter().map(|d| d.range.clone()).collect()
+    }
+
+    #[test]
+    fn ranges_follow_edits() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.set(
+            "lsp",
+            vec![diagnostic(10..15, "lsp"), diagnostic(2..4, "lsp")],
+        );
+        diagnostics.set("lint", vec![diagnostic(20..20, "lint")]);
+        assert_eq!(ranges(&diagnostics), vec![2..4, 10..15, 20..20]);
+
+        // before, inside, and at the end of a diagnostic.
+        diagnostics.inserted(0, 2);
+        diagnostics.inserted(14, 3);
+        diagnostics.inserted(20, 1);
+        assert_eq!(ranges(&diagnostics), vec![4..6, 12..20, 26..26]);
+
+        // the first diagnostic is removed with its text, the second one shrinks.
+        diagnostics.removed(3..14);
+        assert_eq!(ranges(&diagnostics), vec![3..9, 15..15]);
+        assert_eq!(diagnostics.next(3).unwrap().range, 15..15);
+        assert_eq!(diagnostics.next(15).unwrap().range, 3..9);
+        assert_eq!(diagnostics.previous(3).unwrap().range, 15..15);
+        assert_eq!(diagnostics.overlapping(9..14).count(), 0);
+        assert_eq!(diagnostics.overlapping(8..15).count(), 2);
+
+        diagnostics.set("lsp", Vec::new());
+        assert_eq!(ranges(&diagnostics), vec![15..15]);
+    }
+}
@@ -0,0 +1,360 @@
+// Diff.
+// Line based diffs using Myers' algorithm and a three-way merge built on top of them. The merge
+// is used when a file changes on disk while its buffer has unsaved edits: the text the buffer
+// was loaded from is the base, the changes of the buffer and of the file are applied to it.
+// Where both changed the same lines the buffer's lines are kept, the conflicts are left for the
+// user to resolve in a diff view of the buffer and the file.
+// Two texts shown side by side are lined up by padding the shorter side of each hunk with
+// filler rows, the changed characters of the lines paired up in a hunk are diffed again.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// A run of lines that differ, old lines are replaced by new lines. Either range can be empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// The result of a three-way merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge {
+    pub text: String,
+    /// the number of regions both sides changed, the text has our lines there.
+    pub conflicts: usize,
+}
+
+/// One of two texts shown side by side.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Side {
+    /// the changed lines with their changed columns. Lines without a line to pair with on the
+    /// other side have no changed columns.
+    pub changed: BTreeMap<usize, Vec<Range<usize>>>,
+    /// the number of filler rows shown above a line so the matching lines of both sides line
+    /// up. The rows above the line past the end are shown after the last line.
+    pub fillers: BTreeMap<usize, usize>,
+}
+
+impl Side {
+    /// the row a view starting at a line shows at the top, with some of the fillers above the
+    /// line hidden. Both sides have the same number of rows before matching lines.
+    pub fn top_row(&self, line: usize, hidden: usize) -> usize {
+        let above: usize = self.fillers.range(..line).map(|(_, count)| count).sum();
+        line + above + hidden
+    }
+
+    /// the line and the number of hidden fillers a view starts at to show a row at the top.
+    pub fn row_start(&self, row: usize) -> (usize, usize) {
+        let mut above = 0;
+        for (&line, &count) in self.fillers.iter() {
+            if row < line + above {
+                break;
+            }
+            if row <= line + above + count {
+                return (line, row - line - above);
+            }
+            above += count;
+        }
+        (row - above, 0)
+    }
+}
+
+/// lines up the lines of old and new by the hunks between them.
+pub fn sides<T: AsRef<str>>(old: &[T], new: &[T], hunks: &[Hunk]) -> (Side, Side) {
+    let (mut left, mut right) = (Side::default(), Side::default());
+    for hunk in hunks {
+        let (old_len, new_len) = (hunk.old.len(), hunk.new.len());
+        for idx in 0..old_len.max(new_len) {
+            let (x, y) = (hunk.old.start + idx, hunk.new.start + idx);
+            let (mut old_columns, mut new_columns) = (Vec::new(), Vec::new());
+            if idx < old_len && idx < new_len {
+                let a: Vec<char> = old[x].as_ref().chars().collect();
+                let b: Vec<char> = new[y].as_ref().chars().collect();
+                for change in diff(&a, &b) {
+                    old_columns.push(change.old);
+                    new_columns.push(change.new);
+                }
+                old_columns.retain(|columns: &Range<usize>| !columns.is_empty());
+                new_columns.retain(|columns: &Range<usize>| !columns.is_empty());
+            }
+            if idx < old_len {
+                left.changed.insert(x, old_columns);
+            }
+            if idx < new_len {
+                right.changed.insert(y, new_columns);
+            }
+        }
+        if old_len < new_len {
+            left.fillers.insert(hunk.old.end, new_len - old_len);
+        } else if new_len < old_len {
+            right.fillers.insert(hunk.new.end, old_len - new_len);
+        }
+    }
+    (left, right)
+}
+
+/// the changes that turn old into new, in order.
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut old_idx, mut new_idx) = (0, 0);
+    for (x, y) in matching_lines(old, new) {
+        if x > old_idx || y > new_idx {
+            hunks.push(Hunk {
+                old: old_idx..x,
+                new: new_idx..y,
+            });
+        }
+        old_idx = x + 1;
+        new_idx = y + 1;
+    }
+
+    if old_idx < old.len() || new_idx < new.len() {
+        hunks.push(Hunk {
+            old: old_idx..old.len(),
+            new: new_idx..new.len(),
+        });
+    }
+    hunks
+}
+
+/// the pairs of lines that are kept between old and new, a longest common subsequence.
+fn matching_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    // the common prefix and suffix are usually most of a file and don't need to be searched.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut matches: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    matches.extend(
+        myers(a, b)
+            .into_iter()
+            .map(|(x, y)| (x + prefix, y + prefix)),
+    );
+    matches.extend((0..suffix).map(|i| (old.len() - suffix + i, new.len() - suffix + i)));
+    matches
+}
+
+/// finds the shortest edit script between a and b and returns the matching lines along it.
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    if max == 0 {
+        return Vec::new();
+    }
+
+    // v[k] is the furthest x reached on diagonal k, offset so negative diagonals fit.
+    let offset = max as usize + 1;
+    let mut v = vec![0isize; 2 * offset + 1];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset as isize) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // walk back through the trace, collecting the diagonal moves.
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let idx = |k: isize| (k + offset as isize) as usize;
+        let prev_k = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { v[idx(prev_k)] };
+        let prev_y = if d == 0 { 0 } else { prev_x - prev_k };
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    matches.reverse();
+    matches
+}
+
+/// splits text into lines, each line keeps its line ending.
+fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// merges the changes from base to ours and from base to theirs. Changes to the same lines
+/// are a conflict unless both sides made the same change, our lines are kept.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Merge {
+    let base = lines(base);
+    let ours = lines(ours);
+    let theirs = lines(theirs);
+    let our_hunks = diff(&base, &ours);
+    let their_hunks = diff(&base, &theirs);
+
+    let mut text = String::new();
+    let mut conflicts = 0;
+    let mut base_idx = 0;
+    let (mut i, mut j) = (0, 0);
+
+    loop {
+        let start = match (our_hunks.get(i), their_hunks.get(j)) {
+            (Some(a), Some(b)) => a.old.start.min(b.old.start),
+            (Some(a), None) => a.old.start,
+            (None, Some(b)) => b.old.start,
+            (None, None) => break,
+        };
+        text.extend(base[base_idx..start].iter().copied());
+
+        // grow the region until no hunk of either side overlaps its end.
+        let (first_i, first_j) = (i, j);
+        let mut end = start;
+        loop {
+            if let Some(hunk) = our_hunks.get(i).filter(|h| h.old.start <= end) {
+                end = end.max(hunk.old.end);
+                i += 1;
+            } else if let Some(hunk) = their_hunks.get(j).filter(|h| h.old.start <= end) {
+                end = end.max(hunk.old.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        let side = |hunks: &[Hunk], lines: &[&str]| -> Option<String> {
+            let (first, last) = (hunks.first()?, hunks.last()?);
+            let from = start + first.new.start - first.old.start;
+            let to = end + last.new.end - last.old.end;
+            Some(lines[from..to].concat())
+        };
+        let our_text = side(&our_hunks[first_i..i], &ours);
+        let their_text = side(&their_hunks[first_j..j], &theirs);
+
+        match (our_text, their_text) {
+            (Some(text_ours), None) => text.push_str(&text_ours),
+            (None, Some(text_theirs)) => text.push_str(&text_theirs),
+            (Some(text_ours), Some(text_theirs)) if text_ours == text_theirs => {
+                text.push_str(&text_ours)
+            }
+            (Some(text_ours), Some(_)) => {
+                conflicts += 1;
+                text.push_str(&text_ours);
+            }
+            (None, None) => unreachable!(),
+        }
+        base_idx = end;
+    }
+
+    text.extend(base[base_idx..].iter().copied());
+    Merge { text, conflicts }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn line_diff() {
+        let old = ["a", "b", "c", "d", "e"];
+        let new = ["a", "c", "x", "d", "e", "f"];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Hunk {
+                    old: 1..2,
+                    new: 1..1
+                },
+                Hunk {
+                    old: 3..3,
+                    new: 2..3
+                },
+                Hunk {
+                    old: 5..5,
+                    new: 5..6
+                },
+            ]
+        );
+        assert_eq!(diff(&old, &old), vec![]);
+        assert_eq!(
+            diff(&[], &["a"]),
+            vec![Hunk {
+                old: 0..0,
+                new: 0..1
+            }]
+        );
+    }
+
+    #[test]
+    fn side_by_side() {
+        let old = ["a", "b c", "c", "d"];
+        let new = ["a", "B C", "x", "c"];
+        let (left, right) = sides(&old, &new, &diff(&old, &new));
+        assert_eq!(left.changed.get(&1), Some(&vec![0..1, 2..3]));
+        assert_eq!(left.changed.get(&3), Some(&vec![]));
+        assert_eq!(
+            right.changed.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(left.fillers.get(&2), Some(&1));
+        assert_eq!(right.fillers.get(&4), Some(&1));
+
+        // `c` is the fourth row of both sides.
+        assert_eq!(left.top_row(2, 1), 3);
+        assert_eq!(right.row_start(3), (3, 0));
+        assert_eq!(left.row_start(2), (2, 0));
+        assert_eq!(left.row_start(4), (3, 0));
+    }
+
+    #[test]
+    fn clean_merge() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let ours = "one\n2\nthree\nfour\n";
+        let theirs = "one\ntwo\nthree\nfour\nfive\n";
+        assert_eq!(
+            merge(base, ours, theirs),
+            Merge {
+                text: "one\n2\nthree\nfour\nfive\n".to_string(),
+                conflicts: 0,
+            }
+        );
+
+        // both sides made the same change.
+        assert_eq!(merge(base, ours, ours).text, ours);
+    }
+
+    #[test]
+    fn conflicting_merge() {
+        let base = "one\ntwo\nthree\nfour";
+        let ours = "one\n2\nthree\nfour";
+        let theirs = "one\nTWO\nthree\nFOUR";
+        let merged = merge(base, ours, theirs);
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(merged.text, "one\n2\nthree\nFOUR");
+    }
+}
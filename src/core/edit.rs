@@ -3,13 +3,16 @@ use crate::pathfinder_geometry::vector::{vec2f, Vector2F};
 
 use std::collections::BTreeMap;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use super::buffer::BufferResult;
+use super::swap::{SwapFile, SwapFiles};
+use super::watcher::{self, FileWatcher};
 use super::CoreError;
 use super::{Buffer, BufferId};
 use kea::{ptr, Ptr};
+use log::error;
 use std::borrow::Borrow;
 use std::cell::{Ref, RefMut};
 
@@ -34,9 +37,37 @@ impl Counter {
     }
 }
 
+/// How a file changed on disk affected its buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    /// the file has the contents the buffer was loaded or saved with, e.g. the buffer was
+    /// just saved.
+    Unchanged,
+    /// the buffer didn't have unsaved changes and was reloaded.
+    Reloaded,
+    /// the buffer has unsaved changes, the user decides what happens with the file.
+    Conflict,
+}
+
+/// What to do with a buffer whose file changed while it had unsaved changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// keep the buffer as it is.
+    Keep,
+    /// discard the changes of the buffer and load the file.
+    Reload,
+    /// merge the changes of the file that don't conflict into the buffer, the buffer is then
+    /// compared with the file side by side to resolve the rest.
+    Diff,
+}
+
 pub struct Core {
     id_counter: Counter,
     buffers: BTreeMap<BufferId, Ptr<Buffer>>,
+    /// reports changes to the files of buffers, files aren't watched without it.
+    watcher: Option<FileWatcher>,
+    /// keeps the changes of modified buffers on disk until they are saved.
+    swap_files: Option<SwapFiles>,
 }
 
 impl Core {
@@ -44,6 +75,74 @@ impl Core {
         Self {
             id_counter: Counter::new(),
             buffers: BTreeMap::new(),
+            watcher: None,
+            swap_files: None,
+        }
+    }
+
+    pub fn set_swap_files(&mut self, swap_files: SwapFiles) {
+        self.swap_files = Some(swap_files);
+    }
+
+    /// writes the swap files of buffers that changed since the last write. The files are
+    /// written in the background, `wait` blocks until they are written.
+    pub fn write_swap_files(&mut self, wait: bool) {
+        if let Some(swap_files) = self.swap_files.as_mut() {
+            for buffer in self.buffers.values() {
+                swap_files.update(&buffer.deref().borrow());
+            }
+            if wait {
+                swap_files.flush();
+            }
+        }
+    }
+
+    /// the swap files left by editors that crashed or exited with unsaved changes.
+    pub fn orphaned_swap_files(&self) -> Vec<SwapFile> {
+        self.swap_files
+            .as_ref()
+            .map_or_else(Vec::new, SwapFiles::orphans)
+    }
+
+    /// opens the file of a swap file with the changes from the swap file, a buffer that
+    /// already has the file open is used. The swap file is removed.
+    pub fn recover(&mut self, swap: &SwapFile) -> Result<BufferId, CoreError> {
+        let open = swap
+            .path
+            .as_ref()
+            .and_then(|path| self.buffers_with_path(path).first().copied());
+        let buffer_id = match (open, swap.path.as_ref()) {
+            (Some(buffer_id), _) => buffer_id,
+            (None, Some(path)) if !path.exists() => self.new_file(path.clone()),
+            (None, path) => self.open_file(BufferInfo {
+                path: path.cloned(),
+            })?,
+        };
+        if let Some(buffer) = self.buffers.get(&buffer_id) {
+            buffer.deref().borrow_mut().recover(&swap.text);
+        }
+        swap.remove()?;
+        Ok(buffer_id)
+    }
+
+    /// watches the files of all buffers with a watcher, including buffers opened later.
+    pub fn watch_files(&mut self, watcher: FileWatcher) {
+        self.watcher = Some(watcher);
+        let paths: Vec<PathBuf> = self
+            .buffers
+            .values()
+            .filter_map(|buffer| buffer.deref().borrow().path().map(Path::to_path_buf))
+            .collect();
+        for path in paths {
+            self.watch(&path);
+        }
+    }
+
+    fn watch(&mut self, path: &Path) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            if let Err(e) = watcher.watch(path) {
+                error!("{}", e);
+            }
         }
     }
 
@@ -52,12 +151,93 @@ impl Core {
     }
 
     fn insert_buffer(&mut self, buffer: Buffer) {
+        if let Some(path) = buffer.path().map(Path::to_path_buf) {
+            self.watch(&path);
+        }
         assert!(self.buffers.insert(buffer.id(), ptr(buffer)).is_none());
     }
 
+    /// removes a buffer, its file is no longer watched unless another buffer has it open.
+    pub fn remove_buffer(&mut self, id: &BufferId) -> bool {
+        let buffer = match self.buffers.remove(id) {
+            Some(buffer) => buffer,
+            None => return false,
+        };
+        let path = buffer.deref().borrow().path().map(Path::to_path_buf);
+        if let Some(path) = path {
+            if self.buffers_with_path(&path).is_empty() {
+                if let Some(watcher) = self.watcher.as_mut() {
+                    watcher.unwatch(&path);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn buffer_ids(&self) -> Vec<BufferId> {
+        self.buffers.keys().copied().collect()
+    }
+
+    /// the buffers of a file.
+    pub fn buffers_with_path(&self, path: &Path) -> Vec<BufferId> {
+        let path = watcher::absolute(path);
+        self.buffers
+            .iter()
+            .filter(|&(_, buffer)| {
+                let buffer = buffer.deref().borrow();
+                buffer
+                    .path()
+                    .map_or(false, |p| watcher::absolute(p) == path)
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// compares a buffer with its file after the file changed on disk. A buffer without unsaved
+    /// changes is reloaded.
+    pub fn check_file(&mut self, id: &BufferId) -> Result<FileChange, CoreError> {
+        let buffer = match self.buffers.get(id) {
+            Some(buffer) => buffer,
+            None => return Ok(FileChange::Unchanged),
+        };
+        let mut buffer = buffer.deref().borrow_mut();
+        let disk = buffer.read_disk()?;
+        if !buffer.changed_on_disk(&disk) {
+            Ok(FileChange::Unchanged)
+        } else if buffer.is_dirty() {
+            Ok(FileChange::Conflict)
+        } else {
+            buffer.reload(disk);
+            Ok(FileChange::Reloaded)
+        }
+    }
+
+    /// resolves a conflict between a buffer and its file. Returns the number of conflicts
+    /// left to resolve in a diff.
+    pub fn resolve_conflict(
+        &mut self,
+        id: &BufferId,
+        resolution: Resolution,
+    ) -> Result<usize, CoreError> {
+        let buffer = match self.buffers.get(id) {
+            Some(buffer) => buffer,
+            None => return Ok(0),
+        };
+        let mut buffer = buffer.deref().borrow_mut();
+        let disk = buffer.read_disk()?;
+        match resolution {
+            Resolution::Keep => buffer.keep(disk),
+            Resolution::Reload => buffer.reload(disk),
+            Resolution::Diff => return Ok(buffer.merge(disk)),
+        }
+        Ok(0)
+    }
+
+    /// opens a file in a new buffer, directories are opened as a listing of their entries.
     pub fn open_file(&mut self, buffer_info: BufferInfo) -> Result<BufferId, CoreError> {
         let buffer_id = self.next_id();
         let buffer = match buffer_info.path {
+            Some(path) if path.is_dir() => Buffer::from_directory(path, buffer_id),
             Some(path) => Buffer::from_path(path, buffer_id),
             None => Buffer::empty(buffer_id),
         }?;
@@ -68,6 +248,22 @@ impl Core {
         Ok(buffer_id)
     }
 
+    /// creates an empty buffer for a file that doesn't exist yet, it is written to path when saved.
+    pub fn new_file(&mut self, path: PathBuf) -> BufferId {
+        let buffer = Buffer::with_path(path, self.next_id());
+        let buffer_id = buffer.id();
+        self.insert_buffer(buffer);
+        buffer_id
+    }
+
+    /// creates a buffer without a file showing text.
+    pub fn scratch(&mut self, text: &str) -> BufferId {
+        let buffer = Buffer::scratch(text, self.next_id());
+        let buffer_id = buffer.id();
+        self.insert_buffer(buffer);
+        buffer_id
+    }
+
     pub fn request_lines(&self, buffer: BufferId, start: usize, end: usize) -> Vec<String> {
         if let Some(buffer) = self.buffers.get(&buffer) {
             buffer.deref().borrow().request_lines(start, end)
@@ -124,3 +320,45 @@ pub enum Edit {}
 
 #[derive(Debug, Clone)]
 pub enum Update {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn file_changes() {
+        let path = std::env::temp_dir().join(format!("kea-changes-{}.txt", std::process::id()));
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+
+        let mut core = Core::new();
+        let id = core
+            .open_file(BufferInfo {
+                path: Some(path.clone()),
+            })
+            .unwrap();
+        assert_eq!(core.buffers_with_path(&path), vec![id]);
+        assert_eq!(core.check_file(&id).unwrap(), FileChange::Unchanged);
+
+        fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+        assert_eq!(core.check_file(&id).unwrap(), FileChange::Reloaded);
+        assert_eq!(core.get_buffer(&id).unwrap().num_lines(), 4);
+
+        core.get_buffer_mut(&id).unwrap().insert(0, "zero\n");
+        fs::write(&path, "one\n2\nthree\nfour\n").unwrap();
+        assert_eq!(core.check_file(&id).unwrap(), FileChange::Conflict);
+        assert_eq!(core.resolve_conflict(&id, Resolution::Diff).unwrap(), 0);
+        assert_eq!(
+            core.get_buffer(&id).unwrap().content().to_string(),
+            "zero\none\n2\nthree\nfour\n"
+        );
+
+        // the merged buffer is saved, the save isn't a change by another program.
+        core.get_buffer_mut(&id).unwrap().save().unwrap();
+        assert_eq!(core.check_file(&id).unwrap(), FileChange::Unchanged);
+
+        assert!(core.remove_buffer(&id));
+        assert!(core.buffers_with_path(&path).is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+// History.
+// The edits of a buffer, for undo and redo. A step of the history is undone at once: the edits
+// made between `begin` and `end` are one step, other edits are a step each except that typing
+// continues the step of the text typed right before it. Nested groups are part of the outer one.
+// A new edit clears the steps that were undone.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Insert { char_idx: usize, text: String },
+    Remove { char_idx: usize, text: String },
+}
+
+impl Change {
+    /// the change that reverts this one.
+    fn inverse(&self) -> Self {
+        match self {
+            Change::Insert { char_idx, text } => Change::Remove {
+                char_idx: *char_idx,
+                text: text.clone(),
+            },
+            Change::Remove { char_idx, text } => Change::Insert {
+                char_idx: *char_idx,
+                text: text.clone(),
+            },
+        }
+    }
+
+    /// the characters the change inserted or removed.
+    pub fn range(&self) -> Range<usize> {
+        match self {
+            Change::Insert { char_idx, text } | Change::Remove { char_idx, text } => {
+                *char_idx..*char_idx + text.chars().count()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    /// the changes of the open group.
+    group: Vec<Change>,
+    depth: usize,
+    /// can typing continue the last step.
+    open: bool,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, change: Change) {
+        self.redo.clear();
+        if self.depth > 0 {
+            self.group.push(change);
+            return;
+        }
+        self.push(vec![change]);
+    }
+
+    /// adds a step, typed text joins the step of the text typed before it.
+    fn push(&mut self, step: Vec<Change>) {
+        let typed = match step.as_slice() {
+            [Change::Insert { char_idx, text }] if !text.contains('\n') => Some(*char_idx),
+            _ => None,
+        };
+        let last = self
+            .undo
+            .last_mut()
+            .and_then(|step| match step.as_mut_slice() {
+                [Change::Insert { char_idx, text }] => Some((*char_idx, text)),
+                _ => None,
+            });
+        if let (true, Some(at), Some((start, text))) = (self.open, typed, last) {
+            if start + text.chars().count() == at {
+                if let Some(Change::Insert { text: typed, .. }) = step.first() {
+                    text.push_str(typed);
+                }
+                return;
+            }
+        }
+        self.open = typed.is_some();
+        self.undo.push(step);
+    }
+
+    /// starts a group, the changes until the matching `end` are one step.
+    pub fn begin(&mut self) {
+        self.depth += 1;
+    }
+
+    pub fn end(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 && !self.group.is_empty() {
+            let step = std::mem::take(&mut self.group);
+            self.push(step);
+        }
+    }
+
+    /// the next step is a new one even if it is typed text.
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// the changes that revert the last step in the order they are applied, the step can be
+    /// redone.
+    pub fn undo(&mut self) -> Option<Vec<Change>> {
+        let step = self.undo.pop()?;
+        let changes = step.iter().rev().map(Change::inverse).collect();
+        self.redo.push(step);
+        self.open = false;
+        Some(changes)
+    }
+
+    /// the changes of the last undone step.
+    pub fn redo(&mut self) -> Option<Vec<Change>> {
+        let step = self.redo.pop()?;
+        let changes = step.clone();
+        self.undo.push(step);
+        self.open = false;
+        Some(changes)
+    }
+
+    /// forgets every step, e.g. when the text is replaced.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.group.clear();
+        self.open = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn insert(char_idx: usize, text: &str) -> Change {
+        Change::Insert {
+            char_idx,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn steps() {
+        let mut history = History::new();
+        history.record(insert(0, "a"));
+        history.record(insert(1, "b"));
+        history.record(insert(0, "c"));
+        history.begin();
+        history.record(Change::Remove {
+            char_idx: 0,
+            text: "cab".to_string(),
+        });
+        history.begin();
+        history.record(insert(0, "x"));
+        history.end();
+        history.end();
+
+        // the group is undone at once, removing and inserting in reverse.
+        assert_eq!(
+            history.undo(),
+            Some(vec![
+                Change::Remove {
+                    char_idx: 0,
+                    text: "x".to_string()
+                },
+                insert(0, "cab"),
+            ])
+        );
+        assert_eq!(history.redo().unwrap().len(), 2);
+        history.undo();
+        history.undo();
+        // the typed text is one step.
+        assert_eq!(
+            history.undo(),
+            Some(vec![Change::Remove {
+                char_idx: 0,
+                text: "ab".to_string()
+            }])
+        );
+        assert_eq!(history.undo(), None);
+    }
+}
@@ -11,14 +11,27 @@ use crate::app::Config;
 use log::error;
 
 mod buffer;
+mod changes;
+mod diagnostics;
+pub mod diff;
 mod edit;
+mod history;
+mod regions;
+mod swap;
+mod syntax;
 mod view;
+mod watcher;
 
-pub use edit::{BufferInfo, Core, Edit, Update};
+pub use edit::{BufferInfo, Core, Edit, FileChange, Resolution, Update};
 
 pub use buffer::{Buffer, BufferId};
+pub use changes::{Changes, LineChange};
+pub use diagnostics::{Diagnostic, Diagnostics};
+pub use swap::{SwapFile, SwapFiles};
+pub use syntax::{Highlight, Syntax};
 use view::View;
 pub use view::{ViewId, ViewInfo};
+pub use watcher::{absolute, FileWatcher};
 
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum CoreError {
@@ -26,6 +39,14 @@ pub enum CoreError {
     FileNotFound(PathBuf),
     #[error("do not have permission to open: '{0}'")]
     FilePermissions(PathBuf),
+    #[error("failed to read '{0}': {1}")]
+    ReadFailed(PathBuf, String),
+    #[error("failed to write '{0}': {1}")]
+    WriteFailed(PathBuf, String),
+    #[error("buffer does not have a path")]
+    NoPath,
+    #[error("failed to watch '{0}': {1}")]
+    WatchFailed(PathBuf, String),
 }
 
 // pub enum KeaCore {
@@ -0,0 +1,71 @@
+// Regions.
+// Ranges of a buffer that follow its edits and grow with the text typed at their ends, e.g. the
+// tab stops of an expanded snippet or the folds of a frame. Unlike diagnostics a region isn't removed with its text, it
+// becomes empty so text typed there is still part of it.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Default)]
+pub struct Regions {
+    ranges: Vec<Range<usize>>,
+}
+
+impl Regions {
+    pub fn new(ranges: Vec<Range<usize>>) -> Self {
+        Self { ranges }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<Range<usize>> {
+        self.ranges.get(idx).cloned()
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// text inserted at the start or the end of a region is part of it.
+    pub fn inserted(&mut self, char_idx: usize, len: usize) {
+        for range in self.ranges.iter_mut() {
+            if range.start > char_idx {
+                range.start += len;
+                range.end += len;
+            } else if range.end >= char_idx {
+                range.end += len;
+            }
+        }
+    }
+
+    pub fn removed(&mut self, removed: Range<usize>) {
+        let len = removed.end - removed.start;
+        let shift = |idx: usize| {
+            if idx >= removed.end {
+                idx - len
+            } else {
+                idx.min(removed.start)
+            }
+        };
+        for range in self.ranges.iter_mut() {
+            *range = shift(range.start)..shift(range.end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn regions_grow() {
+        let mut regions = Regions::new(vec![2..5, 8..8]);
+        regions.inserted(5, 2);
+        regions.inserted(10, 1);
+        regions.inserted(0, 1);
+        assert_eq!(regions.ranges, vec![3..8, 11..12]);
+        regions.removed(2..12);
+        assert_eq!(regions.ranges, vec![2..2, 2..2]);
+    }
+}
@@ -0,0 +1,248 @@
+// Swap Files.
+// The contents of modified buffers are written to swap files every few seconds so the changes
+// can be recovered if the editor crashes. The files are written by a separate thread, the
+// contents of a buffer are cheap to clone because ropes share their nodes.
+//
+// A swap file starts with a header of three lines: a marker, the id of the process that wrote it
+// and the path of the file, which is empty for unnamed buffers. The contents of the buffer follow.
+// A swap file whose process is no longer running was left by an editor that crashed or was closed
+// with unsaved changes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+
+use log::error;
+use ropey::Rope;
+
+use super::watcher;
+use super::{Buffer, BufferId, CoreError};
+
+const SWAP_MARKER: &str = "kea swap";
+const SWAP_EXTENSION: &str = "swp";
+
+enum SwapJob {
+    Write {
+        swap: PathBuf,
+        header: String,
+        text: Rope,
+    },
+    Remove(PathBuf),
+    /// replies once the previous jobs are finished.
+    Flush(Sender<()>),
+}
+
+/// A swap file left by an editor that is no longer running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapFile {
+    /// the location of the swap file.
+    pub swap: PathBuf,
+    /// the file the changes belong to, None for unnamed buffers.
+    pub path: Option<PathBuf>,
+    pub text: String,
+}
+
+impl SwapFile {
+    fn parse(swap: PathBuf, content: &str) -> Option<(u32, Self)> {
+        let mut parts = content.splitn(4, '\n');
+        if parts.next()? != SWAP_MARKER {
+            return None;
+        }
+        let pid = parts.next()?.parse().ok()?;
+        let path = match parts.next()? {
+            "" => None,
+            path => Some(PathBuf::from(path)),
+        };
+        let text = parts.next().unwrap_or("").to_string();
+        Some((pid, Self { swap, path, text }))
+    }
+
+    /// deletes the swap file, after it was recovered or the changes are discarded.
+    pub fn remove(&self) -> Result<(), CoreError> {
+        fs::remove_file(&self.swap)
+            .map_err(|e| CoreError::WriteFailed(self.swap.clone(), e.to_string()))
+    }
+}
+
+pub struct SwapFiles {
+    directory: PathBuf,
+    pid: u32,
+    sender: Sender<SwapJob>,
+    /// the swap file of each modified buffer and the revision written to it.
+    swapped: HashMap<BufferId, (PathBuf, usize)>,
+}
+
+impl SwapFiles {
+    pub fn new<P: AsRef<Path>>(directory: P) -> Result<Self, CoreError> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)
+            .map_err(|e| CoreError::WriteFailed(directory.clone(), e.to_string()))?;
+
+        let (sender, receiver) = mpsc::channel();
+        // the thread ends when the swap files are dropped and the channel disconnects.
+        kea::utils::spawn_thread("swap files", move || {
+            for job in receiver.iter() {
+                match job {
+                    SwapJob::Write { swap, header, text } => {
+                        if let Err(e) = write_swap(&swap, &header, &text) {
+                            error!("Failed to write swap file '{}': {}", swap.display(), e);
+                        }
+                    }
+                    SwapJob::Remove(swap) => {
+                        fs::remove_file(&swap).ok();
+                    }
+                    SwapJob::Flush(reply) => {
+                        reply.send(()).ok();
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            directory,
+            pid: std::process::id(),
+            sender,
+            swapped: HashMap::new(),
+        })
+    }
+
+    /// writes the swap file of a modified buffer if it changed since the last write, the swap
+    /// file of a saved buffer is removed.
+    pub fn update(&mut self, buffer: &Buffer) {
+        if !buffer.is_dirty() {
+            if let Some((swap, _)) = self.swapped.remove(&buffer.id()) {
+                self.sender.send(SwapJob::Remove(swap)).ok();
+            }
+            return;
+        }
+
+        let swap = self.swap_path(buffer);
+        match self.swapped.get(&buffer.id()) {
+            Some((old, revision)) if *old == swap && *revision == buffer.revision() => return,
+            // the buffer was saved to a different path.
+            Some((old, _)) if *old != swap => {
+                self.sender.send(SwapJob::Remove(old.clone())).ok();
+            }
+            _ => {}
+        }
+
+        let path = buffer.path().map(watcher::absolute).unwrap_or_default();
+        let header = format!("{}\n{}\n{}\n", SWAP_MARKER, self.pid, path.display());
+        let text = buffer.content().clone();
+        self.sender
+            .send(SwapJob::Write {
+                swap: swap.clone(),
+                header,
+                text,
+            })
+            .ok();
+        self.swapped.insert(buffer.id(), (swap, buffer.revision()));
+    }
+
+    /// waits until the swap files that were updated are written.
+    pub fn flush(&self) {
+        let (reply, done) = mpsc::channel();
+        if self.sender.send(SwapJob::Flush(reply)).is_ok() {
+            done.recv().ok();
+        }
+    }
+
+    /// the swap files of editors that are no longer running.
+    pub fn orphans(&self) -> Vec<SwapFile> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut orphans: Vec<SwapFile> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == SWAP_EXTENSION))
+            .filter_map(|path| {
+                let content = fs::read_to_string(&path).ok()?;
+                SwapFile::parse(path, &content)
+            })
+            .filter(|(pid, _)| *pid != self.pid && !process_running(*pid))
+            .map(|(_, swap)| swap)
+            .collect();
+        orphans.sort_by(|a, b| a.swap.cmp(&b.swap));
+        orphans
+    }
+
+    /// the name of the swap file is the path of the file with the separators replaced, so
+    /// files with the same name in different directories don't share a swap file.
+    fn swap_path(&self, buffer: &Buffer) -> PathBuf {
+        let name = match buffer.path() {
+            Some(path) => watcher::absolute(path)
+                .to_string_lossy()
+                .replace(|ch| ch == '/' || ch == '\\' || ch == ':', "%"),
+            None => format!("unnamed-{}", buffer.id().0),
+        };
+        self.directory
+            .join(format!("{}.{}.{}", name, self.pid, SWAP_EXTENSION))
+    }
+}
+
+/// writes to a temporary file first so a crash while writing doesn't leave half a swap file.
+fn write_swap(swap: &Path, header: &str, text: &Rope) -> std::io::Result<()> {
+    let temp = swap.with_extension("tmp");
+    let mut writer = BufWriter::new(fs::File::create(&temp)?);
+    writer.write_all(header.as_bytes())?;
+    text.write_to(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&temp, swap)
+}
+
+#[cfg(target_os = "linux")]
+fn process_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+// without a way to tell, swap files of other editors are treated as left behind.
+#[cfg(not(target_os = "linux"))]
+fn process_running(pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn swap_and_recover() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("swap");
+        let mut swap_files = SwapFiles::new(&dir).unwrap();
+
+        let mut buffer = Buffer::with_path(dir.join("file.txt"), 1);
+        buffer.insert(0, "unsaved\nchanges\n");
+        swap_files.update(&buffer);
+        swap_files.flush();
+        let swap = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        assert!(fs::read_to_string(&swap)
+            .unwrap()
+            .ends_with("\nunsaved\nchanges\n"));
+
+        // the swap file of this editor isn't left behind, one from a process that doesn't
+        // exist is.
+        assert!(swap_files.orphans().is_empty());
+        let content = fs::read_to_string(&swap).unwrap().replacen(
+            &format!("\n{}\n", std::process::id()),
+            &format!("\n{}\n", u32::MAX),
+            1,
+        );
+        fs::write(dir.join("orphan.swp"), content).unwrap();
+        let orphans = swap_files.orphans();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].path, Some(dir.join("file.txt")));
+        assert_eq!(orphans[0].text, "unsaved\nchanges\n");
+
+        buffer.save().unwrap();
+        swap_files.update(&buffer);
+        swap_files.flush();
+        assert!(!swap.exists());
+    }
+}
@@ -0,0 +1,185 @@
+// Grammars.
+// The grammars bundled with the editor. A grammar describes the tokens of a language: its
+// comments, strings, keywords and types, and how its blocks are written, with brackets or by
+// indentation. That is enough to find the structure of the code without a full parser.
+
+/// the bracket pairs of every grammar.
+pub const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+#[derive(Debug)]
+pub struct Grammar {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// can block comments contain block comments.
+    pub nested_comments: bool,
+    pub quotes: &'static [char],
+    /// are strings of three quotes, e.g. `"""`, strings that can span lines.
+    pub triple_quotes: bool,
+    /// can any string span lines.
+    pub multiline_strings: bool,
+    pub keywords: &'static [&'static str],
+    /// the keywords that start a function.
+    pub functions: &'static [&'static str],
+    pub types: &'static [&'static str],
+    /// are capitalized identifiers types.
+    pub capitalized_types: bool,
+    /// are blocks the lines indented deeper than the line before them.
+    pub indented_blocks: bool,
+}
+
+#[rustfmt::skip]
+pub static GRAMMARS: [Grammar; 7] = [
+    Grammar {
+        name: "rust",
+        extensions: &["rs"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        nested_comments: true,
+        quotes: &['"'],
+        triple_quotes: false,
+        multiline_strings: true,
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        functions: &["fn"],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+            "i64", "i128", "isize", "f32", "f64",
+        ],
+        capitalized_types: true,
+        indented_blocks: false,
+    },
+    Grammar {
+        name: "c",
+        extensions: &["c", "h", "cc", "cpp", "hpp"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: &['"', '\''],
+        triple_quotes: false,
+        multiline_strings: false,
+        keywords: &[
+            "break", "case", "class", "const", "continue", "default", "delete", "do", "else",
+            "enum", "extern", "for", "goto", "if", "namespace", "new", "private", "protected",
+            "public", "return", "sizeof", "static", "struct", "switch", "template", "typedef",
+            "union", "using", "virtual", "volatile", "while",
+        ],
+        functions: &[],
+        types: &[
+            "auto", "bool", "char", "double", "float", "int", "long", "short", "signed",
+            "unsigned", "void", "size_t",
+        ],
+        capitalized_types: false,
+        indented_blocks: false,
+    },
+    Grammar {
+        name: "go",
+        extensions: &["go"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: &['"', '\'', '`'],
+        triple_quotes: false,
+        multiline_strings: false,
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "false",
+            "for", "func", "go", "goto", "if", "import", "interface", "map", "nil", "package",
+            "range", "return", "select", "struct", "switch", "true", "type", "var",
+        ],
+        functions: &["func"],
+        types: &[
+            "bool", "byte", "error", "float32", "float64", "int", "int8", "int16", "int32",
+            "int64", "rune", "string", "uint", "uint8", "uint16", "uint32", "uint64",
+        ],
+        capitalized_types: false,
+        indented_blocks: false,
+    },
+    Grammar {
+        name: "javascript",
+        extensions: &["js", "ts"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        nested_comments: false,
+        quotes: &['"', '\'', '`'],
+        triple_quotes: false,
+        multiline_strings: false,
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+            "delete", "do", "else", "export", "extends", "false", "finally", "for", "from",
+            "function", "if", "import", "in", "instanceof", "interface", "let", "new", "null", "of",
+            "return", "switch", "this", "throw", "true", "try", "type", "typeof", "undefined",
+            "var", "while", "yield",
+        ],
+        functions: &["function"],
+        types: &["any", "boolean", "number", "string", "void"],
+        capitalized_types: true,
+        indented_blocks: false,
+    },
+    Grammar {
+        name: "python",
+        extensions: &["py"],
+        line_comment: Some("#"),
+        block_comment: None,
+        nested_comments: false,
+        quotes: &['"', '\''],
+        triple_quotes: true,
+        multiline_strings: false,
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "self", "True", "try", "while", "with", "yield",
+        ],
+        functions: &["def"],
+        types: &[
+            "bool", "bytes", "dict", "float", "int", "list", "set", "str", "tuple",
+        ],
+        capitalized_types: true,
+        indented_blocks: true,
+    },
+    Grammar {
+        name: "shell",
+        extensions: &["sh", "bash"],
+        line_comment: Some("#"),
+        block_comment: None,
+        nested_comments: false,
+        quotes: &['"', '\''],
+        triple_quotes: false,
+        multiline_strings: true,
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "then", "while",
+        ],
+        functions: &["function"],
+        types: &[],
+        capitalized_types: false,
+        indented_blocks: false,
+    },
+    Grammar {
+        name: "json",
+        extensions: &["json"],
+        line_comment: None,
+        block_comment: None,
+        nested_comments: false,
+        quotes: &['"'],
+        triple_quotes: false,
+        multiline_strings: false,
+        keywords: &["false", "null", "true"],
+        functions: &[],
+        types: &[],
+        capitalized_types: false,
+        indented_blocks: false,
+    },
+];
+
+/// the grammar of a file by its extension.
+pub fn for_extension(extension: &str) -> Option<&'static Grammar> {
+    GRAMMARS
+        .iter()
+        .find(|grammar| grammar.extensions.contains(&extension))
+}
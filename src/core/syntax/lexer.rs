@@ -0,0 +1,253 @@
+// Lexer.
+// Splits a line into tokens. Lines are lexed one at a time starting in the state the line before
+// ended in, e.g. inside a block comment, so an edit only relexes lines until a line ends in the
+// same state it did before.
+
+use std::ops::Range;
+
+use super::grammar::{Grammar, BRACKETS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Identifier,
+    Keyword,
+    /// a keyword that starts a function, e.g. `fn`.
+    FunctionKeyword,
+    Type,
+    Function,
+    Number,
+    String,
+    Comment,
+    Open(char),
+    Close(char),
+    /// ends a statement, e.g. `;`.
+    Separator,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// the columns of the token in its line.
+    pub range: Range<usize>,
+    /// does the token continue a string or comment of the line before.
+    pub continued: bool,
+    /// does the string or comment go on in the next line.
+    pub open: bool,
+}
+
+/// where a line starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Code,
+    /// inside block comments nested this deep.
+    Comment(usize),
+    String {
+        quote: char,
+        triple: bool,
+    },
+}
+
+/// the tokens of a line and the state the next line starts in.
+pub fn lex_line(grammar: &Grammar, line: &[char], state: State) -> (Vec<Token>, State) {
+    let mut lexer = Lexer {
+        grammar,
+        line,
+        idx: 0,
+        tokens: Vec::new(),
+    };
+    let mut state = state;
+    if state != State::Code {
+        state = lexer.continue_state(state);
+    }
+    while state == State::Code && lexer.idx < line.len() {
+        state = lexer.next();
+    }
+    (lexer.tokens, state)
+}
+
+struct Lexer<'a> {
+    grammar: &'a Grammar,
+    line: &'a [char],
+    idx: usize,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(offset, ch)| self.line.get(self.idx + offset) == Some(&ch))
+    }
+
+    fn push(&mut self, kind: TokenKind, start: usize) {
+        self.tokens.push(Token {
+            kind,
+            range: start..self.idx,
+            continued: false,
+            open: false,
+        });
+    }
+
+    /// lexes the rest of a comment or string the line before ended in.
+    fn continue_state(&mut self, state: State) -> State {
+        let (kind, state) = match state {
+            State::Comment(depth) => (TokenKind::Comment, self.block_comment(depth)),
+            State::String { quote, triple } => (TokenKind::String, self.string(quote, triple)),
+            State::Code => return state,
+        };
+        if self.idx > 0 || state != State::Code {
+            self.push(kind, 0);
+            let token = self.tokens.last_mut().unwrap();
+            token.continued = true;
+            token.open = state != State::Code;
+        }
+        state
+    }
+
+    /// lexes the token at idx and returns the state after it.
+    fn next(&mut self) -> State {
+        let start = self.idx;
+        let ch = self.line[start];
+        let grammar = self.grammar;
+
+        if grammar.line_comment.map_or(false, |c| self.starts_with(c)) {
+            self.idx = self.line.len();
+            self.push(TokenKind::Comment, start);
+            return State::Code;
+        }
+        if let Some((open, _)) = grammar.block_comment.filter(|(o, _)| self.starts_with(o)) {
+            self.idx += open.chars().count();
+            let state = self.block_comment(1);
+            self.push(TokenKind::Comment, start);
+            self.tokens.last_mut().unwrap().open = state != State::Code;
+            return state;
+        }
+        if grammar.quotes.contains(&ch) {
+            let triple = grammar.triple_quotes
+                && self.line.get(start + 1) == Some(&ch)
+                && self.line.get(start + 2) == Some(&ch);
+            self.idx += if triple { 3 } else { 1 };
+            let state = self.string(ch, triple);
+            self.push(TokenKind::String, start);
+            self.tokens.last_mut().unwrap().open = state != State::Code;
+            return state;
+        }
+
+        if ch.is_alphabetic() || ch == '_' {
+            while self.idx < self.line.len()
+                && (self.line[self.idx].is_alphanumeric() || self.line[self.idx] == '_')
+            {
+                self.idx += 1;
+            }
+            let word: String = self.line[start..self.idx].iter().collect();
+            let kind = self.word_kind(&word);
+            self.push(kind, start);
+        } else if ch.is_ascii_digit() {
+            while self.idx < self.line.len() {
+                let ch = self.line[self.idx];
+                // `0..10` is a range, not a number with a fraction.
+                let fraction = ch == '.' && self.line.get(self.idx + 1) != Some(&'.');
+                if !(ch.is_alphanumeric() || ch == '_' || fraction) {
+                    break;
+                }
+                self.idx += 1;
+            }
+            self.push(TokenKind::Number, start);
+        } else {
+            self.idx += 1;
+            if let Some((open, _)) = BRACKETS.iter().find(|(open, _)| *open == ch) {
+                self.push(TokenKind::Open(*open), start);
+            } else if let Some((open, _)) = BRACKETS.iter().find(|(_, close)| *close == ch) {
+                self.push(TokenKind::Close(*open), start);
+            } else if ch == ';' {
+                self.push(TokenKind::Separator, start);
+            }
+        }
+        State::Code
+    }
+
+    /// a keyword, a type, the name of a function or another identifier.
+    fn word_kind(&self, word: &str) -> TokenKind {
+        let grammar = self.grammar;
+        if grammar.functions.contains(&word) {
+            return TokenKind::FunctionKeyword;
+        }
+        if grammar.keywords.contains(&word) {
+            return TokenKind::Keyword;
+        }
+        let after_function_keyword = self
+            .tokens
+            .last()
+            .map_or(false, |token| token.kind == TokenKind::FunctionKeyword);
+        // a call or a macro, e.g. `print(` or `println!(`.
+        let called = self.line[self.idx..]
+            .iter()
+            .find(|c| !c.is_whitespace())
+            .map_or(false, |c| {
+                *c == '(' || (*c == '!' && grammar.name == "rust")
+            });
+        if after_function_keyword || called {
+            return TokenKind::Function;
+        }
+
+        let mut chars = word.chars();
+        let capitalized =
+            chars.next().map_or(false, char::is_uppercase) && chars.any(char::is_lowercase);
+        if grammar.types.contains(&word) || (grammar.capitalized_types && capitalized) {
+            return TokenKind::Type;
+        }
+        TokenKind::Identifier
+    }
+
+    /// moves past the end of block comments nested depth deep.
+    fn block_comment(&mut self, depth: usize) -> State {
+        let (open, close) = match self.grammar.block_comment {
+            Some(delimiters) => delimiters,
+            None => return State::Code,
+        };
+        let mut depth = depth;
+        while self.idx < self.line.len() {
+            if self.starts_with(close) {
+                self.idx += close.chars().count();
+                depth -= 1;
+                if depth == 0 {
+                    return State::Code;
+                }
+            } else if self.grammar.nested_comments && self.starts_with(open) {
+                self.idx += open.chars().count();
+                depth += 1;
+            } else {
+                self.idx += 1;
+            }
+        }
+        State::Comment(depth)
+    }
+
+    /// moves past the closing quote of a string, a backslash escapes the next character.
+    fn string(&mut self, quote: char, triple: bool) -> State {
+        while self.idx < self.line.len() {
+            let ch = self.line[self.idx];
+            self.idx += 1;
+            if ch == '\\' {
+                self.idx = (self.idx + 1).min(self.line.len());
+            } else if ch == quote {
+                let closed = !triple
+                    || (self.line.get(self.idx) == Some(&quote)
+                        && self.line.get(self.idx + 1) == Some(&quote));
+                if closed {
+                    if triple {
+                        self.idx += 2;
+                    }
+                    return State::Code;
+                }
+            }
+        }
+        // a backslash at the end of the line continues the string.
+        let escaped = self.line.last() == Some(&'\\');
+        if triple || self.grammar.multiline_strings || escaped {
+            State::String { quote, triple }
+        } else {
+            State::Code
+        }
+    }
+}
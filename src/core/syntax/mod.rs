@@ -0,0 +1,636 @@
+// Syntax.
+// The syntax tree of a buffer, kept up to date as the buffer is edited. The lines are lexed with
+// the grammar of the language of the buffer, an edit relexes the edited lines and the lines after
+// them until one starts in the state it did before, e.g. until the end of a comment that was
+// opened. The tree is built from the tokens: bracketed blocks, or indented blocks in languages
+// like python, with the functions, strings and comments inside them. An edit inside a block only
+// rebuilds the nodes in the block, unless its brackets, strings or comments no longer balance,
+// then the whole tree is rebuilt, as it is for indented blocks. It drives highlighting,
+// structural selection, bracket matching and folding.
+//
+// This is not a parser of the grammar of a language, such as a tree-sitter parser. The grammars
+// only describe tokens and blocks, so the tree has no statements, expressions or declarations:
+//
+//     supported    brackets and indented blocks, strings and comments spanning lines, keywords,
+//                  types, numbers, and functions found by the keyword that starts them
+//     unsupported  nodes for the rules of a language, errors for invalid code, highlighting by
+//                  the role of a name, e.g. a field or a parameter
+//
+// Grammars are bundled for rust, c, go, javascript, python, shell and json, see grammar.rs.
+
+mod grammar;
+mod lexer;
+
+use std::ops::Range;
+use std::path::Path;
+
+use ropey::Rope;
+
+pub use grammar::Grammar;
+use lexer::{lex_line, State, Token, TokenKind};
+
+/// how a token is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Highlight {
+    Keyword,
+    Type,
+    Function,
+    Number,
+    String,
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Root,
+    /// text between brackets, with the brackets, by the opening bracket.
+    Block(char),
+    /// the lines indented below a line, from the end of that line.
+    Indented,
+    Function,
+    String,
+    Comment,
+    /// a word or a number.
+    Token,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub kind: NodeKind,
+    /// the characters of the node.
+    pub range: Range<usize>,
+    /// ordered by their start, they don't overlap.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(kind: NodeKind, range: Range<usize>) -> Self {
+        Self {
+            kind,
+            range,
+            children: Vec::new(),
+        }
+    }
+
+    /// nests nodes sorted by their start below a node, nodes that cross are left out.
+    fn nest(self, nodes: Vec<Node>) -> Node {
+        let mut stack = vec![self];
+        for node in nodes {
+            while stack.len() > 1 && stack.last().unwrap().range.end <= node.range.start {
+                let done = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(done);
+            }
+            if node.range.end <= stack.last().unwrap().range.end {
+                stack.push(node);
+            }
+        }
+        while stack.len() > 1 {
+            let done = stack.pop().unwrap();
+            stack.last_mut().unwrap().children.push(done);
+        }
+        stack.pop().unwrap()
+    }
+
+    /// moves the nodes from a character on by delta characters, nodes around it grow or
+    /// shrink.
+    fn shift(&mut self, from: usize, delta: isize) {
+        let moved = |idx: usize| (idx as isize + delta) as usize;
+        if self.range.start >= from {
+            self.range.start = moved(self.range.start);
+        }
+        self.range.end = moved(self.range.end);
+        for child in self.children.iter_mut() {
+            if child.range.end >= from {
+                child.shift(from, delta);
+            }
+        }
+    }
+
+    /// the nodes containing a range from this one down, the innermost last.
+    fn path(&self, range: &Range<usize>) -> Vec<&Node> {
+        let mut path = vec![self];
+        let mut node = self;
+        while let Some(child) = node
+            .children
+            .iter()
+            .find(|child| child.range.start <= range.start && range.end <= child.range.end)
+        {
+            path.push(child);
+            node = child;
+        }
+        path
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    /// the state the line starts in.
+    state: State,
+    /// the state the next line starts in.
+    end: State,
+    tokens: Vec<Token>,
+    /// the characters of the line with its line ending.
+    len: usize,
+    /// the characters of the line without its line ending.
+    width: usize,
+    /// the width of the leading whitespace, None for a blank line.
+    indent: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Syntax {
+    grammar: &'static Grammar,
+    lines: Vec<Line>,
+    /// the first character of each line.
+    starts: Vec<usize>,
+    tree: Node,
+}
+
+impl Syntax {
+    pub fn new(grammar: &'static Grammar, text: &Rope) -> Self {
+        let mut syntax = Self {
+            grammar,
+            lines: Vec::new(),
+            starts: Vec::new(),
+            tree: Node::new(NodeKind::Root, 0..0),
+        };
+        let mut state = State::Code;
+        for idx in 0..text.len_lines() {
+            let line = syntax.lex(text, idx, state);
+            state = line.end;
+            syntax.lines.push(line);
+        }
+        syntax.build();
+        syntax
+    }
+
+    /// the syntax of a file in a bundled language, None if no grammar knows its extension.
+    pub fn for_path(path: &Path, text: &Rope) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        grammar::for_extension(extension).map(|grammar| Self::new(grammar, text))
+    }
+
+    pub fn grammar(&self) -> &'static Grammar {
+        self.grammar
+    }
+
+    pub fn tree(&self) -> &Node {
+        &self.tree
+    }
+
+    fn lex(&self, text: &Rope, idx: usize, state: State) -> Line {
+        let line = text.line(idx);
+        let chars: Vec<char> = line.chars().collect();
+        let mut end = chars.len();
+        while end > 0 && (chars[end - 1] == '\n' || chars[end - 1] == '\r') {
+            end -= 1;
+        }
+        let chars = &chars[..end];
+        let (tokens, end_state) = lex_line(self.grammar, chars, state);
+        let indent = chars.iter().position(|c| !c.is_whitespace());
+        Line {
+            state,
+            end: end_state,
+            tokens,
+            len: line.len_chars(),
+            width: chars.len(),
+            indent,
+        }
+    }
+
+    /// the text was edited: lines old_lines lines from line were replaced by new_lines lines.
+    /// An edit inside a line replaces one line with one.
+    pub fn edited(&mut self, text: &Rope, line: usize, old_lines: usize, new_lines: usize) {
+        let first = line.min(self.lines.len());
+        let old_end = (first + old_lines).min(self.lines.len());
+        let new_end = (first + new_lines).min(text.len_lines());
+        let mut state = match first {
+            0 => State::Code,
+            first => self.lines[first - 1].end,
+        };
+        let mut relexed = Vec::with_capacity(new_end - first);
+        for idx in first..new_end {
+            let line = self.lex(text, idx, state);
+            state = line.end;
+            relexed.push(line);
+        }
+        self.lines.splice(first..old_end, relexed);
+
+        // the lines after the edit only change if they start in a different state.
+        let mut idx = new_end;
+        while idx < self.lines.len() && self.lines[idx].state != state {
+            let line = self.lex(text, idx, state);
+            state = line.end;
+            self.lines[idx] = line;
+            idx += 1;
+        }
+
+        if self.lines.len() != text.len_lines() {
+            *self = Self::new(self.grammar, text);
+            return;
+        }
+
+        // the characters of the relexed lines before the edit, the starts are still the old
+        // ones.
+        let old_start = |line: usize| {
+            self.starts
+                .get(line)
+                .copied()
+                .unwrap_or(self.tree.range.end)
+        };
+        let old = old_start(first)..old_start(idx + old_end - new_end);
+        let delta = text.len_chars() as isize - self.tree.range.end as isize;
+        self.update_starts();
+        if self.grammar.indented_blocks || !self.rebuild_block(old, delta) {
+            self.build();
+        }
+    }
+
+    fn update_starts(&mut self) {
+        self.starts.clear();
+        let mut offset = 0;
+        for line in self.lines.iter() {
+            self.starts.push(offset);
+            offset += line.len;
+        }
+    }
+
+    /// rebuilds the nodes inside the innermost block around the old characters of the edited
+    /// lines and moves the nodes after them. Returns false if the text of the block no longer
+    /// stands on its own, e.g. it has a bracket without its pair, and the tree has to be
+    /// rebuilt.
+    fn rebuild_block(&mut self, old: Range<usize>, delta: isize) -> bool {
+        // the indices of the children down to the block.
+        let mut path = Vec::new();
+        let mut depth = None;
+        let mut node = &self.tree;
+        while let Some(idx) = node
+            .children
+            .iter()
+            .position(|child| child.range.start < old.start && old.end < child.range.end)
+        {
+            node = &node.children[idx];
+            path.push(idx);
+            if let NodeKind::Block(_) = node.kind {
+                depth = Some(path.len());
+            }
+        }
+        match depth {
+            Some(depth) => path.truncate(depth),
+            None => return false,
+        }
+
+        let block = path
+            .iter()
+            .fold(&self.tree, |node, idx| &node.children[*idx]);
+        let end = (block.range.end as isize + delta) as usize;
+        let nodes = match self.nodes(block.range.start + 1..end - 1, true) {
+            Some((nodes, _)) => nodes,
+            None => return false,
+        };
+
+        self.tree.shift(old.end, delta);
+        let block = path
+            .iter()
+            .fold(&mut self.tree, |node, idx| &mut node.children[*idx]);
+        block.children = Node::new(block.kind, block.range.clone())
+            .nest(nodes)
+            .children;
+        true
+    }
+
+    /// rebuilds the tree from the tokens of the lines.
+    fn build(&mut self) {
+        self.update_starts();
+        let len = self.lines.iter().map(|line| line.len).sum();
+        let (mut nodes, depths) = self.nodes(0..len, false).unwrap();
+        if self.grammar.indented_blocks {
+            self.indented_blocks(&depths, &mut nodes);
+        }
+        // the outer node first when two start at the same character.
+        nodes.sort_by_key(|node| (node.range.start, std::cmp::Reverse(node.range.end)));
+        self.tree = Node::new(NodeKind::Root, 0..len).nest(nodes);
+    }
+
+    /// the nodes of the tokens inside a range of characters, sorted by their start with the
+    /// outer node first, and the depth of the brackets each line starts at. With `balanced`
+    /// None is returned if the nodes depend on the text around the range: a bracket is left
+    /// open, a closing bracket has no opening one, or a string or comment goes on before or
+    /// after the range.
+    fn nodes(&self, chars: Range<usize>, balanced: bool) -> Option<(Vec<Node>, Vec<usize>)> {
+        let mut nodes = Vec::new();
+        // the brackets that are open and the depth each line starts at.
+        let mut open: Vec<(char, usize)> = Vec::new();
+        let mut depths = Vec::new();
+        // the start of a string or comment that goes on in the next line.
+        let mut open_text: Option<usize> = None;
+        // functions whose body hasn't ended: their start, depth and if their body started.
+        let mut functions: Vec<(usize, usize, bool)> = Vec::new();
+
+        let first = self.line_of(chars.start);
+        for (line, start) in self.lines[first..].iter().zip(self.starts[first..].iter()) {
+            if *start > chars.end {
+                break;
+            }
+            depths.push(open.len());
+            for token in line.tokens.iter() {
+                let range = start + token.range.start..start + token.range.end;
+                if range.start < chars.start || range.end > chars.end {
+                    continue;
+                }
+                match token.kind {
+                    TokenKind::String | TokenKind::Comment => {
+                        let kind = match token.kind {
+                            TokenKind::String => NodeKind::String,
+                            _ => NodeKind::Comment,
+                        };
+                        let from = match (token.continued, open_text.take()) {
+                            (true, Some(from)) => from,
+                            (true, None) if balanced => return None,
+                            _ => range.start,
+                        };
+                        if token.open {
+                            open_text = Some(from);
+                        } else {
+                            nodes.push(Node::new(kind, from..range.end));
+                        }
+                    }
+                    TokenKind::Open(bracket) => {
+                        if let Some(function) = functions.last_mut() {
+                            if bracket == '{' && function.1 == open.len() {
+                                function.2 = true;
+                            }
+                        }
+                        open.push((bracket, range.start));
+                    }
+                    TokenKind::Close(bracket) => {
+                        // a closing bracket without an opening one is ignored.
+                        let idx = match open.iter().rposition(|(open, _)| *open == bracket) {
+                            Some(idx) => idx,
+                            None if balanced => return None,
+                            None => continue,
+                        };
+                        nodes.push(Node::new(NodeKind::Block(bracket), open[idx].1..range.end));
+                        open.truncate(idx);
+                        while let Some(&(from, depth, body)) = functions.last() {
+                            if depth < open.len() || (depth == open.len() && !body) {
+                                break;
+                            }
+                            functions.pop();
+                            if depth == open.len() && bracket == '{' {
+                                nodes.push(Node::new(NodeKind::Function, from..range.end));
+                            }
+                        }
+                    }
+                    TokenKind::Separator => {
+                        // a declaration without a body.
+                        if functions
+                            .last()
+                            .map_or(false, |f| f.1 == open.len() && !f.2)
+                        {
+                            functions.pop();
+                        }
+                    }
+                    TokenKind::FunctionKeyword if !self.grammar.indented_blocks => {
+                        functions.push((range.start, open.len(), false));
+                    }
+                    _ => nodes.push(Node::new(NodeKind::Token, range)),
+                }
+            }
+        }
+        // functions left without a body are declarations.
+        if balanced && (!open.is_empty() || open_text.is_some()) {
+            return None;
+        }
+        nodes.sort_by_key(|node| (node.range.start, std::cmp::Reverse(node.range.end)));
+        Some((nodes, depths))
+    }
+
+    /// the blocks of the lines indented deeper than the line before them. Lines that start
+    /// inside brackets, a string or a comment are part of the line before.
+    fn indented_blocks(&self, depths: &[usize], nodes: &mut Vec<Node>) {
+        // the start, end, indent and if it defines a function of each logical line.
+        let mut logical: Vec<(usize, usize, usize, bool)> = Vec::new();
+        for (idx, line) in self.lines.iter().enumerate() {
+            let start = self.starts[idx];
+            let end = start + line.width;
+            let indent = match line.indent {
+                Some(indent) => indent,
+                None => continue,
+            };
+            let continued = depths[idx] > 0 || line.state != State::Code;
+            match logical.last_mut() {
+                Some(last) if continued => last.1 = end,
+                _ => {
+                    let function = line
+                        .tokens
+                        .iter()
+                        .take(2)
+                        .any(|token| token.kind == TokenKind::FunctionKeyword);
+                    logical.push((start + indent, end, indent, function));
+                }
+            }
+        }
+
+        let close = |header: usize, last: usize, nodes: &mut Vec<Node>| {
+            if last > header {
+                let (start, end, _, function) = logical[header];
+                let block_end = logical[last].1;
+                nodes.push(Node::new(NodeKind::Indented, end..block_end));
+                if function {
+                    nodes.push(Node::new(NodeKind::Function, start..block_end));
+                }
+            }
+        };
+        let mut headers: Vec<usize> = Vec::new();
+        for (idx, line) in logical.iter().enumerate() {
+            while let Some(&header) = headers.last() {
+                if line.2 > logical[header].2 {
+                    break;
+                }
+                headers.pop();
+                close(header, idx - 1, nodes);
+            }
+            headers.push(idx);
+        }
+        while let Some(header) = headers.pop() {
+            close(header, logical.len() - 1, nodes);
+        }
+    }
+
+    /// the highlighted columns of a line.
+    pub fn highlights(&self, line: usize) -> Vec<(Range<usize>, Highlight)> {
+        let line = match self.lines.get(line) {
+            Some(line) => line,
+            None => return Vec::new(),
+        };
+        line.tokens
+            .iter()
+            .filter_map(|token| {
+                let highlight = match token.kind {
+                    TokenKind::Keyword | TokenKind::FunctionKeyword => Highlight::Keyword,
+                    TokenKind::Type => Highlight::Type,
+                    TokenKind::Function => Highlight::Function,
+                    TokenKind::Number => Highlight::Number,
+                    TokenKind::String => Highlight::String,
+                    TokenKind::Comment => Highlight::Comment,
+                    _ => return None,
+                };
+                Some((token.range.clone(), highlight))
+            })
+            .collect()
+    }
+
+    /// the next larger syntactic range around a range of characters: the word, the inside of
+    /// the brackets or the string, the brackets with their contents, the function, and so on.
+    pub fn expand(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let path = self.tree.path(&range);
+        path.iter()
+            .rev()
+            .flat_map(|node| {
+                let inner = match node.kind {
+                    NodeKind::Block(_) | NodeKind::String if node.range.len() >= 2 => {
+                        Some(node.range.start + 1..node.range.end - 1)
+                    }
+                    _ => None,
+                };
+                inner.into_iter().chain(Some(node.range.clone()))
+            })
+            .find(|candidate| {
+                candidate.start <= range.start
+                    && range.end <= candidate.end
+                    && candidate.len() > range.len()
+            })
+    }
+
+    /// the position of the bracket matching the bracket at a character, or the bracket
+    /// before it.
+    pub fn matching_bracket(&self, char_idx: usize) -> Option<usize> {
+        let at = |idx: usize| {
+            self.tree
+                .path(&(idx..idx + 1))
+                .into_iter()
+                .rev()
+                .find_map(|node| match node.kind {
+                    NodeKind::Block(_) if node.range.start == idx => Some(node.range.end - 1),
+                    NodeKind::Block(_) if node.range.end == idx + 1 => Some(node.range.start),
+                    _ => None,
+                })
+        };
+        at(char_idx).or_else(|| at(char_idx.checked_sub(1)?))
+    }
+
+    /// the innermost function starting before a character.
+    pub fn enclosing_function(&self, char_idx: usize) -> Option<Range<usize>> {
+        self.tree
+            .path(&(char_idx..char_idx))
+            .into_iter()
+            .rev()
+            .find(|node| node.kind == NodeKind::Function && node.range.start < char_idx)
+            .map(|node| node.range.clone())
+    }
+
+    /// the lines of the blocks, functions and comments spanning several lines, as the first
+    /// and the last line. Ranges starting on the same line are merged into the outer one.
+    pub fn folding_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut nodes: Vec<&Node> = self.tree.children.iter().collect();
+        while let Some(node) = nodes.pop() {
+            if node.kind != NodeKind::Token && node.kind != NodeKind::String {
+                let first = self.line_of(node.range.start);
+                let last = self.line_of(node.range.end.saturating_sub(1));
+                if last > first {
+                    ranges.push(first..last);
+                }
+            }
+            nodes.extend(node.children.iter());
+        }
+        ranges.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+        ranges.dedup_by_key(|range| range.start);
+        ranges
+    }
+
+    /// the line of a character.
+    fn line_of(&self, char_idx: usize) -> usize {
+        match self.starts.binary_search(&char_idx) {
+            Ok(line) => line,
+            Err(line) => line.saturating_sub(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn syntax(language: &str, text: &str) -> (Syntax, Rope) {
+        let rope = Rope::from_str(text);
+        let grammar = grammar::for_extension(language).unwrap();
+        (Syntax::new(grammar, &rope), rope)
+    }
+
+    #[test]
+    fn brackets_and_functions() {
+        let text = "fn main() {\n    let v = vec![1, (2)];\n}\n/* a\n b */ fn f();\n";
+        let (mut syntax, mut rope) = syntax("rs", text);
+        assert_eq!(syntax.matching_bracket(10), Some(38));
+        assert_eq!(syntax.matching_bracket(39), Some(10));
+        assert_eq!(syntax.enclosing_function(20), Some(0..39));
+        // `f` has no body.
+        assert_eq!(syntax.enclosing_function(55), None);
+        assert_eq!(syntax.folding_ranges(), vec![0..2, 3..4]);
+
+        // the word, the inside of the parentheses, the parentheses, the inside of the brackets.
+        assert_eq!(syntax.expand(33..33), Some(33..34));
+        assert_eq!(syntax.expand(33..34), Some(32..35));
+        assert_eq!(syntax.expand(32..35), Some(29..35));
+        assert_eq!(syntax.expand(29..35), Some(28..36));
+
+        // opening a comment relexes the lines after it.
+        rope.insert(12, "/*");
+        syntax.edited(&rope, 1, 1, 1);
+        assert_eq!(syntax.highlights(2), vec![(0..1, Highlight::Comment)]);
+        assert_eq!(syntax.matching_bracket(10), None);
+        rope.remove(12..14);
+        syntax.edited(&rope, 1, 1, 1);
+        assert_eq!(
+            syntax.highlights(0),
+            vec![(0..2, Highlight::Keyword), (3..7, Highlight::Function)]
+        );
+        assert_eq!(syntax.enclosing_function(20), Some(0..39));
+    }
+
+    #[test]
+    fn rebuilt_blocks() {
+        let text = "fn f() {\n    if a {\n        b(1);\n    }\n}\n\nfn g() {}\n";
+        let (mut syntax, mut rope) = syntax("rs", text);
+        let grammar = syntax.grammar();
+        let mut edit = |rope: &mut Rope, syntax: &mut Syntax, char_idx: usize, text: &str| {
+            let line = rope.char_to_line(char_idx);
+            rope.insert(char_idx, text);
+            let lines = text.matches('\n').count() + 1;
+            syntax.edited(rope, line, 1, lines);
+            // the same tree as one built from scratch.
+            assert_eq!(syntax.tree(), Syntax::new(grammar, rope).tree());
+        };
+
+        // inside the block of `if`, the block of `f` and the function after them.
+        edit(&mut rope, &mut syntax, 31, ", c");
+        edit(&mut rope, &mut syntax, 36, "\n        d();");
+        edit(&mut rope, &mut syntax, 9, "let x = [0];\n    ");
+        // brackets, strings and comments that don't balance inside the block.
+        edit(&mut rope, &mut syntax, 9, "{");
+        edit(&mut rope, &mut syntax, 28, "}");
+        edit(&mut rope, &mut syntax, 9, "/*");
+        edit(&mut rope, &mut syntax, 40, "*/\"");
+        assert_eq!(syntax.tree().range, 0..rope.len_chars());
+    }
+
+    #[test]
+    fn indented_blocks() {
+        let text = "def f(a,\n      b):\n    if a:\n        return b\n\n    return a\nx = 1\n";
+        let (syntax, _) = syntax("py", text);
+        assert_eq!(syntax.enclosing_function(30), Some(0..59));
+        assert_eq!(syntax.folding_ranges(), vec![0..5, 1..5, 2..3]);
+    }
+}
@@ -0,0 +1,132 @@
+// File Watcher.
+// Watches the files of buffers for changes made by other programs, e.g. a git checkout or a
+// formatter. The directory of each file is watched instead of the file itself because many
+// programs save by writing a temporary file and renaming it over the original, which ends a
+// watch on the original file. Changes are reported from a separate thread.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::CoreError;
+
+/// changes to a file within this duration are reported once.
+const WATCH_DELAY: Duration = Duration::from_millis(100);
+
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    /// the watched files, shared with the thread reporting the changes.
+    files: Arc<Mutex<HashSet<PathBuf>>>,
+    /// the number of watched files in each watched directory.
+    directories: HashMap<PathBuf, usize>,
+}
+
+impl FileWatcher {
+    /// starts watching for changes, on_change is called with the path of a changed file.
+    pub fn new<F>(on_change: F) -> Result<Self, CoreError>
+    where
+        F: Fn(PathBuf) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::watcher(sender, WATCH_DELAY)
+            .map_err(|e| CoreError::WatchFailed(PathBuf::new(), e.to_string()))?;
+
+        let files = Arc::new(Mutex::new(HashSet::new()));
+        let watched = Arc::clone(&files);
+        // the thread ends when the watcher is dropped and the channel disconnects.
+        kea::utils::spawn_thread("file watcher", move || {
+            for event in receiver.iter() {
+                let path = match event {
+                    DebouncedEvent::Write(path)
+                    | DebouncedEvent::Create(path)
+                    | DebouncedEvent::Rename(_, path) => path,
+                    _ => continue,
+                };
+                if watched.lock().unwrap().contains(&path) {
+                    on_change(path);
+                }
+            }
+        });
+
+        Ok(Self {
+            watcher,
+            files,
+            directories: HashMap::new(),
+        })
+    }
+
+    pub fn watch(&mut self, path: &Path) -> Result<(), CoreError> {
+        let path = absolute(path);
+        if !self.files.lock().unwrap().insert(path.clone()) {
+            return Ok(());
+        }
+
+        let directory = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+        let count = self.directories.entry(directory.clone()).or_insert(0);
+        if *count == 0 {
+            if let Err(e) = self.watcher.watch(&directory, RecursiveMode::NonRecursive) {
+                self.directories.remove(&directory);
+                self.files.lock().unwrap().remove(&path);
+                return Err(CoreError::WatchFailed(path, e.to_string()));
+            }
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        let path = absolute(path);
+        if !self.files.lock().unwrap().remove(&path) {
+            return;
+        }
+
+        let directory = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+        if let Some(count) = self.directories.get_mut(&directory) {
+            *count -= 1;
+            if *count == 0 {
+                self.directories.remove(&directory);
+                self.watcher.unwatch(&directory).ok();
+            }
+        }
+    }
+}
+
+/// a relative path joined to the working directory, the watcher reports absolute paths.
+pub fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn watch_and_unwatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watched.txt");
+        fs::write(&path, "one").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = FileWatcher::new(move |path| sender.send(path).unwrap()).unwrap();
+        watcher.watch(&path).unwrap();
+        // files that aren't watched aren't reported, even in a watched directory.
+        fs::write(dir.path().join("other.txt"), "other").unwrap();
+        fs::write(&path, "two").unwrap();
+        let changed = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(changed, absolute(&path));
+
+        watcher.unwatch(&path);
+        fs::write(&path, "three").unwrap();
+        assert!(receiver.recv_timeout(WATCH_DELAY * 5).is_err());
+    }
+}
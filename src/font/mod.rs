@@ -6,22 +6,29 @@ use crate::pathfinder_geometry;
 pub use font_kit::canvas::{Canvas, Format, RasterizationOptions};
 pub use font_kit::family_name::FamilyName;
 pub use font_kit::font;
+use font_kit::handle::Handle;
 pub use font_kit::hinting::HintingOptions;
+use font_kit::loader::Loader;
 pub use font_kit::properties::{Properties, Stretch, Style, Weight};
 
 pub use font_kit::error;
+use log::{info, warn};
 use pathfinder_geometry::{
     transform2d::Transform2F,
     vector::{vec2f, vec2i, Vector2F, Vector2I},
 };
 use std::str;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     hash::Hasher,
     io::{BufReader, Read},
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
 };
 
+mod shaper;
+pub use shaper::{ShapedGlyph, Shaper};
+
 #[derive(Debug, thiserror::Error)]
 pub enum FontError {
     #[error("failed to load font: {:?} | {:?}", font.name, err)]
@@ -42,9 +49,9 @@ pub enum FontError {
     #[error("loaded fonts do not support glyph: '{}'", ch)]
     UnsupportedGlyph { ch: char },
 
-    #[error("failed to load glyph: {} | {}", ch, err)]
+    #[error("failed to load glyph: {} | {}", glyph, err)]
     GlyphError {
-        ch: char,
+        glyph: u32,
         err: font_kit::error::GlyphLoadingError,
     },
 
@@ -89,7 +96,7 @@ impl FontDesc {
 
 impl std::cmp::PartialEq for FontDesc {
     fn eq(&self, other: &Self) -> bool {
-        match (&self.name, &other.name) {
+        (match (&self.name, &other.name) {
             (FamilyName::Title(title), FamilyName::Title(title2)) => title.eq(title2),
             (FamilyName::Serif, FamilyName::Serif)
             | (FamilyName::SansSerif, FamilyName::SansSerif)
@@ -97,7 +104,8 @@ impl std::cmp::PartialEq for FontDesc {
             | (FamilyName::Cursive, FamilyName::Cursive)
             | (FamilyName::Fantasy, FamilyName::Fantasy) => true,
             _ => false,
-        }
+        }) && self.properties.style == other.properties.style
+            && self.properties.weight == other.properties.weight
     }
 }
 
@@ -116,6 +124,7 @@ impl std::hash::Hash for FontDesc {
             font_kit::properties::Style::Italic => 2,
             font_kit::properties::Style::Oblique => 3,
         });
+        state.write_i32(self.properties.weight.0 as i32);
     }
 }
 
@@ -127,6 +136,11 @@ pub struct FontMetrics {
     pub ascent: f32,
     pub descent: f32,
     pub line_gap: f32,
+    /// the height of lowercase letters.
+    pub x_height: f32,
+    /// distance from the baseline to the top of the underline, negative values are below the baseline.
+    pub underline_position: f32,
+    pub underline_thickness: f32,
 }
 
 impl FontMetrics {
@@ -137,6 +151,9 @@ impl FontMetrics {
         ascent: f32,
         descent: f32,
         line_gap: f32,
+        x_height: f32,
+        underline_position: f32,
+        underline_thickness: f32,
     ) -> Self {
         Self {
             x_ppem,
@@ -145,6 +162,9 @@ impl FontMetrics {
             ascent,
             descent,
             line_gap,
+            x_height,
+            underline_position,
+            underline_thickness,
         }
     }
 
@@ -157,6 +177,9 @@ impl FontMetrics {
             self.ascent * scale,
             self.descent * scale,
             self.line_gap * scale,
+            self.x_height * scale,
+            self.underline_position * scale,
+            self.underline_thickness * scale,
         )
     }
 
@@ -179,6 +202,11 @@ pub struct ScaledFontMetrics {
     pub ascent: f32,
     pub descent: f32,
     pub line_gap: f32,
+    /// the height of lowercase letters.
+    pub x_height: f32,
+    /// distance from the baseline to the top of the underline, negative values are below the baseline.
+    pub underline_position: f32,
+    pub underline_thickness: f32,
 }
 
 impl ScaledFontMetrics {
@@ -190,6 +218,9 @@ impl ScaledFontMetrics {
         ascent: f32,
         descent: f32,
         line_gap: f32,
+        x_height: f32,
+        underline_position: f32,
+        underline_thickness: f32,
     ) -> Self {
         Self {
             scale,
@@ -199,6 +230,9 @@ impl ScaledFontMetrics {
             ascent,
             descent,
             line_gap,
+            x_height,
+            underline_position,
+            underline_thickness,
         }
     }
 
@@ -210,6 +244,9 @@ impl ScaledFontMetrics {
             self.ascent / self.scale,
             self.descent / self.scale,
             self.line_gap / self.scale,
+            self.x_height / self.scale,
+            self.underline_position / self.scale,
+            self.underline_thickness / self.scale,
         )
     }
 
@@ -218,11 +255,27 @@ impl ScaledFontMetrics {
     }
 }
 
+/// families tried, in order, when none of the loaded fonts support a codepoint.
+const FALLBACK_FAMILIES: &[&str] = &[
+    "Noto Sans Mono",
+    "DejaVu Sans Mono",
+    "Noto Sans",
+    "DejaVu Sans",
+    "Noto Sans Symbols",
+    "Noto Sans Symbols2",
+    "Noto Sans CJK SC",
+    "Noto Color Emoji",
+    "Symbola",
+];
+
 /// collection of fonts used by the application
 pub struct FontCollection {
     // pub lib: Library,
     fonts: Vec<Font>,
     device_pixel_ratio: f32,
+    /// codepoints no system font could be found for. These are cached so
+    /// the system is only searched once per codepoint.
+    missing: HashSet<char>,
 }
 
 #[derive(Clone)]
@@ -232,12 +285,19 @@ pub struct Font {
     /// description of what font is to be loaded.
     pub(crate) desc: FontDesc,
     pub(crate) device_pixel_ratio: f32,
-    glyph_info: HashMap<char, GlyphInfo>,
+    /// raw font data, used by the shaper.
+    data: Option<&'static [u8]>,
+    /// index of the face within the font data.
+    face_index: u32,
+    /// the face the shaper reads the font tables from, None if the data can't be parsed.
+    face: Option<rustybuzz::Face<'static>>,
+    glyph_info: HashMap<u32, GlyphInfo>,
 }
 
 #[derive(Debug, Clone)]
 pub struct GlyphId {
-    pub glyph: char,
+    /// index of the glyph in the font, as produced by the shaper.
+    pub glyph: u32,
     pub size: f32,
     pub font: FontDesc,
 }
@@ -246,11 +306,13 @@ impl FontCollection {
     pub const DEFAULT_FONT: usize = 0;
     pub const DEFAULT_ITALIC_FONT: usize = 1;
     pub const DEFAULT_BOLD_FONT: usize = 2;
+    pub const DEFAULT_BOLD_ITALIC_FONT: usize = 3;
 
     pub fn new(device_pixel_ratio: f32) -> Result<Self, FontError> {
         Ok(Self {
             fonts: Vec::new(),
             device_pixel_ratio,
+            missing: HashSet::new(),
         })
     }
 
@@ -288,12 +350,25 @@ impl FontCollection {
         let bold = FontDesc::new(name, bold_prop);
         self.add_font(bold)?;
 
+        // add bold italic
+        let bold_italic_prop = Properties {
+            style: Style::Italic,
+            weight: Weight::BOLD,
+            stretch: Stretch::NORMAL,
+        };
+        let bold_italic = FontDesc::new(name, bold_italic_prop);
+        self.add_font(bold_italic)?;
+
         Ok(())
     }
 
     pub fn add_font(&mut self, desc: FontDesc) -> Result<(), FontError> {
         let font = Font::new(desc, self.device_pixel_ratio)?;
-        println!("Num GLyphs: {}", font.num_glyphs());
+        info!(
+            "Loaded font {:?} with {} glyphs",
+            font.desc().name,
+            font.num_glyphs()
+        );
         self.fonts.push(font);
         Ok(())
     }
@@ -307,6 +382,102 @@ impl FontCollection {
         None
     }
 
+    /// finds the font that should be used to render codepoint. The primary font is
+    /// preferred, then any font already in the collection, and finally the system
+    /// is searched for a fallback font which is added to the collection.
+    /// Returns None if no font supporting the codepoint could be found.
+    pub fn font_for_codepoint(&mut self, primary: usize, codepoint: char) -> Option<usize> {
+        if let Some(font) = self.fonts.get(primary) {
+            if font.supports_codepoint(codepoint) {
+                return Some(primary);
+            }
+        }
+
+        if let Some(idx) = self.find_codepoint_font(codepoint) {
+            return Some(idx);
+        }
+
+        if self.missing.contains(&codepoint) {
+            return None;
+        }
+
+        match self.load_fallback(codepoint) {
+            Some(idx) => Some(idx),
+            None => {
+                warn!(
+                    "no font found for codepoint: {:?} (U+{:04X})",
+                    codepoint, codepoint as u32
+                );
+                self.missing.insert(codepoint);
+                None
+            }
+        }
+    }
+
+    /// searches the system for a font supporting codepoint and adds it to the collection.
+    fn load_fallback(&mut self, codepoint: char) -> Option<usize> {
+        // some loaders are able to provide fallbacks directly.
+        let source = self
+            .fonts
+            .get(Self::DEFAULT_FONT)
+            .and_then(|font| {
+                font.source
+                    .get_fallbacks(codepoint.to_string().as_str(), "en-US")
+                    .fonts
+                    .into_iter()
+                    .map(|fallback| fallback.font)
+                    .find(|font| font.glyph_for_char(codepoint).is_some())
+            })
+            .or_else(|| Self::search_system(codepoint))?;
+
+        let font = Font::from_source(source, self.device_pixel_ratio);
+        info!(
+            "Loaded fallback font: {:?} for {:?}",
+            font.desc().name,
+            codepoint
+        );
+        self.fonts.push(font);
+        Some(self.fonts.len() - 1)
+    }
+
+    fn search_system(codepoint: char) -> Option<font::Font> {
+        let system = font_kit::source::SystemSource::new();
+        let supports = |handle: font_kit::handle::Handle| {
+            handle
+                .load()
+                .ok()
+                .filter(|font| font.glyph_for_char(codepoint).is_some())
+        };
+
+        let families = FALLBACK_FAMILIES
+            .iter()
+            .map(|name| FamilyName::Title(name.to_string()))
+            .chain(vec![
+                FamilyName::Monospace,
+                FamilyName::SansSerif,
+                FamilyName::Serif,
+            ]);
+
+        for family in families {
+            if let Ok(handle) = system.select_best_match(&[family], &Properties::new()) {
+                if let Some(font) = supports(handle) {
+                    return Some(font);
+                }
+            }
+        }
+
+        // last resort, check every family known to the system.
+        for family in system.all_families().unwrap_or_default() {
+            if let Ok(family) = system.select_family_by_name(family.as_str()) {
+                if let Some(font) = family.fonts().iter().cloned().find_map(supports) {
+                    return Some(font);
+                }
+            }
+        }
+
+        None
+    }
+
     pub fn font_at(&self, index: usize) -> Option<&Font> {
         self.fonts.get(index)
     }
@@ -316,15 +487,29 @@ impl FontCollection {
     }
 
     pub fn default_font(&self) -> &Font {
-        unsafe { self.fonts.get_unchecked(Self::DEFAULT_FONT) }
+        &self.fonts[Self::DEFAULT_FONT]
     }
 
     pub fn default_italicfont(&self) -> &Font {
-        unsafe { self.fonts.get_unchecked(Self::DEFAULT_ITALIC_FONT) }
+        &self.fonts[Self::DEFAULT_ITALIC_FONT]
     }
 
     pub fn default_bold_font(&self) -> &Font {
-        unsafe { self.fonts.get_unchecked(Self::DEFAULT_BOLD_FONT) }
+        &self.fonts[Self::DEFAULT_BOLD_FONT]
+    }
+
+    pub fn default_bold_italic_font(&self) -> &Font {
+        &self.fonts[Self::DEFAULT_BOLD_ITALIC_FONT]
+    }
+
+    /// the index of the face of the configured family for the given style.
+    pub fn face_for_style(bold: bool, italic: bool) -> usize {
+        match (bold, italic) {
+            (false, false) => Self::DEFAULT_FONT,
+            (false, true) => Self::DEFAULT_ITALIC_FONT,
+            (true, false) => Self::DEFAULT_BOLD_FONT,
+            (true, true) => Self::DEFAULT_BOLD_ITALIC_FONT,
+        }
     }
 
     pub fn num_fonts(&self) -> usize {
@@ -335,6 +520,16 @@ impl FontCollection {
         self.device_pixel_ratio
     }
 
+    /// updates the device pixel ratio of every font, this happens when the window is moved
+    /// to a monitor with a different dpi.
+    pub fn set_dpi_factor(&mut self, device_pixel_ratio: f32) {
+        self.device_pixel_ratio = device_pixel_ratio;
+        for font in &mut self.fonts {
+            font.device_pixel_ratio = device_pixel_ratio;
+            font.glyph_info.clear();
+        }
+    }
+
     pub fn add_default(&mut self) {
         let family_name = if cfg!(target_os = "win32") {
             "Courier New"
@@ -350,7 +545,7 @@ impl FontCollection {
 }
 
 impl GlyphId {
-    pub fn new(glyph: char, size: f32, font: FontDesc) -> Self {
+    pub fn new(glyph: u32, size: f32, font: FontDesc) -> Self {
         Self { glyph, size, font }
     }
     pub fn scale_size(&self, dpi: f32) -> f32 {
@@ -387,23 +582,142 @@ pub struct RasterizedGlyph {
     pub bitmap: Vec<u8>,
 }
 
+/// what the data of a font is loaded from, fonts without a file are known by their name.
+#[derive(PartialEq, Eq, Hash)]
+enum DataSource {
+    File(PathBuf),
+    Name(String),
+}
+
+/// the data of a font. A face borrows the data of its font, so the data of a font is kept until
+/// the editor exits and loading the font again reuses it.
+fn font_data(source: &font::Font) -> Option<&'static [u8]> {
+    static DATA: OnceLock<Mutex<HashMap<DataSource, &'static [u8]>>> = OnceLock::new();
+    let key = match source.handle() {
+        Some(Handle::Path { path, .. }) => DataSource::File(path),
+        _ => DataSource::Name(
+            source
+                .postscript_name()
+                .unwrap_or_else(|| source.full_name()),
+        ),
+    };
+    let mut data = DATA.get_or_init(Default::default).lock().unwrap();
+    if let Some(data) = data.get(&key) {
+        return Some(*data);
+    }
+    let loaded: &'static [u8] = Vec::leak(source.copy_font_data()?.to_vec());
+    data.insert(key, loaded);
+    Some(loaded)
+}
+
+/// the font a family names when it is a path: a font file, or a directory of the files of a
+/// family where the file closest to the style is used. None when the family isn't a path, a
+/// path has more than one component or the extension of a font file.
+fn font_file(desc: &FontDesc) -> Option<Handle> {
+    let path = match &desc.name {
+        FamilyName::Title(name) => Path::new(name),
+        _ => return None,
+    };
+    if path.components().count() == 1 && !is_font_file(path) {
+        return None;
+    }
+    if !path.is_dir() {
+        return Some(Handle::from_path(path.to_path_buf(), 0));
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|file| is_font_file(file))
+        .collect();
+    files.sort();
+    let italic = desc.properties.style != Style::Normal;
+    let closest = files
+        .into_iter()
+        .rev()
+        .filter_map(|file| {
+            let handle = Handle::from_path(file, 0);
+            let properties = handle.load().ok()?.properties();
+            let score = (
+                (properties.style != Style::Normal) == italic,
+                properties.weight == desc.properties.weight,
+            );
+            Some((handle, score))
+        })
+        .max_by_key(|(_, score)| *score);
+    // a directory without fonts fails to load.
+    let handle = match closest {
+        Some((handle, _)) => handle,
+        None => Handle::from_path(path.to_path_buf(), 0),
+    };
+    Some(handle)
+}
+
+fn is_font_file(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    matches!(
+        extension.map(str::to_ascii_lowercase).as_deref(),
+        Some("ttf") | Some("otf") | Some("ttc")
+    )
+}
+
 impl Font {
+    /// loads the font of a family, or the font file the family names.
     fn new(desc: FontDesc, device_pixel_ratio: f32) -> Result<Self, FontError> {
-        let source = font_kit::source::SystemSource::new()
-            .select_best_match(&[desc.name.clone()], &desc.properties)
-            .map_err(|e| FontError::SelectionError { err: e })?
-            .load()
-            .map_err(|e| FontError::FontLoadError {
-                font: desc.clone(),
-                err: e,
-            })?;
+        let handle = match font_file(&desc) {
+            Some(handle) => handle,
+            None => font_kit::source::SystemSource::new()
+                .select_best_match(&[desc.name.clone()], &desc.properties)
+                .map_err(|e| FontError::SelectionError { err: e })?,
+        };
+        let source = handle.load().map_err(|e| FontError::FontLoadError {
+            font: desc.clone(),
+            err: e,
+        })?;
 
-        Ok(Self {
+        Ok(Self::with_desc(desc, source, device_pixel_ratio))
+    }
+
+    /// creates a font from an already loaded source. This is used for fallback fonts
+    /// where the font is found by its coverage instead of by its description.
+    fn from_source(source: font::Font, device_pixel_ratio: f32) -> Self {
+        let desc = FontDesc::new(source.family_name().as_str(), source.properties());
+        Self::with_desc(desc, source, device_pixel_ratio)
+    }
+
+    fn with_desc(desc: FontDesc, source: font::Font, device_pixel_ratio: f32) -> Self {
+        let face_index = match source.handle() {
+            Some(font_kit::handle::Handle::Path { font_index, .. })
+            | Some(font_kit::handle::Handle::Memory { font_index, .. }) => font_index,
+            None => 0,
+        };
+
+        let data = font_data(&source);
+        let face = data.and_then(|data| rustybuzz::Face::from_slice(data, face_index));
+        Self {
             desc,
+            data,
+            face_index,
+            face,
             source,
             device_pixel_ratio,
             glyph_info: HashMap::new(),
-        })
+        }
+    }
+
+    /// the raw data of the font file, if the loader is able to provide it.
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data
+    }
+
+    /// the face used to shape text with the font.
+    pub fn face(&self) -> Option<&rustybuzz::Face<'static>> {
+        self.face.as_ref()
+    }
+
+    pub fn face_index(&self) -> u32 {
+        self.face_index
     }
 
     pub fn num_glyphs(&self) -> u32 {
@@ -418,7 +732,6 @@ impl Font {
         // let metrics = self.source.size_metrics().ok_or(FontError::InvalidFontMetrics { font: self.desc.clone() })?;
         let metrics = self.source.metrics();
 
-        let height = metrics.x_height as f32;
         let ascent = metrics.ascent as f32;
         let descent = metrics.descent as f32;
         let ppem = metrics.units_per_em as f32;
@@ -430,6 +743,9 @@ impl Font {
             ascent,
             descent,
             line_gap: metrics.line_gap,
+            x_height: metrics.x_height,
+            underline_position: metrics.underline_position,
+            underline_thickness: metrics.underline_thickness,
         }
     }
 
@@ -441,70 +757,82 @@ impl Font {
         self.get_glyph_index(codepoint).is_some()
     }
 
+    /// the number of pixels per font unit at the given font size.
+    pub fn units_scale(&self, size: f32) -> f32 {
+        Self::scale_size(size, self.device_pixel_ratio) / self.source.metrics().units_per_em as f32
+    }
+
+    /// the horizontal advance of a glyph in pixels.
+    pub fn advance(&self, glyph: u32, size: f32) -> Result<f32, FontError> {
+        let advance = self
+            .source
+            .advance(glyph)
+            .map_err(|err| FontError::GlyphError { glyph, err })?;
+        Ok(advance.x() * self.units_scale(size))
+    }
+
     pub fn rasterize_glyph(
         &mut self,
-        codepoint: char,
+        glyph_index: u32,
         height: f32,
     ) -> Result<RasterizedGlyph, FontError> {
-        //println!("Char: {}", codepoint);
-        let glyph = GlyphId::new(codepoint, height, self.desc.clone());
+        let glyph = GlyphId::new(glyph_index, height, self.desc.clone());
+        let advance = vec2f(self.advance(glyph_index, height)?, 0.0);
         let height = glyph.scale_size(self.device_pixel_ratio);
-        let metrics = self.source.metrics();
-        let scale = Self::scale_size(height, self.device_pixel_ratio) / metrics.units_per_em as f32;
 
-        if let Some(glyph_id) = self.get_glyph_index(codepoint) {
-            let bounding_box = self
-                .source
-                .raster_bounds(
-                    glyph_id,
-                    height,
-                    Transform2F::default(),
-                    HintingOptions::None,
-                    RasterizationOptions::SubpixelAa,
-                )
-                .unwrap();
+        let bounding_box = self
+            .source
+            .raster_bounds(
+                glyph_index,
+                height,
+                Transform2F::default(),
+                HintingOptions::None,
+                RasterizationOptions::SubpixelAa,
+            )
+            .map_err(|err| FontError::GlyphError {
+                glyph: glyph_index,
+                err,
+            })?;
 
-            // let size = bounding_box.size().0 + vec2i(0, 1).0;
-            let mut canvas = Canvas::new(bounding_box.size(), Format::Rgb24);
+        // let size = bounding_box.size().0 + vec2i(0, 1).0;
+        let mut canvas = Canvas::new(bounding_box.size(), Format::Rgb24);
 
+        // glyphs without an outline, such as spaces, have nothing to rasterize.
+        if bounding_box.width() > 0 && bounding_box.height() > 0 {
             self.source
                 .rasterize_glyph(
                     &mut canvas,
-                    glyph_id,
+                    glyph_index,
                     height,
                     Transform2F::from_translation(-bounding_box.origin().to_f32()),
                     HintingOptions::None,
                     RasterizationOptions::SubpixelAa,
                 )
-                .map_err(|err| FontError::GlyphError { ch: codepoint, err })?;
-            let advance = self
-                .source
-                .advance(glyph_id)
-                .map_err(|err| FontError::GlyphError { ch: codepoint, err })?;
-            let advance = vec2f(advance.x() * scale, advance.y() * scale);
-            let origin = bounding_box.origin().to_f32();
-
-            self.glyph_info.insert(
-                codepoint,
-                GlyphInfo::new(
-                    canvas.size.clone().to_f32(),
-                    advance.clone(),
-                    origin.clone(),
-                ),
-            );
-
-            Ok(RasterizedGlyph {
-                glyph,
-                stride: canvas.stride,
-                width: canvas.size.x(),
-                height: canvas.size.y(),
-                origin,
-                advance,
-                bitmap: canvas.pixels, // temp_buffer.into_iter().rev().flat_map(|e| e.to_vec()).collect(),
-            })
-        } else {
-            Err(FontError::UnsupportedGlyph { ch: codepoint })
+                .map_err(|err| FontError::GlyphError {
+                    glyph: glyph_index,
+                    err,
+                })?;
         }
+        let origin = bounding_box.origin().to_f32();
+
+        self.glyph_info.insert(
+            glyph_index,
+            GlyphInfo::new(
+                canvas.size.clone().to_f32(),
+                advance.clone(),
+                origin.clone(),
+            ),
+        );
+
+        Ok(RasterizedGlyph {
+            glyph,
+            stride: canvas.stride,
+            width: canvas.size.x(),
+            height: canvas.size.y(),
+            origin,
+            advance,
+            bitmap: canvas.pixels, // temp_buffer.into_iter().rev().flat_map(|e| e.to_vec()).collect(),
+        })
     }
 
     #[inline]
@@ -512,8 +840,8 @@ impl Font {
         height * dpi * 96.0 / 72.0
     }
 
-    pub fn info(&self, codepoint: char) -> Option<&GlyphInfo> {
-        self.glyph_info.get(&codepoint)
+    pub fn info(&self, glyph: u32) -> Option<&GlyphInfo> {
+        self.glyph_info.get(&glyph)
     }
 }
 
@@ -526,3 +854,54 @@ pub fn to_freetype_26_6(f: f32) -> isize {
 pub fn from_freetype_26_6(f: f32) -> isize {
     ((1i32 >> 6) as f32 * f) as isize
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn family_of_a_directory() {
+        let mut fonts = FontCollection::new(1.0).unwrap();
+        fonts.add_font_by_name("tests/fixtures/fonts").unwrap();
+        let name = |idx: usize| fonts.font_at(idx).unwrap().source.postscript_name();
+        assert_eq!(
+            name(FontCollection::DEFAULT_FONT).unwrap(),
+            "DejaVuSansMono"
+        );
+        assert_eq!(
+            name(FontCollection::DEFAULT_ITALIC_FONT).unwrap(),
+            "DejaVuSansMono-Oblique"
+        );
+        assert_eq!(
+            name(FontCollection::DEFAULT_BOLD_FONT).unwrap(),
+            "DejaVuSansMono-Bold"
+        );
+        assert_eq!(
+            name(FontCollection::DEFAULT_BOLD_ITALIC_FONT).unwrap(),
+            "DejaVuSansMono-BoldOblique"
+        );
+
+        // a missing path isn't looked up as the name of an installed family.
+        assert!(matches!(
+            fonts.add_font_by_name("tests/fixtures/missing"),
+            Err(FontError::FontLoadError { .. })
+        ));
+    }
+
+    #[test]
+    fn data_of_a_font_is_loaded_once() {
+        let file = std::fs::read("tests/fixtures/fonts/DejaVuSansMono.ttf").unwrap();
+        let load = || {
+            let source = Handle::from_memory(Arc::new(file.clone()), 0)
+                .load()
+                .unwrap();
+            Font::from_source(source, 1.0)
+        };
+        let (first, second) = (load(), load());
+        assert!(first.face().is_some());
+        assert_eq!(
+            first.data().unwrap().as_ptr(),
+            second.data().unwrap().as_ptr()
+        );
+    }
+}
@@ -0,0 +1,304 @@
+// Text Shaping.
+// Converts a run of text into positioned glyphs of a font. This is what allows ligatures,
+// combining characters, and emoji sequences to be rendered correctly. Shaping is done with
+// rustybuzz, a pure rust port of HarfBuzz.
+
+use super::{Font, FontCollection};
+use log::warn;
+use rustybuzz::{Feature, UnicodeBuffer};
+
+/// features disabled when the user turns off ligatures. Many programming fonts
+/// (Fira Code for example) implement their ligatures with contextual alternates.
+const LIGATURE_FEATURES: &[&str] = &["-liga", "-clig", "-dlig", "-calt"];
+
+/// A glyph produced by the shaper.
+#[derive(Debug, Clone, Copy)]
+pub struct ShapedGlyph {
+    /// index of the glyph in its font.
+    pub glyph: u32,
+    /// index of the font in the collection, None if no font supports the text.
+    pub font: Option<usize>,
+    /// byte offset of the first character of the cluster this glyph belongs to.
+    pub cluster: usize,
+    /// the horizontal position of the glyph relative to the start of the text.
+    pub x: f32,
+    /// vertical offset from the baseline, positive values move the glyph down.
+    pub y: f32,
+    /// how far the pen moves after this glyph.
+    pub advance: f32,
+}
+
+/// a range of text that is shaped with a single font.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    start: usize,
+    end: usize,
+    font: Option<usize>,
+}
+
+pub struct Shaper {
+    features: Vec<Feature>,
+    ligatures: bool,
+}
+
+impl Shaper {
+    pub fn new(ligatures: bool) -> Self {
+        let features = if ligatures {
+            Vec::new()
+        } else {
+            LIGATURE_FEATURES
+                .iter()
+                .filter_map(|feature| feature.parse::<Feature>().ok())
+                .collect()
+        };
+
+        Self {
+            features,
+            ligatures,
+        }
+    }
+
+    pub fn ligatures(&self) -> bool {
+        self.ligatures
+    }
+
+    /// shapes text with the font at index font, characters not supported by the font
+    /// are shaped with a fallback font. The missing advance is used for characters
+    /// that no font supports.
+    pub fn shape(
+        &self,
+        fonts: &mut FontCollection,
+        text: &str,
+        font: usize,
+        size: f32,
+        missing_advance: f32,
+    ) -> Vec<ShapedGlyph> {
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+
+        for run in Self::runs(fonts, text, font) {
+            let start = glyphs.len();
+            let font = run.font.and_then(|idx| fonts.font_at(idx));
+            match font {
+                Some(font) => {
+                    let text = &text[run.start..run.end];
+                    if !self.shape_run(font, text, size, &mut glyphs) {
+                        Self::shape_simple(font, text, size, &mut glyphs);
+                    }
+                }
+                None => {
+                    for (idx, (offset, _)) in text[run.start..run.end].char_indices().enumerate() {
+                        glyphs.push(ShapedGlyph {
+                            glyph: 0,
+                            font: None,
+                            cluster: offset,
+                            x: idx as f32 * missing_advance,
+                            y: 0.0,
+                            advance: missing_advance,
+                        });
+                    }
+                }
+            }
+
+            // glyphs of a run are positioned relative to the start of the run.
+            let run_x = x;
+            for glyph in &mut glyphs[start..] {
+                glyph.font = run.font;
+                glyph.cluster += run.start;
+                glyph.x += run_x;
+                x += glyph.advance;
+            }
+        }
+
+        glyphs
+    }
+
+    /// shapes text using rustybuzz. Returns false if the font data could not be used.
+    fn shape_run(&self, font: &Font, text: &str, size: f32, glyphs: &mut Vec<ShapedGlyph>) -> bool {
+        let face = match font.face() {
+            Some(face) => face,
+            None => {
+                warn!("unable to shape with font: {:?}", font.desc());
+                return false;
+            }
+        };
+
+        let scale = font.units_scale(size);
+
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+
+        let output = rustybuzz::shape(face, self.features.as_slice(), buffer);
+        let mut x = 0.0;
+        for (info, pos) in output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions().iter())
+        {
+            let advance = pos.x_advance as f32 * scale;
+            glyphs.push(ShapedGlyph {
+                glyph: info.glyph_id,
+                font: None,
+                cluster: info.cluster as usize,
+                x: x + pos.x_offset as f32 * scale,
+                y: -pos.y_offset as f32 * scale,
+                advance,
+            });
+            x += advance;
+        }
+
+        true
+    }
+
+    /// maps each character directly to a glyph, used when the font can't be shaped.
+    fn shape_simple(font: &Font, text: &str, size: f32, glyphs: &mut Vec<ShapedGlyph>) {
+        let mut x = 0.0;
+        for (offset, ch) in text.char_indices() {
+            let glyph = font.get_glyph_index(ch).unwrap_or(0);
+            let advance = font.advance(glyph, size).unwrap_or(0.0);
+            glyphs.push(ShapedGlyph {
+                glyph,
+                font: None,
+                cluster: offset,
+                x,
+                y: 0.0,
+                advance,
+            });
+            x += advance;
+        }
+    }
+
+    /// splits text into runs that can each be shaped by a single font.
+    fn runs(fonts: &mut FontCollection, text: &str, primary: usize) -> Vec<Run> {
+        let mut runs: Vec<Run> = Vec::new();
+
+        for (offset, ch) in text.char_indices() {
+            let end = offset + ch.len_utf8();
+
+            // combining marks and joiners stay with the character they modify when the
+            // font of the current run supports them or they are invisible.
+            if let Some(run) = runs.last_mut() {
+                let supported = run
+                    .font
+                    .and_then(|idx| fonts.font_at(idx))
+                    .map_or(false, |font| font.supports_codepoint(ch));
+
+                if (supported && is_cluster_extender(ch)) || is_joiner(ch) {
+                    run.end = end;
+                    continue;
+                }
+            }
+
+            let font = fonts.font_for_codepoint(primary, ch);
+            match runs.last_mut() {
+                Some(run) if run.font == font => run.end = end,
+                _ => runs.push(Run {
+                    start: offset,
+                    end,
+                    font,
+                }),
+            }
+        }
+
+        runs
+    }
+}
+
+/// characters that modify the previous character instead of standing on their own.
+fn is_cluster_extender(ch: char) -> bool {
+    match ch as u32 {
+        0x0300..=0x036F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x20D0..=0x20FF
+        | 0xFE20..=0xFE2F
+        | 0x1F3FB..=0x1F3FF => true,
+        _ => is_joiner(ch),
+    }
+}
+
+/// invisible characters that join or select the presentation of the surrounding characters.
+fn is_joiner(ch: char) -> bool {
+    match ch as u32 {
+        0x200C | 0x200D | 0xFE00..=0xFE0F | 0xE0020..=0xE007F => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// a collection with a font bundled with the test fixtures.
+    fn fonts(family: &str) -> FontCollection {
+        let mut fonts = FontCollection::new(1.0).unwrap();
+        fonts
+            .add_font_by_name(family)
+            .expect("failed to load the font of the test fixtures");
+        fonts
+    }
+
+    #[test]
+    fn clusters_are_byte_offsets() {
+        let mut fonts = fonts("tests/fixtures/fonts");
+        let glyphs = Shaper::new(true).shape(&mut fonts, "aé b", 0, 10.0, 5.0);
+        let clusters: Vec<usize> = glyphs.iter().map(|glyph| glyph.cluster).collect();
+        assert_eq!(clusters, vec![0, 1, 3, 4]);
+        assert!(glyphs.iter().all(|glyph| glyph.font == Some(0)));
+        // the glyphs follow each other.
+        for pair in glyphs.windows(2) {
+            assert!((pair[0].x + pair[0].advance - pair[1].x).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn combining_marks_stay_in_cluster() {
+        let mut fonts = fonts("tests/fixtures/fonts");
+        let glyphs = Shaper::new(true).shape(&mut fonts, "e\u{301}x", 0, 10.0, 5.0);
+        assert!(glyphs.iter().all(|glyph| glyph.font == Some(0)));
+        assert_eq!(glyphs.last().unwrap().cluster, 3);
+        assert!(glyphs[..glyphs.len() - 1]
+            .iter()
+            .all(|glyph| glyph.cluster == 0));
+    }
+
+    #[test]
+    fn unsupported_characters_use_missing_advance() {
+        let mut fonts = fonts("tests/fixtures/fonts");
+        let glyphs = Shaper::new(true).shape(&mut fonts, "a\u{10FFFD}b", 0, 10.0, 7.0);
+        assert_eq!(glyphs.len(), 3);
+        assert_eq!(glyphs[1].font, None);
+        assert_eq!(glyphs[1].cluster, 1);
+        assert_eq!(glyphs[1].advance, 7.0);
+        assert_eq!(glyphs[2].cluster, 5);
+        assert!((glyphs[2].x - glyphs[1].x - 7.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn ligatures_can_be_turned_off() {
+        // DejaVu Sans joins `ffi` into a single glyph.
+        let mut fonts = fonts("tests/fixtures/fonts/sans/DejaVuSans.ttf");
+        let clusters = |glyphs: Vec<ShapedGlyph>| -> Vec<usize> {
+            glyphs.iter().map(|glyph| glyph.cluster).collect()
+        };
+
+        let glyphs = Shaper::new(true).shape(&mut fonts, "ffi x", 0, 10.0, 5.0);
+        assert_eq!(glyphs.len(), 3);
+        assert_eq!(clusters(glyphs), vec![0, 3, 4]);
+
+        let glyphs = Shaper::new(false).shape(&mut fonts, "ffi x", 0, 10.0, 5.0);
+        assert_eq!(glyphs.len(), 5);
+        assert_eq!(clusters(glyphs), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn extenders_and_joiners() {
+        assert!(is_cluster_extender('\u{301}'));
+        assert!(is_cluster_extender('\u{1F3FB}'));
+        assert!(is_cluster_extender('\u{200D}'));
+        assert!(!is_cluster_extender('a'));
+        assert!(is_joiner('\u{FE0F}'));
+        assert!(!is_joiner('\u{301}'));
+    }
+}
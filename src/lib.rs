@@ -5,6 +5,8 @@ pub fn ptr<T>(val: T) -> Ptr<T> {
     Ptr::new(RefCell::new(val))
 }
 
+pub mod lsp;
+
 pub mod comm {
     pub use std::sync::mpsc::{self, Receiver, Sender};
 
@@ -62,9 +64,36 @@ pub mod utils {
         "./logs/kea.log".to_string()
     }
 
+    /// the directory swap files of modified buffers are written to.
+    pub fn swap_directory_path() -> String {
+        state_directory()
+            .join("swap")
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// the open files and frames are saved here when the editor exits.
+    pub fn session_file_path() -> String {
+        state_directory()
+            .join("session.yml")
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// $XDG_STATE_HOME/kea, or ~/.local/state/kea when it isn't set. Without a home directory
+    /// the working directory is used.
+    fn state_directory() -> std::path::PathBuf {
+        let state = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => Some(std::path::PathBuf::from(dir)),
+            _ => std::env::var_os("HOME")
+                .map(|home| std::path::PathBuf::from(home).join(".local").join("state")),
+        };
+        state.map_or_else(|| std::path::PathBuf::from("."), |dir| dir.join("kea"))
+    }
+
     pub fn spawn_thread<F, T>(name: &str, f: F) -> std::thread::JoinHandle<T>
     where
-        F: Fn() -> T,
+        F: FnOnce() -> T,
         F: Send + 'static,
         T: Send + 'static,
     {
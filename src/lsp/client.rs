@@ -0,0 +1,596 @@
+// Language Server Client.
+// Runs one language server. A writer thread sends the queued messages, it holds them back until
+// the server answered the initialize request. A reader thread handles what the server sends:
+// answers to requests are converted and reported with the id of their request, notifications
+// such as diagnostics are reported as they arrive, requests of the server are answered with null.
+
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, error};
+use lsp_types as lsp;
+use serde_json::{json, Value};
+
+use super::rpc;
+use super::{Completion, Diagnostic, Location, LspError, LspEvent, Position, Response, Severity};
+use super::{TextChange, TextEdit, TextRange};
+
+/// the ids of requests are unique among all servers so an answer identifies its request.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+const INITIALIZE_ID: u64 = 0;
+/// how long a server is given to answer the shutdown request before it's told to exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub type EventCallback = Arc<dyn Fn(LspEvent) + Send + Sync>;
+
+/// what is expected in the answer to a request.
+#[derive(Debug, Clone)]
+enum Pending {
+    Hover,
+    Locations,
+    /// text edits of the document that was formatted.
+    TextEdits(PathBuf),
+    WorkspaceEdit,
+    Completions,
+    /// the answer is ignored.
+    Ignored,
+    /// the server is ready to exit once it answered.
+    Shutdown(Sender<()>),
+}
+
+type PendingRequests = Arc<Mutex<HashMap<u64, Pending>>>;
+
+pub struct LspClient {
+    name: String,
+    sender: Sender<Value>,
+    pending: PendingRequests,
+    /// how the server wants changes of documents, None until the server is initialized.
+    sync: Arc<Mutex<Option<lsp::TextDocumentSyncKind>>>,
+    /// the version of each open document.
+    versions: HashMap<PathBuf, i64>,
+}
+
+impl LspClient {
+    /// starts the server in root, the directory of the project.
+    pub fn start(
+        name: &str,
+        command: &str,
+        args: &[String],
+        root: &Path,
+        on_event: EventCallback,
+    ) -> Result<Self, LspError> {
+        let mut child = Command::new(command)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| LspError::SpawnFailed(name.to_string(), e.to_string()))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, receiver) = mpsc::channel::<Value>();
+        let (ready, initialized) = mpsc::channel::<()>();
+        let initialize = rpc::request(INITIALIZE_ID, "initialize", initialize_params(root));
+        let server = name.to_string();
+        crate::utils::spawn_thread(&format!("{} writer", name), move || {
+            let mut writer = BufWriter::new(stdin);
+            let write = |writer: &mut BufWriter<_>, message: &Value| {
+                rpc::write_message(writer, message)
+                    .map_err(|e| error!("Failed to write to '{}': {}", server, e))
+            };
+            if write(&mut writer, &initialize).is_err() || initialized.recv().is_err() {
+                return;
+            }
+            let message = rpc::notification("initialized", json!({}));
+            if write(&mut writer, &message).is_err() {
+                return;
+            }
+            // the thread ends when the client is dropped and the channel disconnects.
+            for message in receiver.iter() {
+                if write(&mut writer, &message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let pending = PendingRequests::default();
+        let sync = Arc::new(Mutex::new(None));
+        let mut reader = Reader {
+            name: name.to_string(),
+            sender: sender.clone(),
+            pending: Arc::clone(&pending),
+            sync: Arc::clone(&sync),
+            ready: Some(ready),
+            on_event: Arc::clone(&on_event),
+        };
+        crate::utils::spawn_thread(&format!("{} reader", name), move || {
+            let mut stdout = BufReader::new(stdout);
+            loop {
+                match rpc::read_message(&mut stdout) {
+                    Ok(Some(message)) => reader.handle(message),
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("Invalid message from '{}': {}", reader.name, e);
+                        break;
+                    }
+                }
+            }
+            child.wait().ok();
+            (reader.on_event)(LspEvent::Exited(reader.name.clone()));
+        });
+
+        Ok(Self {
+            name: name.to_string(),
+            sender,
+            pending,
+            sync,
+            versions: HashMap::new(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.versions.contains_key(path)
+    }
+
+    pub fn did_open(&mut self, path: &Path, language_id: &str, text: String) {
+        self.versions.insert(path.to_path_buf(), 0);
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri(path),
+                    "languageId": language_id,
+                    "version": 0,
+                    "text": text,
+                }
+            }),
+        );
+    }
+
+    /// sends the changes of a document. A server that wants the whole document, or one that
+    /// isn't initialized yet, is sent the text instead.
+    pub fn did_change<F>(&mut self, path: &Path, changes: Vec<TextChange>, text: F)
+    where
+        F: FnOnce() -> String,
+    {
+        let version = match self.versions.get_mut(path) {
+            Some(version) => {
+                *version += 1;
+                *version
+            }
+            None => return,
+        };
+
+        let incremental =
+            *self.sync.lock().unwrap() == Some(lsp::TextDocumentSyncKind::Incremental);
+        let changes: Vec<Value> = if incremental {
+            changes
+                .into_iter()
+                .map(|change| match change.range {
+                    Some(range) => json!({ "range": range_value(range), "text": change.text }),
+                    None => json!({ "text": change.text }),
+                })
+                .collect()
+        } else {
+            vec![json!({ "text": text() })]
+        };
+
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri(path), "version": version },
+                "contentChanges": changes,
+            }),
+        );
+    }
+
+    pub fn did_save(&mut self, path: &Path) {
+        if self.is_open(path) {
+            let params = json!({ "textDocument": { "uri": uri(path) } });
+            self.notify("textDocument/didSave", params);
+        }
+    }
+
+    pub fn did_close(&mut self, path: &Path) {
+        if self.versions.remove(path).is_some() {
+            let params = json!({ "textDocument": { "uri": uri(path) } });
+            self.notify("textDocument/didClose", params);
+        }
+    }
+
+    /// requests the documentation of the symbol at a position, returns the id of the request.
+    pub fn hover(&mut self, path: &Path, position: Position) -> u64 {
+        let params = position_params(path, position);
+        self.request("textDocument/hover", params, Pending::Hover)
+    }
+
+    pub fn definition(&mut self, path: &Path, position: Position) -> u64 {
+        let params = position_params(path, position);
+        self.request("textDocument/definition", params, Pending::Locations)
+    }
+
+    pub fn references(&mut self, path: &Path, position: Position) -> u64 {
+        let mut params = position_params(path, position);
+        params["context"] = json!({ "includeDeclaration": true });
+        self.request("textDocument/references", params, Pending::Locations)
+    }
+
+    pub fn rename(&mut self, path: &Path, position: Position, new_name: &str) -> u64 {
+        let mut params = position_params(path, position);
+        params["newName"] = json!(new_name);
+        self.request("textDocument/rename", params, Pending::WorkspaceEdit)
+    }
+
+    pub fn formatting(&mut self, path: &Path, tab_size: usize, insert_spaces: bool) -> u64 {
+        let params = json!({
+            "textDocument": { "uri": uri(path) },
+            "options": { "tabSize": tab_size, "insertSpaces": insert_spaces },
+        });
+        let pending = Pending::TextEdits(path.to_path_buf());
+        self.request("textDocument/formatting", params, pending)
+    }
+
+    pub fn completion(&mut self, path: &Path, position: Position) -> u64 {
+        let params = position_params(path, position);
+        self.request("textDocument/completion", params, Pending::Completions)
+    }
+
+    /// tells the server a request is no longer needed, its answer isn't reported.
+    pub fn cancel(&mut self, id: u64) {
+        if self.pending.lock().unwrap().remove(&id).is_some() {
+            self.notify("$/cancelRequest", json!({ "id": id }));
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value, pending: Pending) -> u64 {
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        self.pending.lock().unwrap().insert(id, pending);
+        self.sender.send(rpc::request(id, method, params)).ok();
+        id
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.sender.send(rpc::notification(method, params)).ok();
+    }
+}
+
+impl Drop for LspClient {
+    /// asks the server to shut down and to exit once it answered, the reader thread waits for
+    /// it to exit. A server that doesn't answer in time is told to exit anyway.
+    fn drop(&mut self) {
+        let (done, answered) = mpsc::channel();
+        self.request("shutdown", Value::Null, Pending::Shutdown(done));
+        answered.recv_timeout(SHUTDOWN_TIMEOUT).ok();
+        self.notify("exit", Value::Null);
+    }
+}
+
+/// handles the messages of a server on the reader thread.
+struct Reader {
+    name: String,
+    /// answers requests of the server.
+    sender: Sender<Value>,
+    pending: PendingRequests,
+    sync: Arc<Mutex<Option<lsp::TextDocumentSyncKind>>>,
+    /// lets the writer continue once the server is initialized.
+    ready: Option<Sender<()>>,
+    on_event: EventCallback,
+}
+
+impl Reader {
+    fn handle(&mut self, message: Value) {
+        let method = message.get("method").and_then(Value::as_str);
+        match (message.get("id"), method) {
+            (Some(id), Some(method)) => self.answer(id.clone(), method, &message["params"]),
+            (None, Some(method)) => self.notification(method, &message["params"]),
+            (Some(id), None) => match id.as_u64() {
+                Some(INITIALIZE_ID) => self.initialized(&message),
+                Some(id) => self.response(id, &message),
+                None => {}
+            },
+            (None, None) => {}
+        }
+    }
+
+    /// requests of the server aren't supported, they are answered so the server doesn't wait.
+    fn answer(&self, id: Value, method: &str, params: &Value) {
+        debug!("'{}' requested {}", self.name, method);
+        let result = match method {
+            // a setting for each requested item.
+            "workspace/configuration" => {
+                let items = params["items"].as_array().map_or(0, Vec::len);
+                Value::Array(vec![Value::Null; items])
+            }
+            _ => Value::Null,
+        };
+        self.sender.send(rpc::response(id, result)).ok();
+    }
+
+    fn notification(&self, method: &str, params: &Value) {
+        match method {
+            "textDocument/publishDiagnostics" => {
+                let params: lsp::PublishDiagnosticsParams =
+                    match serde_json::from_value(params.clone()) {
+                        Ok(params) => params,
+                        Err(e) => return error!("Invalid diagnostics from '{}': {}", self.name, e),
+                    };
+                if let Ok(path) = params.uri.to_file_path() {
+                    let diagnostics = params.diagnostics.into_iter().map(diagnostic).collect();
+                    (self.on_event)(LspEvent::Diagnostics { path, diagnostics });
+                }
+            }
+            "window/showMessage" => {
+                if let Some(message) = params["message"].as_str() {
+                    (self.on_event)(LspEvent::Message(format!("{}: {}", self.name, message)));
+                }
+            }
+            "window/logMessage" => debug!("{}: {}", self.name, params["message"]),
+            _ => {}
+        }
+    }
+
+    fn initialized(&mut self, message: &Value) {
+        if let Some(error) = message.get("error") {
+            let text = format!("{} failed to initialize: {}", self.name, error["message"]);
+            (self.on_event)(LspEvent::Message(text));
+            // the writer stops, the server can't be used.
+            self.ready = None;
+            return;
+        }
+
+        let capabilities = &message["result"]["capabilities"];
+        let sync = serde_json::from_value(capabilities["textDocumentSync"].clone())
+            .ok()
+            .map(|sync| match sync {
+                lsp::TextDocumentSyncCapability::Kind(kind) => kind,
+                lsp::TextDocumentSyncCapability::Options(options) => {
+                    options.change.unwrap_or(lsp::TextDocumentSyncKind::None)
+                }
+            });
+        *self.sync.lock().unwrap() = Some(sync.unwrap_or(lsp::TextDocumentSyncKind::Full));
+        if let Some(ready) = self.ready.take() {
+            ready.send(()).ok();
+        }
+    }
+
+    fn response(&self, id: u64, message: &Value) {
+        let pending = match self.pending.lock().unwrap().remove(&id) {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        match pending {
+            Pending::Ignored => return,
+            Pending::Shutdown(done) => {
+                done.send(()).ok();
+                return;
+            }
+            _ => {}
+        }
+
+        let response = match message.get("error") {
+            Some(error) => Response::Failed(format!("{}: {}", self.name, error["message"])),
+            None => match convert(&pending, message["result"].clone()) {
+                Ok(response) => response,
+                Err(e) => Response::Failed(format!("invalid answer from {}: {}", self.name, e)),
+            },
+        };
+        (self.on_event)(LspEvent::Response { id, response });
+    }
+}
+
+fn initialize_params(root: &Path) -> Value {
+    json!({
+        "processId": std::process::id(),
+        "rootUri": uri(root),
+        "clientInfo": { "name": "kea" },
+        "capabilities": {
+            "textDocument": {
+                "synchronization": { "didSave": true },
+                "hover": { "contentFormat": ["plaintext"] },
+                "publishDiagnostics": {},
+                "definition": {},
+                "references": {},
+                "rename": {},
+                "formatting": {},
+                "completion": { "completionItem": { "snippetSupport": false } },
+            },
+            "workspace": {
+                "workspaceEdit": { "documentChanges": true },
+                "configuration": true,
+            },
+        },
+    })
+}
+
+/// converts the result of a request to what the request expects.
+fn convert(pending: &Pending, result: Value) -> Result<Response, serde_json::Error> {
+    let response = match pending {
+        Pending::Hover => {
+            let hover: Option<lsp::Hover> = serde_json::from_value(result)?;
+            Response::Hover(hover.map(|hover| hover_text(hover.contents)))
+        }
+        Pending::Locations => {
+            let locations = match serde_json::from_value(result)? {
+                Some(lsp::GotoDefinitionResponse::Scalar(location)) => vec![location],
+                Some(lsp::GotoDefinitionResponse::Array(locations)) => locations,
+                Some(lsp::GotoDefinitionResponse::Link(links)) => links
+                    .into_iter()
+                    .map(|link| lsp::Location::new(link.target_uri, link.target_selection_range))
+                    .collect(),
+                None => Vec::new(),
+            };
+            let locations = locations
+                .into_iter()
+                .filter_map(|location| {
+                    let path = location.uri.to_file_path().ok()?;
+                    let range = range(location.range);
+                    Some(Location { path, range })
+                })
+                .collect();
+            Response::Locations(locations)
+        }
+        Pending::TextEdits(path) => {
+            let edits: Option<Vec<lsp::TextEdit>> = serde_json::from_value(result)?;
+            let edits = edits
+                .unwrap_or_default()
+                .into_iter()
+                .map(text_edit)
+                .collect();
+            Response::Edits(vec![(path.clone(), edits)])
+        }
+        Pending::WorkspaceEdit => {
+            let edit: Option<lsp::WorkspaceEdit> = serde_json::from_value(result)?;
+            Response::Edits(edit.map_or_else(Vec::new, workspace_edits))
+        }
+        Pending::Completions => {
+            let items = match serde_json::from_value(result)? {
+                Some(lsp::CompletionResponse::Array(items)) => items,
+                Some(lsp::CompletionResponse::List(list)) => list.items,
+                None => Vec::new(),
+            };
+            Response::Completions(items.into_iter().map(completion).collect())
+        }
+        Pending::Ignored | Pending::Shutdown(_) => Response::Hover(None),
+    };
+    Ok(response)
+}
+
+fn workspace_edits(edit: lsp::WorkspaceEdit) -> Vec<(PathBuf, Vec<TextEdit>)> {
+    let mut files: Vec<(lsp::Url, Vec<lsp::TextEdit>)> =
+        edit.changes.unwrap_or_default().into_iter().collect();
+
+    let document_edits = match edit.document_changes {
+        Some(lsp::DocumentChanges::Edits(edits)) => edits,
+        // creating, renaming and deleting files isn't supported.
+        Some(lsp::DocumentChanges::Operations(operations)) => operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                lsp::DocumentChangeOperation::Edit(edit) => Some(edit),
+                lsp::DocumentChangeOperation::Op(_) => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    for edit in document_edits {
+        let edits = edit
+            .edits
+            .into_iter()
+            .map(|edit| match edit {
+                lsp::OneOf::Left(edit) => edit,
+                lsp::OneOf::Right(annotated) => annotated.text_edit,
+            })
+            .collect();
+        files.push((edit.text_document.uri, edits));
+    }
+
+    files
+        .into_iter()
+        .filter_map(|(uri, edits)| {
+            let path = uri.to_file_path().ok()?;
+            Some((path, edits.into_iter().map(text_edit).collect()))
+        })
+        .collect()
+}
+
+fn hover_text(contents: lsp::HoverContents) -> String {
+    let marked = |marked: lsp::MarkedString| match marked {
+        lsp::MarkedString::String(text) => text,
+        lsp::MarkedString::LanguageString(code) => code.value,
+    };
+    match contents {
+        lsp::HoverContents::Scalar(text) => marked(text),
+        lsp::HoverContents::Array(texts) => texts
+            .into_iter()
+            .map(marked)
+            .collect::<Vec<String>>()
+            .join("\n"),
+        lsp::HoverContents::Markup(content) => content.value,
+    }
+}
+
+fn completion(item: lsp::CompletionItem) -> Completion {
+    let (text, replaced) = match item.text_edit {
+        Some(lsp::CompletionTextEdit::Edit(edit)) => (edit.new_text, Some(range(edit.range))),
+        // the text is inserted, the rest of the word after the cursor is kept.
+        Some(lsp::CompletionTextEdit::InsertAndReplace(edit)) => {
+            (edit.new_text, Some(range(edit.insert)))
+        }
+        None => match item.insert_text {
+            Some(text) => (text, None),
+            None => (item.label.clone(), None),
+        },
+    };
+    Completion {
+        label: item.label,
+        text,
+        range: replaced,
+        detail: item.detail,
+    }
+}
+
+fn diagnostic(diagnostic: lsp::Diagnostic) -> Diagnostic {
+    let severity = match diagnostic.severity {
+        Some(lsp::DiagnosticSeverity::Warning) => Severity::Warning,
+        Some(lsp::DiagnosticSeverity::Information) => Severity::Information,
+        Some(lsp::DiagnosticSeverity::Hint) => Severity::Hint,
+        // diagnostics without a severity are treated as errors.
+        _ => Severity::Error,
+    };
+    Diagnostic {
+        range: range(diagnostic.range),
+        severity,
+        message: diagnostic.message,
+        source: diagnostic.source,
+    }
+}
+
+fn text_edit(edit: lsp::TextEdit) -> TextEdit {
+    TextEdit {
+        range: range(edit.range),
+        text: edit.new_text,
+    }
+}
+
+fn range(range: lsp::Range) -> TextRange {
+    let position = |position: lsp::Position| {
+        Position::new(position.line as usize, position.character as usize)
+    };
+    TextRange::new(position(range.start), position(range.end))
+}
+
+fn range_value(range: TextRange) -> Value {
+    let position =
+        |position: Position| json!({ "line": position.line, "character": position.character });
+    json!({ "start": position(range.start), "end": position(range.end) })
+}
+
+fn position_params(path: &Path, position: Position) -> Value {
+    json!({
+        "textDocument": { "uri": uri(path) },
+        "position": { "line": position.line, "character": position.character },
+    })
+}
+
+/// the file uri of a path, relative paths are relative to the working directory.
+fn uri(path: &Path) -> String {
+    let path = match std::env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    };
+    lsp::Url::from_file_path(&path)
+        .map(|uri| uri.to_string())
+        .unwrap_or_else(|_| format!("file://{}", path.display()))
+}
@@ -0,0 +1,127 @@
+// Language Server Protocol.
+// A client for language servers, the programs that provide diagnostics, hover information, go to
+// definition and the like for a language. A server is a process speaking JSON-RPC over its stdin
+// and stdout. Messages are written and read on background threads, what the server sends is
+// reported through a callback so the editor never waits for a server.
+//
+// Positions are zero based lines and utf-16 columns, the way the protocol counts them.
+
+mod client;
+pub mod rpc;
+
+use std::path::PathBuf;
+
+pub use client::{EventCallback, LspClient};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum LspError {
+    #[error("failed to start language server '{0}': {1}")]
+    SpawnFailed(String, String),
+    #[error("language server '{0}' is not running")]
+    NotRunning(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Position {
+    pub line: usize,
+    /// the column in utf-16 code units.
+    pub character: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, character: usize) -> Self {
+        Self { line, character }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TextRange {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl TextRange {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+}
+
+/// an edit of a document, changes are applied in order. A change without a range replaces the
+/// whole document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChange {
+    pub range: Option<TextRange>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: TextRange,
+    pub severity: Severity,
+    pub message: String,
+    /// what reported the diagnostic, e.g. `rustc` or `clippy`.
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub range: TextRange,
+}
+
+/// replaces a range of a document with text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: TextRange,
+    pub text: String,
+}
+
+/// a completion of the word at a position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    /// the text that replaces the word.
+    pub text: String,
+    /// the range the text replaces instead of the word before the cursor, when the server
+    /// gave one.
+    pub range: Option<TextRange>,
+    /// e.g. the type of a function.
+    pub detail: Option<String>,
+}
+
+/// The answer of a server to a request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// the documentation of the symbol at a position, None if there is none.
+    Hover(Option<String>),
+    /// the definition or the references of a symbol.
+    Locations(Vec<Location>),
+    /// the edits of a rename or a formatting, by file.
+    Edits(Vec<(PathBuf, Vec<TextEdit>)>),
+    Completions(Vec<Completion>),
+    /// the server couldn't answer the request.
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspEvent {
+    /// the diagnostics of a file replace its previous diagnostics.
+    Diagnostics {
+        path: PathBuf,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// the answer to the request with id.
+    Response { id: u64, response: Response },
+    /// a message the server wants the user to see.
+    Message(String),
+    /// the server with this name exited.
+    Exited(String),
+}
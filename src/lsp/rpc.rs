@@ -0,0 +1,83 @@
+// JSON-RPC.
+// Messages are framed with a header of `Content-Length: <bytes>` lines, an empty line, then the
+// json of the message. Other headers, such as `Content-Type`, are ignored.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+pub fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// the answer to a request of the server.
+pub fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        writer,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    writer.flush()
+}
+
+/// reads the next message, None when the stream ended.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim();
+        if header.is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        if name.eq_ignore_ascii_case("content-length") {
+            length = parts
+                .next()
+                .and_then(|value| value.trim().parse::<usize>().ok());
+        }
+    }
+
+    let length = length.ok_or_else(|| invalid("missing Content-Length header"))?;
+    let mut content = vec![0; length];
+    reader.read_exact(&mut content)?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| invalid(&e.to_string()))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn framing() {
+        let mut stream = Vec::new();
+        write_message(&mut stream, &request(1, "initialize", json!({}))).unwrap();
+        stream.extend_from_slice(b"content-length: 2\r\nContent-Type: utf-8\r\n\r\n{}");
+
+        let mut reader = io::Cursor::new(stream);
+        let message = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message["method"], "initialize");
+        assert_eq!(read_message(&mut reader).unwrap(), Some(json!({})));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
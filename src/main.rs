@@ -14,14 +14,93 @@ mod app;
 mod core;
 mod font;
 mod renderer;
+mod terminal;
 mod ui;
 
 use log::{debug, error, info, trace, warn};
 use renderer::{window::LogicalSize, Window};
+use std::path::PathBuf;
+
+/// the command line: `kea [--config <path>] [--headless [script]]`.
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    config_file: Option<PathBuf>,
+    /// run without a window, the commands are read from the script or stdin.
+    headless: bool,
+    script: Option<PathBuf>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Args, String> {
+    let mut args = args.peekable();
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => {
+                parsed.headless = true;
+                // the script is optional, the next option isn't taken for it.
+                parsed.script = args
+                    .next_if(|arg| !arg.starts_with("--"))
+                    .map(PathBuf::from);
+            }
+            "--config" => match args.next() {
+                Some(path) => parsed.config_file = Some(PathBuf::from(path)),
+                None => return Err("--config requires a path".to_string()),
+            },
+            arg => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    Ok(parsed)
+}
 
 fn main() {
-    let config = app::Config::load_config();
+    let args = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let (config, errors) = app::Config::load_config(args.config_file.clone());
+    if args.headless {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        if let Err(e) = app::run_headless(args.script, &config) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     app::setup_logger(&config).unwrap();
+    for e in errors {
+        error!("{}", e);
+    }
     info!("Loaded Config: {:#?}", config);
-    app::run(config).unwrap();
+    app::run(config, args.config_file).unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn command_line() {
+        let script = parse(&["--headless", "edit.txt", "--config", "kea.yml"]).unwrap();
+        assert!(script.headless);
+        assert_eq!(script.script, Some(PathBuf::from("edit.txt")));
+        assert_eq!(script.config_file, Some(PathBuf::from("kea.yml")));
+
+        // without a script the commands are read from stdin.
+        let stdin = parse(&["--headless", "--config", "kea.yml"]).unwrap();
+        assert!(stdin.headless);
+        assert_eq!(stdin.script, None);
+        assert_eq!(stdin.config_file, Some(PathBuf::from("kea.yml")));
+
+        assert_eq!(parse(&[]).unwrap(), Args::default());
+        assert!(parse(&["--config"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
 }
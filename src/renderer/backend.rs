@@ -0,0 +1,135 @@
+// Render Backend.
+// The renderer batches rects and glyphs, the backend is what actually draws them. This
+// allows the renderer to run without a gpu (see software.rs).
+
+use super::{Color, RectVertex, RenderError, TextVertex};
+
+/// handle of a texture created by a backend.
+pub type TextureId = u32;
+
+/// (width, height) of a texture in pixels.
+pub type TextureSize = (i32, i32);
+
+/// An 8 bit per channel rgba image, rows are stored top to bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Option<Self> {
+        if pixels.len() == width as usize * height as usize * 4 {
+            Some(Self {
+                width,
+                height,
+                pixels,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        self.pixels.as_slice()
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let idx = self.index(x, y);
+        [
+            self.pixels[idx],
+            self.pixels[idx + 1],
+            self.pixels[idx + 2],
+            self.pixels[idx + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        let idx = self.index(x, y);
+        self.pixels[idx..idx + 4].copy_from_slice(&pixel);
+    }
+
+    pub fn fill(&mut self, color: Color) {
+        let pixel = to_pixel(color);
+        for chunk in self.pixels.chunks_mut(4) {
+            chunk.copy_from_slice(&pixel);
+        }
+    }
+
+    #[inline]
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+}
+
+pub(crate) fn to_pixel(color: Color) -> [u8; 4] {
+    [
+        to_channel(color.r),
+        to_channel(color.g),
+        to_channel(color.b),
+        to_channel(color.a),
+    ]
+}
+
+#[inline]
+pub(crate) fn to_channel(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+pub trait RenderBackend {
+    fn init(&mut self) -> Result<(), RenderError>;
+
+    /// the size of the render target changed.
+    fn resize(&mut self, width: i32, height: i32);
+
+    fn clear(&mut self, color: Color);
+
+    fn draw_rects(&mut self, rects: &[RectVertex]);
+
+    /// draws glyphs from the atlas textures, the texture_id of each vertex is an index into textures.
+    fn draw_text(&mut self, glyphs: &[TextVertex], textures: &[TextureId]);
+
+    /// creates an empty rgb texture.
+    fn create_texture(&mut self, size: TextureSize) -> TextureId;
+
+    /// creates a larger texture containing the contents of texture, the old texture is deleted.
+    fn grow_texture(
+        &mut self,
+        texture: TextureId,
+        old_size: TextureSize,
+        new_size: TextureSize,
+    ) -> TextureId;
+
+    /// copies rgb data into a region of the texture.
+    fn upload_texture(
+        &mut self,
+        texture: TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        data: &[u8],
+    );
+
+    fn delete_texture(&mut self, texture: TextureId);
+
+    /// copies the contents of the render target into an image.
+    fn read_pixels(&mut self) -> RgbaImage;
+}
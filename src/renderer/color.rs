@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(C)]
 pub struct Color {
     pub r: f32,
@@ -51,4 +51,40 @@ impl Color {
     pub fn uniform(ch: f32) -> Color {
         Color::rgb(ch, ch, ch)
     }
+
+    /// parses a color written as `#rrggbb` or `#rrggbbaa`.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let hex = hex.strip_prefix('#')?;
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+            return None;
+        }
+
+        let channel = |idx: usize| {
+            u8::from_str_radix(&hex[idx..idx + 2], 16)
+                .ok()
+                .map(|ch| ch as f32 / 255.0)
+        };
+        let alpha = if hex.len() == 8 { channel(6)? } else { 1.0 };
+        Some(Color::rgba(channel(0)?, channel(2)?, channel(4)?, alpha))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_hex() {
+        assert_eq!(Color::from_hex("#ff0000"), Some(Color::red()));
+        assert_eq!(
+            Color::from_hex("#00000080"),
+            Some(Color::rgba(0.0, 0.0, 0.0, 128.0 / 255.0))
+        );
+        assert_eq!(Color::from_hex("#FFFFFF"), Some(Color::white()));
+
+        assert_eq!(Color::from_hex("ff0000"), None);
+        assert_eq!(Color::from_hex("#fff"), None);
+        assert_eq!(Color::from_hex("#gg0000"), None);
+        assert_eq!(Color::from_hex("#ff00é"), None);
+    }
 }
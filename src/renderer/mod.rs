@@ -1,19 +1,23 @@
 #![allow(dead_code)]
 
 use kea::comm::Receiver;
+pub mod backend;
 mod color;
 pub mod platform;
 mod rect;
 mod renderer;
+pub mod software;
 pub mod style;
+mod theme;
 pub mod window;
 
 use crate::gl::{self, types::*};
 pub use color::Color;
 use log::{debug, error, info};
 pub use rect::Rect;
-pub use renderer::{RenderContext, Renderer};
+pub use renderer::{RectVertex, RenderContext, Renderer, TextVertex, DEFAULT_TAB_WIDTH};
 use std::sync::{Arc, Mutex, MutexGuard, Weak};
+pub use theme::Theme;
 pub use window::Window;
 // use euclid::default::Transform3D;
 use crate::font::{Font, FontCollection};
@@ -54,19 +58,97 @@ pub enum RenderError {
 
 #[derive(Debug, Clone, Copy)]
 pub struct Glyph {
-    pub ch: char,
+    /// index of the glyph in its font.
+    pub id: u32,
+    /// byte offset of the first character of the cluster this glyph was shaped from.
+    pub cluster: usize,
     pub x: f32,
+    /// offset from the baseline.
+    pub y: f32,
+    pub advance: f32,
+    /// index of the font used to render this glyph, None if no font supports it.
+    pub font: Option<usize>,
+    /// the style the glyph is rendered with.
+    pub style: style::StyleId,
 }
 
 #[derive(Debug, Clone)]
 pub struct TextLine {
     glyphs: Vec<Glyph>,
     styles: Vec<style::StyleSpan>,
+    /// x position of the caret before each character, plus one past the end of the line.
+    carets: Vec<f32>,
 }
 
 impl TextLine {
-    pub fn new(glyphs: Vec<Glyph>, styles: Vec<style::StyleSpan>) -> Self {
-        Self { glyphs, styles }
+    pub fn new(glyphs: Vec<Glyph>, styles: Vec<style::StyleSpan>, carets: Vec<f32>) -> Self {
+        Self {
+            glyphs,
+            styles,
+            carets,
+        }
+    }
+
+    pub fn glyphs(&self) -> &[Glyph] {
+        self.glyphs.as_slice()
+    }
+
+    /// the x position of the caret before the character at column. Columns past the
+    /// end of the line are placed at the end of the line.
+    pub fn caret(&self, column: usize) -> f32 {
+        self.carets
+            .get(column)
+            .or_else(|| self.carets.last())
+            .cloned()
+            .unwrap_or(0.0)
+    }
+
+    /// the number of characters of the line.
+    pub fn columns(&self) -> usize {
+        self.carets.len().saturating_sub(1)
+    }
+
+    /// the column of the caret closest to x.
+    pub fn column_at(&self, x: f32) -> usize {
+        self.carets
+            .windows(2)
+            .position(|carets| x < (carets[0] + carets[1]) / 2.0)
+            .unwrap_or_else(|| self.carets.len().saturating_sub(1))
+    }
+
+    /// splits the line into rows no wider than width, each row is returned with the column
+    /// it starts at. A row has at least one character so a narrow width still makes progress.
+    pub fn wrap(&self, width: f32) -> Vec<(usize, TextLine)> {
+        let columns = self.carets.len().saturating_sub(1);
+        let mut starts = vec![0];
+        for column in 1..columns {
+            let row_x = self.carets[*starts.last().unwrap()];
+            if self.carets[column + 1] - row_x > width {
+                starts.push(column);
+            }
+        }
+
+        let mut rows = Vec::with_capacity(starts.len());
+        for (idx, &start) in starts.iter().enumerate() {
+            let end = starts.get(idx + 1).copied();
+            let start_x = self.carets[start];
+            let end_x = end.map_or(f32::INFINITY, |end| self.carets[end]);
+            let glyphs = self
+                .glyphs
+                .iter()
+                .filter(|glyph| glyph.x >= start_x && glyph.x < end_x)
+                .map(|glyph| Glyph {
+                    x: glyph.x - start_x,
+                    ..*glyph
+                })
+                .collect();
+            let carets = self.carets[start..=end.unwrap_or(columns)]
+                .iter()
+                .map(|caret| caret - start_x)
+                .collect();
+            rows.push((start, TextLine::new(glyphs, self.styles.clone(), carets)));
+        }
+        rows
     }
 }
 
@@ -119,3 +201,44 @@ impl Renderer {
     //     })
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// a line of characters 10 wide, each with a glyph.
+    fn line(columns: usize) -> TextLine {
+        let glyphs = (0..columns)
+            .map(|column| Glyph {
+                id: 0,
+                cluster: column,
+                x: column as f32 * 10.0,
+                y: 0.0,
+                advance: 10.0,
+                font: Some(0),
+                style: style::StyleId(0),
+            })
+            .collect();
+        let carets = (0..=columns).map(|column| column as f32 * 10.0).collect();
+        TextLine::new(glyphs, Vec::new(), carets)
+    }
+
+    #[test]
+    fn wrap_rows() {
+        let rows = line(10).wrap(40.0);
+        let starts: Vec<usize> = rows.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, vec![0, 4, 8]);
+
+        let (_, row) = &rows[1];
+        assert_eq!(row.glyphs().len(), 4);
+        assert_eq!(row.glyphs()[0].cluster, 4);
+        assert_eq!(row.glyphs()[0].x, 0.0);
+        assert_eq!(row.caret(4), 40.0);
+        assert_eq!(rows[2].1.glyphs().len(), 2);
+
+        // a line that fits, an empty line and a row narrower than a character.
+        assert_eq!(line(4).wrap(40.0).len(), 1);
+        assert_eq!(line(0).wrap(40.0).len(), 1);
+        assert_eq!(line(3).wrap(5.0).len(), 3);
+    }
+}
@@ -1,94 +1,34 @@
 // Font Character Atlas.
-// This is to assist rendering of text. All common characters are preloaded into a set of fixed sized textures
-// to allow quick access and render.
+// This is to assist rendering of text. Glyphs are rasterized into a set of textures the first
+// time they are rendered to allow quick access and render. Each texture starts small and grows
+// as glyphs are added, once the maximum number of textures are full the least recently used
+// texture is cleared and reused.
 
 use crate::font::{self, Font, FontCollection, FontDesc, FontMetrics, GlyphId, RasterizedGlyph};
-use crate::gl::{self, types::*};
+use crate::renderer::backend::{RenderBackend, TextureId, TextureSize};
 use crate::renderer::{vec4, Vector4F};
+use log::{error, info};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::rc::Rc;
 // use euclid::{default::Vector2D, vec2};
 use crate::pathfinder_geometry::vector::{vec2f, Vector2F, Vector2I};
-type AtlasSize = (i32, i32);
-
-const ATLAS_SIZE: AtlasSize = (1024, 1024);
-const MAX_ATLASES: usize = 4;
-
-macro_rules! gl_check_impl {
-    ($f:expr) => {
-        if cfg!(debug_assertions) {
-            let err = gl::GetError();
-            // println!("Error {:?}", err);
-            if err != gl::NO_ERROR {
-                let err_str = match err {
-                    gl::INVALID_ENUM => "GL_INVALID_ENUM",
-                    gl::INVALID_VALUE => "GL_INVALID_VALUE",
-                    gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
-                    gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
-                    gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
-                    gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
-                    gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
-                    _ => "unknown error",
-                };
-
-                panic!(
-                    "{}:{} error {} {}",
-                    file!(),
-                    line!(),
-                    std::stringify!($f),
-                    err_str
-                );
-            }
-        }
-    };
-}
-
-macro_rules! gl_check {
-    ($f:expr) => {{
-        $f;
-        gl_check_impl!($f)
-    }};
-    () => {{
-        gl_check_impl!("no_function")
-    }};
-}
-
-fn create_texture(size: &AtlasSize, wrap: GLenum, mapping: GLenum) -> u32 {
-    let mut handle = 0;
-
-    unsafe {
-        gl::GenTextures(1, &mut handle);
-
-        if handle == 0 {
-            panic!("Failed to generate texture");
-        }
+type AtlasSize = TextureSize;
 
-        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
-        gl::BindTexture(gl::TEXTURE_2D, handle);
-
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, mapping as GLint);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mapping as GLint);
-
-        gl::TexImage2D(
-            gl::TEXTURE_2D,
-            0,
-            gl::RGB as i32,
-            size.0,
-            size.1,
-            0,
-            gl::RGB,
-            gl::UNSIGNED_BYTE,
-            std::ptr::null(),
-        );
+/// the size of a newly created atlas. Atlases grow vertically so the uv
+/// of a glyph only changes in the y direction.
+const INITIAL_ATLAS_SIZE: AtlasSize = (1024, 128);
+/// the largest an atlas is allowed to grow.
+const MAX_ATLAS_SIZE: AtlasSize = (1024, 1024);
+/// each atlas is bound to its own texture unit of the text shader.
+const MAX_ATLASES: usize = super::MAX_TEXTURES;
 
-        gl::BindTexture(gl::TEXTURE_2D, 0);
-    }
-    handle
-}
+/// the textures of dropped atlases, they are deleted the next time the backend is at hand.
+type Released = Rc<RefCell<Vec<TextureId>>>;
 
 #[derive(Debug, Clone)]
 pub struct GlyphInfo {
+    /// index of the atlas containing the glyph, this is also the texture unit it is bound to.
     pub tex: u32,
     pub uv: Vector2F,
     pub uv_delta: Vector2F,
@@ -108,20 +48,41 @@ impl GlyphInfo {
     }
 }
 
+/// Usage information of the font atlas.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AtlasStats {
+    /// number of glyphs currently in the atlases.
+    pub glyphs: usize,
+    /// number of atlases created.
+    pub atlases: usize,
+    /// number of times an atlas was grown.
+    pub grows: usize,
+    /// number of times an atlas was cleared to make room for new glyphs.
+    pub evictions: usize,
+}
+
+impl std::fmt::Display for AtlasStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "glyphs: {} atlases: {}/{} grows: {} evictions: {}",
+            self.glyphs, self.atlases, MAX_ATLASES, self.grows, self.evictions
+        )
+    }
+}
+
 /// Collection of Atlass that make up the font.
-/// When an atlas becomes full, a new
+/// When an atlas becomes full it is grown, if it is as large as it can be a new atlas is
+/// created. When no more atlases can be created, the least recently used atlas is cleared.
 pub struct FontAtlas {
     // a list of atlases
     atlases: Vec<Atlas>,
-    // font: FontDesc,
-    // metrics: FontMetrics,
-    // meta data used for constructing the current atlas.
-
-    // the x and y offset into the current texture.
-    pub(crate) x_offset: i32,
-    pub(crate) y_offset: i32,
-    // the largest height of the current row of glyphs.
-    pub(crate) max_height: i32,
+    // the atlas new glyphs are added to.
+    current: usize,
+    // incremented every frame, used to find the least recently used atlas.
+    generation: u64,
+    stats: AtlasStats,
+    released: Released,
     dpi_factor: f32,
 }
 
@@ -129,9 +90,10 @@ impl FontAtlas {
     pub fn new(dpi_factor: f32) -> Self {
         Self {
             atlases: Vec::new(),
-            x_offset: 0,
-            y_offset: 0,
-            max_height: 0,
+            current: 0,
+            generation: 0,
+            stats: AtlasStats::default(),
+            released: Released::default(),
             dpi_factor,
         }
     }
@@ -140,110 +102,135 @@ impl FontAtlas {
         self.dpi_factor
     }
 
-    pub fn from_collection<P>(
-        collection: &mut FontCollection,
-        loader: P,
-    ) -> Result<Self, font::FontError>
-    where
-        P: FnMut(&mut Self, &mut Font) -> Result<(), font::FontError>,
-    {
-        let mut atlas = Self::new(collection.dpi_factor());
-        atlas.new_atlas(&ATLAS_SIZE);
-        atlas.load_collection(collection, loader)?;
-        Ok(atlas)
+    pub fn atlas(&self) -> &[Atlas] {
+        self.atlases.as_slice()
     }
 
-    fn load_collection<P>(
-        &mut self,
-        collection: &mut FontCollection,
-        mut loader: P,
-    ) -> Result<(), font::FontError>
-    where
-        P: FnMut(&mut Self, &mut Font) -> Result<(), font::FontError>,
-    {
-        for i in 0..collection.num_fonts() {
-            println!("Loading Font index: {}", i);
-            match collection.font_at_mut(i) {
-                Some(font) => loader(self, font)?,
-                _ => {}
-            }
-        }
-        Ok(())
+    /// the texture of each atlas, the tex of a glyph info is an index into this.
+    pub fn textures(&self) -> Vec<TextureId> {
+        self.atlases.iter().map(|atlas| atlas.texture).collect()
     }
 
-    pub fn atlas(&self) -> &[Atlas] {
-        self.atlases.as_slice()
+    /// deletes the textures of the atlases.
+    pub fn release(&mut self, backend: &mut dyn RenderBackend) {
+        self.atlases.clear();
+        self.current = 0;
+        self.delete_released(backend);
     }
 
-    pub fn add_glyph(&mut self, glyph: &RasterizedGlyph) {
-        if self.atlases.is_empty() {
-            self.new_atlas(&ATLAS_SIZE);
+    fn delete_released(&mut self, backend: &mut dyn RenderBackend) {
+        for texture in self.released.borrow_mut().drain(..) {
+            backend.delete_texture(texture);
         }
+    }
 
-        // check if room on line
-        if !self.room_on_line(glyph) {
-            // if not advance to next line
-            self.next_line(glyph);
+    pub fn stats(&self) -> AtlasStats {
+        AtlasStats {
+            glyphs: self.atlases.iter().map(|a| a.glyph_infos.len()).sum(),
+            atlases: self.atlases.len(),
+            ..self.stats
         }
+    }
 
-        // check if the
-        if !self.fit_vertical(glyph) {
-            self.new_atlas(&ATLAS_SIZE)
-        }
+    /// marks the start of a new frame, glyphs used from here on are considered
+    /// more recent than the glyphs of previous frames.
+    pub fn begin_frame(&mut self) {
+        self.generation += 1;
+    }
 
-        // I know this is safe
-        let atlas = self.atlases.last_mut().unwrap();
-
-        atlas.bind();
-
-        unsafe {
-            gl_check!(gl::TexSubImage2D(
-                gl::TEXTURE_2D,
-                0,
-                self.x_offset,
-                self.y_offset,
-                glyph.width,
-                glyph.height,
-                gl::RGB,
-                gl::UNSIGNED_BYTE,
-                glyph.bitmap.as_ptr() as *const _
-            ));
+    /// removes all glyphs, this is used when the font size or dpi changes and all of the
+    /// glyphs need to be rasterized again. The textures are kept and reused.
+    pub fn reset(&mut self, dpi_factor: f32) {
+        info!("Resetting font atlas | {}", self.stats());
+        for atlas in &mut self.atlases {
+            atlas.clear();
         }
+        self.current = 0;
+        self.dpi_factor = dpi_factor;
+    }
 
-        atlas.unbind();
+    /// returns true if glyph can be added without growing or clearing an atlas. When this
+    /// is false, glyphs that have already been submitted must be flushed before adding
+    /// because their texture coordinates could change.
+    pub fn fits(&self, glyph: &RasterizedGlyph) -> bool {
+        self.atlases
+            .get(self.current)
+            .map_or(false, |atlas| atlas.fits(glyph))
+    }
 
-        let uv = vec2f(
-            self.x_offset as f32 / ATLAS_SIZE.0 as f32,
-            self.y_offset as f32 / ATLAS_SIZE.1 as f32,
-        );
+    pub fn add_glyph(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        glyph: &RasterizedGlyph,
+    ) -> Option<GlyphInfo> {
+        // the same bounds as Atlas::fits in an empty atlas of the largest size.
+        if glyph.width >= MAX_ATLAS_SIZE.0 || glyph.height + 1 >= MAX_ATLAS_SIZE.1 {
+            error!(
+                "glyph is too large for the font atlas: {}x{}",
+                glyph.width, glyph.height
+            );
+            return None;
+        }
 
-        let uv_delta = vec2f(
-            glyph.width as f32 / ATLAS_SIZE.0 as f32,
-            (glyph.height + 1) as f32 / ATLAS_SIZE.1 as f32,
-        );
+        self.delete_released(backend);
+        if !self.fits(glyph) && !self.make_room(backend, glyph) {
+            error!(
+                "no room in the font atlas for glyph: {}x{}",
+                glyph.width, glyph.height
+            );
+            return None;
+        }
 
-        let advance = glyph.advance;
-        let origin = glyph.origin;
+        let tex = self.current as u32;
+        let generation = self.generation;
+        let atlas = &mut self.atlases[self.current];
+        let info = atlas.insert(backend, glyph, tex);
+        atlas.last_used.set(generation);
+        Some(info)
+    }
 
-        let atlas_glyph = GlyphInfo {
-            tex: atlas.handle,
-            uv,
-            uv_delta,
-            size: vec2f(glyph.width as f32, (glyph.height + 1) as f32),
-            advance,
-            origin,
-        };
+    /// makes room in the current atlas for the glyph, returns false if it still doesn't fit.
+    fn make_room(&mut self, backend: &mut dyn RenderBackend, glyph: &RasterizedGlyph) -> bool {
+        if self.grow_current(backend, glyph) {
+            return true;
+        }
 
-        atlas
-            .glyph_infos
-            .entry(glyph.glyph.clone())
-            .or_insert(atlas_glyph);
+        // try to create a new atlas.
+        if self.atlases.len() < MAX_ATLASES {
+            let atlas = Atlas::new(backend, &INITIAL_ATLAS_SIZE, self.released.clone());
+            self.atlases.push(atlas);
+            self.current = self.atlases.len() - 1;
+            info!("Created font atlas {} | {}", self.current, self.stats());
+            return self.make_room(backend, glyph);
+        }
 
-        self.x_offset += glyph.width;
+        // reuse the least recently used atlas.
+        let (idx, _) = self
+            .atlases
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, atlas)| atlas.last_used.get())
+            .unwrap();
+        self.atlases[idx].clear();
+        self.current = idx;
+        self.stats.evictions += 1;
+        info!("Evicted font atlas {} | {}", idx, self.stats());
+        self.grow_current(backend, glyph)
+    }
 
-        if glyph.height > self.max_height {
-            self.max_height = glyph.height;
+    /// starts a new row in the current atlas and grows it until the glyph fits, returns false
+    /// if it doesn't fit in the largest size.
+    fn grow_current(&mut self, backend: &mut dyn RenderBackend, glyph: &RasterizedGlyph) -> bool {
+        let atlas = match self.atlases.get_mut(self.current) {
+            Some(atlas) => atlas,
+            None => return false,
+        };
+        atlas.next_line();
+        while !atlas.fits(glyph) && atlas.grow(backend) {
+            self.stats.grows += 1;
+            info!("Grew font atlas {} to {:?}", self.current, atlas.size);
         }
+        atlas.fits(glyph)
     }
 
     fn flip_y(glyph: &RasterizedGlyph) -> Vec<u8> {
@@ -268,7 +255,10 @@ impl FontAtlas {
     pub fn get_info(&self, glyph: &GlyphId) -> Option<&GlyphInfo> {
         for atlas in &self.atlases {
             match atlas.get_info(glyph) {
-                Some(info) => return Some(info),
+                Some(info) => {
+                    atlas.last_used.set(self.generation);
+                    return Some(info);
+                }
                 None => continue,
             }
         }
@@ -283,24 +273,66 @@ impl FontAtlas {
         }
         false
     }
+}
 
-    fn new_atlas(&mut self, size: &AtlasSize) {
-        if self.atlases.len() == MAX_ATLASES {
-            // maybe this could be changed to a Result.
-            panic!("Unable to create anymore atlases for this font");
+pub struct Atlas {
+    // texture handle of the backend.
+    pub(crate) texture: TextureId,
+    // size of the created texture.
+    size: AtlasSize,
+    // glyph lookup for texture info.
+    glyph_infos: HashMap<GlyphId, GlyphInfo>,
+    // the x and y offset of where the next glyph is placed.
+    x_offset: i32,
+    y_offset: i32,
+    // the largest height of the current row of glyphs.
+    max_height: i32,
+    // the last frame a glyph of this atlas was used.
+    last_used: Cell<u64>,
+    // the texture is added here when the atlas is dropped.
+    released: Released,
+}
+
+impl Atlas {
+    // Fix, the creatino of of a texture could fail.
+    pub(crate) fn new(
+        backend: &mut dyn RenderBackend,
+        size: &AtlasSize,
+        released: Released,
+    ) -> Self {
+        let texture = backend.create_texture(*size);
+
+        Self {
+            texture,
+            size: *size,
+            glyph_infos: HashMap::new(),
+            x_offset: 0,
+            y_offset: 0,
+            max_height: 0,
+            last_used: Cell::new(0),
+            released,
         }
+    }
 
-        self.atlases.push(Atlas::new(size));
-        self.x_offset = 0;
-        self.y_offset = 0;
-        self.max_height = 0;
+    pub fn get_info(&self, glyph: &GlyphId) -> Option<&GlyphInfo> {
+        self.glyph_infos.get(glyph)
     }
 
-    fn room_on_line(&self, glyph: &RasterizedGlyph) -> bool {
-        self.x_offset + glyph.width < ATLAS_SIZE.0
+    pub fn has_info(&self, glyph: &GlyphId) -> bool {
+        self.glyph_infos.contains_key(glyph)
     }
 
-    fn next_line(&mut self, glyph: &RasterizedGlyph) {
+    /// checks if the glyph fits in the current row or in a new row.
+    fn fits(&self, glyph: &RasterizedGlyph) -> bool {
+        let height = glyph.height + 1;
+        if self.x_offset + glyph.width < self.size.0 {
+            self.y_offset + height < self.size.1
+        } else {
+            glyph.width < self.size.0 && self.y_offset + self.max_height + height < self.size.1
+        }
+    }
+
+    fn next_line(&mut self) {
         // move to next line
         self.x_offset = 0;
         self.y_offset += self.max_height;
@@ -308,51 +340,207 @@ impl FontAtlas {
         self.max_height = 0;
     }
 
-    fn fit_vertical(&self, glyph: &RasterizedGlyph) -> bool {
-        self.y_offset + glyph.height < ATLAS_SIZE.1
+    /// doubles the height of the atlas, returns false if it is already the max size.
+    fn grow(&mut self, backend: &mut dyn RenderBackend) -> bool {
+        if self.size.1 >= MAX_ATLAS_SIZE.1 {
+            return false;
+        }
+
+        let new_size = (self.size.0, (self.size.1 * 2).min(MAX_ATLAS_SIZE.1));
+        self.texture = backend.grow_texture(self.texture, self.size, new_size);
+
+        // only the height changed so only the vertical texture coordinates need updating.
+        let scale = self.size.1 as f32 / new_size.1 as f32;
+        for info in self.glyph_infos.values_mut() {
+            info.uv = vec2f(info.uv.x(), info.uv.y() * scale);
+            info.uv_delta = vec2f(info.uv_delta.x(), info.uv_delta.y() * scale);
+        }
+
+        self.size = new_size;
+        true
     }
-}
 
-pub struct Atlas {
-    // gl texture handle.
-    pub(crate) handle: u32,
-    // size of the created texture.
-    size: AtlasSize,
-    // glyph lookup for texture info.
-    glyph_infos: HashMap<GlyphId, GlyphInfo>,
+    fn clear(&mut self) {
+        self.glyph_infos.clear();
+        self.x_offset = 0;
+        self.y_offset = 0;
+        self.max_height = 0;
+    }
+
+    /// copies the glyph into the texture, the caller must make sure the glyph fits.
+    fn insert(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        glyph: &RasterizedGlyph,
+        tex: u32,
+    ) -> GlyphInfo {
+        // check if room on line
+        if self.x_offset + glyph.width >= self.size.0 {
+            // if not advance to next line
+            self.next_line();
+        }
+
+        // the glyph is rendered one row taller than its bitmap, the extra row is cleared
+        // so glyphs of a cleared atlas don't show through.
+        let mut bitmap = glyph.bitmap.clone();
+        bitmap.resize(bitmap.len() + glyph.width as usize * 3, 0);
+
+        backend.upload_texture(
+            self.texture,
+            self.x_offset,
+            self.y_offset,
+            glyph.width,
+            glyph.height + 1,
+            bitmap.as_slice(),
+        );
+
+        let uv = vec2f(
+            self.x_offset as f32 / self.size.0 as f32,
+            self.y_offset as f32 / self.size.1 as f32,
+        );
+
+        let uv_delta = vec2f(
+            glyph.width as f32 / self.size.0 as f32,
+            (glyph.height + 1) as f32 / self.size.1 as f32,
+        );
+
+        let info = GlyphInfo {
+            tex,
+            uv,
+            uv_delta,
+            size: vec2f(glyph.width as f32, (glyph.height + 1) as f32),
+            advance: glyph.advance,
+            origin: glyph.origin,
+        };
+
+        self.glyph_infos
+            .entry(glyph.glyph.clone())
+            .or_insert(info.clone());
+
+        self.x_offset += glyph.width;
+
+        if glyph.height + 1 > self.max_height {
+            self.max_height = glyph.height + 1;
+        }
+
+        info
+    }
 }
 
-impl Atlas {
-    // Fix, the creatino of of a texture could fail.
-    pub(crate) fn new(size: &AtlasSize) -> Self {
-        let handle = create_texture(size, gl::CLAMP_TO_BORDER, gl::LINEAR);
+impl Drop for Atlas {
+    fn drop(&mut self) {
+        self.released.borrow_mut().push(self.texture);
+    }
+}
 
-        Self {
-            handle,
-            size: *size,
-            glyph_infos: HashMap::new(),
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::font::Properties;
+    use crate::renderer::software::SoftwareBackend;
+
+    fn glyph(id: u32, width: i32, height: i32) -> RasterizedGlyph {
+        RasterizedGlyph {
+            glyph: GlyphId::new(id, 10.0, FontDesc::new("test", Properties::new())),
+            stride: width as usize * 3,
+            width,
+            height,
+            origin: Vector2F::zero(),
+            advance: vec2f(width as f32, 0.0),
+            bitmap: vec![255; (width * height * 3) as usize],
         }
     }
 
-    pub fn get_info(&self, glyph: &GlyphId) -> Option<&GlyphInfo> {
-        self.glyph_infos.get(glyph)
+    #[test]
+    fn glyphs_are_packed_in_rows() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        let mut atlas = FontAtlas::new(1.0);
+        let first = atlas.add_glyph(&mut backend, &glyph(0, 600, 20)).unwrap();
+        let second = atlas.add_glyph(&mut backend, &glyph(1, 400, 30)).unwrap();
+        // the third glyph doesn't fit next to the others, a new row is started below them.
+        let third = atlas.add_glyph(&mut backend, &glyph(2, 100, 10)).unwrap();
+
+        assert_eq!(first.uv, vec2f(0.0, 0.0));
+        assert_eq!(second.uv, vec2f(600.0 / 1024.0, 0.0));
+        assert_eq!(third.uv, vec2f(0.0, 31.0 / 128.0));
+        assert_eq!(third.size, vec2f(100.0, 11.0));
+        assert!(atlas.has_info(&glyph(1, 400, 30).glyph));
+        assert_eq!(atlas.stats().glyphs, 3);
+        assert_eq!(atlas.stats().atlases, 1);
     }
 
-    #[inline]
-    pub fn bind(&self) {
-        unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, self.handle);
+    #[test]
+    fn atlas_grows_before_adding_another() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        let mut atlas = FontAtlas::new(1.0);
+        let first = atlas.add_glyph(&mut backend, &glyph(0, 1000, 100)).unwrap();
+        assert_eq!(first.uv_delta.y(), 101.0 / 128.0);
+        atlas.add_glyph(&mut backend, &glyph(1, 1000, 100)).unwrap();
+
+        // the uv of the first glyph is scaled to the grown texture.
+        let first = atlas.get_info(&glyph(0, 1000, 100).glyph).unwrap();
+        assert_eq!(first.uv_delta.y(), 101.0 / 256.0);
+        let stats = atlas.stats();
+        assert_eq!((stats.atlases, stats.grows, stats.evictions), (1, 1, 0));
+    }
+
+    #[test]
+    fn least_recently_used_atlas_is_evicted() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        let mut atlas = FontAtlas::new(1.0);
+        // a glyph fills an atlas of the largest size.
+        for id in 0..MAX_ATLASES as u32 {
+            atlas.begin_frame();
+            atlas
+                .add_glyph(&mut backend, &glyph(id, 1000, 600))
+                .unwrap();
         }
+        assert_eq!(atlas.stats().atlases, MAX_ATLASES);
+
+        // the first glyph is used again, the second atlas was used the longest time ago.
+        atlas.begin_frame();
+        assert!(atlas.get_info(&glyph(0, 1000, 600).glyph).is_some());
+        let info = atlas
+            .add_glyph(&mut backend, &glyph(99, 1000, 600))
+            .unwrap();
+        assert_eq!(info.tex, 1);
+        assert!(!atlas.has_info(&glyph(1, 1000, 600).glyph));
+        assert!(atlas.has_info(&glyph(0, 1000, 600).glyph));
+        assert_eq!(atlas.stats().evictions, 1);
     }
 
-    #[inline]
-    pub fn unbind(&self) {
-        unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+    #[test]
+    fn glyphs_at_the_size_limit() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        let mut atlas = FontAtlas::new(1.0);
+        assert!(atlas.add_glyph(&mut backend, &glyph(0, 1024, 10)).is_none());
+        assert!(atlas.add_glyph(&mut backend, &glyph(1, 10, 1023)).is_none());
+
+        // the largest glyph fills an atlas of the largest size.
+        let info = atlas
+            .add_glyph(&mut backend, &glyph(2, 1023, 1022))
+            .unwrap();
+        assert_eq!(info.size, vec2f(1023.0, 1023.0));
+        for id in 3..3 + MAX_ATLASES as u32 {
+            atlas.begin_frame();
+            atlas
+                .add_glyph(&mut backend, &glyph(id, 1023, 1022))
+                .unwrap();
         }
+        assert_eq!(atlas.stats().evictions, 1);
     }
 
-    pub fn has_info(&self, glyph: &GlyphId) -> bool {
-        self.glyph_infos.contains_key(glyph)
+    #[test]
+    fn dropped_atlases_release_textures() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        let mut atlas = FontAtlas::new(1.0);
+        atlas.add_glyph(&mut backend, &glyph(0, 10, 10)).unwrap();
+        let textures = atlas.textures();
+
+        let dropped = atlas.atlases.pop().unwrap();
+        drop(dropped);
+        assert_eq!(*atlas.released.borrow(), textures);
+        atlas.release(&mut backend);
+        assert!(atlas.released.borrow().is_empty());
     }
 }
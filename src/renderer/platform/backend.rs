@@ -0,0 +1,476 @@
+// OpenGL Render Backend.
+// Draws the batches of the renderer with instanced quads, glyphs are sampled from one
+// texture per font atlas.
+
+use gl::types::*;
+use pathfinder_geometry::transform3d::Transform4F;
+
+use super::shader::{RectShader, Shader, TextShader};
+use super::MAX_TEXTURES;
+use crate::renderer::backend::{RenderBackend, RgbaImage, TextureId, TextureSize};
+use crate::renderer::{Color, RectVertex, RenderError, TextVertex};
+
+macro_rules! gl_check {
+    ($f:expr) => {{
+        $f;
+        if cfg!(debug_assertions) {
+            let err = gl::GetError();
+            // println!("Error {:?}", err);
+            if err != gl::NO_ERROR {
+                let err_str = match err {
+                    gl::INVALID_ENUM => "GL_INVALID_ENUM",
+                    gl::INVALID_VALUE => "GL_INVALID_VALUE",
+                    gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+                    gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+                    gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+                    gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+                    gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+                    _ => "unknown error",
+                };
+
+                panic!(
+                    "{}:{} error {} {}",
+                    file!(),
+                    line!(),
+                    std::stringify!($f),
+                    err_str
+                );
+            }
+        }
+    }};
+}
+
+const RECT_QUAD: usize = 4096;
+const TEXT_QUAD: usize = 65535;
+
+fn generate_indices() -> Vec<u32> {
+    vec![0, 1, 2, 0, 2, 3]
+}
+
+pub struct GlBackend {
+    // size of the viewport.
+    width: i32,
+    height: i32,
+
+    rect_vao: u32,
+    rect_vbo: u32,
+    text_vao: u32,
+    text_vbo: u32,
+    ibo: u32,
+
+    rect_shader: RectShader,
+    text_shader: TextShader,
+}
+
+impl GlBackend {
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            rect_vao: 0,
+            rect_vbo: 0,
+            text_vao: 0,
+            text_vbo: 0,
+            ibo: 0,
+            rect_shader: RectShader::create(),
+            text_shader: TextShader::create(),
+        }
+    }
+
+    fn init_gl(&mut self) -> Result<(), RenderError> {
+        self.rect_shader.init()?;
+        self.text_shader.init()?;
+
+        self.init_ibo()?;
+        self.init_rect()?;
+        self.init_text()?;
+
+        // each atlas is bound to the texture unit matching its index.
+        let units: Vec<u32> = (0..MAX_TEXTURES as u32).collect();
+        self.text_shader
+            .bounded(|shader| shader.set_textures(units.as_slice()));
+
+        unsafe {
+            gl::ClearColor(1.0, 1.0, 1.0, 1.0);
+        }
+        Ok(())
+    }
+
+    fn init_ibo(&mut self) -> Result<(), RenderError> {
+        let indices = generate_indices();
+
+        unsafe {
+            gl_check!(gl::GenBuffers(1, &mut self.ibo));
+
+            if self.ibo == 0 {
+                return Err(RenderError::IboFailed);
+            }
+
+            // create the index buffer
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
+            gl_check!(gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (6 * std::mem::size_of::<u32>()) as isize,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW
+            ));
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+
+        Ok(())
+    }
+
+    unsafe fn set_attrib_pointers(layout: &[i32], size: i32) {
+        let mut stride = 0;
+        for (i, sz) in layout.iter().enumerate() {
+            gl_check!(gl::EnableVertexAttribArray(i as u32));
+            gl_check!(gl::VertexAttribPointer(
+                i as u32,
+                *sz,
+                gl::FLOAT,
+                gl::FALSE,
+                size,
+                (stride * std::mem::size_of::<f32>()) as *const _
+            ));
+            gl_check!(gl::VertexAttribDivisor(i as u32, 1));
+            stride += *sz as usize;
+        }
+    }
+
+    fn init_rect(&mut self) -> Result<(), RenderError> {
+        let layout_sizes = [4, 4];
+
+        self.rect_shader.bind();
+        unsafe {
+            gl_check!(gl::GenVertexArrays(1, &mut self.rect_vao));
+            gl_check!(gl::GenBuffers(1, &mut self.rect_vbo));
+
+            if self.rect_vao == 0 || self.rect_vbo == 0 {
+                self.rect_shader.unbind();
+                return Err(RenderError::RectInitFailed);
+            }
+
+            gl_check!(gl::BindVertexArray(self.rect_vao));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
+
+            gl_check!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (RECT_QUAD * std::mem::size_of::<f32>()) as isize,
+                std::ptr::null() as *const _,
+                gl::STREAM_DRAW
+            ));
+
+            Self::set_attrib_pointers(&layout_sizes, std::mem::size_of::<RectVertex>() as i32);
+
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+
+            gl::BindVertexArray(0);
+        }
+        self.rect_shader.unbind();
+
+        Ok(())
+    }
+
+    fn init_text(&mut self) -> Result<(), RenderError> {
+        let layout_sizes = [4, 4, 4, 1];
+
+        self.text_shader.bind();
+        unsafe {
+            gl_check!(gl::GenVertexArrays(1, &mut self.text_vao));
+            gl_check!(gl::GenBuffers(1, &mut self.text_vbo));
+
+            if self.text_vao == 0 || self.text_vbo == 0 {
+                self.text_shader.unbind();
+                return Err(RenderError::TextInitFailed);
+            }
+
+            gl_check!(gl::BindVertexArray(self.text_vao));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.text_vbo));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
+
+            gl_check!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (TEXT_QUAD * std::mem::size_of::<f32>()) as isize,
+                std::ptr::null() as *const _,
+                gl::STREAM_DRAW
+            ));
+
+            Self::set_attrib_pointers(&layout_sizes, std::mem::size_of::<TextVertex>() as i32);
+
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+
+            gl::BindVertexArray(0);
+        }
+        self.text_shader.unbind();
+
+        Ok(())
+    }
+
+    fn set_perspective(&self, perf: &Transform4F) {
+        self.rect_shader
+            .bounded(|shader| shader.set_perspective(perf));
+        self.text_shader
+            .bounded(|shader| shader.set_perspective(perf));
+    }
+
+    fn flush_rect(&self, vertices: &[RectVertex]) {
+        let len = vertices.len();
+
+        if len == 0 {
+            return;
+        }
+        self.rect_shader.bind();
+        unsafe {
+            gl_check!(gl::BindVertexArray(self.rect_vao));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
+
+            gl_check!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (len * std::mem::size_of::<RectVertex>()) as isize,
+                vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW
+            ));
+
+            gl_check!(gl::Enable(gl::BLEND));
+            gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ZERO, gl::ONE);
+
+            gl_check!(gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                6,
+                gl::UNSIGNED_INT,
+                std::ptr::null() as *const _,
+                len as i32
+            ));
+
+            gl_check!(gl::Disable(gl::BLEND));
+
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+        }
+        self.rect_shader.unbind();
+    }
+
+    fn flush_text(&self, vertices: &[TextVertex], textures: &[TextureId]) {
+        let len = vertices.len();
+        if len == 0 {
+            return;
+        }
+
+        self.text_shader.bind();
+        unsafe {
+            for (unit, texture) in textures.iter().enumerate() {
+                gl_check!(gl::ActiveTexture(gl::TEXTURE0 + unit as u32));
+                gl_check!(gl::BindTexture(gl::TEXTURE_2D, *texture));
+            }
+
+            gl_check!(gl::BindVertexArray(self.text_vao));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.text_vbo));
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
+
+            gl_check!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (len * std::mem::size_of::<TextVertex>()) as isize,
+                vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW
+            ));
+
+            gl_check!(gl::Enable(gl::BLEND));
+            gl::BlendFuncSeparate(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR, gl::ZERO, gl::ONE);
+
+            gl_check!(gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                6,
+                gl::UNSIGNED_INT,
+                std::ptr::null() as *const _,
+                len as i32
+            ));
+
+            gl_check!(gl::Disable(gl::BLEND));
+
+            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_check!(gl::BindVertexArray(0));
+        }
+        self.text_shader.unbind();
+    }
+}
+
+impl RenderBackend for GlBackend {
+    fn init(&mut self) -> Result<(), RenderError> {
+        self.init_gl()
+    }
+
+    fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+        // let ortho = Transform4F::from_ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
+        let ortho = Transform4F::from_ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        self.set_perspective(&ortho);
+    }
+
+    fn clear(&mut self, color: Color) {
+        unsafe {
+            gl::ClearColor(color.r, color.g, color.b, color.a);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+    }
+
+    fn draw_rects(&mut self, rects: &[RectVertex]) {
+        self.flush_rect(rects);
+    }
+
+    fn draw_text(&mut self, glyphs: &[TextVertex], textures: &[TextureId]) {
+        self.flush_text(glyphs, textures);
+    }
+
+    fn create_texture(&mut self, size: TextureSize) -> TextureId {
+        let mut handle = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut handle);
+
+            if handle == 0 {
+                panic!("Failed to generate texture");
+            }
+
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::BindTexture(gl::TEXTURE_2D, handle);
+
+            let wrap = gl::CLAMP_TO_BORDER;
+            let mapping = gl::LINEAR;
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, mapping as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mapping as GLint);
+
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGB as i32,
+                size.0,
+                size.1,
+                0,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        handle
+    }
+
+    fn grow_texture(
+        &mut self,
+        texture: TextureId,
+        old_size: TextureSize,
+        new_size: TextureSize,
+    ) -> TextureId {
+        let new_texture = self.create_texture(new_size);
+
+        unsafe {
+            let mut fbo = 0;
+            gl_check!(gl::GenFramebuffers(1, &mut fbo));
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo));
+            gl_check!(gl::FramebufferTexture2D(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture,
+                0
+            ));
+
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, new_texture));
+            gl_check!(gl::CopyTexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                0,
+                old_size.0,
+                old_size.1
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+
+            gl_check!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0));
+            gl_check!(gl::DeleteFramebuffers(1, &fbo));
+        }
+
+        self.delete_texture(texture);
+        new_texture
+    }
+
+    fn upload_texture(
+        &mut self,
+        texture: TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        data: &[u8],
+    ) {
+        unsafe {
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, texture));
+            gl_check!(gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x,
+                y,
+                width,
+                height,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                data.as_ptr() as *const _
+            ));
+            gl_check!(gl::BindTexture(gl::TEXTURE_2D, 0));
+        }
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        if texture != 0 {
+            unsafe {
+                gl::DeleteTextures(1, &texture);
+            }
+        }
+    }
+
+    fn read_pixels(&mut self) -> RgbaImage {
+        let (width, height) = (self.width.max(0) as usize, self.height.max(0) as usize);
+        let mut pixels = vec![0u8; width * height * 4];
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl_check!(gl::ReadPixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _
+            ));
+        }
+
+        // gl rows start at the bottom of the viewport.
+        let row_len = width * 4;
+        let flipped = pixels
+            .chunks(row_len.max(1))
+            .rev()
+            .flat_map(|row| row.iter().cloned())
+            .collect();
+
+        RgbaImage::from_pixels(width as u32, height as u32, flipped).unwrap()
+    }
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 pub mod atlas;
+pub mod backend;
 pub mod shader;
 pub use std::ffi::CString;
 
@@ -1,47 +1,23 @@
 use std::collections::HashMap;
-
-use super::platform::shader::{RectShader, Shader, TextShader};
-use super::style::{self, Style, StyleMap};
-use super::{platform, vec4, Color, Glyph, Rect, RenderError, TextLine, Vector4F};
-use crate::font::{self, Font, FontCollection, FontDesc, FontMetrics, GlyphId};
+use std::ops::Range;
+
+use super::backend::RenderBackend;
+use super::style::{self, Span, Style, StyleId, StyleMap, StyleSpan, SEVERITIES};
+use super::{platform, vec4, Color, Glyph, Rect, RenderError, TextLine, Theme, Vector4F};
+use crate::core::{Highlight, LineChange};
+use crate::font::{
+    self, Font, FontCollection, FontDesc, FontMetrics, GlyphId, ScaledFontMetrics, Shaper,
+};
 use crate::glutin::dpi::{LogicalPosition, LogicalSize};
+use crate::terminal::{Attributes, CellColor, Terminal};
+use crate::ui::completion::CompletionPopup;
 use crate::ui::{Frame, Text};
 
-use crate::pathfinder_geometry::vector::vec2f;
-use pathfinder_geometry::transform3d::Transform4F;
-use platform::atlas::{Atlas, FontAtlas};
-
-use log::{debug, info};
-
-macro_rules! gl_check {
-    ($f:expr) => {{
-        $f;
-        if cfg!(debug_assertions) {
-            let err = gl::GetError();
-            // println!("Error {:?}", err);
-            if err != gl::NO_ERROR {
-                let err_str = match err {
-                    gl::INVALID_ENUM => "GL_INVALID_ENUM",
-                    gl::INVALID_VALUE => "GL_INVALID_VALUE",
-                    gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
-                    gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
-                    gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
-                    gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
-                    gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
-                    _ => "unknown error",
-                };
-
-                panic!(
-                    "{}:{} error {} {}",
-                    file!(),
-                    line!(),
-                    std::stringify!($f),
-                    err_str
-                );
-            }
-        }
-    }};
-}
+use crate::pathfinder_geometry::vector::{vec2f, Vector2F};
+use platform::atlas::{Atlas, FontAtlas, GlyphInfo};
+
+use kea::lsp::Severity;
+use log::{debug, error, info};
 
 /// mode for how the renderer is to render data.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -68,6 +44,8 @@ const RECT_QUAD: usize = 4096;
 const TEXT_QUAD: usize = 65535;
 const RECT_VERTEX_SIZE: usize = 13;
 const TEXT_VERTEX_SIZE: usize = 12;
+/// the number of spaces a tab advances to when the config doesn't set it.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
 
 pub trait Vertex {}
 
@@ -160,10 +138,6 @@ impl<T: Clone> Batch<T> {
     }
 }
 
-fn generate_indices() -> Vec<u32> {
-    vec![0, 1, 2, 0, 2, 3]
-}
-
 /// auxiliary render information.
 /// From a design standpoint, I wasn't sure exactly where
 /// the information stored in this struct should actually be stored
@@ -171,36 +145,192 @@ fn generate_indices() -> Vec<u32> {
 pub struct RenderContext {
     style_map: StyleMap,
     font_collection: FontCollection,
+    shaper: Shaper,
     font_size: f32,
     dpi_factor: f32,
+    theme: Theme,
+    /// the styles of plain text, they are colored by the theme.
+    text_styles: Vec<StyleId>,
+    /// the styles of highlighted text.
+    highlight_styles: HashMap<Highlight, StyleId>,
+    /// the styles of text marked by a diagnostic by the style of the text and the severity.
+    diagnostic_styles: HashMap<(StyleId, Severity), StyleId>,
+    /// the styles of the changed characters of a diff by the style of the text.
+    diff_styles: HashMap<StyleId, StyleId>,
+    /// the number of spaces a tab advances to.
+    tab_width: usize,
 }
 
 impl RenderContext {
-    pub fn new(collection: FontCollection, font_size: f32, dpi_factor: f32) -> RenderContext {
+    pub fn new(
+        collection: FontCollection,
+        font_size: f32,
+        dpi_factor: f32,
+        ligatures: bool,
+    ) -> RenderContext {
         let mut context = Self {
             style_map: StyleMap::new(),
             font_collection: collection,
+            shaper: Shaper::new(ligatures),
             font_size,
             dpi_factor,
+            theme: Theme::default(),
+            text_styles: Vec::new(),
+            highlight_styles: HashMap::new(),
+            diagnostic_styles: HashMap::new(),
+            diff_styles: HashMap::new(),
+            tab_width: DEFAULT_TAB_WIDTH,
         };
 
-        // temporary until real color themes are implemented.
-        let normal = Style::new(0, Color::black(), Color::white(), false, false);
-        let italic = Style::new(1, Color::black(), Color::white(), true, false);
-        let bold = Style::new(2, Color::black(), Color::white(), false, true);
+        let (fg, bg) = (context.theme.foreground, context.theme.background);
+        let normal = Style::new(0, fg, bg, false, false);
+        let italic = Style::new(1, fg, bg, true, false);
+        let bold = Style::new(2, fg, bg, false, false).with_bold(true);
+        let bold_italic = Style::new(3, fg, bg, true, false).with_bold(true);
 
-        context.register_style(normal);
-        context.register_style(italic);
-        context.register_style(bold);
+        for style in [normal, italic, bold, bold_italic].iter() {
+            context.text_styles.push(style.id());
+            context.register_style(style.clone());
+        }
+        for severity in SEVERITIES.iter() {
+            let color = context.theme.severity(*severity);
+            context.register_style(Style::diagnostic(*severity, color));
+        }
 
         context
     }
 
+    /// a context with the font bundled with the test fixtures.
+    #[cfg(test)]
+    pub fn for_tests() -> RenderContext {
+        let mut fonts = FontCollection::new(1.0).unwrap();
+        fonts
+            .add_font_by_name("tests/fixtures/fonts")
+            .expect("failed to load the font of the test fixtures");
+        Self::new(fonts, 10.0, 1.0, true)
+    }
+
     #[inline]
     pub fn register_style(&mut self, style: Style) {
         self.style_map.register_style(style);
     }
 
+    /// recolors the text with the colors of a theme.
+    pub fn set_theme(&mut self, theme: Theme) {
+        for id in self.text_styles.iter() {
+            if let Some(style) = self.style_map.style_mut(id) {
+                style.set_colors(theme.foreground, theme.background);
+            }
+        }
+        for (highlight, id) in self.highlight_styles.iter() {
+            if let Some(style) = self.style_map.style_mut(id) {
+                style.set_colors(theme.syntax(*highlight), theme.background);
+            }
+        }
+        for (base, id) in self.diff_styles.iter() {
+            let color = self.style_map.style(base).map(|base| *base.text_color());
+            if let (Some(color), Some(style)) = (color, self.style_map.style_mut(id)) {
+                style.set_colors(color, theme.diff_text);
+            }
+        }
+        for severity in SEVERITIES.iter() {
+            let id = StyleId::diagnostic(*severity);
+            if let Some(style) = self.style_map.style_mut(&id) {
+                let color = theme.severity(*severity);
+                style.set_colors(color, color);
+                style.set_decoration_color(color);
+            }
+        }
+        for ((base, severity), id) in self.diagnostic_styles.iter() {
+            let colors = self
+                .style_map
+                .style(base)
+                .map(|base| (*base.text_color(), *base.bg_color()));
+            if let (Some((fg, bg)), Some(style)) = (colors, self.style_map.style_mut(id)) {
+                style.set_colors(fg, bg);
+                style.set_decoration_color(theme.severity(*severity));
+            }
+        }
+        self.theme = theme;
+    }
+
+    /// the style of highlighted text, comments are italic.
+    pub fn highlight_style(&mut self, highlight: Highlight) -> StyleId {
+        if let Some(id) = self.highlight_styles.get(&highlight) {
+            return *id;
+        }
+        let italic = highlight == Highlight::Comment;
+        let font = if italic { 1 } else { 0 };
+        let color = self.theme.syntax(highlight);
+        let style = Style::new(font, color, self.theme.background, italic, false);
+        let id = style.id();
+        self.register_style(style);
+        self.highlight_styles.insert(highlight, id);
+        id
+    }
+
+    /// the style of text of the base style marked by a diagnostic, the text is undercurled in
+    /// the color of the severity.
+    pub fn diagnostic_style(&mut self, base: StyleId, severity: Severity) -> StyleId {
+        if let Some(id) = self.diagnostic_styles.get(&(base, severity)) {
+            return *id;
+        }
+        let style = match self.style_map.style(&base) {
+            Some(style) => style
+                .derive()
+                .with_undercurl(true)
+                .with_decoration_color(self.theme.severity(severity)),
+            None => return base,
+        };
+        let id = style.id();
+        self.register_style(style);
+        self.diagnostic_styles.insert((base, severity), id);
+        id
+    }
+
+    /// the style of changed characters of a diff in the base style, they have a background.
+    pub fn diff_style(&mut self, base: StyleId) -> StyleId {
+        if let Some(id) = self.diff_styles.get(&base) {
+            return *id;
+        }
+        let style = match self.style_map.style(&base) {
+            Some(style) => {
+                let mut style = style.derive();
+                style.set_colors(*style.text_color(), self.theme.diff_text);
+                style
+            }
+            None => return base,
+        };
+        let id = style.id();
+        self.register_style(style);
+        self.diff_styles.insert(base, id);
+        id
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// the color of text and cursors.
+    pub fn foreground(&self) -> Color {
+        self.theme.foreground
+    }
+
+    /// the outline of a frame a file is dragged over.
+    pub fn highlight_color(&self) -> Color {
+        self.theme.highlight
+    }
+
+    /// the background of selected text.
+    pub fn selection_color(&self) -> Color {
+        self.theme.selection
+    }
+
+    /// the background color of the default style, backgrounds of this color are not drawn.
+    pub fn background(&self) -> Color {
+        self.theme.background
+    }
+
     #[inline]
     pub fn fonts(&self) -> &FontCollection {
         &self.font_collection
@@ -215,207 +345,289 @@ impl RenderContext {
     pub fn dpi_factor(&self) -> f32 {
         self.dpi_factor
     }
+
+    /// changes the size text is rendered at. Text that has already been positioned
+    /// must be positioned again.
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.font_size = font_size;
+    }
+
+    /// replaces the fonts, e.g. when the font family is changed. Text that has already been
+    /// positioned must be positioned again.
+    pub fn set_fonts(&mut self, collection: FontCollection) {
+        self.font_collection = collection;
+    }
+
+    pub fn set_ligatures(&mut self, ligatures: bool) {
+        self.shaper = Shaper::new(ligatures);
+    }
+
+    #[inline]
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+    }
+
+    pub fn set_dpi_factor(&mut self, dpi_factor: f32) {
+        self.dpi_factor = dpi_factor;
+        self.font_collection.set_dpi_factor(dpi_factor);
+    }
+
+    /// the width of a character of the default font, the width of a cell of a terminal.
+    pub fn cell_width(&self) -> f32 {
+        Renderer::tofu_advance(self, self.font_size)
+    }
+
+    /// the metrics of the default font at the current font size.
+    pub fn font_metrics(&self) -> ScaledFontMetrics {
+        self.font_collection
+            .default_font()
+            .metrics()
+            .scale_with(self.font_size, self.dpi_factor)
+    }
 }
 
 /// Maintains information needed to render
 pub struct Renderer {
-    textures: Vec<u32>,
     mode: RenderMode,
-    rect_vao: u32,
-    rect_vbo: u32,
-    text_vao: u32,
-    text_vbo: u32,
-    ibo: u32,
 
     rect_batch: Batch<RectVertex>,
     text_batch: Batch<TextVertex>,
 
-    rect_shader: RectShader,
-    text_shader: TextShader,
+    /// what the batches are drawn with.
+    backend: Box<dyn RenderBackend>,
+    /// the color the target is cleared to each frame.
+    clear_color: Color,
 
     atlas: FontAtlas,
 }
 
 impl Renderer {
-    pub fn new() -> Self {
+    pub fn new(backend: Box<dyn RenderBackend>) -> Self {
         Self {
-            textures: Vec::with_capacity(platform::MAX_TEXTURES),
             mode: RenderMode::None,
-            rect_vao: 0,
-            rect_vbo: 0,
-            text_vao: 0,
-            text_vbo: 0,
-
-            ibo: 0,
             rect_batch: Batch::new(RECT_QUAD),
             text_batch: Batch::new(TEXT_QUAD),
-            rect_shader: RectShader::create(),
-            text_shader: TextShader::create(),
+            backend,
+            clear_color: Color::white(),
             atlas: FontAtlas::new(0.0),
         }
     }
 
     pub fn init(&mut self) -> Result<(), RenderError> {
-        self.rect_shader.init()?;
-        self.text_shader.init()?;
-
-        self.init_ibo()?;
-        self.init_rect()?;
-        self.init_text()?;
-
-        unsafe {
-            gl::ClearColor(1.0, 1.0, 1.0, 1.0);
-        }
-        Ok(())
+        self.backend.init()
     }
 
-    fn init_ibo(&mut self) -> Result<(), RenderError> {
-        let indices = generate_indices();
-
-        unsafe {
-            gl_check!(gl::GenBuffers(1, &mut self.ibo));
-
-            if self.ibo == 0 {
-                return Err(RenderError::IboFailed);
-            }
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
 
-            // create the index buffer
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
-            gl_check!(gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (6 * std::mem::size_of::<u32>()) as isize,
-                indices.as_ptr() as *const _,
-                gl::STATIC_DRAW
-            ));
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_color = color;
+    }
 
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-        }
+    pub fn backend(&self) -> &dyn RenderBackend {
+        self.backend.as_ref()
+    }
 
-        Ok(())
+    pub fn backend_mut(&mut self) -> &mut dyn RenderBackend {
+        self.backend.as_mut()
     }
 
-    unsafe fn set_attrib_pointers(layout: &[i32], size: i32) {
-        let mut stride = 0;
-        for (i, sz) in layout.iter().enumerate() {
-            gl_check!(gl::EnableVertexAttribArray(i as u32));
-            gl_check!(gl::VertexAttribPointer(
-                i as u32,
-                *sz,
-                gl::FLOAT,
-                gl::FALSE,
-                size,
-                (stride * std::mem::size_of::<f32>()) as *const _
-            ));
-            gl_check!(gl::VertexAttribDivisor(i as u32, 1));
-            stride += *sz as usize;
-        }
+    pub fn set_atlas(&mut self, atlas: FontAtlas) {
+        self.atlas.release(self.backend.as_mut());
+        self.atlas = atlas;
+        info!("Setting Renderers Font Atlas");
     }
 
-    fn init_rect(&mut self) -> Result<(), RenderError> {
-        let layout_sizes = [4, 4];
+    /// retrieves the atlas information of a glyph, rasterizing it into the atlas
+    /// the first time it is requested.
+    fn glyph_info(
+        &mut self,
+        context: &mut RenderContext,
+        font_idx: usize,
+        glyph: u32,
+        size: f32,
+    ) -> Option<GlyphInfo> {
+        let font = context.font_collection.font_at_mut(font_idx)?;
+        let glyph_id = GlyphId::new(glyph, size, font.desc().clone());
 
-        self.rect_shader.bind();
-        unsafe {
-            gl_check!(gl::GenVertexArrays(1, &mut self.rect_vao));
-            gl_check!(gl::GenBuffers(1, &mut self.rect_vbo));
+        if let Some(info) = self.atlas.get_info(&glyph_id) {
+            return Some(info.clone());
+        }
 
-            if self.rect_vao == 0 || self.rect_vbo == 0 {
-                self.rect_shader.unbind();
-                return Err(RenderError::RectInitFailed);
+        match font.rasterize_glyph(glyph, size) {
+            Ok(glyph) => {
+                // glyphs already submitted could be moved or removed to make room.
+                if !self.atlas.fits(&glyph) {
+                    self.flush();
+                }
+                self.atlas.add_glyph(self.backend.as_mut(), &glyph)
+            }
+            Err(e) => {
+                error!("{}", e);
+                None
             }
-
-            gl_check!(gl::BindVertexArray(self.rect_vao));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo));
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
-
-            gl_check!(gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (RECT_QUAD * std::mem::size_of::<f32>()) as isize,
-                std::ptr::null() as *const _,
-                gl::STREAM_DRAW
-            ));
-
-            Self::set_attrib_pointers(&layout_sizes, std::mem::size_of::<RectVertex>() as i32);
-
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
-
-            gl::BindVertexArray(0);
         }
-        self.rect_shader.unbind();
-
-        Ok(())
     }
 
-    fn init_text(&mut self) -> Result<(), RenderError> {
-        let layout_sizes = [4, 4, 4, 1];
+    /// removes all glyphs from the atlas so they are rasterized again, this is needed
+    /// when the font size or dpi changes.
+    pub fn reset_glyphs(&mut self, dpi_factor: f32) {
+        self.flush();
+        self.atlas.reset(dpi_factor);
+    }
 
-        self.text_shader.bind();
-        unsafe {
-            gl_check!(gl::GenVertexArrays(1, &mut self.text_vao));
-            gl_check!(gl::GenBuffers(1, &mut self.text_vbo));
+    /// the width of the box rendered in place of a glyph no font supports.
+    fn tofu_advance(context: &RenderContext, size: f32) -> f32 {
+        let font = context.font_collection.default_font();
+        font.get_glyph_index('0')
+            .and_then(|glyph| font.advance(glyph, size).ok())
+            .unwrap_or(size)
+    }
 
-            if self.text_vao == 0 || self.text_vbo == 0 {
-                self.text_shader.unbind();
-                return Err(RenderError::TextInitFailed);
+    /// shapes a line of text, each style span is shaped with the font of its style.
+    /// Parts of the text not covered by a span use the default style.
+    fn shape_line(
+        context: &mut RenderContext,
+        text: &str,
+        styles: &[StyleSpan],
+        size: f32,
+    ) -> TextLine {
+        let source = text;
+        // control characters, such as tabs, are rendered as whitespace.
+        let text: String = text
+            .chars()
+            .map(|ch| if ch.is_ascii_control() { ' ' } else { ch })
+            .collect();
+        let missing_advance = Self::tofu_advance(context, size);
+
+        let mut segments = Vec::new();
+        let mut last = 0;
+        for style in styles {
+            let start = style.start().max(last).min(text.len());
+            let end = style.end().min(text.len());
+            if start > last {
+                segments.push((last, start, StyleId(0)));
             }
+            if end > start {
+                segments.push((start, end, style.style()));
+                last = end;
+            }
+        }
+        if last < text.len() {
+            segments.push((last, text.len(), StyleId(0)));
+        }
 
-            gl_check!(gl::BindVertexArray(self.text_vao));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.text_vbo));
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
-
-            gl_check!(gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (TEXT_QUAD * std::mem::size_of::<f32>()) as isize,
-                std::ptr::null() as *const _,
-                gl::STREAM_DRAW
-            ));
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+        for (start, end, style) in segments {
+            // a span that doesn't fall on a character boundary can't be shaped, the characters
+            // starting in it are drawn as missing glyphs.
+            if !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+                let starts = text.char_indices().map(|(idx, _)| idx);
+                for cluster in starts.filter(|idx| (start..end).contains(idx)) {
+                    glyphs.push(Glyph {
+                        id: 0,
+                        cluster,
+                        x,
+                        y: 0.0,
+                        advance: missing_advance,
+                        font: None,
+                        style,
+                    });
+                    x += missing_advance;
+                }
+                continue;
+            }
 
-            Self::set_attrib_pointers(&layout_sizes, std::mem::size_of::<TextVertex>() as i32);
+            let font = context
+                .style_map
+                .style(&style)
+                .map_or(FontCollection::DEFAULT_FONT, |style| style.font());
 
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            let shaped = context.shaper.shape(
+                &mut context.font_collection,
+                &text[start..end],
+                font,
+                size,
+                missing_advance,
+            );
+
+            let mut width = 0.0;
+            for glyph in shaped {
+                glyphs.push(Glyph {
+                    id: glyph.glyph,
+                    cluster: start + glyph.cluster,
+                    x: x + glyph.x,
+                    y: glyph.y,
+                    advance: glyph.advance,
+                    font: glyph.font,
+                    style,
+                });
+                width += glyph.advance;
+            }
+            x += width;
+        }
 
-            gl::BindVertexArray(0);
+        // a tab advances to the next tab stop, the glyphs after it move along.
+        let tab_width = context.tab_width as f32;
+        let mut shift = 0.0;
+        for glyph in glyphs.iter_mut() {
+            glyph.x += shift;
+            let stop = glyph.advance * tab_width;
+            if source.as_bytes().get(glyph.cluster) == Some(&b'\t') && stop > 0.0 {
+                let end = ((glyph.x / stop + 0.001).floor() + 1.0) * stop;
+                shift += end - glyph.x - glyph.advance;
+                glyph.advance = end - glyph.x;
+            }
         }
-        self.text_shader.unbind();
 
-        Ok(())
+        let carets = Self::caret_positions(text.as_str(), glyphs.as_slice());
+        TextLine::new(glyphs, styles.to_vec(), carets)
     }
 
-    pub fn set_atlas(&mut self, atlas: FontAtlas) {
-        self.atlas = atlas;
-        info!("Setting Renderers Font Atlas");
-
-        self.text_shader.bind();
-
-        for (idx, atlas) in self.atlas.atlas().iter().enumerate() {
-            let handle = atlas.handle;
-            unsafe {
-                gl::ActiveTexture(gl::TEXTURE0 + idx as u32);
-                gl::BindTexture(gl::TEXTURE_2D, handle);
+    /// computes the x position of the caret before each character of the text and one
+    /// past the last character. When several characters are shaped into a single glyph,
+    /// a ligature for example, the glyph is divided evenly between them.
+    fn caret_positions(text: &str, glyphs: &[Glyph]) -> Vec<f32> {
+        // (cluster, x, width) in logical order.
+        let mut clusters: Vec<(usize, f32, f32)> = Vec::new();
+        for glyph in glyphs {
+            match clusters.last_mut() {
+                Some(cluster) if cluster.0 == glyph.cluster => cluster.2 += glyph.advance,
+                _ => clusters.push((glyph.cluster, glyph.x, glyph.advance)),
             }
         }
 
-        self.text_shader.unbind();
-    }
+        let mut carets = Vec::with_capacity(text.len() + 1);
+        let mut current = 0;
+        for (offset, _) in text.char_indices() {
+            while current + 1 < clusters.len() && clusters[current + 1].0 <= offset {
+                current += 1;
+            }
 
-    pub fn set_perspective(&self, perf: &Transform4F) {
-        self.rect_shader
-            .bounded(|shader| shader.set_perspective(perf));
-        self.text_shader
-            .bounded(|shader| shader.set_perspective(perf));
+            let caret = match clusters.get(current) {
+                Some(&(start, x, width)) if start <= offset => {
+                    let end = clusters.get(current + 1).map_or(text.len(), |c| c.0);
+                    let chars = text[start..end].chars().count().max(1);
+                    let idx = text[start..offset].chars().count();
+                    x + width * idx as f32 / chars as f32
+                }
+                _ => 0.0,
+            };
+            carets.push(caret);
+        }
+
+        carets.push(glyphs.iter().map(|g| g.advance).sum());
+        carets
     }
 
-    pub fn update_perspective(&self, width: i32, height: i32) {
-        unsafe {
-            gl::Viewport(0, 0, width, height);
-        }
-        // let ortho = Transform4F::from_ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
-        let ortho = Transform4F::from_ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
-        self.set_perspective(&ortho);
+    pub fn update_perspective(&mut self, width: i32, height: i32) {
+        self.backend.resize(width, height);
     }
 
     /// renders a Rect
@@ -433,99 +645,236 @@ impl Renderer {
     //  uses the atlas for layout font information.
     pub fn render_str(
         &mut self,
-        context: &RenderContext,
+        context: &mut RenderContext,
         s: &str,
-        mut x: f32,
-        y: f32,
+        x: f32,
+        mut y: f32,
         fg_color: Color,
         bg_color: Color,
         size: f32,
     ) {
-        let font = context.font_collection.default_font();
-        //pub fn render_str(&mut self, s: &str, mut x: f32, y: f32, fg_color: Color, bg_color: Color, desc: FontDesc, size: f32) {
+        let metrics = context
+            .font_collection
+            .default_font()
+            .metrics()
+            .scale_with(size, context.dpi_factor());
 
-        // let metrics = self.font_metrics.get(&desc).expect("requesting metrics for unknown font").scale_with(size, self.atlas.dpi_factor());
-        let metrics = font.metrics().scale_with(size, self.atlas.dpi_factor());
+        for text in s.split('\n') {
+            let line = Self::shape_line(context, text, &[], size);
+            self.render_glyphs(context, &line, x, y, size, &metrics, |_| fg_color);
+            y += metrics.line_height();
+        }
+    }
+
+    pub fn render_line(
+        &mut self,
+        context: &mut RenderContext,
+        line: &TextLine,
+        x: f32,
+        y: f32,
+        size: f32,
+    ) {
+        let metrics = context
+            .font_collection
+            .default_font()
+            .metrics()
+            .scale_with(size, context.dpi_factor());
+
+        let styles: HashMap<StyleId, Style> = line
+            .glyphs
+            .iter()
+            .filter_map(|glyph| {
+                context
+                    .style_map
+                    .style(&glyph.style)
+                    .map(|style| (glyph.style, style.clone()))
+            })
+            .collect();
+        let runs = Self::style_runs(line);
+
+        // backgrounds are drawn first so the glyphs are drawn on top of them.
+        let background = context.background();
+        for (style, start, end) in runs.iter() {
+            if let Some(style) = styles.get(style) {
+                if *style.bg_color() != background {
+                    let rect = Rect::with_position(
+                        vec2f(x + start, y - metrics.ascent),
+                        end - start,
+                        metrics.line_height(),
+                    )
+                    .with_color(*style.bg_color());
+                    self.render_rect(context, &rect);
+                }
+            }
+        }
 
-        // this will make the vector longer then it needs to be
-        let mut lines: u32 = 1;
-        let mut width: f32 = 0.0;
-        let start_x = x;
-        let start_y = y - metrics.ascent;
+        let foreground = context.foreground();
+        self.render_glyphs(context, line, x, y, size, &metrics, |style| {
+            styles
+                .get(&style)
+                .map_or(foreground, |style| *style.text_color())
+        });
+
+        for (style, start, end) in runs.iter() {
+            if let Some(style) = styles.get(style) {
+                if style.has_decoration() {
+                    self.render_decorations(context, style, x + start, end - start, y, &metrics);
+                }
+            }
+        }
+    }
 
-        for (idx, c) in s.chars().enumerate() {
-            let glyph_id = GlyphId::new(c, size, font.desc().clone());
-            if c == '\n' {
-                lines += 1;
-                continue;
+    /// groups neighboring glyphs with the same style, returns the style and the horizontal
+    /// extent of each group.
+    fn style_runs(line: &TextLine) -> Vec<(StyleId, f32, f32)> {
+        let mut runs: Vec<(StyleId, f32, f32)> = Vec::new();
+        for glyph in line.glyphs.as_slice() {
+            let end = glyph.x + glyph.advance;
+            match runs.last_mut() {
+                Some(run) if run.0 == glyph.style => {
+                    run.1 = run.1.min(glyph.x);
+                    run.2 = run.2.max(end);
+                }
+                _ => runs.push((glyph.style, glyph.x, end)),
             }
+        }
+        runs
+    }
 
-            if let Some(info) = self.atlas.get_info(&glyph_id) {
-                let pos = vec2f(x, y) + info.origin;
-                let vertex = vec4(pos.x(), pos.y(), info.size.x(), info.size.y());
-                let tex_info = vec4(
-                    info.uv.x(),
-                    info.uv.y(),
-                    info.uv_delta.x(),
-                    info.uv_delta.y(),
-                );
-                let tex_id = info.tex as f32;
-                let vertex = TextVertex::create(vertex, fg_color, tex_info, tex_id);
+    /// draws the underline, strikethrough, and undercurl of a style. y is the baseline.
+    fn render_decorations(
+        &mut self,
+        context: &RenderContext,
+        style: &Style,
+        x: f32,
+        width: f32,
+        y: f32,
+        metrics: &ScaledFontMetrics,
+    ) {
+        let color = *style.decoration_color();
+        let thickness = metrics.underline_thickness.round().max(1.0);
+        // the underline position is relative to the baseline with up being positive.
+        let underline_y = y - metrics.underline_position;
+
+        if style.underline() {
+            let rect = Rect::with_position(vec2f(x, underline_y), width, thickness);
+            self.render_rect(context, &rect.with_color(color));
+        }
 
-                x += info.advance.x();
-                width += info.advance.x();
+        if style.strikethrough() {
+            let strike_y = y - (metrics.x_height + thickness) / 2.0;
+            let rect = Rect::with_position(vec2f(x, strike_y), width, thickness);
+            self.render_rect(context, &rect.with_color(color));
+        }
 
-                self.submit_character(&vertex);
-            }
+        if style.undercurl() {
+            self.render_undercurl(context, x, width, underline_y, thickness, color);
         }
     }
 
-    pub fn render_line(
+    /// approximates a wave with small rects following a triangle wave.
+    fn render_undercurl(
         &mut self,
         context: &RenderContext,
+        x: f32,
+        width: f32,
+        y: f32,
+        thickness: f32,
+        color: Color,
+    ) {
+        let amplitude = thickness * 2.0;
+        let mut offset = 0.0;
+        while offset < width {
+            let phase = (offset / amplitude) % 2.0;
+            let wave = if phase < 1.0 { phase } else { 2.0 - phase };
+            let rect = Rect::with_position(
+                vec2f(x + offset, y + wave * amplitude),
+                thickness.min(width - offset),
+                thickness,
+            );
+            self.render_rect(context, &rect.with_color(color));
+            offset += thickness;
+        }
+    }
+
+    /// submits the glyphs of a line, y is the baseline of the line.
+    fn render_glyphs<F>(
+        &mut self,
+        context: &mut RenderContext,
         line: &TextLine,
         x: f32,
         y: f32,
         size: f32,
-    ) {
-        let glyphs = line.glyphs.as_slice();
-        for style in line.styles.as_slice() {
-            let span = style.span();
-            let id = style.style();
-
-            if let Some(style) = context.style_map.style(&id) {
-                let font = context.font_collection.font_at(style.font_idx()).unwrap();
-
-                let glyph_span = glyphs.get(span.start..span.end).unwrap();
-                for glyph in glyph_span {
-                    let glyph_id = GlyphId::new(glyph.ch, size, font.desc.clone());
-                    if let Some(info) = self.atlas.get_info(&glyph_id) {
-                        let vertex = TextVertex::create(
-                            vec4(
-                                x + glyph.x + info.origin.x(),
-                                y + info.origin.y(),
-                                info.size.x(),
-                                info.size.y(),
-                            ),
-                            style.text_color().clone(),
-                            info.tex_info(),
-                            info.tex as f32,
-                        );
-
-                        self.submit_character(&vertex);
-                    }
+        metrics: &ScaledFontMetrics,
+        color: F,
+    ) where
+        F: Fn(StyleId) -> Color,
+    {
+        for glyph in line.glyphs.as_slice() {
+            let color = color(glyph.style);
+
+            let font_idx = match glyph.font {
+                Some(font_idx) => font_idx,
+                None => {
+                    self.render_tofu(context, x + glyph.x, y, glyph.advance, metrics, color);
+                    continue;
                 }
-            } else {
-                println!("Failed to find style: {:?}", id);
+            };
+
+            if let Some(info) = self.glyph_info(context, font_idx, glyph.id, size) {
+                let vertex = TextVertex::create(
+                    vec4(
+                        x + glyph.x + info.origin.x(),
+                        y + glyph.y + info.origin.y(),
+                        info.size.x(),
+                        info.size.y(),
+                    ),
+                    color,
+                    info.tex_info(),
+                    info.tex as f32,
+                );
+
+                self.submit_character(&vertex);
             }
         }
     }
 
+    /// renders the outline of a box in place of a glyph that could not be rendered.
+    fn render_tofu(
+        &mut self,
+        context: &RenderContext,
+        x: f32,
+        baseline: f32,
+        advance: f32,
+        metrics: &ScaledFontMetrics,
+        color: Color,
+    ) {
+        let thickness = context.dpi_factor().max(1.0);
+        let margin = (advance * 0.1).max(1.0);
+        let top = baseline - metrics.ascent * 0.8;
+        let width = advance - 2.0 * margin;
+        let height = baseline - top;
+        let x = x + margin;
+
+        let edges = [
+            Rect::with_position(vec2f(x, top), width, thickness),
+            Rect::with_position(vec2f(x, baseline - thickness), width, thickness),
+            Rect::with_position(vec2f(x, top), thickness, height),
+            Rect::with_position(vec2f(x + width - thickness, top), thickness, height),
+        ];
+
+        for edge in edges.iter() {
+            self.render_rect(context, &edge.clone().with_color(color));
+        }
+    }
+
+    /// renders the cursors of a line, x is the start of the line and y is the top.
     pub fn render_cursors(
         &mut self,
         context: &RenderContext,
         line: &TextLine,
         cursors: &[usize],
+        x: f32,
         y: f32,
         size: f32,
     ) {
@@ -535,184 +884,866 @@ impl Renderer {
             .metrics()
             .scale_with(size, context.font_collection.dpi_factor());
         for column in cursors {
-            let x = line.glyphs[*column].x;
+            let x = x + line.caret(*column);
             self.render_cursor(context, x, y, metrics.line_height());
         }
     }
 
+    /// highlights the selected columns of a line. A selection that continues on the next line
+    /// selects the line break, it is drawn as a character after the end of the line.
+    pub fn render_selection(
+        &mut self,
+        context: &RenderContext,
+        line: &TextLine,
+        selection: &Range<usize>,
+        x: f32,
+        y: f32,
+        height: f32,
+    ) {
+        let start = line.caret(selection.start);
+        let end = if selection.end > line.columns() {
+            line.caret(line.columns()) + Self::tofu_advance(context, context.font_size())
+        } else {
+            line.caret(selection.end)
+        };
+        if end > start {
+            let rect = Rect::with_position(vec2f(x + start, y), end - start, height)
+                .with_color(context.selection_color());
+            self.render_rect(context, &rect);
+        }
+    }
+
     pub fn render_cursor(&mut self, context: &RenderContext, x: f32, y: f32, size: f32) {
         static CURSOR_WIDTH: f32 = 2.5;
         // @micro-optimization: build a RectVertex directly instead of building a Rect. Reduces some copying.
-        let rect = Rect::with_position(vec2f(x, y), CURSOR_WIDTH, size);
+        let rect =
+            Rect::with_position(vec2f(x, y), CURSOR_WIDTH, size).with_color(context.foreground());
         self.render_rect(context, &rect);
     }
 
-    pub fn render_frame(&mut self, context: &RenderContext, frame: &mut Frame) {
+    /// the width of the line numbers on the left of a frame, 0 when the frame has no gutter.
+    pub fn gutter_width(context: &RenderContext, frame: &Frame) -> f32 {
+        if !frame.options().gutter {
+            return 0.0;
+        }
+        // the digits are as wide as the box drawn for a missing glyph, plus one for a margin.
+        let digits = Self::gutter_digits(frame) + 1;
+        digits as f32 * Self::tofu_advance(context, context.font_size())
+    }
+
+    fn gutter_digits(frame: &Frame) -> usize {
+        frame.buffer().num_lines().to_string().len().max(2)
+    }
+
+    pub fn render_frame(&mut self, context: &mut RenderContext, frame: &mut Frame) {
         let width = frame.width();
         let height = frame.height();
-        let origin = frame.origin();
-        let font = context.fonts().default_font();
-        let metrics = font
+        let origin = *frame.origin();
+        let metrics = context
+            .fonts()
+            .default_font()
             .metrics()
             .scale_with(context.font_size(), context.dpi_factor());
-        let start_y = metrics.ascent;
-        let start_x = 0.0;
+        let options = frame.options();
+        frame.fit_wrapped(context);
+        let gutter = Self::gutter_width(context, frame);
+        let digits = Self::gutter_digits(frame);
+        let line_height = metrics.line_height();
+
+        if let Some(terminal) = frame.terminal() {
+            let active = frame.is_active();
+            return self.render_terminal(context, terminal, origin, width, height, active);
+        }
 
-        let x = start_x + origin.x();
-        let mut y = start_y + origin.y();
+        let x = origin.x() + gutter;
+        let mut y = origin.y() + metrics.ascent;
+        let bottom = origin.y() + height;
+
+        // text isn't cut off at the edge of a frame, a frame beside another one covers the text
+        // running into it and is separated from it by a line.
+        if origin.x() > 0.0 {
+            let cover = Rect::with_position(origin, width, height).with_color(context.background());
+            self.render_rect(context, &cover);
+            let color = context.theme().line_number;
+            let separator = Rect::with_position(origin, 1.0, height).with_color(color);
+            self.render_rect(context, &separator);
+        }
 
         for line in frame.lines_mut() {
-            if let Some(line) = line {
-                if line.assoc.is_none() {
-                    // generate glyphs
-                    let text_line = Self::position_line(&line, font);
-                    line.assoc = Some(text_line);
+            // wrapped lines can push the last lines out of the frame.
+            if y - metrics.ascent >= bottom {
+                break;
+            }
+
+            let line = match line {
+                Some(line) => line,
+                None => {
+                    y += line_height;
+                    continue;
                 }
+            };
+            if line.filler {
+                let top = y - metrics.ascent;
+                let rect = Rect::with_position(vec2f(x, top), width - gutter, line_height)
+                    .with_color(context.theme().filler);
+                self.render_rect(context, &rect);
+                y += line_height;
+                continue;
+            }
+            if line.assoc.is_none() {
+                // generate glyphs
+                let text_line = Self::position_line(context, line);
+                line.assoc = Some(text_line);
+            }
 
-                if let Some(text) = line.assoc.as_ref() {
-                    // render glyphs.
-                    self.render_line(context, text, x, y, context.font_size());
+            if gutter > 0.0 {
+                let number = format!("{:>1$}", line.line_number, digits);
+                let color = context.theme().line_number;
+                let background = context.background();
+                let size = context.font_size();
+                self.render_str(context, &number, origin.x(), y, color, background, size);
+
+                // the sign of the diagnostics of the line is drawn in the margin after the
+                // line number. A folded line has a marker there instead, in the color of its
+                // sign.
+                let margin = gutter / (digits + 1) as f32;
+                if line.folded.is_some() {
+                    let color = line
+                        .sign
+                        .map_or(color, |severity| context.theme().severity(severity));
+                    let x = origin.x() + gutter - margin;
+                    self.render_str(context, "+", x, y, color, background, size);
+                } else if let Some(severity) = line.sign {
+                    let rect = Rect::with_position(
+                        vec2f(
+                            origin.x() + gutter - margin * 0.75,
+                            y - metrics.ascent + line_height * 0.2,
+                        ),
+                        margin * 0.5,
+                        line_height * 0.6,
+                    )
+                    .with_color(context.theme().severity(severity));
+                    self.render_rect(context, &rect);
+                }
+
+                // a changed line has a bar at the left edge of the gutter, removed lines a line
+                // under the line before them.
+                if let Some(change) = line.change {
+                    let top = y - metrics.ascent;
+                    let rect = if change == LineChange::Removed {
+                        Rect::with_position(
+                            vec2f(origin.x(), top + line_height * 0.85),
+                            margin,
+                            line_height * 0.15,
+                        )
+                    } else {
+                        Rect::with_position(vec2f(origin.x(), top), margin * 0.25, line_height)
+                    }
+                    .with_color(context.theme().change(change));
+                    self.render_rect(context, &rect);
                 }
             }
-            y += metrics.line_height();
+
+            let text = match line.assoc.as_ref() {
+                Some(text) => text,
+                None => continue,
+            };
+            if !options.wrap {
+                self.render_diff_line(context, line, x, origin.x() + width, y);
+                self.render_row(context, text, &line.cursors, &line.selections, x, y);
+                let start = x + text.caret(usize::MAX);
+                self.render_after_text(context, line, start, origin.x() + width, y);
+                y += line_height;
+                continue;
+            }
+
+            let rows = text.wrap(width - gutter);
+            for (idx, (start, row)) in rows.iter().enumerate() {
+                let end = rows.get(idx + 1).map_or(usize::MAX, |(end, _)| *end);
+                // a cursor between two rows is drawn at the start of the second.
+                let cursors: Vec<usize> = line
+                    .cursors
+                    .iter()
+                    .filter(|column| (*start..end).contains(column))
+                    .map(|column| column - start)
+                    .collect();
+                let selections: Vec<Range<usize>> = line
+                    .selections
+                    .iter()
+                    .map(|selection| selection.start.max(*start)..selection.end.min(end))
+                    .filter(|selected| selected.start < selected.end)
+                    .map(|selected| selected.start - start..selected.end - start)
+                    .collect();
+                self.render_diff_line(context, line, x, origin.x() + width, y);
+                self.render_row(context, row, &cursors, &selections, x, y);
+                if idx + 1 == rows.len() {
+                    let start = x + row.caret(usize::MAX);
+                    self.render_after_text(context, line, start, origin.x() + width, y);
+                }
+                y += line_height;
+            }
         }
-    }
 
-    pub fn submit_rect(&mut self, vertex: &RectVertex) {
-        if self.mode != RenderMode::Rect {
-            self.flush();
-            self.mode = RenderMode::Rect;
-        } else if !self.rect_batch.has_room() {
-            self.flush();
+        if frame.is_hovered() {
+            self.render_outline(context, origin, width, height);
         }
-        self.rect_batch.push(vertex);
     }
 
-    pub fn submit_character(&mut self, vertex: &TextVertex) {
-        if self.mode != RenderMode::Text {
-            self.flush();
-            self.mode = RenderMode::Text;
-        } else if !self.text_batch.has_room() {
-            self.flush();
+    /// draws the background of a row of a line changed in a diff, y is the baseline.
+    fn render_diff_line(
+        &mut self,
+        context: &mut RenderContext,
+        line: &Text<TextLine>,
+        left: f32,
+        right: f32,
+        y: f32,
+    ) {
+        if line.diff.is_none() {
+            return;
         }
-        self.text_batch.push(vertex);
+        let metrics = context.font_metrics();
+        let top = y - metrics.ascent;
+        let rect = Rect::with_position(vec2f(left, top), right - left, metrics.line_height())
+            .with_color(context.theme().diff_line);
+        self.render_rect(context, &rect);
     }
 
-    pub fn clear(&self) {
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+    /// draws what follows the text of a line: the lines hidden by its fold, then the message of
+    /// its diagnostic or else its blame. start is the end of the text and y is the baseline.
+    fn render_after_text(
+        &mut self,
+        context: &mut RenderContext,
+        line: &Text<TextLine>,
+        start: f32,
+        right: f32,
+        y: f32,
+    ) {
+        let mut start = start;
+        if let Some(hidden) = line.folded {
+            start = self.render_fold(context, hidden, start, y);
+        }
+        if let Some((message, severity)) = line.virtual_text.as_ref() {
+            let color = context.theme().severity(*severity);
+            self.render_virtual_text(context, message, color, start, right, y);
+        } else if let Some(blame) = line.blame.as_ref() {
+            let color = context.theme().line_number;
+            self.render_virtual_text(context, blame, color, start, right, y);
         }
     }
 
-    fn flush_rect(&self) {
-        let vertices = self.rect_batch.data.as_slice();
-        let len = self.rect_batch.len();
+    /// draws the number of lines hidden by a fold after the text of its first line, returns
+    /// where the drawn text ends.
+    fn render_fold(
+        &mut self,
+        context: &mut RenderContext,
+        hidden: usize,
+        start: f32,
+        y: f32,
+    ) -> f32 {
+        let size = context.font_size();
+        let advance = Self::tofu_advance(context, size);
+        let placeholder = format!("... {} lines", hidden);
+        let x = start + advance;
+        let color = context.theme().line_number;
+        let background = context.background();
+        self.render_str(context, &placeholder, x, y, color, background, size);
+        x + advance * placeholder.chars().count() as f32
+    }
 
-        if len == 0 {
+    /// draws a message after the text of a line, e.g. of a diagnostic, it is cut off at the
+    /// right edge of the frame. start is the end of the text and y is the baseline.
+    fn render_virtual_text(
+        &mut self,
+        context: &mut RenderContext,
+        message: &str,
+        color: Color,
+        start: f32,
+        right: f32,
+        y: f32,
+    ) {
+        let size = context.font_size();
+        let advance = Self::tofu_advance(context, size);
+        let x = start + advance * 2.0;
+        let fit = ((right - x) / advance).max(0.0) as usize;
+        let message: String = message.chars().take(fit).collect();
+        if message.is_empty() {
             return;
         }
-        self.rect_shader.bind();
-        unsafe {
-            gl_check!(gl::BindVertexArray(self.rect_vao));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo));
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
+        let background = context.background();
+        self.render_str(context, &message, x, y, color, background, size);
+    }
 
-            gl_check!(gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (len * std::mem::size_of::<RectVertex>()) as isize,
-                vertices.as_ptr() as *const _,
-                gl::STREAM_DRAW
-            ));
+    /// renders a line of text with its selection and cursors, y is the baseline.
+    fn render_row(
+        &mut self,
+        context: &mut RenderContext,
+        text: &TextLine,
+        cursors: &[usize],
+        selections: &[Range<usize>],
+        x: f32,
+        y: f32,
+    ) {
+        let metrics = context.font_metrics();
+        let top = y - metrics.ascent;
+        let size = context.font_size();
+        for selection in selections {
+            self.render_selection(context, text, selection, x, top, metrics.line_height());
+        }
 
-            gl_check!(gl::Enable(gl::BLEND));
-            gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ZERO, gl::ONE);
+        self.render_line(context, text, x, y, size);
 
-            gl_check!(gl::DrawElementsInstanced(
-                gl::TRIANGLES,
-                6,
-                gl::UNSIGNED_INT,
-                std::ptr::null() as *const _,
-                len as i32
-            ));
+        if !cursors.is_empty() {
+            self.render_cursors(context, text, cursors, x, top, size);
+        }
+    }
+
+    /// renders the cells of a terminal, cells of the same colors are drawn together. The
+    /// terminal covers the frames below it and is separated from them by a line.
+    fn render_terminal(
+        &mut self,
+        context: &mut RenderContext,
+        terminal: &Terminal,
+        origin: Vector2F,
+        width: f32,
+        height: f32,
+        active: bool,
+    ) {
+        let metrics = context.font_metrics();
+        let line_height = metrics.line_height();
+        let cell_width = context.cell_width();
+        let size = context.font_size();
+        let background = context.background();
+
+        let cover = Rect::with_position(origin, width, height).with_color(background);
+        self.render_rect(context, &cover);
+        if origin.y() > 0.0 {
+            let color = context.theme().line_number;
+            let separator = Rect::with_position(origin, width, 1.0).with_color(color);
+            self.render_rect(context, &separator);
+        }
+
+        for row in 0..terminal.grid().rows() {
+            let cells = match terminal.visible_line(row) {
+                Some(cells) => cells,
+                None => break,
+            };
+            let top = origin.y() + row as f32 * line_height;
+            let mut column = 0;
+            while column < cells.len() {
+                let selected = terminal.is_selected(row, column);
+                let attributes = cells[column].attributes;
+                let (fg, bg) = Self::cell_colors(context, &attributes, selected);
+                // the run of cells drawn the same way.
+                let mut text = String::new();
+                let start = column;
+                while column < cells.len()
+                    && cells[column].attributes == attributes
+                    && terminal.is_selected(row, column) == selected
+                {
+                    text.push(cells[column].ch);
+                    column += 1;
+                }
 
-            gl_check!(gl::Disable(gl::BLEND));
+                let x = origin.x() + start as f32 * cell_width;
+                let run_width = (column - start) as f32 * cell_width;
+                if bg != background {
+                    let rect =
+                        Rect::with_position(vec2f(x, top), run_width, line_height).with_color(bg);
+                    self.render_rect(context, &rect);
+                }
+                if !text.trim_end().is_empty() {
+                    self.render_str(context, &text, x, top + metrics.ascent, fg, bg, size);
+                }
+                if attributes.underline {
+                    let y = top + metrics.ascent + line_height * 0.1;
+                    let rect = Rect::with_position(vec2f(x, y), run_width, 1.0).with_color(fg);
+                    self.render_rect(context, &rect);
+                }
+            }
+        }
 
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
-            gl_check!(gl::BindVertexArray(0));
+        if let Some(cursor) = terminal.cursor().filter(|_| active) {
+            let x = origin.x() + cursor.column as f32 * cell_width;
+            let y = origin.y() + cursor.row as f32 * line_height;
+            self.render_cursor(context, x, y, line_height);
         }
-        self.rect_shader.unbind();
     }
 
-    fn flush_text(&self) {
-        let vertices = self.text_batch.data.as_slice();
-        let len = self.text_batch.len();
-        if len == 0 {
-            return;
+    /// the foreground and background of a terminal cell. Bold text of the first 8 colors is
+    /// drawn in their bright versions.
+    fn cell_colors(
+        context: &RenderContext,
+        attributes: &Attributes,
+        selected: bool,
+    ) -> (Color, Color) {
+        let theme = context.theme();
+        let color = |color: CellColor, default: Color, bold: bool| match color {
+            CellColor::Default => default,
+            CellColor::Indexed(idx) if bold && idx < 8 => theme.terminal_color(idx + 8),
+            CellColor::Indexed(idx) => theme.terminal_color(idx),
+            CellColor::Rgb(r, g, b) => {
+                Color::rgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+            }
+        };
+        let fg = color(attributes.foreground, theme.foreground, attributes.bold);
+        let bg = color(attributes.background, theme.background, false);
+        let (fg, bg) = if attributes.inverse {
+            (bg, fg)
+        } else {
+            (fg, bg)
+        };
+        if selected {
+            (fg, theme.selection)
+        } else {
+            (fg, bg)
         }
+    }
 
-        self.text_shader.bind();
-        unsafe {
-            gl_check!(gl::BindVertexArray(self.text_vao));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, self.text_vbo));
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo));
+    /// draws a border along the inside of an area.
+    pub fn render_outline(
+        &mut self,
+        context: &RenderContext,
+        origin: Vector2F,
+        width: f32,
+        height: f32,
+    ) {
+        static OUTLINE_WIDTH: f32 = 3.0;
+        let color = context.highlight_color();
+        let edges = [
+            (origin, width, OUTLINE_WIDTH),
+            (
+                origin + vec2f(0.0, height - OUTLINE_WIDTH),
+                width,
+                OUTLINE_WIDTH,
+            ),
+            (origin, OUTLINE_WIDTH, height),
+            (
+                origin + vec2f(width - OUTLINE_WIDTH, 0.0),
+                OUTLINE_WIDTH,
+                height,
+            ),
+        ];
+        for (pos, width, height) in edges.iter() {
+            let rect = Rect::with_position(*pos, *width, *height).with_color(color);
+            self.render_rect(context, &rect);
+        }
+    }
 
-            gl_check!(gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (len * std::mem::size_of::<TextVertex>()) as isize,
-                vertices.as_ptr() as *const _,
-                gl::STREAM_DRAW
-            ));
+    /// draws the text of a prompt on a bar along the bottom of an area.
+    pub fn render_prompt(
+        &mut self,
+        context: &mut RenderContext,
+        text: &str,
+        origin: Vector2F,
+        width: f32,
+        height: f32,
+    ) {
+        let metrics = context.font_metrics();
+        let top = origin.y() + height - metrics.line_height();
+        let color = context.selection_color();
+        let rect = Rect::with_position(vec2f(origin.x(), top), width, metrics.line_height())
+            .with_color(color);
+        self.render_rect(context, &rect);
+
+        let size = context.font_size();
+        let y = top + metrics.ascent;
+        let foreground = context.foreground();
+        self.render_str(context, text, origin.x(), y, foreground, color, size);
+    }
+
+    /// draws the matches of a completion below point, the top left of the completed word. The
+    /// popup is moved above the word when there isn't room below it.
+    pub fn render_completion(
+        &mut self,
+        context: &mut RenderContext,
+        popup: &CompletionPopup,
+        point: Vector2F,
+        window: Vector2F,
+    ) {
+        let metrics = context.font_metrics();
+        let line_height = metrics.line_height();
+        let size = context.font_size();
+        let visible = popup.visible();
+        let rows: Vec<(String, String)> = popup
+            .matches()
+            .skip(visible.start)
+            .take(visible.len())
+            .map(|item| {
+                let detail = item.detail.as_deref().unwrap_or_else(|| item.kind.name());
+                (item.label.clone(), detail.to_string())
+            })
+            .collect();
+        if rows.is_empty() {
+            return;
+        }
 
-            gl_check!(gl::Enable(gl::BLEND));
-            gl::BlendFuncSeparate(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR, gl::ZERO, gl::ONE);
+        // the labels are aligned and the details follow them after a margin.
+        let margin = Self::tofu_advance(context, size);
+        let width = |context: &mut RenderContext, text: &str| {
+            Self::shape_line(context, text, &[], size).caret(usize::MAX)
+        };
+        let mut label_width: f32 = 0.0;
+        let mut detail_width: f32 = 0.0;
+        for (label, detail) in rows.iter() {
+            label_width = label_width.max(width(context, label));
+            detail_width = detail_width.max(width(context, detail));
+        }
+        let popup_width = label_width + detail_width + margin * 3.0;
+        let popup_height = rows.len() as f32 * line_height;
+
+        let below = point.y() + line_height;
+        let top = if below + popup_height > window.y() && point.y() >= popup_height {
+            point.y() - popup_height
+        } else {
+            below
+        };
+        let left = point.x().min(window.x() - popup_width).max(0.0);
 
-            gl_check!(gl::DrawElementsInstanced(
-                gl::TRIANGLES,
-                6,
-                gl::UNSIGNED_INT,
-                std::ptr::null() as *const _,
-                len as i32
-            ));
+        let background = context.theme().popup;
+        let rect =
+            Rect::with_position(vec2f(left, top), popup_width, popup_height).with_color(background);
+        self.render_rect(context, &rect);
+        let selected = popup.selected_index() - visible.start;
+        let rect = Rect::with_position(
+            vec2f(left, top + selected as f32 * line_height),
+            popup_width,
+            line_height,
+        )
+        .with_color(context.selection_color());
+        self.render_rect(context, &rect);
 
-            gl_check!(gl::Disable(gl::BLEND));
+        let foreground = context.foreground();
+        let dimmed = context.theme().line_number;
+        for (idx, (label, detail)) in rows.iter().enumerate() {
+            let y = top + idx as f32 * line_height + metrics.ascent;
+            let x = left + margin;
+            self.render_str(context, label, x, y, foreground, background, size);
+            let x = x + label_width + margin;
+            self.render_str(context, detail, x, y, dimmed, background, size);
+        }
+    }
 
-            gl_check!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
-            gl_check!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
-            gl_check!(gl::BindVertexArray(0));
+    pub fn submit_rect(&mut self, vertex: &RectVertex) {
+        if self.mode != RenderMode::Rect {
+            self.flush();
+            self.mode = RenderMode::Rect;
+        } else if !self.rect_batch.has_room() {
+            self.flush();
         }
-        self.text_shader.unbind();
+        self.rect_batch.push(vertex);
+    }
+
+    pub fn submit_character(&mut self, vertex: &TextVertex) {
+        if self.mode != RenderMode::Text {
+            self.flush();
+            self.mode = RenderMode::Text;
+        } else if !self.text_batch.has_room() {
+            self.flush();
+        }
+        self.text_batch.push(vertex);
+    }
+
+    pub fn clear(&mut self) {
+        self.atlas.begin_frame();
+        self.backend.clear(self.clear_color);
     }
 
     pub fn flush(&mut self) {
         use RenderMode::*;
         match self.mode {
             Rect => {
-                self.flush_rect();
+                if self.rect_batch.len() != 0 {
+                    self.backend.draw_rects(self.rect_batch.data.as_slice());
+                }
                 self.rect_batch.clear();
             }
             Text => {
-                self.flush_text();
+                if self.text_batch.len() != 0 {
+                    let textures = self.atlas.textures();
+                    self.backend
+                        .draw_text(self.text_batch.data.as_slice(), textures.as_slice());
+                }
                 self.text_batch.clear();
             }
             None => {}
         }
     }
 
-    fn position_line(line: &Text<TextLine>, font: &Font) -> TextLine {
-        // use crate::eucd::vec2;
-        let mut glyphs = Vec::new();
+    pub fn position_line(context: &mut RenderContext, line: &Text<TextLine>) -> TextLine {
+        let size = context.font_size();
+        // the spans of diagnostics are drawn over the other styles.
+        let (marks, styles): (Vec<StyleSpan>, Vec<StyleSpan>) = line
+            .styles
+            .iter()
+            .partition(|span| span.style().severity().is_some());
+        let styles = Self::highlight(context, line, styles);
+        let styles = Self::mark_diff(context, line, styles);
+        let styles = Self::mark_diagnostics(context, line, &marks, styles);
+        Self::shape_line(context, line.text.as_str(), styles.as_slice(), size)
+    }
 
-        let mut x = 0.0;
-        for ch in line.text.chars() {
-            if let Some(info) = font.info(ch) {
-                let glyph = Glyph { ch, x };
+    /// the style spans of a line with the highlighted parts in the styles of their highlights.
+    fn highlight(
+        context: &mut RenderContext,
+        line: &Text<TextLine>,
+        base: Vec<StyleSpan>,
+    ) -> Vec<StyleSpan> {
+        if line.highlights.is_empty() {
+            return base;
+        }
 
-                glyphs.push(glyph);
-                x += info.advance.x();
+        let len = line.text.len();
+        let mut styles = vec![StyleId(0); len];
+        for span in base.iter() {
+            for style in styles[span.start().min(len)..span.end().min(len)].iter_mut() {
+                *style = span.style();
+            }
+        }
+        for (span, highlight) in line.highlights.iter() {
+            let id = context.highlight_style(*highlight);
+            for style in styles[span.start.min(len)..span.end.min(len)].iter_mut() {
+                *style = id;
             }
         }
 
-        TextLine::new(glyphs, line.styles.clone())
+        let mut spans: Vec<StyleSpan> = Vec::new();
+        let mut start = 0;
+        for idx in 1..=len {
+            if idx == len || styles[idx] != styles[start] {
+                spans.push(StyleSpan::new(styles[start], Span::new(start, idx)));
+                start = idx;
+            }
+        }
+        spans
+    }
+
+    /// the style spans of a line changed in a diff split where its characters changed, the
+    /// changed parts are drawn with the diff style of their style.
+    fn mark_diff(
+        context: &mut RenderContext,
+        line: &Text<TextLine>,
+        styles: Vec<StyleSpan>,
+    ) -> Vec<StyleSpan> {
+        let changed = match line.diff.as_ref() {
+            Some(changed) if !changed.is_empty() => changed,
+            _ => return styles,
+        };
+
+        let len = line.text.len();
+        let mut bases = vec![StyleId(0); len];
+        for span in styles.iter() {
+            for base in bases[span.start().min(len)..span.end().min(len)].iter_mut() {
+                *base = span.style();
+            }
+        }
+        let mut marks = vec![false; len];
+        for span in changed.iter() {
+            for mark in marks[span.start.min(len)..span.end.min(len)].iter_mut() {
+                *mark = true;
+            }
+        }
+
+        let mut spans: Vec<StyleSpan> = Vec::new();
+        let mut start = 0;
+        for idx in 1..=len {
+            if idx < len && bases[idx] == bases[start] && marks[idx] == marks[start] {
+                continue;
+            }
+            let style = if marks[start] {
+                context.diff_style(bases[start])
+            } else {
+                bases[start]
+            };
+            spans.push(StyleSpan::new(style, Span::new(start, idx)));
+            start = idx;
+        }
+        spans
+    }
+
+    /// the style spans of a line split where the spans of diagnostics mark it, the marked parts
+    /// are drawn with the diagnostic style of their style. Overlapping diagnostics use the
+    /// worst severity.
+    fn mark_diagnostics(
+        context: &mut RenderContext,
+        line: &Text<TextLine>,
+        diagnostics: &[StyleSpan],
+        styles: Vec<StyleSpan>,
+    ) -> Vec<StyleSpan> {
+        if diagnostics.is_empty() {
+            return styles;
+        }
+
+        let len = line.text.len();
+        let mut bases = vec![StyleId(0); len];
+        for span in styles.iter() {
+            for base in bases[span.start().min(len)..span.end().min(len)].iter_mut() {
+                *base = span.style();
+            }
+        }
+        let mut marks: Vec<Option<Severity>> = vec![None; len];
+        for span in diagnostics.iter() {
+            let severity = match span.style().severity() {
+                Some(severity) => severity,
+                None => continue,
+            };
+            for mark in marks[span.start().min(len)..span.end().min(len)].iter_mut() {
+                *mark = Some(mark.map_or(severity, |mark| mark.min(severity)));
+            }
+        }
+
+        let mut spans: Vec<StyleSpan> = Vec::new();
+        let mut start = 0;
+        for idx in 1..=len {
+            if idx < len && bases[idx] == bases[start] && marks[idx] == marks[start] {
+                continue;
+            }
+            let style = match marks[start] {
+                Some(severity) => context.diagnostic_style(bases[start], severity),
+                None => bases[start],
+            };
+            spans.push(StyleSpan::new(style, Span::new(start, idx)));
+            start = idx;
+        }
+        spans
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        self.atlas.release(self.backend.as_mut());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::renderer::backend::to_pixel;
+    use crate::renderer::software::SoftwareBackend;
+
+    #[test]
+    fn tabs_advance_to_tab_stops() {
+        let mut context = RenderContext::for_tests();
+        let size = context.font_size();
+        let mut x_of = |text: &str, ch: char| {
+            let line = Renderer::shape_line(&mut context, text, &[], size);
+            let cluster = text.find(ch).unwrap();
+            let glyph = line.glyphs().iter().find(|g| g.cluster == cluster).unwrap();
+            (glyph.x, glyph.advance)
+        };
+
+        let (x, advance) = x_of("a\tx", 'x');
+        assert!((x - 4.0 * advance).abs() < 0.01);
+        let (x, advance) = x_of("abc\tx", 'x');
+        assert!((x - 4.0 * advance).abs() < 0.01);
+        // a tab at a stop advances to the next one.
+        let (x, advance) = x_of("abcd\tx", 'x');
+        assert!((x - 8.0 * advance).abs() < 0.01);
+        let (x, advance) = x_of("\t\tx", 'x');
+        assert!((x - 8.0 * advance).abs() < 0.01);
+    }
+
+    #[test]
+    fn glyphs_of_a_style_are_grouped() {
+        let glyph = |x: f32, style: usize| Glyph {
+            id: 0,
+            cluster: 0,
+            x,
+            y: 0.0,
+            advance: 10.0,
+            font: Some(0),
+            style: StyleId(style),
+        };
+        let glyphs = vec![
+            glyph(0.0, 1),
+            glyph(10.0, 1),
+            glyph(20.0, 2),
+            glyph(30.0, 1),
+        ];
+        let line = TextLine::new(glyphs, Vec::new(), Vec::new());
+        let runs = Renderer::style_runs(&line);
+        let expected = vec![
+            (StyleId(1), 0.0, 20.0),
+            (StyleId(2), 20.0, 30.0),
+            (StyleId(1), 30.0, 40.0),
+        ];
+        assert_eq!(runs, expected);
+        assert!(
+            Renderer::style_runs(&TextLine::new(Vec::new(), Vec::new(), Vec::new())).is_empty()
+        );
+    }
+
+    #[test]
+    fn runs_are_shaped_with_the_font_of_their_style() {
+        let mut context = RenderContext::for_tests();
+        let bold = Style::new(0, Color::black(), Color::white(), false, false).with_bold(true);
+        let bold_id = bold.id();
+        context.register_style(bold);
+        let size = context.font_size();
+
+        let styles = [StyleSpan::new(bold_id, Span::new(0, 3))];
+        let line = Renderer::shape_line(&mut context, "let x", &styles, size);
+        let fonts: Vec<Option<usize>> = line.glyphs().iter().map(|glyph| glyph.font).collect();
+        let (bold_font, font) = (
+            Some(FontCollection::DEFAULT_BOLD_FONT),
+            Some(FontCollection::DEFAULT_FONT),
+        );
+        assert_eq!(fonts, vec![bold_font, bold_font, bold_font, font, font]);
+
+        // the runs of the styles follow each other.
+        let runs = Renderer::style_runs(&line);
+        assert_eq!(runs.len(), 2);
+        assert_eq!((runs[0].0, runs[1].0), (bold_id, StyleId(0)));
+        assert_eq!(runs[0].1, 0.0);
+        assert!((runs[0].2 - line.glyphs()[3].x).abs() < 0.01);
+        assert!((runs[1].1 - runs[0].2).abs() < 0.01);
+    }
+
+    #[test]
+    fn selected_line_break() {
+        let mut context = RenderContext::for_tests();
+        let mut renderer = Renderer::new(Box::new(SoftwareBackend::new(40, 10)));
+        renderer.init().unwrap();
+        renderer.update_perspective(40, 10);
+        renderer.set_clear_color(Color::black());
+        renderer.clear();
+
+        // the line break of an empty line is as wide as a character.
+        let empty = TextLine::new(Vec::new(), Vec::new(), vec![0.0]);
+        renderer.render_selection(&context, &empty, &(0..1), 0.0, 0.0, 10.0);
+        renderer.flush();
+        let image = renderer.backend_mut().read_pixels();
+        let width = Renderer::tofu_advance(&context, context.font_size()).floor() as u32;
+        let selected = to_pixel(context.selection_color());
+        assert_eq!(image.pixel(0, 5), selected);
+        assert_eq!(image.pixel(width - 1, 5), selected);
+        assert_eq!(image.pixel(width + 1, 5), to_pixel(Color::black()));
+
+        // the line break of a line with text is drawn after its last character.
+        let size = context.font_size();
+        let line = Renderer::shape_line(&mut context, "ab", &[], size);
+        let end = line.caret(2);
+        renderer.clear();
+        renderer.render_selection(&context, &line, &(2..3), 0.0, 0.0, 10.0);
+        renderer.flush();
+        let image = renderer.backend_mut().read_pixels();
+        assert_eq!(
+            image.pixel(end.floor() as u32 - 1, 5),
+            to_pixel(Color::black())
+        );
+        assert_eq!(image.pixel(end.ceil() as u32, 5), selected);
+        assert_eq!(image.pixel((end + width as f32) as u32 - 1, 5), selected);
+    }
+
+    #[test]
+    fn split_characters_are_missing_glyphs() {
+        let mut context = RenderContext::for_tests();
+        let size = context.font_size();
+        // the span ends inside the two bytes of the accent.
+        let styles = [StyleSpan::new(StyleId(0), Span::new(0, 2))];
+        let line = Renderer::shape_line(&mut context, "aéb", &styles, size);
+        let clusters: Vec<usize> = line.glyphs().iter().map(|glyph| glyph.cluster).collect();
+        assert_eq!(clusters, vec![0, 1, 3]);
+        assert_eq!(line.glyphs()[1].font, None);
+        assert!(line.glyphs()[2].x > line.glyphs()[1].x);
     }
 }
@@ -0,0 +1,285 @@
+// Software Render Backend.
+// Rasterizes the batches of the renderer on the cpu into an rgba image. It doesn't need a
+// window or a gpu so the ui can be rendered headless, for tests for example. Blending follows
+// the gl backend: rects are alpha blended and glyphs use the subpixel coverage of the atlas.
+
+use std::collections::HashMap;
+
+use super::backend::{to_channel, to_pixel, RenderBackend, RgbaImage, TextureId, TextureSize};
+use super::{Color, RectVertex, RenderError, TextVertex};
+
+/// bytes per pixel of an atlas texture.
+const TEXTURE_CHANNELS: usize = 3;
+
+#[inline]
+fn from_channel(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+struct Texture {
+    size: TextureSize,
+    data: Vec<u8>,
+}
+
+impl Texture {
+    fn new(size: TextureSize) -> Self {
+        Self {
+            size,
+            data: vec![0; size.0 as usize * size.1 as usize * TEXTURE_CHANNELS],
+        }
+    }
+
+    /// nearest sample of the texture at uv, coordinates outside of the texture are black.
+    fn sample(&self, u: f32, v: f32) -> [f32; 3] {
+        let x = (u * self.size.0 as f32).floor();
+        let y = (v * self.size.1 as f32).floor();
+        if x < 0.0 || y < 0.0 || x >= self.size.0 as f32 || y >= self.size.1 as f32 {
+            return [0.0; 3];
+        }
+
+        let idx = (y as usize * self.size.0 as usize + x as usize) * TEXTURE_CHANNELS;
+        [
+            from_channel(self.data[idx]),
+            from_channel(self.data[idx + 1]),
+            from_channel(self.data[idx + 2]),
+        ]
+    }
+}
+
+pub struct SoftwareBackend {
+    target: RgbaImage,
+    textures: HashMap<TextureId, Texture>,
+    next_texture: TextureId,
+}
+
+impl SoftwareBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            target: RgbaImage::new(width, height),
+            textures: HashMap::new(),
+            next_texture: 1,
+        }
+    }
+
+    /// the rendered image.
+    pub fn image(&self) -> &RgbaImage {
+        &self.target
+    }
+
+    /// the pixels covered by a quad. A pixel is covered when its center is inside the quad.
+    fn covered(&self, x: f32, y: f32, width: f32, height: f32) -> (u32, u32, u32, u32) {
+        let clamp = |value: f32, max: u32| (value - 0.5).ceil().max(0.0).min(max as f32) as u32;
+        (
+            clamp(x, self.target.width()),
+            clamp(y, self.target.height()),
+            clamp(x + width, self.target.width()),
+            clamp(y + height, self.target.height()),
+        )
+    }
+
+    /// blends color into the pixel, with a separate factor for each color channel. The
+    /// alpha of the target is left unchanged.
+    fn blend(&mut self, x: u32, y: u32, color: Color, factor: [f32; 3]) {
+        let dst = self.target.pixel(x, y);
+        let src = [color.r, color.g, color.b];
+        let mut out = dst;
+        for channel in 0..3 {
+            let value = src[channel] * factor[channel]
+                + from_channel(dst[channel]) * (1.0 - factor[channel]);
+            out[channel] = to_channel(value);
+        }
+        self.target.set_pixel(x, y, out);
+    }
+}
+
+impl RenderBackend for SoftwareBackend {
+    fn init(&mut self) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    fn resize(&mut self, width: i32, height: i32) {
+        self.target = RgbaImage::new(width.max(0) as u32, height.max(0) as u32);
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.target.fill(color);
+    }
+
+    fn draw_rects(&mut self, rects: &[RectVertex]) {
+        for rect in rects {
+            let v = &rect.vertex;
+            let (x0, y0, x1, y1) = self.covered(v.x, v.y, v.z, v.w);
+            let alpha = rect.bg_color.a;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    self.blend(x, y, rect.bg_color, [alpha; 3]);
+                }
+            }
+        }
+    }
+
+    fn draw_text(&mut self, glyphs: &[TextVertex], textures: &[TextureId]) {
+        for glyph in glyphs {
+            let texture = match textures
+                .get(glyph.texture_id as usize)
+                .and_then(|id| self.textures.get(id))
+            {
+                Some(texture) => texture,
+                None => continue,
+            };
+
+            let v = &glyph.vertex;
+            let uv = &glyph.tex_info;
+            if v.z <= 0.0 || v.w <= 0.0 {
+                continue;
+            }
+
+            let (x0, y0, x1, y1) = self.covered(v.x, v.y, v.z, v.w);
+            let mut coverage = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let u = uv.x + uv.z * (x as f32 + 0.5 - v.x) / v.z;
+                    let v = uv.y + uv.w * (y as f32 + 0.5 - v.y) / v.w;
+                    coverage.push((x, y, texture.sample(u, v)));
+                }
+            }
+
+            for (x, y, factor) in coverage {
+                self.blend(x, y, glyph.fg_color, factor);
+            }
+        }
+    }
+
+    fn create_texture(&mut self, size: TextureSize) -> TextureId {
+        let id = self.next_texture;
+        self.next_texture += 1;
+        self.textures.insert(id, Texture::new(size));
+        id
+    }
+
+    fn grow_texture(
+        &mut self,
+        texture: TextureId,
+        old_size: TextureSize,
+        new_size: TextureSize,
+    ) -> TextureId {
+        let id = self.create_texture(new_size);
+        if let Some(old) = self.textures.remove(&texture) {
+            let new = self.textures.get_mut(&id).unwrap();
+            let width = old_size.0.min(new_size.0) as usize * TEXTURE_CHANNELS;
+            for row in 0..old_size.1.min(new_size.1) as usize {
+                let src = row * old.size.0 as usize * TEXTURE_CHANNELS;
+                let dst = row * new.size.0 as usize * TEXTURE_CHANNELS;
+                new.data[dst..dst + width].copy_from_slice(&old.data[src..src + width]);
+            }
+        }
+        id
+    }
+
+    fn upload_texture(
+        &mut self,
+        texture: TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        data: &[u8],
+    ) {
+        let texture = match self.textures.get_mut(&texture) {
+            Some(texture) => texture,
+            None => return,
+        };
+
+        let row_len = width as usize * TEXTURE_CHANNELS;
+        for row in 0..height as usize {
+            let ty = y as usize + row;
+            if ty >= texture.size.1 as usize || x + width > texture.size.0 {
+                break;
+            }
+            let dst = (ty * texture.size.0 as usize + x as usize) * TEXTURE_CHANNELS;
+            let src = row * row_len;
+            texture.data[dst..dst + row_len].copy_from_slice(&data[src..src + row_len]);
+        }
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        self.textures.remove(&texture);
+    }
+
+    fn read_pixels(&mut self) -> RgbaImage {
+        self.target.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::renderer::vec4;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32, color: Color) -> RectVertex {
+        RectVertex {
+            vertex: vec4(x, y, width, height),
+            bg_color: color,
+        }
+    }
+
+    #[test]
+    fn clear_fills_target() {
+        let mut backend = SoftwareBackend::new(4, 3);
+        backend.clear(Color::red());
+        let image = backend.image();
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(3, 2), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn rects_are_clipped_and_blended() {
+        let mut backend = SoftwareBackend::new(4, 4);
+        backend.clear(Color::white());
+        backend.draw_rects(&[
+            rect(-2.0, -2.0, 4.0, 4.0, Color::black()),
+            rect(2.0, 2.0, 10.0, 10.0, Color::rgba(0.0, 0.0, 0.0, 0.5)),
+        ]);
+
+        let image = backend.image();
+        assert_eq!(image.pixel(1, 1), [0, 0, 0, 255]);
+        assert_eq!(image.pixel(2, 1), [255, 255, 255, 255]);
+        assert_eq!(image.pixel(3, 3), [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn text_uses_coverage() {
+        let mut backend = SoftwareBackend::new(2, 1);
+        backend.clear(Color::white());
+
+        // a 2x1 texture, left texel is fully covered and the right is empty.
+        let texture = backend.create_texture((2, 1));
+        backend.upload_texture(texture, 0, 0, 2, 1, &[255, 255, 255, 0, 0, 0]);
+
+        let glyph = TextVertex {
+            vertex: vec4(0.0, 0.0, 2.0, 1.0),
+            fg_color: Color::blue(),
+            tex_info: vec4(0.0, 0.0, 1.0, 1.0),
+            texture_id: 0.0,
+        };
+        backend.draw_text(&[glyph], &[texture]);
+
+        let image = backend.image();
+        assert_eq!(image.pixel(0, 0), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn grow_keeps_contents() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        let texture = backend.create_texture((2, 1));
+        backend.upload_texture(texture, 1, 0, 1, 1, &[1, 2, 3]);
+
+        let grown = backend.grow_texture(texture, (2, 1), (2, 2));
+        assert!(backend.textures.get(&texture).is_none());
+
+        let grown = &backend.textures[&grown];
+        assert_eq!(&grown.data[3..6], &[1, 2, 3]);
+        assert_eq!(grown.data.len(), 2 * 2 * TEXTURE_CHANNELS);
+    }
+}
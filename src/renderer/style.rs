@@ -1,19 +1,41 @@
 use super::Color;
+use crate::font::FontCollection;
+use kea::lsp::Severity;
 use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub struct StyleId(pub usize);
 
+/// the severities with a reserved style, in the order of their ids from the last one down.
+pub const SEVERITIES: [Severity; 4] = [
+    Severity::Error,
+    Severity::Warning,
+    Severity::Information,
+    Severity::Hint,
+];
+
 impl StyleId {
     fn next() -> Self {
         static TOKEN: AtomicUsize = AtomicUsize::new(0);
         Self(TOKEN.fetch_add(1, Ordering::SeqCst))
     }
+
+    /// the undercurl of text marked by a diagnostic, it is drawn over the style of the text.
+    /// The ids are reserved so lines can be marked without a render context.
+    pub fn diagnostic(severity: Severity) -> Self {
+        let idx = SEVERITIES.iter().position(|s| *s == severity).unwrap();
+        Self(usize::MAX - idx)
+    }
+
+    /// the severity of a diagnostic style, None for other styles.
+    pub fn severity(self) -> Option<Severity> {
+        SEVERITIES.get(usize::MAX - self.0).copied()
+    }
 }
 
 /// Span of a string in bytes [start..end)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -70,7 +92,13 @@ pub struct Style {
     fg_color: Color,
     bg_color: Color,
     italic: bool,
+    bold: bool,
     underline: bool,
+    strikethrough: bool,
+    /// a wavy underline, used to mark diagnostics.
+    undercurl: bool,
+    /// color of the underline, strikethrough, and undercurl. The text color is used when None.
+    decoration_color: Option<Color>,
 }
 
 impl Style {
@@ -87,7 +115,48 @@ impl Style {
             fg_color,
             bg_color,
             italic,
+            bold: false,
             underline,
+            strikethrough: false,
+            undercurl: false,
+            decoration_color: None,
+        }
+    }
+
+    pub fn with_bold(mut self, bold: bool) -> Self {
+        self.bold = bold;
+        self
+    }
+
+    pub fn with_strikethrough(mut self, strikethrough: bool) -> Self {
+        self.strikethrough = strikethrough;
+        self
+    }
+
+    pub fn with_undercurl(mut self, undercurl: bool) -> Self {
+        self.undercurl = undercurl;
+        self
+    }
+
+    pub fn with_decoration_color(mut self, color: Color) -> Self {
+        self.decoration_color = Some(color);
+        self
+    }
+
+    /// the style marking the diagnostics of a severity, an undercurl over the text.
+    pub fn diagnostic(severity: Severity, color: Color) -> Self {
+        let mut style = Self::new(0, color, color, false, false)
+            .with_undercurl(true)
+            .with_decoration_color(color);
+        style.id = StyleId::diagnostic(severity);
+        style
+    }
+
+    /// a copy of the style with its own id.
+    pub fn derive(&self) -> Self {
+        Self {
+            id: StyleId::next(),
+            ..self.clone()
         }
     }
 
@@ -95,6 +164,15 @@ impl Style {
         self.id
     }
 
+    pub fn set_colors(&mut self, fg_color: Color, bg_color: Color) {
+        self.fg_color = fg_color;
+        self.bg_color = bg_color;
+    }
+
+    pub fn set_decoration_color(&mut self, color: Color) {
+        self.decoration_color = Some(color);
+    }
+
     pub fn font_idx(&self) -> usize {
         self.font_idx
     }
@@ -111,9 +189,40 @@ impl Style {
         self.italic
     }
 
+    pub fn bold(&self) -> bool {
+        self.bold
+    }
+
     pub fn underline(&self) -> bool {
         self.underline
     }
+
+    pub fn strikethrough(&self) -> bool {
+        self.strikethrough
+    }
+
+    pub fn undercurl(&self) -> bool {
+        self.undercurl
+    }
+
+    pub fn decoration_color(&self) -> &Color {
+        self.decoration_color.as_ref().unwrap_or(&self.fg_color)
+    }
+
+    /// does the style draw anything other than the glyphs.
+    pub fn has_decoration(&self) -> bool {
+        self.underline || self.strikethrough || self.undercurl
+    }
+
+    /// the font used to render text of this style. Bold and italic styles use the
+    /// matching face of the configured family.
+    pub fn font(&self) -> usize {
+        if self.bold || self.italic {
+            FontCollection::face_for_style(self.bold, self.italic)
+        } else {
+            self.font_idx
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -135,4 +244,49 @@ impl StyleMap {
     pub fn style(&self, id: &StyleId) -> Option<&Style> {
         self.styles.get(id)
     }
+
+    pub fn style_mut(&mut self, id: &StyleId) -> Option<&mut Style> {
+        self.styles.get_mut(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn font_of_style() {
+        let (fg, bg) = (Color::black(), Color::white());
+        let font = |style: Style| style.font();
+        assert_eq!(
+            font(Style::new(0, fg, bg, false, false)),
+            FontCollection::DEFAULT_FONT
+        );
+        assert_eq!(
+            font(Style::new(0, fg, bg, true, false)),
+            FontCollection::DEFAULT_ITALIC_FONT
+        );
+        let bold = Style::new(0, fg, bg, false, false).with_bold(true);
+        assert_eq!(font(bold.clone()), FontCollection::DEFAULT_BOLD_FONT);
+        let bold_italic = Style::new(0, fg, bg, true, false).with_bold(true);
+        assert_eq!(font(bold_italic), FontCollection::DEFAULT_BOLD_ITALIC_FONT);
+        // a derived style keeps the font of its style.
+        assert_eq!(font(bold.derive()), FontCollection::DEFAULT_BOLD_FONT);
+        assert_eq!(font(Style::new(5, fg, bg, false, false)), 5);
+    }
+
+    #[test]
+    fn diagnostic_ids() {
+        for severity in SEVERITIES.iter() {
+            let style = Style::diagnostic(*severity, Color::red());
+            assert_eq!(style.id().severity(), Some(*severity));
+            assert!(style.undercurl());
+        }
+        assert_eq!(
+            Style::new(0, Color::red(), Color::red(), false, false)
+                .id()
+                .severity(),
+            None
+        );
+    }
 }
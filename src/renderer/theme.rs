@@ -0,0 +1,185 @@
+// Themes.
+// The colors the editor is drawn with. Themes are yaml files named after the color scheme, a
+// color is written as `#rrggbb` or `#rrggbbaa`. Colors a theme leaves out keep their defaults.
+
+use kea::lsp::Severity;
+
+use crate::core::{Highlight, LineChange};
+use serde::{Deserialize, Deserializer};
+
+use super::Color;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "color")]
+    pub foreground: Color,
+    #[serde(deserialize_with = "color")]
+    pub background: Color,
+    /// the background of selected text and of prompts.
+    #[serde(deserialize_with = "color")]
+    pub selection: Color,
+    /// the outline of a frame a file is dragged over.
+    #[serde(deserialize_with = "color")]
+    pub highlight: Color,
+    /// the line numbers in the gutter.
+    #[serde(deserialize_with = "color")]
+    pub line_number: Color,
+    /// the background of popups, e.g. the completion list.
+    #[serde(deserialize_with = "color")]
+    pub popup: Color,
+    /// the marks of diagnostics by their severity.
+    #[serde(deserialize_with = "color")]
+    pub error: Color,
+    #[serde(deserialize_with = "color")]
+    pub warning: Color,
+    #[serde(deserialize_with = "color")]
+    pub info: Color,
+    #[serde(deserialize_with = "color")]
+    pub hint: Color,
+    /// the colors of the syntax of the text.
+    #[serde(deserialize_with = "color")]
+    pub keyword: Color,
+    #[serde(deserialize_with = "color", rename = "type")]
+    pub type_name: Color,
+    #[serde(deserialize_with = "color")]
+    pub function: Color,
+    #[serde(deserialize_with = "color")]
+    pub number: Color,
+    #[serde(deserialize_with = "color")]
+    pub string: Color,
+    #[serde(deserialize_with = "color")]
+    pub comment: Color,
+    /// the marks in the gutter of lines changed since the version of the file in git.
+    #[serde(deserialize_with = "color")]
+    pub added: Color,
+    #[serde(deserialize_with = "color")]
+    pub modified: Color,
+    #[serde(deserialize_with = "color")]
+    pub removed: Color,
+    /// the background of changed lines in a diff, and of their changed characters.
+    #[serde(deserialize_with = "color")]
+    pub diff_line: Color,
+    #[serde(deserialize_with = "color")]
+    pub diff_text: Color,
+    /// the empty rows lining up the two sides of a diff.
+    #[serde(deserialize_with = "color")]
+    pub filler: Color,
+    /// the 16 colors of terminals, black, red, green, yellow, blue, magenta, cyan and white
+    /// followed by their bright versions.
+    #[serde(deserialize_with = "colors")]
+    pub terminal: Vec<Color>,
+}
+
+/// the colors of terminals of the default theme.
+const TERMINAL_COLORS: [&str; 16] = [
+    "#000000", "#cd3131", "#00bc00", "#949800", "#0451a5", "#bc05bc", "#0598bc", "#555555",
+    "#666666", "#cd3131", "#14ce14", "#b5ba00", "#0451a5", "#bc05bc", "#0598bc", "#a5a5a5",
+];
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            foreground: Color::black(),
+            background: Color::white(),
+            selection: Color::rgb(0.7, 0.82, 1.0),
+            highlight: Color::rgb(0.3, 0.5, 0.9),
+            line_number: Color::grey(),
+            popup: Color::rgb(0.93, 0.93, 0.93),
+            error: Color::rgb(0.85, 0.2, 0.2),
+            warning: Color::rgb(0.9, 0.6, 0.1),
+            info: Color::rgb(0.25, 0.5, 0.9),
+            hint: Color::grey(),
+            keyword: Color::rgb(0.55, 0.15, 0.6),
+            type_name: Color::rgb(0.1, 0.45, 0.55),
+            function: Color::rgb(0.2, 0.3, 0.75),
+            number: Color::rgb(0.7, 0.35, 0.1),
+            string: Color::rgb(0.2, 0.5, 0.15),
+            comment: Color::grey(),
+            added: Color::rgb(0.3, 0.65, 0.3),
+            modified: Color::rgb(0.3, 0.5, 0.85),
+            removed: Color::rgb(0.85, 0.3, 0.3),
+            diff_line: Color::rgb(0.9, 0.94, 1.0),
+            diff_text: Color::rgb(0.75, 0.85, 1.0),
+            filler: Color::rgb(0.95, 0.95, 0.95),
+            terminal: TERMINAL_COLORS
+                .iter()
+                .filter_map(|hex| Color::from_hex(hex))
+                .collect(),
+        }
+    }
+}
+
+impl Theme {
+    /// the color of the marks of a diagnostic.
+    pub fn severity(&self, severity: Severity) -> Color {
+        match severity {
+            Severity::Error => self.error,
+            Severity::Warning => self.warning,
+            Severity::Information => self.info,
+            Severity::Hint => self.hint,
+        }
+    }
+
+    /// the color of the mark of a changed line.
+    pub fn change(&self, change: LineChange) -> Color {
+        match change {
+            LineChange::Added => self.added,
+            LineChange::Modified => self.modified,
+            LineChange::Removed => self.removed,
+        }
+    }
+
+    /// the color of highlighted text.
+    pub fn syntax(&self, highlight: Highlight) -> Color {
+        match highlight {
+            Highlight::Keyword => self.keyword,
+            Highlight::Type => self.type_name,
+            Highlight::Function => self.function,
+            Highlight::Number => self.number,
+            Highlight::String => self.string,
+            Highlight::Comment => self.comment,
+        }
+    }
+
+    /// one of the 256 colors of xterm, the first 16 are the colors of the theme. They are
+    /// followed by a 6x6x6 color cube and 24 shades of grey.
+    pub fn terminal_color(&self, idx: u8) -> Color {
+        let idx = idx as usize;
+        if idx < 16 {
+            return self.terminal.get(idx).copied().unwrap_or(self.foreground);
+        }
+        if idx >= 232 {
+            let level = (8 + (idx - 232) * 10) as f32 / 255.0;
+            return Color::rgb(level, level, level);
+        }
+        let level = |value: usize| {
+            if value == 0 {
+                0.0
+            } else {
+                (55 + value * 40) as f32 / 255.0
+            }
+        };
+        let idx = idx - 16;
+        Color::rgb(level(idx / 36), level(idx / 6 % 6), level(idx % 6))
+    }
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    Color::from_hex(&hex).ok_or_else(|| {
+        serde::de::Error::custom(format!("invalid color '{}', expected #rrggbb", hex))
+    })
+}
+
+fn colors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+    let hexes = Vec::<String>::deserialize(deserializer)?;
+    hexes
+        .iter()
+        .map(|hex| {
+            Color::from_hex(hex).ok_or_else(|| {
+                serde::de::Error::custom(format!("invalid color '{}', expected #rrggbb", hex))
+            })
+        })
+        .collect()
+}
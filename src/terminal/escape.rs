@@ -0,0 +1,203 @@
+// Escape Sequences.
+// Applies what a program writes to the grid: printed characters, control characters and the
+// CSI, ESC and OSC sequences of xterm that shells and common full screen programs use. The bytes
+// are split into sequences by the parser of the vte crate, sequences that aren't supported are
+// ignored.
+
+use vte::{Params, Perform};
+
+use super::grid::{CellColor, Grid};
+
+impl Perform for Grid {
+    fn print(&mut self, ch: char) {
+        Grid::print(self, ch);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            0x08 => self.backspace(),
+            0x09 => self.tab(),
+            0x0a..=0x0c => self.line_feed(),
+            0x0d => self.carriage_return(),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let args: Vec<u16> = params.iter().map(|param| param[0]).collect();
+        // missing and zero arguments are the default, the first row or column is 1.
+        let arg = |idx: usize, default: usize| {
+            args.get(idx)
+                .map(|&arg| arg as usize)
+                .filter(|&arg| arg != 0)
+                .unwrap_or(default)
+        };
+        let mode = args.first().copied().unwrap_or(0);
+        if intermediates == b"?" {
+            if action == 'h' || action == 'l' {
+                for mode in args.iter() {
+                    self.set_private_mode(*mode, action == 'h');
+                }
+            }
+            return;
+        }
+        if !intermediates.is_empty() {
+            return;
+        }
+
+        let cursor = self.cursor();
+        match action {
+            'A' => self.move_by(-(arg(0, 1) as isize), 0),
+            'B' | 'e' => self.move_by(arg(0, 1) as isize, 0),
+            'C' | 'a' => self.move_by(0, arg(0, 1) as isize),
+            'D' => self.move_by(0, -(arg(0, 1) as isize)),
+            'E' => self.goto(cursor.row + arg(0, 1), 0),
+            'F' => self.goto(cursor.row.saturating_sub(arg(0, 1)), 0),
+            'G' | '`' => self.goto(cursor.row, arg(0, 1) - 1),
+            'H' | 'f' => self.goto(arg(0, 1) - 1, arg(1, 1) - 1),
+            'd' => self.goto(arg(0, 1) - 1, cursor.column),
+            'J' => self.erase_display(mode),
+            'K' => self.erase_line(mode),
+            'L' => self.insert_lines(arg(0, 1)),
+            'M' => self.delete_lines(arg(0, 1)),
+            '@' => self.insert_chars(arg(0, 1)),
+            'P' => self.delete_chars(arg(0, 1)),
+            'X' => self.erase_chars(arg(0, 1)),
+            'S' => self.scroll_up(arg(0, 1)),
+            'T' => self.scroll_down(arg(0, 1)),
+            'm' => self.select_graphic_rendition(params),
+            'r' => {
+                let rows = self.rows();
+                self.set_scroll_region(arg(0, 1) - 1, arg(1, rows));
+            }
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            // the position of the cursor, or that the terminal is fine.
+            'n' if mode == 6 => {
+                let report = format!("\x1b[{};{}R", cursor.row + 1, cursor.column + 1);
+                self.responses.extend_from_slice(report.as_bytes());
+            }
+            'n' if mode == 5 => self.responses.extend_from_slice(b"\x1b[0n"),
+            // a VT100 with advanced video.
+            'c' => self.responses.extend_from_slice(b"\x1b[?1;2c"),
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], ignore: bool, byte: u8) {
+        // designating character sets isn't supported, the text is UTF-8.
+        if ignore || !intermediates.is_empty() {
+            return;
+        }
+        match byte {
+            b'D' => self.line_feed(),
+            b'E' => {
+                self.carriage_return();
+                self.line_feed();
+            }
+            b'M' => self.reverse_line_feed(),
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'c' => self.reset(),
+            _ => {}
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        // the icon name and the title are the same.
+        if let [b"0", title] | [b"2", title] = params {
+            self.title = Some(String::from_utf8_lossy(title).into_owned());
+        }
+    }
+}
+
+impl Grid {
+    fn set_private_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            1 => self.application_cursor = on,
+            7 => self.autowrap = on,
+            25 => self.cursor_visible = on,
+            47 | 1047 => self.set_alternate_screen(on),
+            1049 => {
+                if on {
+                    self.save_cursor();
+                    self.set_alternate_screen(true);
+                } else {
+                    self.set_alternate_screen(false);
+                    self.restore_cursor();
+                }
+            }
+            2004 => self.bracketed_paste = on,
+            _ => {}
+        }
+    }
+
+    /// changes the attributes of the characters printed next.
+    fn select_graphic_rendition(&mut self, params: &Params) {
+        let params: Vec<&[u16]> = params.iter().collect();
+        if params.is_empty() {
+            self.attributes = Default::default();
+            return;
+        }
+        let mut idx = 0;
+        while idx < params.len() {
+            let param = params[idx];
+            let attributes = &mut self.attributes;
+            match param[0] {
+                0 => *attributes = Default::default(),
+                1 => attributes.bold = true,
+                3 => attributes.italic = true,
+                4 => attributes.underline = true,
+                7 => attributes.inverse = true,
+                22 => attributes.bold = false,
+                23 => attributes.italic = false,
+                24 => attributes.underline = false,
+                27 => attributes.inverse = false,
+                code @ 30..=37 => attributes.foreground = CellColor::Indexed(code as u8 - 30),
+                39 => attributes.foreground = CellColor::Default,
+                code @ 40..=47 => attributes.background = CellColor::Indexed(code as u8 - 40),
+                49 => attributes.background = CellColor::Default,
+                code @ 90..=97 => attributes.foreground = CellColor::Indexed(code as u8 - 82),
+                code @ 100..=107 => attributes.background = CellColor::Indexed(code as u8 - 92),
+                code @ (38 | 48) => {
+                    // the color is in sub-parameters, `38:5:n`, or in the parameters after it,
+                    // `38;5;n`.
+                    let (color, used) = if param.len() > 1 {
+                        (extended_color(&param[1..]), 0)
+                    } else {
+                        let rest: Vec<u16> = params[idx + 1..].iter().map(|p| p[0]).collect();
+                        let color = extended_color(&rest);
+                        let used = match rest.first() {
+                            Some(5) => 2,
+                            Some(2) => 4,
+                            _ => 0,
+                        };
+                        (color, used)
+                    };
+                    if let Some(color) = color {
+                        if code == 38 {
+                            attributes.foreground = color;
+                        } else {
+                            attributes.background = color;
+                        }
+                    }
+                    idx += used;
+                }
+                _ => {}
+            }
+            idx += 1;
+        }
+    }
+}
+
+/// a color of the 256 colors, `5;n`, or a true color, `2;r;g;b`.
+fn extended_color(args: &[u16]) -> Option<CellColor> {
+    match args {
+        [5, idx, ..] => Some(CellColor::Indexed(*idx as u8)),
+        [2, r, g, b, ..] => Some(CellColor::Rgb(*r as u8, *g as u8, *b as u8)),
+        _ => None,
+    }
+}
@@ -0,0 +1,468 @@
+// Grid.
+// The cells of a terminal: the screen programs draw on and the lines that scrolled off its top.
+// Full screen programs, e.g. editors, draw on an alternate screen without scrollback, the main
+// screen is shown again as it was when they switch back.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// how many lines that scrolled off the screen are kept.
+const SCROLLBACK_LINES: usize = 10_000;
+/// the columns of tab stops are multiples of this.
+const TAB_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellColor {
+    /// the foreground or background of the theme.
+    #[default]
+    Default,
+    /// one of the 256 colors of xterm, the first 16 are the colors of the theme.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// how a character is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Attributes {
+    pub foreground: CellColor,
+    pub background: CellColor,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// the foreground and the background are swapped.
+    pub inverse: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub attributes: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+/// a position in the grid, zero-indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Point {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl Point {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self { row, column }
+    }
+}
+
+pub struct Grid {
+    columns: usize,
+    rows: usize,
+    screen: Vec<Vec<Cell>>,
+    /// the lines scrolled off the top of the main screen, the oldest first.
+    scrollback: VecDeque<Vec<Cell>>,
+    /// the main screen and its cursor while the alternate screen is shown.
+    main_screen: Option<(Vec<Vec<Cell>>, Point)>,
+    cursor: Point,
+    /// the cursor is past the last column, the next character is printed on the next line.
+    wrap_pending: bool,
+    saved_cursor: (Point, Attributes),
+    /// the attributes of printed characters.
+    pub attributes: Attributes,
+    /// the rows scrolled by line feeds and by inserting and deleting lines.
+    scroll_region: Range<usize>,
+    pub cursor_visible: bool,
+    /// characters printed past the last column continue on the next line.
+    pub autowrap: bool,
+    /// the cursor keys send application sequences, e.g. `ESC O A` instead of `ESC [ A`.
+    pub application_cursor: bool,
+    /// pasted text is put between `ESC [ 200 ~` and `ESC [ 201 ~`.
+    pub bracketed_paste: bool,
+    /// the title set by the program.
+    pub title: Option<String>,
+    /// the answers to queries of the program, e.g. for the position of the cursor.
+    pub responses: Vec<u8>,
+}
+
+impl Grid {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        Self {
+            columns,
+            rows,
+            screen: vec![vec![Cell::default(); columns]; rows],
+            scrollback: VecDeque::new(),
+            main_screen: None,
+            cursor: Point::default(),
+            wrap_pending: false,
+            saved_cursor: (Point::default(), Attributes::default()),
+            attributes: Attributes::default(),
+            scroll_region: 0..rows,
+            cursor_visible: true,
+            autowrap: true,
+            application_cursor: false,
+            bracketed_paste: false,
+            title: None,
+            responses: Vec::new(),
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cursor(&self) -> Point {
+        self.cursor
+    }
+
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    /// a line of the scrollback followed by the screen.
+    pub fn line(&self, idx: usize) -> Option<&[Cell]> {
+        match idx.checked_sub(self.scrollback.len()) {
+            Some(row) => self.screen.get(row),
+            None => self.scrollback.get(idx),
+        }
+        .map(Vec::as_slice)
+    }
+
+    /// changes the size of the screen. Lines that don't fit below the cursor move to the
+    /// scrollback and move back when there's room again, lines are cut off or padded and aren't
+    /// wrapped again.
+    pub fn resize(&mut self, columns: usize, rows: usize) {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        if (columns, rows) == (self.columns, self.rows) {
+            return;
+        }
+        while self.screen.len() > rows {
+            if self.cursor.row + 1 < self.screen.len() {
+                self.screen.pop();
+            } else {
+                let line = self.screen.remove(0);
+                self.push_scrollback(line);
+                self.cursor.row = self.cursor.row.saturating_sub(1);
+            }
+        }
+        // the lines scrolled off the main screen come back when it grows.
+        while self.screen.len() < rows {
+            let line = match self.main_screen {
+                None => self.scrollback.pop_back(),
+                Some(_) => None,
+            };
+            match line {
+                Some(line) => {
+                    self.screen.insert(0, line);
+                    self.cursor.row += 1;
+                }
+                None => self.screen.push(vec![Cell::default(); columns]),
+            }
+        }
+        for line in self.screen.iter_mut() {
+            line.resize(columns, Cell::default());
+        }
+        if let Some((screen, _)) = self.main_screen.as_mut() {
+            screen.resize(rows, vec![Cell::default(); columns]);
+            for line in screen.iter_mut() {
+                line.resize(columns, Cell::default());
+            }
+        }
+        self.columns = columns;
+        self.rows = rows;
+        self.scroll_region = 0..rows;
+        self.cursor.row = self.cursor.row.min(rows - 1);
+        self.cursor.column = self.cursor.column.min(columns - 1);
+        self.wrap_pending = false;
+    }
+
+    fn push_scrollback(&mut self, line: Vec<Cell>) {
+        if self.main_screen.is_some() {
+            return;
+        }
+        if self.scrollback.len() == SCROLLBACK_LINES {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(line);
+    }
+
+    fn blank(&self) -> Cell {
+        // erased cells keep the background color.
+        let mut cell = Cell::default();
+        cell.attributes.background = self.attributes.background;
+        cell
+    }
+
+    /// prints a character at the cursor and moves the cursor after it.
+    pub fn print(&mut self, ch: char) {
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            self.cursor.column = 0;
+            self.line_feed();
+        }
+        let cell = Cell {
+            ch,
+            attributes: self.attributes,
+        };
+        self.screen[self.cursor.row][self.cursor.column] = cell;
+        if self.cursor.column + 1 < self.columns {
+            self.cursor.column += 1;
+        } else if self.autowrap {
+            self.wrap_pending = true;
+        }
+    }
+
+    /// moves the cursor down a line, the scroll region scrolls up at its bottom.
+    pub fn line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.row + 1 == self.scroll_region.end {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    /// moves the cursor up a line, the scroll region scrolls down at its top.
+    pub fn reverse_line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.row == self.scroll_region.start {
+            self.scroll_down(1);
+        } else {
+            self.cursor.row = self.cursor.row.saturating_sub(1);
+        }
+    }
+
+    pub fn carriage_return(&mut self) {
+        self.wrap_pending = false;
+        self.cursor.column = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        self.wrap_pending = false;
+        self.cursor.column = self.cursor.column.saturating_sub(1);
+    }
+
+    pub fn tab(&mut self) {
+        let next = (self.cursor.column / TAB_WIDTH + 1) * TAB_WIDTH;
+        self.cursor.column = next.min(self.columns - 1);
+    }
+
+    /// scrolls the lines of the scroll region up, lines scrolled off the top of the screen are
+    /// kept in the scrollback.
+    pub fn scroll_up(&mut self, count: usize) {
+        let Range { start, end } = self.scroll_region.clone();
+        for _ in 0..count.min(end - start) {
+            let line = self.screen.remove(start);
+            if start == 0 {
+                self.push_scrollback(line);
+            }
+            let blank = vec![self.blank(); self.columns];
+            self.screen.insert(end - 1, blank);
+        }
+    }
+
+    /// scrolls the lines of the scroll region down, blank lines appear at its top.
+    pub fn scroll_down(&mut self, count: usize) {
+        let Range { start, end } = self.scroll_region.clone();
+        for _ in 0..count.min(end - start) {
+            self.screen.remove(end - 1);
+            let blank = vec![self.blank(); self.columns];
+            self.screen.insert(start, blank);
+        }
+    }
+
+    /// moves the cursor, positions outside of the screen are clamped.
+    pub fn goto(&mut self, row: usize, column: usize) {
+        self.wrap_pending = false;
+        self.cursor = Point::new(row.min(self.rows - 1), column.min(self.columns - 1));
+    }
+
+    /// moves the cursor by rows and columns, it stops at the edges of the screen.
+    pub fn move_by(&mut self, rows: isize, columns: isize) {
+        let row = (self.cursor.row as isize + rows).max(0) as usize;
+        let column = (self.cursor.column as isize + columns).max(0) as usize;
+        self.goto(row, column);
+    }
+
+    /// sets the rows scrolled by line feeds, the cursor moves to the top left.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.rows);
+        if top + 1 < bottom {
+            self.scroll_region = top..bottom;
+            self.goto(0, 0);
+        }
+    }
+
+    /// erases the screen below the cursor (0), above it (1) or all of it (2 and 3). The
+    /// scrollback is erased too by 3.
+    pub fn erase_display(&mut self, mode: u16) {
+        let blank = self.blank();
+        let Point { row, .. } = self.cursor;
+        match mode {
+            0 => {
+                self.erase_line(0);
+                for line in self.screen[row + 1..].iter_mut() {
+                    line.iter_mut().for_each(|cell| *cell = blank);
+                }
+            }
+            1 => {
+                self.erase_line(1);
+                for line in self.screen[..row].iter_mut() {
+                    line.iter_mut().for_each(|cell| *cell = blank);
+                }
+            }
+            2 | 3 => {
+                for line in self.screen.iter_mut() {
+                    line.iter_mut().for_each(|cell| *cell = blank);
+                }
+                if mode == 3 {
+                    self.scrollback.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// erases the line after the cursor (0), before it (1) or all of it (2).
+    pub fn erase_line(&mut self, mode: u16) {
+        let blank = self.blank();
+        let Point { row, column } = self.cursor;
+        let cells = match mode {
+            0 => column..self.columns,
+            1 => 0..column + 1,
+            2 => 0..self.columns,
+            _ => return,
+        };
+        self.screen[row][cells]
+            .iter_mut()
+            .for_each(|cell| *cell = blank);
+    }
+
+    /// erases characters from the cursor on without moving the rest of the line.
+    pub fn erase_chars(&mut self, count: usize) {
+        let blank = self.blank();
+        let Point { row, column } = self.cursor;
+        let end = (column + count).min(self.columns);
+        self.screen[row][column..end]
+            .iter_mut()
+            .for_each(|cell| *cell = blank);
+    }
+
+    /// inserts blank characters at the cursor, the characters after it move right.
+    pub fn insert_chars(&mut self, count: usize) {
+        let blank = self.blank();
+        let Point { row, column } = self.cursor;
+        let line = &mut self.screen[row];
+        for _ in 0..count.min(self.columns - column) {
+            line.pop();
+            line.insert(column, blank);
+        }
+    }
+
+    /// deletes characters at the cursor, the characters after it move left.
+    pub fn delete_chars(&mut self, count: usize) {
+        let blank = self.blank();
+        let Point { row, column } = self.cursor;
+        let line = &mut self.screen[row];
+        for _ in 0..count.min(self.columns - column) {
+            line.remove(column);
+            line.push(blank);
+        }
+    }
+
+    /// inserts blank lines at the cursor inside of the scroll region.
+    pub fn insert_lines(&mut self, count: usize) {
+        if !self.scroll_region.contains(&self.cursor.row) {
+            return;
+        }
+        let region = self.scroll_region.clone();
+        self.scroll_region = self.cursor.row..region.end;
+        self.scroll_down(count);
+        self.scroll_region = region;
+    }
+
+    /// deletes lines at the cursor inside of the scroll region.
+    pub fn delete_lines(&mut self, count: usize) {
+        if !self.scroll_region.contains(&self.cursor.row) {
+            return;
+        }
+        let region = self.scroll_region.clone();
+        self.scroll_region = self.cursor.row..region.end;
+        // deleted lines don't go to the scrollback.
+        for _ in 0..count.min(region.end - self.cursor.row) {
+            self.screen.remove(self.cursor.row);
+            let blank = vec![self.blank(); self.columns];
+            self.screen.insert(region.end - 1, blank);
+        }
+        self.scroll_region = region;
+    }
+
+    pub fn save_cursor(&mut self) {
+        self.saved_cursor = (self.cursor, self.attributes);
+    }
+
+    pub fn restore_cursor(&mut self) {
+        let (cursor, attributes) = self.saved_cursor;
+        self.goto(cursor.row, cursor.column);
+        self.attributes = attributes;
+    }
+
+    /// switches to a blank alternate screen, or back to the main screen.
+    pub fn set_alternate_screen(&mut self, alternate: bool) {
+        if alternate == self.main_screen.is_some() {
+            return;
+        }
+        if alternate {
+            let blank = vec![vec![Cell::default(); self.columns]; self.rows];
+            let main = std::mem::replace(&mut self.screen, blank);
+            self.main_screen = Some((main, self.cursor));
+        } else if let Some((main, cursor)) = self.main_screen.take() {
+            self.screen = main;
+            self.goto(cursor.row, cursor.column);
+        }
+    }
+
+    /// the text of the cells between two points of the scrollback and the screen, the last
+    /// point is included. Trailing spaces of lines are left out.
+    pub fn text(&self, start: Point, end: Point) -> String {
+        let mut text = String::new();
+        for row in start.row..=end.row {
+            let line = match self.line(row) {
+                Some(line) => line,
+                None => break,
+            };
+            let from = if row == start.row { start.column } else { 0 };
+            let to = if row == end.row {
+                end.column + 1
+            } else {
+                line.len()
+            };
+            let chars: String = line[from.min(line.len())..to.min(line.len())]
+                .iter()
+                .map(|cell| cell.ch)
+                .collect();
+            text.push_str(chars.trim_end());
+            if row != end.row {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// the state of a new terminal, the scrollback is kept.
+    pub fn reset(&mut self) {
+        let scrollback = std::mem::take(&mut self.scrollback);
+        *self = Self::new(self.columns, self.rows);
+        self.scrollback = scrollback;
+    }
+}
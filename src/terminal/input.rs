@@ -0,0 +1,86 @@
+// Input.
+// The bytes xterm sends for keys that don't type a character, e.g. the cursor keys, and for
+// escape and letters typed with ctrl. Other typed characters, including enter and tab, are sent
+// as they are.
+
+use crate::glutin::event::{ModifiersState, VirtualKeyCode};
+
+/// the bytes sent for a key, None if the key types a character or isn't sent at all.
+pub fn key_bytes(
+    key: VirtualKeyCode,
+    mods: &ModifiersState,
+    application_cursor: bool,
+) -> Option<Vec<u8>> {
+    use VirtualKeyCode::*;
+    // modifiers are sent as a parameter, 1 + shift + 2 * alt + 4 * ctrl.
+    let modifier = 1 + mods.shift() as u8 + 2 * mods.alt() as u8 + 4 * mods.ctrl() as u8;
+    let cursor = |ch: char| {
+        if modifier > 1 {
+            format!("\x1b[1;{}{}", modifier, ch)
+        } else if application_cursor {
+            format!("\x1bO{}", ch)
+        } else {
+            format!("\x1b[{}", ch)
+        }
+    };
+    // F1 to F4 are always sent with `ESC O` without modifiers.
+    let function = |ch: char| {
+        if modifier > 1 {
+            format!("\x1b[1;{}{}", modifier, ch)
+        } else {
+            format!("\x1bO{}", ch)
+        }
+    };
+    let tilde = |code: u8| {
+        if modifier > 1 {
+            format!("\x1b[{};{}~", code, modifier)
+        } else {
+            format!("\x1b[{}~", code)
+        }
+    };
+    let sequence = match key {
+        Escape => "\x1b".to_string(),
+        // a letter with ctrl is its control character, e.g. ctrl+c is 0x03.
+        key if mods.ctrl() && (A as u32..=Z as u32).contains(&(key as u32)) => {
+            let control = (key as u32 - A as u32 + 1) as u8;
+            return Some(if mods.alt() {
+                vec![0x1b, control]
+            } else {
+                vec![control]
+            });
+        }
+        Up => cursor('A'),
+        Down => cursor('B'),
+        Right => cursor('C'),
+        Left => cursor('D'),
+        Home => cursor('H'),
+        End => cursor('F'),
+        Insert => tilde(2),
+        Delete => tilde(3),
+        PageUp => tilde(5),
+        PageDown => tilde(6),
+        F1 => function('P'),
+        F2 => function('Q'),
+        F3 => function('R'),
+        F4 => function('S'),
+        F5 => tilde(15),
+        F6 => tilde(17),
+        F7 => tilde(18),
+        F8 => tilde(19),
+        F9 => tilde(20),
+        F10 => tilde(21),
+        F11 => tilde(23),
+        F12 => tilde(24),
+        _ => return None,
+    };
+    Some(sequence.into_bytes())
+}
+
+/// the bytes sent for a typed character. Backspace is sent as delete like xterm does.
+pub fn char_bytes(ch: char) -> Vec<u8> {
+    match ch {
+        '\u{8}' => vec![0x7f],
+        '\n' => vec![b'\r'],
+        ch => ch.encode_utf8(&mut [0; 4]).as_bytes().to_vec(),
+    }
+}
@@ -0,0 +1,278 @@
+// Terminal.
+// A shell running in a pseudo terminal, shown in a frame. What the shell writes is parsed into a
+// grid of cells like xterm would, the frame draws the cells and sends the keys typed into it to
+// the shell. Lines that scrolled off the screen can be scrolled back to, and the text of the
+// grid can be selected and copied.
+
+mod escape;
+mod grid;
+mod input;
+mod pty;
+
+use std::sync::Arc;
+
+pub use grid::{Attributes, Cell, CellColor, Grid, Point};
+pub use input::{char_bytes, key_bytes};
+use pty::Pty;
+
+#[derive(thiserror::Error, Debug)]
+pub enum TerminalError {
+    #[error("failed to open a terminal: {0}")]
+    Pty(String),
+    #[error("failed to start the shell: {0}")]
+    Spawn(String),
+    #[error("failed to write to the terminal: {0}")]
+    Write(std::io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum TerminalEvent {
+    /// the shell wrote something.
+    Output(Vec<u8>),
+    /// the shell exited.
+    Exited,
+}
+
+pub type TerminalCallback = Arc<dyn Fn(TerminalEvent) + Send + Sync>;
+
+pub struct Terminal {
+    parser: vte::Parser,
+    grid: Grid,
+    /// the shell, None if the output is fed by hand, e.g. in tests.
+    pty: Option<Pty>,
+    /// how many lines of the scrollback are shown above the screen.
+    scrolled: usize,
+    /// the selected cells, from where the selection started to where it ends. The rows are lines
+    /// of the scrollback followed by the screen.
+    selection: Option<(Point, Point)>,
+}
+
+impl Terminal {
+    /// a terminal without a shell.
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            grid: Grid::new(columns, rows),
+            pty: None,
+            scrolled: 0,
+            selection: None,
+        }
+    }
+
+    /// a terminal running the shell of the user, its output is reported to the callback and
+    /// has to be fed back to the terminal.
+    pub fn spawn(
+        columns: usize,
+        rows: usize,
+        on_event: TerminalCallback,
+    ) -> Result<Self, TerminalError> {
+        let mut terminal = Self::new(columns, rows);
+        terminal.pty = Some(Pty::spawn(columns, rows, on_event)?);
+        Ok(terminal)
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// applies the output of the shell to the grid. The view stays on the lines it shows when
+    /// it is scrolled back.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let scrollback = self.grid.scrollback_len();
+        for byte in bytes {
+            self.parser.advance(&mut self.grid, *byte);
+        }
+        if self.scrolled > 0 {
+            let added = self.grid.scrollback_len().saturating_sub(scrollback);
+            self.scrolled = (self.scrolled + added).min(self.grid.scrollback_len());
+        }
+        if !self.grid.responses.is_empty() {
+            let responses = std::mem::take(&mut self.grid.responses);
+            if let Err(e) = self.write(&responses) {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    /// sends input to the shell, the view scrolls back down to the screen.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), TerminalError> {
+        self.scrolled = 0;
+        match self.pty.as_mut() {
+            Some(pty) => pty.write(bytes),
+            None => Ok(()),
+        }
+    }
+
+    /// sends pasted text, marked as pasted if the program asked for it.
+    pub fn paste(&mut self, text: &str) -> Result<(), TerminalError> {
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        if self.grid.bracketed_paste {
+            let pasted = format!("\x1b[200~{}\x1b[201~", text);
+            self.write(pasted.as_bytes())
+        } else {
+            self.write(text.as_bytes())
+        }
+    }
+
+    /// changes the size of the grid and tells the shell about it.
+    pub fn resize(&mut self, columns: usize, rows: usize) -> Result<(), TerminalError> {
+        if (columns.max(1), rows.max(1)) == (self.grid.columns(), self.grid.rows()) {
+            return Ok(());
+        }
+        self.grid.resize(columns, rows);
+        self.scrolled = self.scrolled.min(self.grid.scrollback_len());
+        match self.pty.as_ref() {
+            Some(pty) => pty.resize(columns, rows),
+            None => Ok(()),
+        }
+    }
+
+    /// scrolls the view into the scrollback, or back down towards the screen.
+    pub fn scroll(&mut self, lines: isize) {
+        let scrolled = self.scrolled as isize - lines;
+        self.scrolled = (scrolled.max(0) as usize).min(self.grid.scrollback_len());
+    }
+
+    /// the line of the grid at the top of the view.
+    fn top(&self) -> usize {
+        self.grid.scrollback_len() - self.scrolled
+    }
+
+    /// a row of the view.
+    pub fn visible_line(&self, row: usize) -> Option<&[Cell]> {
+        if row < self.grid.rows() {
+            self.grid.line(self.top() + row)
+        } else {
+            None
+        }
+    }
+
+    /// where the cursor is in the view, None if it's hidden or scrolled out of the view.
+    pub fn cursor(&self) -> Option<Point> {
+        let cursor = self.grid.cursor();
+        let row = cursor.row + self.scrolled;
+        if self.grid.cursor_visible && row < self.grid.rows() {
+            Some(Point::new(row, cursor.column))
+        } else {
+            None
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.grid.title.as_deref()
+    }
+
+    pub fn application_cursor(&self) -> bool {
+        self.grid.application_cursor
+    }
+
+    /// starts a selection at a cell of the view.
+    pub fn select_start(&mut self, point: Point) {
+        let point = Point::new(self.top() + point.row, point.column);
+        self.selection = Some((point, point));
+    }
+
+    /// extends the selection to a cell of the view.
+    pub fn select_to(&mut self, point: Point) {
+        let point = Point::new(self.top() + point.row, point.column);
+        if let Some((_, end)) = self.selection.as_mut() {
+            *end = point;
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection = None;
+    }
+
+    /// the first and the last selected cell of the grid, in order. A click without dragging
+    /// selects nothing.
+    fn selected(&self) -> Option<(Point, Point)> {
+        match self.selection? {
+            (start, end) if start == end => None,
+            (start, end) => Some((start.min(end), start.max(end))),
+        }
+    }
+
+    /// whether a cell of the view is selected.
+    pub fn is_selected(&self, row: usize, column: usize) -> bool {
+        let point = Point::new(self.top() + row, column);
+        matches!(self.selected(), Some((start, end)) if start <= point && point <= end)
+    }
+
+    /// the text of the selection, None without one.
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selected()?;
+        Some(self.grid.text(start, end))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line(terminal: &Terminal, row: usize) -> String {
+        let cells = terminal.visible_line(row).unwrap();
+        let text: String = cells.iter().map(|cell| cell.ch).collect();
+        text.trim_end().to_string()
+    }
+
+    #[test]
+    fn recorded_output() {
+        let mut terminal = Terminal::new(20, 4);
+        // a prompt, a colored word and a line the shell redraws.
+        terminal.feed(b"$ ls\r\n\x1b[1;31mred\x1b[0m plain\r\nabcdef\x1b[3D\x1b[K\x1b[2;11Hx");
+        assert_eq!(line(&terminal, 0), "$ ls");
+        assert_eq!(line(&terminal, 1), "red plain x");
+        assert_eq!(line(&terminal, 2), "abc");
+        let red = terminal.visible_line(1).unwrap()[0].attributes;
+        assert_eq!(red.foreground, CellColor::Indexed(1));
+        assert!(red.bold);
+        let plain = terminal.visible_line(1).unwrap()[4].attributes;
+        assert_eq!(plain, Attributes::default());
+        assert_eq!(terminal.cursor(), Some(Point::new(1, 11)));
+
+        // 256 colors and true colors.
+        terminal.feed(b"\x1b[38;5;208;48;2;1;2;3mx");
+        let attributes = terminal.visible_line(1).unwrap()[11].attributes;
+        assert_eq!(attributes.foreground, CellColor::Indexed(208));
+        assert_eq!(attributes.background, CellColor::Rgb(1, 2, 3));
+
+        // a program switching to the alternate screen and back.
+        terminal.feed(b"\x1b[?1049h\x1b[2J\x1b[Hvim");
+        assert_eq!(line(&terminal, 0), "vim");
+        terminal.feed(b"\x1b[?1049l");
+        assert_eq!(line(&terminal, 0), "$ ls");
+        assert_eq!(terminal.cursor(), Some(Point::new(1, 12)));
+    }
+
+    #[test]
+    fn scrollback_and_selection() {
+        let mut terminal = Terminal::new(10, 2);
+        terminal.feed(b"one\r\ntwo\r\nthree\r\nfour");
+        assert_eq!(terminal.grid().scrollback_len(), 2);
+        assert_eq!(line(&terminal, 0), "three");
+        terminal.scroll(-2);
+        assert_eq!(line(&terminal, 0), "one");
+        assert_eq!(terminal.cursor(), None);
+        // new output doesn't move the view.
+        terminal.feed(b"\r\nfive");
+        assert_eq!(line(&terminal, 0), "one");
+
+        terminal.select_start(Point::new(0, 1));
+        terminal.select_to(Point::new(1, 1));
+        assert!(terminal.is_selected(0, 5));
+        assert_eq!(terminal.selected_text().unwrap(), "ne\ntw");
+        terminal.scroll(10);
+        assert_eq!(line(&terminal, 1), "five");
+
+        terminal.resize(10, 3).unwrap();
+        assert_eq!(line(&terminal, 2), "five");
+
+        // growing the alternate screen keeps the scrollback.
+        let scrollback = terminal.grid().scrollback_len();
+        terminal.feed(b"\x1b[?1049h");
+        terminal.resize(10, 5).unwrap();
+        terminal.feed(b"\x1b[?1049l");
+        assert_eq!(terminal.grid().scrollback_len(), scrollback);
+    }
+}
@@ -0,0 +1,92 @@
+// Pty.
+// The pseudo terminal the shell runs in. What the shell writes is read on a thread of its own and
+// reported to the callback, the shell is killed when the pty is dropped.
+
+use std::io::{Read, Write};
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use super::{TerminalCallback, TerminalError, TerminalEvent};
+
+pub struct Pty {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+fn size(columns: usize, rows: usize) -> PtySize {
+    PtySize {
+        rows: rows.min(u16::MAX as usize) as u16,
+        cols: columns.min(u16::MAX as usize) as u16,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+impl Pty {
+    /// runs the shell of the user, `$SHELL` on unix, in the current directory.
+    pub fn spawn(
+        columns: usize,
+        rows: usize,
+        on_event: TerminalCallback,
+    ) -> Result<Self, TerminalError> {
+        let pair = native_pty_system()
+            .openpty(size(columns, rows))
+            .map_err(|e| TerminalError::Pty(e.to_string()))?;
+        let mut command = CommandBuilder::new_default_prog();
+        command.env("TERM", "xterm-256color");
+        if let Ok(dir) = std::env::current_dir() {
+            command.cwd(dir);
+        }
+        let child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|e| TerminalError::Spawn(e.to_string()))?;
+        let mut reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| TerminalError::Pty(e.to_string()))?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| TerminalError::Pty(e.to_string()))?;
+
+        kea::utils::spawn_thread("terminal", move || {
+            let mut buf = [0; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => on_event(TerminalEvent::Output(buf[..len].to_vec())),
+                }
+            }
+            on_event(TerminalEvent::Exited);
+        });
+
+        Ok(Self {
+            master: pair.master,
+            writer,
+            child,
+        })
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), TerminalError> {
+        self.writer
+            .write_all(bytes)
+            .and_then(|_| self.writer.flush())
+            .map_err(TerminalError::Write)
+    }
+
+    /// tells the shell the size of the terminal changed.
+    pub fn resize(&self, columns: usize, rows: usize) -> Result<(), TerminalError> {
+        self.master
+            .resize(size(columns, rows))
+            .map_err(|e| TerminalError::Pty(e.to_string()))
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
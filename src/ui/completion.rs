@@ -0,0 +1,254 @@
+// Completion.
+// The candidates for the word before the cursor, shown in a popup below the start of the word.
+// Candidates arrive from several sources, possibly late, and are filtered by a fuzzy match of
+// the word: the characters of the word have to appear in order in the label of a candidate.
+// Matches at the start of the label, at the start of a word in the label and consecutive
+// characters rank higher.
+
+use kea::lsp::TextRange;
+
+/// the most candidates the popup shows at once, the list scrolls to the selected one.
+pub const MAX_VISIBLE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CompletionKind {
+    Word,
+    Path,
+    Snippet,
+    Lsp,
+    /// a choice of a snippet tab stop.
+    Choice,
+}
+
+impl CompletionKind {
+    /// shown after the label in the popup.
+    pub fn name(self) -> &'static str {
+        match self {
+            CompletionKind::Word => "word",
+            CompletionKind::Path => "path",
+            CompletionKind::Snippet => "snippet",
+            CompletionKind::Lsp => "lsp",
+            CompletionKind::Choice => "choice",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    /// what is shown and matched against the word.
+    pub label: String,
+    /// what replaces the word when the item is chosen.
+    pub text: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+    /// the range the text replaces instead of the word, from a language server.
+    pub range: Option<TextRange>,
+}
+
+impl CompletionItem {
+    pub fn new<S: Into<String>>(label: S, kind: CompletionKind) -> Self {
+        let label = label.into();
+        Self {
+            text: label.clone(),
+            label,
+            kind,
+            detail: None,
+            range: None,
+        }
+    }
+
+    pub fn with_text<S: Into<String>>(mut self, text: S) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    pub fn with_detail<S: Into<String>>(mut self, detail: S) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn with_range(mut self, range: TextRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionPopup {
+    /// the line and column of the start of the completed word.
+    line: usize,
+    column: usize,
+    prefix: String,
+    items: Vec<CompletionItem>,
+    /// the indices of the items matching the prefix, best first.
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl CompletionPopup {
+    pub fn new(line: usize, column: usize, prefix: &str) -> Self {
+        Self {
+            line,
+            column,
+            prefix: prefix.to_string(),
+            items: Vec::new(),
+            matches: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// the line and column of the start of the word.
+    pub fn anchor(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_str()
+    }
+
+    /// the word changed while typing, the items are filtered again.
+    pub fn set_prefix(&mut self, prefix: &str) {
+        if prefix != self.prefix {
+            self.prefix = prefix.to_string();
+            self.filter();
+        }
+    }
+
+    /// adds the items of a source, items with the label and text of an earlier item are
+    /// skipped.
+    pub fn add_items(&mut self, items: Vec<CompletionItem>) {
+        for item in items {
+            let duplicate = self
+                .items
+                .iter()
+                .any(|other| other.label == item.label && other.text == item.text);
+            if !duplicate {
+                self.items.push(item);
+            }
+        }
+        self.filter();
+    }
+
+    fn filter(&mut self) {
+        let selected = self.selected().map(|item| item.label.clone());
+        let mut matches: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.label != self.prefix)
+            .filter_map(|(idx, item)| Some((fuzzy_score(&self.prefix, &item.label)?, idx)))
+            .collect();
+        // the best score first, equal scores keep the order of the sources.
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = matches.into_iter().map(|(_, idx)| idx).collect();
+
+        // the selection stays on the same item while it matches.
+        self.selected = selected
+            .and_then(|label| {
+                self.matches
+                    .iter()
+                    .position(|idx| self.items[*idx].label == label)
+            })
+            .unwrap_or(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    pub fn matches(&self) -> impl Iterator<Item = &CompletionItem> {
+        self.matches.iter().map(move |idx| &self.items[*idx])
+    }
+
+    pub fn selected(&self) -> Option<&CompletionItem> {
+        self.matches.get(self.selected).map(|idx| &self.items[*idx])
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    /// moves the selection by an offset, past the last match is the first one and the other
+    /// way round.
+    pub fn select(&mut self, offset: isize) {
+        let len = self.matches.len() as isize;
+        if len > 0 {
+            self.selected = (self.selected as isize + offset).rem_euclid(len) as usize;
+        }
+    }
+
+    /// the range of the matches that are shown, it includes the selection.
+    pub fn visible(&self) -> std::ops::Range<usize> {
+        let len = self.matches.len();
+        let start = (self.selected + 1).saturating_sub(MAX_VISIBLE);
+        start..len.min(start + MAX_VISIBLE)
+    }
+}
+
+/// scores how well a label matches a pattern, None if the characters of the pattern don't
+/// appear in the label in order. Case is ignored.
+pub fn fuzzy_score(pattern: &str, label: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut label_chars = label.chars().enumerate();
+    let mut last_char = None;
+
+    for pattern_char in pattern.chars() {
+        loop {
+            let (idx, ch) = label_chars.next()?;
+            let before = last_char.replace(ch);
+            if !ch.to_lowercase().eq(pattern_char.to_lowercase()) {
+                continue;
+            }
+            score += 1;
+            if idx == 0 {
+                score += 8;
+            } else if before.map_or(false, |c: char| !c.is_alphanumeric())
+                || (ch.is_uppercase() && before.map_or(false, char::is_lowercase))
+            {
+                // the start of a word in the label, e.g. `b` in `foo_bar` or `fooBar`.
+                score += 4;
+            }
+            if previous.map_or(false, |previous| previous + 1 == idx) {
+                score += 4;
+            }
+            previous = Some(idx);
+            break;
+        }
+    }
+
+    // shorter labels are closer to the pattern.
+    Some(score * 100 - label.chars().count() as i64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_score("abc", "a_b_c").is_some());
+        assert!(fuzzy_score("abc", "acb").is_none());
+        assert!(fuzzy_score("ABC", "abc").is_some());
+        assert!(fuzzy_score("buf", "buffer") > fuzzy_score("buf", "a_bu_f"));
+        assert!(fuzzy_score("fb", "fooBar") > fuzzy_score("fb", "fabric"));
+
+        let mut popup = CompletionPopup::new(0, 0, "ren");
+        popup.add_items(vec![
+            CompletionItem::new("reference", CompletionKind::Word),
+            CompletionItem::new("render", CompletionKind::Word),
+            CompletionItem::new("ren", CompletionKind::Word),
+            CompletionItem::new("render", CompletionKind::Lsp).with_detail("fn()"),
+        ]);
+        let labels: Vec<&str> = popup.matches().map(|item| item.label.as_str()).collect();
+        // the item of the language server is the same as the word.
+        assert_eq!(labels, vec!["render", "reference"]);
+
+        popup.select(-1);
+        assert_eq!(popup.selected().unwrap().label, "reference");
+        popup.set_prefix("ref");
+        assert_eq!(popup.selected().unwrap().label, "reference");
+        popup.set_prefix("xyz");
+        assert!(popup.is_empty() && popup.selected().is_none());
+    }
+}
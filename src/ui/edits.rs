@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operation {
     ScrollUp,
     ScrollDown,
@@ -6,6 +7,72 @@ pub enum Operation {
     CursorUp,
     CursorLeft,
     CursorRight,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    AddCursorAbove,
+    AddCursorBelow,
+    Copy,
+    Cut,
+    Paste,
+    /// makes the previous entry of the kill ring the one that is put.
+    CycleKillRing,
+    NormalMode,
+    /// shows the documentation of the symbol at the cursor, from its language server.
+    Hover,
+    GotoDefinition,
+    FindReferences,
+    /// asks for a new name of the symbol at the cursor.
+    Rename,
+    /// formats the buffer with its language server.
+    Format,
+    /// shows the completions of the word before the cursor.
+    Complete,
+    /// moves the cursor to the next diagnostic of the buffer.
+    NextDiagnostic,
+    PreviousDiagnostic,
+    /// lists the diagnostics of the open buffers in a new buffer.
+    ListDiagnostics,
+    /// runs the linter of the buffer.
+    Lint,
+    /// moves the cursor to the next lines changed since the version of the file in git.
+    NextHunk,
+    PreviousHunk,
+    /// writes the changed lines at the cursor to the git index.
+    StageHunk,
+    /// puts the lines of the git index back in place of the changed lines at the cursor.
+    RevertHunk,
+    /// compares the buffer with another buffer side by side, or closes the diff.
+    DiffBuffers,
+    /// compares the buffer with its file on disk side by side, or closes the diff.
+    DiffDisk,
+    /// takes the lines of the other side of a diff in place of the hunk at the cursor.
+    DiffGet,
+    /// puts the lines of the hunk at the cursor in place of the lines of the other side.
+    DiffPut,
+    /// opens a terminal below the frames, or moves the focus between it and the frames.
+    Terminal,
+    /// asks for a shell command and shows its output in a new buffer.
+    RunCommand,
+    /// asks for a shell command and replaces the selection, or the buffer without one, with
+    /// the output of the command given the text on stdin.
+    FilterCommand,
+    /// kills the running shell command.
+    CancelCommand,
+    Undo,
+    Redo,
+    /// selects the next larger syntactic range around the selection.
+    ExpandSelection,
+    ShrinkSelection,
+    /// moves the cursor to the bracket matching the one at the cursor.
+    MatchingBracket,
+    /// moves the cursor to the start of the function around it.
+    EnclosingFunction,
+    /// folds the region around the cursor, its lines are hidden behind its first line.
+    Fold,
+    Unfold,
+    /// opens every fold, or folds every region when nothing is folded.
+    ToggleFolds,
 }
 
 impl std::str::FromStr for Operation {
@@ -19,6 +86,47 @@ impl std::str::FromStr for Operation {
             "cursor_up" => Ok(Self::CursorUp),
             "cursor_left" => Ok(Self::CursorLeft),
             "cursor_right" => Ok(Self::CursorRight),
+            "zoom_in" => Ok(Self::ZoomIn),
+            "zoom_out" => Ok(Self::ZoomOut),
+            "zoom_reset" => Ok(Self::ZoomReset),
+            "add_cursor_above" => Ok(Self::AddCursorAbove),
+            "add_cursor_below" => Ok(Self::AddCursorBelow),
+            "copy" => Ok(Self::Copy),
+            "cut" => Ok(Self::Cut),
+            "paste" => Ok(Self::Paste),
+            "cycle_kill_ring" => Ok(Self::CycleKillRing),
+            "normal_mode" => Ok(Self::NormalMode),
+            "hover" => Ok(Self::Hover),
+            "goto_definition" => Ok(Self::GotoDefinition),
+            "find_references" => Ok(Self::FindReferences),
+            "rename" => Ok(Self::Rename),
+            "format" => Ok(Self::Format),
+            "complete" => Ok(Self::Complete),
+            "next_diagnostic" => Ok(Self::NextDiagnostic),
+            "previous_diagnostic" => Ok(Self::PreviousDiagnostic),
+            "list_diagnostics" => Ok(Self::ListDiagnostics),
+            "lint" => Ok(Self::Lint),
+            "next_hunk" => Ok(Self::NextHunk),
+            "previous_hunk" => Ok(Self::PreviousHunk),
+            "stage_hunk" => Ok(Self::StageHunk),
+            "revert_hunk" => Ok(Self::RevertHunk),
+            "diff_buffers" => Ok(Self::DiffBuffers),
+            "diff_disk" => Ok(Self::DiffDisk),
+            "diff_get" => Ok(Self::DiffGet),
+            "diff_put" => Ok(Self::DiffPut),
+            "terminal" => Ok(Self::Terminal),
+            "run_command" => Ok(Self::RunCommand),
+            "filter_command" => Ok(Self::FilterCommand),
+            "cancel_command" => Ok(Self::CancelCommand),
+            "undo" => Ok(Self::Undo),
+            "redo" => Ok(Self::Redo),
+            "expand_selection" => Ok(Self::ExpandSelection),
+            "shrink_selection" => Ok(Self::ShrinkSelection),
+            "matching_bracket" => Ok(Self::MatchingBracket),
+            "enclosing_function" => Ok(Self::EnclosingFunction),
+            "fold" => Ok(Self::Fold),
+            "unfold" => Ok(Self::Unfold),
+            "toggle_folds" => Ok(Self::ToggleFolds),
             _ => Err(()),
         }
     }
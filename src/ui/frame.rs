@@ -4,16 +4,21 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 // use euclid::default::Vector2D;
 use crate::core;
 use crate::font::ScaledFontMetrics;
-use crate::pathfinder_geometry::vector::Vector2F;
+use crate::pathfinder_geometry::vector::{vec2f, Vector2F};
+use crate::pathfinder_geometry::rect::RectF;
 use crate::renderer::{
     style::{Span, StyleId, StyleSpan},
-    Color, Renderable, Renderer, TextLine,
+    Color, RenderContext, Renderable, Renderer, TextLine,
 };
+use crate::terminal::{self, Terminal};
+use crate::ui::edits::Operation;
 use crate::ui::line_cache::{LineCache, Text};
 use log::error;
+use kea::lsp::Severity;
 use kea::{Ptr};
-use std::cell::Ref;
+use std::cell::{Ref, RefMut};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CursorMotion {
     Left,
     Right,
@@ -21,7 +26,7 @@ pub enum CursorMotion {
     Down,
 }
 /// cursor position, zero-indexed.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Cursor {
     line: usize,
     column: usize,
@@ -52,6 +57,19 @@ pub enum Invalidation {
     ScrollDown { pixels: usize, lines: usize },
 }
 
+/// how a frame shows its buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameOptions {
+    /// long lines continue on the next row instead of running past the edge of the frame.
+    pub wrap: bool,
+    /// line numbers are shown on the left of the text.
+    pub gutter: bool,
+    /// the message of a diagnostic is shown after the text of its line.
+    pub virtual_text: bool,
+    /// the commit that last changed the line of the cursor is shown after its text.
+    pub blame: bool,
+}
+
 pub struct Frame {
     /// id of this frame
     id: FrameId,
@@ -69,9 +87,33 @@ pub struct Frame {
     // font_metrics: ScaledFontMetrics,
     /// the lines of the buffer this view is viewing.
     view: Range<usize>,
-    /// the position of the cursor in the buffer. For now there is only one, this will be expanded
-    /// to allow multiple cursors.
-    cursor: Cursor,
+    /// the positions of the cursors in the buffer, the first cursor is the primary cursor.
+    /// There is always at least one cursor.
+    cursors: Vec<Cursor>,
+    /// the other end of the selection of each cursor, the text between anchors[i] and
+    /// cursors[i] is selected. A cursor without an anchor selects nothing.
+    anchors: Vec<Option<Cursor>>,
+    /// a file is being dragged over the frame.
+    hovered: bool,
+    options: FrameOptions,
+    /// the selections before and after each expansion of the selection, for shrinking it.
+    expansions: Vec<(Range<usize>, Range<usize>)>,
+    /// the buffer line of each line of the cache, the lines hidden by folds are skipped. The
+    /// filler rows of a diff have no line.
+    shown: Vec<Option<usize>>,
+    /// the side of a diff the frame shows, its lines are lined up with the other side.
+    diff: Option<core::diff::Side>,
+    /// the number of filler rows above the first line of the view scrolled out of view.
+    hidden_fillers: usize,
+    /// the terminal the frame shows instead of its buffer.
+    terminal: Option<Terminal>,
+    /// the buffer can't be edited in the frame, e.g. the file on disk of a diff.
+    read_only: bool,
+    /// with wrapping, the rows to scroll by once the rows of the lines are known.
+    pending_rows: isize,
+    /// with wrapping, the cursor moved and is kept in view once the rows of the lines are
+    /// known.
+    reveal_cursor: bool,
 }
 
 impl Frame {
@@ -84,7 +126,18 @@ impl Frame {
             active: false,
             cache: LineCache::new(lines),
             view: 0..lines,
-            cursor: Cursor::new(0, 0),
+            cursors: vec![Cursor::new(0, 0)],
+            anchors: Vec::new(),
+            hovered: false,
+            options: FrameOptions::default(),
+            expansions: Vec::new(),
+            shown: Vec::new(),
+            diff: None,
+            hidden_fillers: 0,
+            terminal: None,
+            read_only: false,
+            pending_rows: 0,
+            reveal_cursor: false,
         }
     }
 
@@ -92,6 +145,14 @@ impl Frame {
         (height / metrics.line_height()) as usize
     }
 
+    /// changes the size of the frame and the number of lines it can show. The line cache
+    /// is repopulated so the text is positioned again.
+    pub fn resize(&mut self, size: Vector2F, lines: usize) {
+        self.size = size;
+        self.view = self.view.start..self.view.start + lines;
+        self.fill_cache();
+    }
+
     fn next_id() -> usize {
         static TOKEN: AtomicUsize = AtomicUsize::new(1);
         TOKEN.fetch_add(1, Ordering::SeqCst)
@@ -101,10 +162,18 @@ impl Frame {
         self.buffer.borrow()
     }
 
+    pub fn buffer_mut(&self) -> RefMut<core::Buffer> {
+        self.buffer.borrow_mut()
+    }
+
     pub fn id(&self) -> FrameId {
         self.id
     }
 
+    pub fn buffer_id(&self) -> core::BufferId {
+        self.buffer.borrow().id()
+    }
+
     pub fn width(&self) -> f32 {
         self.size.x()
     }
@@ -117,8 +186,1069 @@ impl Frame {
         &self.origin
     }
 
+    /// moves the frame, the top left is in window coordinates.
+    pub fn set_origin(&mut self, origin: Vector2F) {
+        self.origin = origin;
+    }
+
+    /// shows the frame as a side of a diff, or as a plain buffer again with None.
+    pub fn set_diff(&mut self, diff: Option<core::diff::Side>) {
+        self.diff = diff;
+        self.fill_cache();
+    }
+
+    /// the row of the diff at the top of the view, counting filler rows.
+    pub fn top_row(&self) -> usize {
+        match self.diff.as_ref() {
+            Some(side) => side.top_row(self.view.start, self.hidden_fillers),
+            None => self.view.start,
+        }
+    }
+
+    /// scrolls the view so a row of the diff is at the top, used to keep both sides of a diff
+    /// scrolled together.
+    pub fn scroll_to_row(&mut self, row: usize) {
+        let (line, hidden) = match self.diff.as_ref() {
+            Some(side) => side.row_start(row),
+            None => (row, 0),
+        };
+        let last_line = self.buffer.borrow().num_lines().saturating_sub(1);
+        self.view = line.min(last_line)..line.min(last_line) + self.view.len();
+        self.hidden_fillers = if line > last_line { 0 } else { hidden };
+        self.fill_cache();
+    }
+
+    /// a read only frame doesn't let its buffer be edited.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// shows a terminal in the frame instead of its buffer.
+    pub fn set_terminal(&mut self, terminal: Terminal) {
+        self.terminal = Some(terminal);
+    }
+
+    pub fn terminal(&self) -> Option<&Terminal> {
+        self.terminal.as_ref()
+    }
+
+    pub fn terminal_mut(&mut self) -> Option<&mut Terminal> {
+        self.terminal.as_mut()
+    }
+
+    /// the cell of the terminal under a point in window coordinates, points outside of the
+    /// frame are clamped to its cells.
+    pub fn cell_at(&self, point: Vector2F, context: &RenderContext) -> terminal::Point {
+        let line_height = context.font_metrics().line_height();
+        let relative = point - self.origin;
+        let row = (relative.y() / line_height).max(0.0) as usize;
+        let column = (relative.x() / context.cell_width()).max(0.0) as usize;
+        match self.terminal.as_ref() {
+            Some(terminal) => {
+                let grid = terminal.grid();
+                terminal::Point::new(
+                    row.min(grid.rows().saturating_sub(1)),
+                    column.min(grid.columns().saturating_sub(1)),
+                )
+            }
+            None => terminal::Point::new(row, column),
+        }
+    }
+
+    /// the blame of the line of the cursor is only shown by the active frame.
     pub fn set_active(&mut self, active: bool) {
-        self.active = active
+        if self.active != active {
+            self.active = active;
+            self.fill_cache();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn options(&self) -> FrameOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: FrameOptions) {
+        self.options = options;
+    }
+
+    pub fn set_hovered(&mut self, hovered: bool) {
+        self.hovered = hovered
+    }
+
+    pub fn is_hovered(&self) -> bool {
+        self.hovered
+    }
+
+    /// shows a different buffer in the frame, the view and cursor start at the top.
+    pub fn set_buffer(&mut self, buffer: Ptr<core::Buffer>) {
+        self.set_folds(Vec::new());
+        self.buffer = buffer;
+        self.cursors = vec![Cursor::new(0, 0)];
+        self.anchors.clear();
+        self.diff = None;
+        self.hidden_fillers = 0;
+        self.view = 0..self.view.len();
+        self.fill_cache();
+    }
+
+    /// the contents of the buffer were replaced, e.g. its file was reloaded. The cursors are
+    /// moved into the new contents and the view stays where it was.
+    pub fn buffer_reloaded(&mut self) {
+        self.set_folds(Vec::new());
+        self.clamp_to_buffer();
+    }
+
+    /// places the cursors and scrolls the view to a line, used when a session is restored.
+    /// Positions outside of the buffer are clamped.
+    pub fn restore(&mut self, cursors: &[(usize, usize)], scroll: usize) {
+        self.cursors = cursors
+            .iter()
+            .map(|&(line, column)| Cursor::new(line, column))
+            .collect();
+        if self.cursors.is_empty() {
+            self.cursors.push(Cursor::new(0, 0));
+        }
+        self.anchors.clear();
+        self.view = scroll..scroll + self.view.len();
+        self.clamp_to_buffer();
+    }
+
+    fn clamp_to_buffer(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            let last_line = buffer.num_lines().saturating_sub(1);
+            for cursor in self.cursors.iter_mut().chain(self.anchors.iter_mut().flatten()) {
+                cursor.line = cursor.line.min(last_line);
+                cursor.column = cursor.column.min(buffer.line_len(cursor.line));
+            }
+        }
+        self.dedup_cursors();
+        self.scroll(0);
+    }
+
+    /// the area of the window the frame covers.
+    pub fn rect(&self) -> RectF {
+        RectF::new(self.origin, self.size)
+    }
+
+    /// is the point, in window coordinates, inside of the frame.
+    pub fn contains(&self, point: Vector2F) -> bool {
+        self.rect().contains_point(point)
+    }
+
+    /// the primary cursor.
+    pub fn cursor(&self) -> &Cursor {
+        &self.cursors[0]
+    }
+
+    pub fn cursors(&self) -> &[Cursor] {
+        self.cursors.as_slice()
+    }
+
+    /// the lines of the buffer currently visible.
+    pub fn view(&self) -> &Range<usize> {
+        &self.view
+    }
+
+    /// applies an operation that only affects this frame. Returns false if the
+    /// operation is not handled by frames.
+    pub fn handle_operation(&mut self, operation: Operation) -> bool {
+        // scrolling by a page keeps one line of the previous page visible.
+        let page = self.view.len().saturating_sub(1).max(1);
+        let motion = match operation {
+            Operation::CursorUp => Some(CursorMotion::Up),
+            Operation::CursorDown => Some(CursorMotion::Down),
+            Operation::CursorLeft => Some(CursorMotion::Left),
+            Operation::CursorRight => Some(CursorMotion::Right),
+            _ => None,
+        };
+        if let Some(motion) = motion {
+            self.anchors.clear();
+            self.move_cursor(motion);
+            return true;
+        }
+
+        match operation {
+            Operation::AddCursorAbove => self.add_cursor_line(false),
+            Operation::AddCursorBelow => self.add_cursor_line(true),
+            Operation::ScrollUp => {
+                self.update_line_cache(Invalidation::ScrollUp { pixels: 0, lines: page })
+            }
+            Operation::ScrollDown => {
+                self.update_line_cache(Invalidation::ScrollDown { pixels: 0, lines: page })
+            }
+            Operation::ExpandSelection => self.expand_selection(),
+            Operation::ShrinkSelection => self.shrink_selection(),
+            Operation::MatchingBracket => self.goto_matching_bracket(),
+            Operation::EnclosingFunction => self.goto_enclosing_function(),
+            Operation::Fold => self.fold(),
+            Operation::Unfold => self.unfold(),
+            Operation::ToggleFolds => self.toggle_folds(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// moves every cursor, cursors that end up at the same position are merged.
+    pub fn move_cursor(&mut self, motion: CursorMotion) {
+        {
+            let buffer = self.buffer.borrow();
+            let folds = folded_lines(&buffer, self.id);
+            for cursor in self.cursors.iter_mut() {
+                Self::apply_motion(&buffer, &folds, cursor, motion);
+            }
+        }
+
+        self.dedup_cursors();
+        self.show_cursor();
+    }
+
+    /// moves a cursor, the lines hidden by folds are stepped over.
+    fn apply_motion(
+        buffer: &core::Buffer,
+        folds: &[Range<usize>],
+        cursor: &mut Cursor,
+        motion: CursorMotion,
+    ) {
+        let num_lines = buffer.num_lines();
+        let next_line = next_shown_line(folds, cursor.line);
+        match motion {
+            CursorMotion::Up => {
+                if cursor.line > 0 {
+                    cursor.line = shown_line(folds, cursor.line - 1);
+                }
+                cursor.column = cursor.column.min(buffer.line_len(cursor.line));
+            }
+            CursorMotion::Down => {
+                if next_line < num_lines {
+                    cursor.line = next_line;
+                }
+                cursor.column = cursor.column.min(buffer.line_len(cursor.line));
+            }
+            CursorMotion::Left => {
+                if cursor.column > 0 {
+                    cursor.column -= 1;
+                } else if cursor.line > 0 {
+                    cursor.line = shown_line(folds, cursor.line - 1);
+                    cursor.column = buffer.line_len(cursor.line);
+                }
+            }
+            CursorMotion::Right => {
+                if cursor.column < buffer.line_len(cursor.line) {
+                    cursor.column += 1;
+                } else if next_line < num_lines {
+                    cursor.line = next_line;
+                    cursor.column = 0;
+                }
+            }
+        }
+    }
+
+    /// removes cursors at the same position, keeping the first one and its anchor.
+    fn dedup_cursors(&mut self) {
+        let mut cursors: Vec<Cursor> = Vec::with_capacity(self.cursors.len());
+        let mut anchors = Vec::with_capacity(self.anchors.len());
+        for (idx, cursor) in self.cursors.drain(..).enumerate() {
+            if !cursors.contains(&cursor) {
+                cursors.push(cursor);
+                anchors.push(self.anchors.get(idx).cloned().flatten());
+            }
+        }
+        self.cursors = cursors;
+        self.anchors = anchors;
+    }
+
+    /// adds a cursor, if there already is a cursor at the position nothing changes.
+    pub fn add_cursor(&mut self, line: usize, column: usize) {
+        self.add_selection(line, column, None);
+    }
+
+    /// adds a cursor with the other end of its selection in a column of the same line, if
+    /// there already is a cursor at the position nothing changes.
+    fn add_selection(&mut self, line: usize, column: usize, anchor: Option<usize>) {
+        let (cursor, anchor) = {
+            let buffer = self.buffer.borrow();
+            let line = line.min(buffer.num_lines().saturating_sub(1));
+            let len = buffer.line_len(line);
+            let anchor = anchor.map(|anchor| Cursor::new(line, anchor.min(len)));
+            (Cursor::new(line, column.min(len)), anchor)
+        };
+        if !self.cursors.contains(&cursor) {
+            self.anchors.resize(self.cursors.len(), None);
+            self.cursors.push(cursor);
+            self.anchors.push(anchor);
+        }
+        self.fill_cache();
+    }
+
+    /// adds a cursor on the line above the top cursor or below the bottom one, in the column
+    /// of the primary cursor. When the primary cursor selects part of its line the same
+    /// columns are selected by the new cursor.
+    fn add_cursor_line(&mut self, below: bool) {
+        let column = self.cursor().column;
+        let anchor = match self.anchors.first() {
+            Some(Some(anchor)) if anchor.line == self.cursor().line => Some(anchor.column),
+            _ => None,
+        };
+        let line = if below {
+            let line = self.cursors.iter().map(|c| c.line).max().unwrap_or(0) + 1;
+            if line >= self.buffer.borrow().num_lines() {
+                return;
+            }
+            line
+        } else {
+            match self.cursors.iter().map(|c| c.line).min().unwrap_or(0) {
+                0 => return,
+                line => line - 1,
+            }
+        };
+        self.add_selection(line, column, anchor);
+    }
+
+    /// removes every cursor but the primary one and clears the selection.
+    pub fn collapse_cursors(&mut self) {
+        self.cursors.truncate(1);
+        self.anchors.clear();
+        self.fill_cache();
+    }
+
+    /// moves the primary cursor to a line and column, both are clamped to the buffer. Other
+    /// cursors are removed.
+    pub fn set_cursor(&mut self, line: usize, column: usize) {
+        {
+            let buffer = self.buffer.borrow();
+            let line = line.min(buffer.num_lines().saturating_sub(1));
+            self.cursors = vec![Cursor::new(line, column.min(buffer.line_len(line)))];
+            self.anchors.truncate(1);
+        }
+        self.show_cursor();
+    }
+
+    /// moves the cursor, if extend is true the selection is extended to the cursor
+    /// otherwise the selection is cleared.
+    pub fn place_cursor(&mut self, line: usize, column: usize, extend: bool) {
+        if !extend {
+            self.anchors.clear();
+        } else if !matches!(self.anchors.first(), Some(Some(_))) {
+            self.anchors = vec![Some(self.cursor().clone())];
+        }
+        self.set_cursor(line, column);
+    }
+
+    /// selects the first range of characters and places a cursor at the end of each other
+    /// range, e.g. a tab stop of a snippet and its mirrors.
+    pub fn select_ranges(&mut self, ranges: &[Range<usize>]) {
+        if ranges.is_empty() {
+            return;
+        }
+        {
+            let buffer = self.buffer.borrow();
+            let cursor = |char_idx: usize| {
+                let (line, column) = buffer.position(char_idx);
+                Cursor::new(line, column)
+            };
+            self.anchors = if ranges[0].is_empty() {
+                Vec::new()
+            } else {
+                vec![Some(cursor(ranges[0].start))]
+            };
+            self.cursors = ranges.iter().map(|range| cursor(range.end)).collect();
+        }
+        self.dedup_cursors();
+        self.show_cursor();
+    }
+
+    /// the selected characters, or the position of the primary cursor when nothing is
+    /// selected.
+    fn selected_range(&self) -> Range<usize> {
+        let cursor = self.cursor().clone();
+        let (start, end) = self.selection().unwrap_or((cursor.clone(), cursor));
+        let buffer = self.buffer.borrow();
+        buffer.char_index(start.line, start.column)..buffer.char_index(end.line, end.column)
+    }
+
+    /// selects the next larger syntactic range around the selection, e.g. the word, the
+    /// inside of the brackets, the brackets.
+    fn expand_selection(&mut self) {
+        let range = self.selected_range();
+        let expanded = self
+            .buffer
+            .borrow()
+            .syntax()
+            .and_then(|syntax| syntax.expand(range.clone()));
+        let expanded = match expanded {
+            Some(expanded) => expanded,
+            None => return,
+        };
+        // the selection was changed since it was last expanded.
+        if self.expansions.last().map_or(false, |(_, after)| *after != range) {
+            self.expansions.clear();
+        }
+        self.expansions.push((range, expanded.clone()));
+        self.select_ranges(&[expanded]);
+    }
+
+    /// selects what was selected before the selection was last expanded.
+    fn shrink_selection(&mut self) {
+        let range = self.selected_range();
+        match self.expansions.pop() {
+            Some((before, after)) if after == range => self.select_ranges(&[before]),
+            _ => self.expansions.clear(),
+        }
+    }
+
+    /// moves the cursor to the bracket matching the bracket at the cursor or before it.
+    fn goto_matching_bracket(&mut self) {
+        let position = {
+            let buffer = self.buffer.borrow();
+            let cursor = self.cursor();
+            let char_idx = buffer.char_index(cursor.line, cursor.column);
+            buffer
+                .syntax()
+                .and_then(|syntax| syntax.matching_bracket(char_idx))
+                .map(|char_idx| buffer.position(char_idx))
+        };
+        if let Some((line, column)) = position {
+            self.place_cursor(line, column, false);
+        }
+    }
+
+    /// moves the cursor to the start of the function around it, again to the function around
+    /// that one.
+    fn goto_enclosing_function(&mut self) {
+        let position = {
+            let buffer = self.buffer.borrow();
+            let cursor = self.cursor();
+            let char_idx = buffer.char_index(cursor.line, cursor.column);
+            buffer
+                .syntax()
+                .and_then(|syntax| syntax.enclosing_function(char_idx))
+                .map(|function| buffer.position(function.start))
+        };
+        if let Some((line, column)) = position {
+            self.place_cursor(line, column, false);
+        }
+    }
+
+    /// the regions of the buffer that can be folded as their first and last line, the blocks of
+    /// its syntax or, without a grammar, the lines indented deeper than the line before them.
+    fn foldable_ranges(&self) -> Vec<Range<usize>> {
+        let buffer = self.buffer.borrow();
+        match buffer.syntax() {
+            Some(syntax) => syntax.folding_ranges(),
+            None => indented_ranges(&buffer.request_lines(0, buffer.num_lines())),
+        }
+    }
+
+    /// the folded regions as their first and last line, only the first line of a fold is shown.
+    /// Folds can be nested.
+    fn folds(&self) -> Vec<Range<usize>> {
+        folded_lines(&self.buffer.borrow(), self.id)
+    }
+
+    /// replaces the folds. They are kept by the buffer from the end of their first line to the
+    /// end of their last line, so they follow every edit of the buffer.
+    fn set_folds(&mut self, folds: Vec<Range<usize>>) {
+        let mut buffer = self.buffer.borrow_mut();
+        let ranges = folds
+            .into_iter()
+            .map(|fold| {
+                let end_of = |line: usize| buffer.char_index(line, buffer.line_len(line));
+                end_of(fold.start)..end_of(fold.end)
+            })
+            .collect();
+        buffer.set_anchored(self.id.0, ranges);
+    }
+
+    /// folds the innermost region around the primary cursor that isn't folded yet.
+    fn fold(&mut self) {
+        let line = self.cursor().line;
+        let mut folds = self.folds();
+        let fold = self
+            .foldable_ranges()
+            .into_iter()
+            .filter(|range| range.start <= line && line <= range.end)
+            .filter(|range| !folds.contains(range))
+            .min_by_key(|range| range.end - range.start);
+        if let Some(fold) = fold {
+            folds.push(fold);
+            self.set_folds(folds);
+            self.folds_changed();
+        }
+    }
+
+    /// opens the folds starting on the line of the primary cursor.
+    fn unfold(&mut self) {
+        let line = self.cursor().line;
+        let mut folds = self.folds();
+        folds.retain(|fold| fold.start != line);
+        self.set_folds(folds);
+        self.fill_cache();
+    }
+
+    /// opens every fold, or folds every region when nothing is folded.
+    fn toggle_folds(&mut self) {
+        if self.folds().is_empty() {
+            let folds = self.foldable_ranges();
+            self.set_folds(folds);
+        } else {
+            self.set_folds(Vec::new());
+        }
+        self.folds_changed();
+    }
+
+    /// moves the cursors on lines hidden by the folds to the first line of their fold.
+    fn folds_changed(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            let folds = folded_lines(&buffer, self.id);
+            for cursor in self.cursors.iter_mut().chain(self.anchors.iter_mut().flatten()) {
+                let line = shown_line(&folds, cursor.line);
+                if line != cursor.line {
+                    cursor.line = line;
+                    cursor.column = cursor.column.min(buffer.line_len(line));
+                }
+            }
+        }
+        self.dedup_cursors();
+        self.show_cursor();
+    }
+
+    /// count lines shown from a line on, the lines hidden by folds are skipped.
+    fn shown_lines(&self, start: usize, count: usize) -> Vec<usize> {
+        let folds = self.folds();
+        let mut line = shown_line(&folds, start);
+        let mut lines = Vec::with_capacity(count);
+        while lines.len() < count {
+            lines.push(line);
+            line = next_shown_line(&folds, line);
+        }
+        lines
+    }
+
+    /// the start and end of the selection of the primary cursor, None if nothing is selected.
+    pub fn selection(&self) -> Option<(Cursor, Cursor)> {
+        self.selection_of(0)
+    }
+
+    /// the start and end of the selection of a cursor.
+    fn selection_of(&self, idx: usize) -> Option<(Cursor, Cursor)> {
+        let anchor = self.anchors.get(idx)?.as_ref()?;
+        let cursor = &self.cursors[idx];
+        if anchor < cursor {
+            Some((anchor.clone(), cursor.clone()))
+        } else if anchor > cursor {
+            Some((cursor.clone(), anchor.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// the text selected by the primary cursor, None if nothing is selected.
+    pub fn selected_text(&self) -> Option<String> {
+        if let Some(terminal) = self.terminal.as_ref() {
+            return terminal.selected_text();
+        }
+        let (start, end) = self.selection()?;
+        let buffer = self.buffer.borrow();
+        let start = buffer.char_index(start.line, start.column);
+        let end = buffer.char_index(end.line, end.column);
+        Some(buffer.text(start..end))
+    }
+
+    /// the text selected by each cursor, empty for the cursors without a selection. None if
+    /// no cursor selects anything.
+    pub fn selected_texts(&self) -> Option<Vec<String>> {
+        if let Some(terminal) = self.terminal.as_ref() {
+            return terminal.selected_text().map(|text| vec![text]);
+        }
+        let buffer = self.buffer.borrow();
+        let texts: Vec<String> = self
+            .selected_ranges()
+            .into_iter()
+            .map(|range| buffer.text(range))
+            .collect();
+        if texts.iter().all(String::is_empty) {
+            None
+        } else {
+            Some(texts)
+        }
+    }
+
+    /// the characters selected by each cursor, an empty range at the cursor when it selects
+    /// nothing.
+    fn selected_ranges(&self) -> Vec<Range<usize>> {
+        let buffer = self.buffer.borrow();
+        (0..self.cursors.len())
+            .map(|idx| {
+                let cursor = self.cursors[idx].clone();
+                let (start, end) = self.selection_of(idx).unwrap_or((cursor.clone(), cursor));
+                buffer.char_index(start.line, start.column)..buffer.char_index(end.line, end.column)
+            })
+            .collect()
+    }
+
+    /// removes the text selected by each cursor and returns it, one text for each cursor.
+    /// The cursors are placed where their text was.
+    pub fn delete_selections(&mut self) -> Option<Vec<String>> {
+        if self.read_only {
+            return None;
+        }
+        let texts = self.selected_texts()?;
+        let ranges = self.selected_ranges();
+        self.remove_ranges(ranges);
+        Some(texts)
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.anchors.clear();
+        self.fill_cache();
+    }
+
+    /// selects the word at a position. Whitespace and punctuation are selected as a run of
+    /// the same kind of character.
+    pub fn select_word(&mut self, line: usize, column: usize) {
+        let text = self.buffer.borrow().request_lines(line, line + 1).pop();
+        let chars: Vec<char> = text.unwrap_or_default().chars().collect();
+        if chars.is_empty() {
+            self.place_cursor(line, 0, false);
+            return;
+        }
+
+        let class = |ch: char| {
+            if ch.is_alphanumeric() || ch == '_' {
+                0
+            } else if ch.is_whitespace() {
+                1
+            } else {
+                2
+            }
+        };
+
+        let column = column.min(chars.len() - 1);
+        let kind = class(chars[column]);
+        let mut start = column;
+        while start > 0 && class(chars[start - 1]) == kind {
+            start -= 1;
+        }
+        let mut end = column + 1;
+        while end < chars.len() && class(chars[end]) == kind {
+            end += 1;
+        }
+
+        self.anchors = vec![Some(Cursor::new(line, start))];
+        self.set_cursor(line, end);
+    }
+
+    /// selects a line including its line ending.
+    pub fn select_line(&mut self, line: usize) {
+        let num_lines = self.buffer.borrow().num_lines();
+        let line = line.min(num_lines.saturating_sub(1));
+        self.anchors = vec![Some(Cursor::new(line, 0))];
+        if line + 1 < num_lines {
+            self.set_cursor(line + 1, 0);
+        } else {
+            let len = self.buffer.borrow().line_len(line);
+            self.set_cursor(line, len);
+        }
+    }
+
+    /// the buffer position under a point in window coordinates. Points outside of the
+    /// frame are clamped to the visible lines.
+    pub fn position_at(&mut self, point: Vector2F, context: &mut RenderContext) -> (usize, usize) {
+        let line_height = context.font_metrics().line_height();
+        let gutter = Renderer::gutter_width(context, self);
+        let width = self.size.x() - gutter;
+        let wrap = self.options.wrap;
+        let y = point.y() - self.origin.y();
+        let mut row = (y / line_height).max(0.0) as usize;
+
+        // with wrapping a line of the view can take several rows.
+        let mut idx = row;
+        if wrap {
+            idx = self.view.len();
+            for (line_idx, text) in self.cache.lines_mut().iter_mut().enumerate() {
+                let rows = match text {
+                    Some(text) => Self::shaped(text, context).wrap(width).len(),
+                    None => 1,
+                };
+                if row < rows {
+                    idx = line_idx;
+                    break;
+                }
+                row -= rows;
+            }
+        }
+        let idx = idx.min(self.view.len().saturating_sub(1));
+
+        // a filler row of a diff belongs to the line after it.
+        let line = match self.shown.get(idx..).and_then(|rows| rows.iter().flatten().next()) {
+            Some(&line) => line,
+            None => {
+                // below the end of the buffer.
+                let buffer = self.buffer.borrow();
+                let last_line = buffer.num_lines().saturating_sub(1);
+                return (last_line, buffer.line_len(last_line));
+            }
+        };
+        let line_len = self.buffer.borrow().line_len(line);
+
+        let x = point.x() - self.origin.x() - gutter;
+        let column = match self.cache.lines_mut().get_mut(idx) {
+            Some(Some(text)) => {
+                let text = Self::shaped(text, context);
+                if wrap {
+                    let rows = text.wrap(width);
+                    rows.get(row)
+                        .or_else(|| rows.last())
+                        .map_or(0, |(start, row)| start + row.column_at(x))
+                } else {
+                    text.column_at(x)
+                }
+            }
+            _ => 0,
+        };
+
+        (line, column.min(line_len))
+    }
+
+    /// the top left of the character at a line and column in window coordinates, None if the
+    /// line isn't visible.
+    pub fn point_of(
+        &mut self,
+        line: usize,
+        column: usize,
+        context: &mut RenderContext,
+    ) -> Option<Vector2F> {
+        let idx = self.shown.iter().position(|shown| *shown == Some(line))?;
+        let line_height = context.font_metrics().line_height();
+        let gutter = Renderer::gutter_width(context, self);
+        let width = self.size.x() - gutter;
+
+        let mut row = idx;
+        if self.options.wrap {
+            row = self.cache.lines_mut()[..idx]
+                .iter_mut()
+                .map(|text| match text {
+                    Some(text) => Self::shaped(text, context).wrap(width).len(),
+                    None => 1,
+                })
+                .sum();
+        }
+
+        let x = match self.cache.lines_mut().get_mut(idx) {
+            Some(Some(text)) => {
+                let text = Self::shaped(text, context);
+                if self.options.wrap {
+                    let rows = text.wrap(width);
+                    let wrapped = rows
+                        .iter()
+                        .rposition(|(start, _)| *start <= column)
+                        .unwrap_or(0);
+                    row += wrapped;
+                    rows.get(wrapped)
+                        .map_or(0.0, |(start, text)| text.caret(column - start))
+                } else {
+                    text.caret(column)
+                }
+            }
+            _ => 0.0,
+        };
+        let y = row as f32 * line_height;
+        Some(self.origin + vec2f(gutter + x, y))
+    }
+
+    /// with wrapping a line can take several rows, so the view counted in lines can run past
+    /// the bottom of the frame. Applies the scrolling counted in rows and moves the view down
+    /// until the line of a cursor that moved fits, called before the frame is drawn.
+    pub fn fit_wrapped(&mut self, context: &mut RenderContext) {
+        if !self.options.wrap {
+            return;
+        }
+        let width = self.size.x() - Renderer::gutter_width(context, self);
+        let rows = self.view.len();
+        let folds = self.folds();
+        let last_line = self.buffer.borrow().num_lines().saturating_sub(1);
+
+        // whole lines are scrolled, as many as fit in the rows.
+        let pending = std::mem::take(&mut self.pending_rows);
+        if pending != 0 {
+            let mut start = self.view.start;
+            let mut scrolled = 0;
+            loop {
+                let next = if pending < 0 {
+                    match start {
+                        0 => break,
+                        start => shown_line(&folds, start - 1),
+                    }
+                } else {
+                    next_shown_line(&folds, start)
+                };
+                if next > last_line {
+                    break;
+                }
+                let line = if pending < 0 { next } else { start };
+                scrolled += self.line_rows(line, width, context);
+                if scrolled > pending.unsigned_abs() && start != self.view.start {
+                    break;
+                }
+                start = next;
+            }
+            self.view = start..start + rows;
+            self.hidden_fillers = 0;
+            self.fill_cache();
+        }
+
+        if !std::mem::take(&mut self.reveal_cursor) {
+            return;
+        }
+        let line = self.cursor().line;
+        let idx = match self.shown.iter().position(|shown| *shown == Some(line)) {
+            Some(idx) => idx,
+            None => return,
+        };
+        let mut used = 0;
+        let mut first = idx;
+        for row in (0..=idx).rev() {
+            used += match self.cache.lines_mut().get_mut(row) {
+                Some(Some(text)) => Self::shaped(text, context).wrap(width).len().max(1),
+                _ => 1,
+            };
+            if used > rows && row < idx {
+                break;
+            }
+            first = row;
+        }
+        if let Some(&Some(start)) = self.shown[first..].iter().find(|shown| shown.is_some()) {
+            if start != self.view.start {
+                self.view = start..start + rows;
+                self.hidden_fillers = 0;
+                self.fill_cache();
+            }
+        }
+    }
+
+    /// the rows a line takes with wrapping.
+    fn line_rows(&mut self, line: usize, width: f32, context: &mut RenderContext) -> usize {
+        if let Some(idx) = self.shown.iter().position(|shown| *shown == Some(line)) {
+            return match self.cache.lines_mut().get_mut(idx) {
+                Some(Some(text)) => Self::shaped(text, context).wrap(width).len().max(1),
+                _ => 1,
+            };
+        }
+        let text = self.buffer.borrow().request_lines(line, line + 1).pop();
+        self.layout_line(line + 1, text.unwrap_or_default(), Vec::new())
+            .iter()
+            .map(|text| Renderer::position_line(context, text).wrap(width).len().max(1))
+            .sum()
+    }
+
+    /// lines are shaped when rendered, the line may not have been rendered yet.
+    fn shaped<'a>(text: &'a mut Text<TextLine>, context: &mut RenderContext) -> &'a TextLine {
+        if text.assoc.is_none() {
+            text.assoc = Some(Renderer::position_line(context, text));
+        }
+        text.assoc.as_ref().unwrap()
+    }
+
+    /// inserts text at every cursor, the cursors are placed after the inserted text.
+    pub fn insert(&mut self, text: &str) {
+        let texts = vec![text.to_string(); self.cursors.len()];
+        self.insert_each(&texts);
+    }
+
+    /// inserts a text at each cursor, texts[i] is inserted at the i-th cursor. Missing texts
+    /// are treated as empty.
+    pub fn insert_each(&mut self, texts: &[String]) {
+        if self.read_only {
+            return;
+        }
+        self.anchors.clear();
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            // the text inserted at every cursor is undone at once.
+            buffer.begin_edit();
+            let indices: Vec<usize> = self
+                .cursors
+                .iter()
+                .map(|c| buffer.char_index(c.line, c.column))
+                .collect();
+            let lens: Vec<usize> = (0..indices.len())
+                .map(|i| texts.get(i).map_or(0, |text| text.chars().count()))
+                .collect();
+
+            // insert from the end of the buffer so earlier positions stay valid.
+            let mut order: Vec<usize> = (0..indices.len()).collect();
+            order.sort_by_key(|&i| std::cmp::Reverse(indices[i]));
+            for &i in &order {
+                if let Some(text) = texts.get(i) {
+                    buffer.insert(indices[i], text);
+                }
+            }
+            buffer.end_edit();
+
+            for (i, cursor) in self.cursors.iter_mut().enumerate() {
+                let shift: usize = (0..indices.len())
+                    .filter(|&j| indices[j] <= indices[i])
+                    .map(|j| lens[j])
+                    .sum();
+                let (line, column) = buffer.position(indices[i] + shift);
+                *cursor = Cursor::new(line, column);
+            }
+        }
+        self.dedup_cursors();
+        self.show_cursor();
+    }
+
+    /// reverts the last edit of the buffer, or applies the last reverted edit again when redo
+    /// is true. The cursor is placed where the edit happened.
+    pub fn undo(&mut self, redo: bool) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let position = {
+            let mut buffer = self.buffer.borrow_mut();
+            let char_idx = if redo { buffer.redo() } else { buffer.undo() };
+            char_idx.map(|char_idx| buffer.position(char_idx))
+        };
+        match position {
+            Some((line, column)) => {
+                self.place_cursor(line, column, false);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// deletes count characters before each cursor.
+    pub fn delete_backward(&mut self, count: usize) {
+        self.delete_ranges(|idx, _| idx.saturating_sub(count)..idx);
+    }
+
+    /// deletes count characters after each cursor.
+    pub fn delete_forward(&mut self, count: usize) {
+        self.delete_ranges(|idx, len| idx..(idx + count).min(len));
+    }
+
+    /// removes a range of characters around each cursor, range maps the character index of a
+    /// cursor and the length of the buffer to the range. Overlapping ranges are merged.
+    fn delete_ranges<F: Fn(usize, usize) -> Range<usize>>(&mut self, range: F) {
+        if self.read_only {
+            return;
+        }
+        let ranges = {
+            let buffer = self.buffer.borrow();
+            let len = buffer.len_chars();
+            self.cursors
+                .iter()
+                .map(|c| range(buffer.char_index(c.line, c.column), len))
+                .collect()
+        };
+        self.remove_ranges(ranges);
+    }
+
+    /// removes a range of characters for each cursor, ranges[i] holds the i-th cursor.
+    /// Overlapping ranges are merged and the selections are cleared.
+    fn remove_ranges(&mut self, mut ranges: Vec<Range<usize>>) {
+        self.anchors.clear();
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            let indices: Vec<usize> = self
+                .cursors
+                .iter()
+                .map(|c| buffer.char_index(c.line, c.column))
+                .collect();
+
+            ranges.sort_by_key(|r| r.start);
+            let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+            for r in ranges {
+                match merged.last_mut() {
+                    Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                    _ => merged.push(r),
+                }
+            }
+
+            buffer.begin_edit();
+            for r in merged.iter().rev() {
+                buffer.remove(r.clone());
+            }
+            buffer.end_edit();
+
+            for (cursor, idx) in self.cursors.iter_mut().zip(indices) {
+                // the characters removed before the cursor.
+                let removed: usize = merged
+                    .iter()
+                    .map(|r| r.end.min(idx).saturating_sub(r.start))
+                    .sum();
+                let (line, column) = buffer.position(idx - removed);
+                *cursor = Cursor::new(line, column);
+            }
+        }
+        self.dedup_cursors();
+        self.show_cursor();
+    }
+
+    /// scrolls the view so the cursor is visible.
+    fn show_cursor(&mut self) {
+        let lines = self.view.len();
+        let line = self.cursor().line;
+        // a cursor moved into a fold, e.g. to a definition, opens it.
+        let mut folds = self.folds();
+        let count = folds.len();
+        folds.retain(|fold| !(fold.start < line && line <= fold.end));
+        if folds.len() != count {
+            self.set_folds(folds.clone());
+        }
+        self.hidden_fillers = 0;
+        self.reveal_cursor = true;
+        if line < self.view.start {
+            self.view = line..line + lines;
+        } else if !self.shown_lines(self.view.start, lines).contains(&line) {
+            // the cursor ends up on the last line of the view.
+            let mut start = line;
+            for _ in 1..lines {
+                if start == 0 {
+                    break;
+                }
+                start = shown_line(&folds, start - 1);
+            }
+            self.view = start..start + lines;
+        }
+
+        self.fill_cache();
+    }
+
+    /// moves the view by a number of lines, negative values scroll up. The lines hidden by
+    /// folds aren't counted and the first line of the view is kept within the buffer. With
+    /// wrapping rows are counted instead, the view moves when the frame is fitted.
+    pub fn scroll(&mut self, lines: isize) {
+        self.reveal_cursor = false;
+        if self.options.wrap && lines != 0 {
+            self.pending_rows += lines;
+            return;
+        }
+        let num_lines = self.buffer.borrow().num_lines();
+        let folds = self.folds();
+        let mut start = self.view.start;
+        for _ in 0..lines.unsigned_abs() {
+            start = if lines < 0 {
+                shown_line(&folds, start.saturating_sub(1))
+            } else {
+                next_shown_line(&folds, start)
+            };
+        }
+        let start = shown_line(&folds, start.min(num_lines.saturating_sub(1)));
+        self.view = start..start + self.view.len();
+        self.hidden_fillers = 0;
+        self.fill_cache();
     }
 
     pub fn lines(&self) -> &[Option<Text<TextLine>>] {
@@ -132,42 +1262,81 @@ impl Frame {
     pub fn update_line_cache(&mut self, invalidation: Invalidation) {
         match invalidation {
             Invalidation::Init => self.fill_cache(),
-            Invalidation::ScrollUp { pixels, lines } => {},
-            Invalidation::ScrollDown { pixels, lines } => {},
+            Invalidation::ScrollUp { pixels, lines } => self.scroll(-(lines as isize)),
+            Invalidation::ScrollDown { pixels, lines } => self.scroll(lines as isize),
         }
     }
 
     fn fill_cache(&mut self) {
-        let lines = self.buffer.borrow().request_lines(self.view.start, self.view.end);
+        let num_lines = self.buffer.borrow().num_lines();
+        let folds = self.folds();
+        let start = shown_line(&folds, self.view.start);
+        self.view = start..start + self.view.len();
+        // lines past the end of the buffer are left empty.
+        self.shown.clear();
+        for line in self.shown_lines(start, self.view.len()) {
+            let mut fillers = self.fillers(line);
+            if line == start {
+                fillers = fillers.saturating_sub(self.hidden_fillers);
+            }
+            self.shown.resize(self.shown.len() + fillers, None);
+            if line >= num_lines {
+                break;
+            }
+            self.shown.push(Some(line));
+        }
+        self.shown.truncate(self.view.len());
+
+        self.cache = LineCache::new(self.view.len());
         let mut populated_lines = 0;
-        for (idx, line) in lines.into_iter().enumerate() {
+        for (row, line_idx) in self.shown.clone().into_iter().enumerate() {
             // if we have populated the line cache before viewing all of the lines
             // then ignore the rest of the given lines.
             if populated_lines >= self.view.len() {
                 break;
             }
+            let line_idx = match line_idx {
+                Some(line_idx) => line_idx,
+                None => {
+                    let mut text = Text::from_string(0, String::new());
+                    text.filler = true;
+                    self.set_line(row, text);
+                    populated_lines += 1;
+                    continue;
+                }
+            };
 
-            // let cursor = self.get_cursors(self.view.start + idx);
+            let line = self.buffer.borrow().request_lines(line_idx, line_idx + 1).pop();
+            let line = line.unwrap_or_default();
+            let cursors = self.get_cursors(line_idx);
+            let selections = self.get_selections(line_idx);
 
             // layout the line
             for (offset, text) in self
-                .layout_line(idx + 1, line, None)
+                .layout_line(line_idx + 1, line, cursors)
                 .into_iter()
                 .enumerate()
             {
-                self.set_line(idx + offset, text);
+                let mut text = text;
+                text.selections = selections.clone();
+                let hidden = next_shown_line(&folds, line_idx) - line_idx - 1;
+                text.folded = Some(hidden).filter(|&hidden| hidden > 0);
+                self.mark_highlights(line_idx, &mut text);
+                self.mark_diagnostics(line_idx, &mut text);
+                self.mark_changes(line_idx, &mut text);
+                self.mark_diff(line_idx, &mut text);
+                self.set_line(row + offset, text);
                 populated_lines += 1;
             }
         }
     }
 
-    fn set_line(&mut self, line_idx: usize, text: Text<TextLine>) {
-        if self.view.contains(&line_idx) {
-            // gets the index relative to the view of the buffer.
-            let line_cache_idx = line_idx - self.view.start;
-            self.cache.replace(line_cache_idx, text);
+    /// sets a line of the cache, row is the index relative to the top of the view.
+    fn set_line(&mut self, row: usize, text: Text<TextLine>) {
+        if row < self.view.len() {
+            self.cache.replace(row, text);
         } else {
-            error!("attempting an invalid line set: {}", line_idx);
+            error!("attempting an invalid line set: {}", row);
         }
     }
 
@@ -175,27 +1344,438 @@ impl Frame {
         &mut self,
         line_number: usize,
         text: String,
-        cursor: Option<Cursor>,
+        cursors: Vec<usize>,
     ) -> Vec<Text<TextLine>> {
         let style = StyleSpan::new(StyleId(0), Span::new(0, text.len()));
-        let cursors = if let Some(cursor) = cursor {
-            vec![cursor.column]
-        } else {
-            Vec::new()
-        };
 
         let line = Text::new(text, line_number, false, None, cursors, vec![style]);
 
         vec![line]
     }
 
-    /// retreives all cursors for a given line.
-    // pub fn get_cursors(&sself, line: usize) -> Vec<Cursor>
-    pub fn get_cursors(&self, line: usize) -> Option<Cursor> {
-        if self.cursor.line == line {
-            Some(self.cursor.clone())
-        } else {
-            None
+    /// colors the text of a line by its syntax.
+    fn mark_highlights(&self, line: usize, text: &mut Text<TextLine>) {
+        let buffer = self.buffer.borrow();
+        let syntax = match buffer.syntax() {
+            Some(syntax) => syntax,
+            None => return,
+        };
+        let line_text = text.text.as_str();
+        let byte = |column: usize| {
+            line_text
+                .char_indices()
+                .nth(column)
+                .map_or(line_text.len(), |(idx, _)| idx)
+        };
+        text.highlights = syntax
+            .highlights(line)
+            .into_iter()
+            .map(|(columns, highlight)| {
+                (Span::new(byte(columns.start), byte(columns.end)), highlight)
+            })
+            .collect();
+    }
+
+    /// marks how a line differs from the file in git, the line of the primary cursor of the
+    /// active frame shows the commit that last changed it.
+    fn mark_changes(&self, line: usize, text: &mut Text<TextLine>) {
+        let buffer = self.buffer.borrow();
+        let changes = buffer.git_changes();
+        text.change = changes.change(line);
+        if self.options.blame && self.active && line == self.cursor().line {
+            text.blame = changes.blame(line).map(str::to_string);
+        }
+    }
+
+    /// the number of filler rows of a diff shown above a line.
+    fn fillers(&self, line: usize) -> usize {
+        self.diff
+            .as_ref()
+            .and_then(|side| side.fillers.get(&line).copied())
+            .unwrap_or(0)
+    }
+
+    /// marks a line changed in a diff, with the bytes of the changed characters.
+    fn mark_diff(&self, line: usize, text: &mut Text<TextLine>) {
+        let columns = match self.diff.as_ref().and_then(|side| side.changed.get(&line)) {
+            Some(columns) => columns,
+            None => return,
+        };
+        let line_text = text.text.as_str();
+        let byte = |column: usize| {
+            line_text
+                .char_indices()
+                .nth(column)
+                .map_or(line_text.len(), |(idx, _)| idx)
+        };
+        let spans = columns
+            .iter()
+            .map(|columns| Span::new(byte(columns.start), byte(columns.end)))
+            .collect();
+        text.diff = Some(spans);
+    }
+
+    /// marks the text of a line with the diagnostics touching it. An empty diagnostic marks the
+    /// character it is on, the message shown after the text is the first of the worst
+    /// diagnostics starting on the line.
+    fn mark_diagnostics(&self, line: usize, text: &mut Text<TextLine>) {
+        let buffer = self.buffer.borrow();
+        let start = buffer.char_index(line, 0);
+        let end = start + buffer.line_len(line);
+        let line_text = text.text.as_str();
+        let byte = |column: usize| {
+            line_text
+                .char_indices()
+                .nth(column)
+                .map_or(line_text.len(), |(idx, _)| idx)
+        };
+
+        let mut marks = Vec::new();
+        let mut sign: Option<Severity> = None;
+        let mut message: Option<(&str, Severity)> = None;
+        for diagnostic in buffer.diagnostics().overlapping(start..end) {
+            let severity = diagnostic.severity;
+            let from = diagnostic.range.start.max(start) - start;
+            let to = (diagnostic.range.end.min(end) - start).max(from + 1);
+            let span = Span::new(byte(from), byte(to));
+            if span.start < span.end {
+                marks.push(StyleSpan::new(StyleId::diagnostic(severity), span));
+            }
+            sign = Some(sign.map_or(severity, |sign| sign.min(severity)));
+
+            let worse = message.map_or(true, |(_, worst)| severity < worst);
+            if diagnostic.range.start >= start && worse {
+                let first_line = diagnostic.message.lines().next().unwrap_or("");
+                message = Some((first_line, severity));
+            }
+        }
+        text.styles.extend(marks);
+        text.sign = sign;
+        if self.options.virtual_text {
+            text.virtual_text = message.map(|(message, severity)| (message.to_string(), severity));
+        }
+    }
+
+    /// the columns of a line selected by each cursor. A line ending that is selected is
+    /// included as one column past the end of the line.
+    fn get_selections(&self, line: usize) -> Vec<Range<usize>> {
+        (0..self.cursors.len())
+            .filter_map(|idx| self.selection_of(idx))
+            .filter(|(start, end)| start.line <= line && line <= end.line)
+            .map(|(start, end)| {
+                let start = if line == start.line { start.column } else { 0 };
+                let end = if line == end.line {
+                    end.column
+                } else {
+                    self.buffer.borrow().line_len(line) + 1
+                };
+                start..end
+            })
+            .collect()
+    }
+
+    /// retreives the columns of all cursors on a given line.
+    pub fn get_cursors(&self, line: usize) -> Vec<usize> {
+        self.cursors
+            .iter()
+            .filter(|cursor| cursor.line == line)
+            .map(|cursor| cursor.column)
+            .collect()
+    }
+}
+
+impl Drop for Frame {
+    /// the folds the buffer keeps for the frame go with it.
+    fn drop(&mut self) {
+        if let Ok(mut buffer) = self.buffer.try_borrow_mut() {
+            buffer.set_anchored(self.id.0, Vec::new());
         }
     }
 }
+
+/// the line shown for a line, the first line of the outermost fold hiding it.
+fn shown_line(folds: &[Range<usize>], line: usize) -> usize {
+    let mut line = line;
+    while let Some(fold) = folds.iter().find(|fold| fold.start < line && line <= fold.end) {
+        line = fold.start;
+    }
+    line
+}
+
+/// the first line after a line that isn't hidden by a fold.
+fn next_shown_line(folds: &[Range<usize>], line: usize) -> usize {
+    let mut line = line + 1;
+    while let Some(fold) = folds.iter().find(|fold| fold.start < line && line <= fold.end) {
+        line = fold.end + 1;
+    }
+    line
+}
+
+/// the folds a buffer keeps for a frame as their first and last line. A fold whose lines were
+/// joined into one is open.
+fn folded_lines(buffer: &core::Buffer, id: FrameId) -> Vec<Range<usize>> {
+    buffer
+        .anchored(id.0)
+        .iter()
+        .map(|range| buffer.position(range.start).0..buffer.position(range.end).0)
+        .filter(|fold| fold.start < fold.end)
+        .collect()
+}
+
+/// the lines indented deeper than the line before them, as that line and the last deeper line.
+/// Blank lines don't end a region.
+fn indented_ranges(lines: &[String]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    // the first line and the indentation of the regions that are still open.
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last = 0;
+    for (idx, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+        while let Some(&(first, first_indent)) = open.last() {
+            if indent > first_indent {
+                break;
+            }
+            open.pop();
+            if last > first {
+                ranges.push(first..last);
+            }
+        }
+        open.push((idx, indent));
+        last = idx;
+    }
+    for (first, _) in open {
+        if last > first {
+            ranges.push(first..last);
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kea::ptr;
+
+    fn frame(text: &str) -> Frame {
+        let mut buffer = core::Buffer::empty(0).unwrap();
+        buffer.insert(0, text);
+        let mut frame = Frame::new(ptr(buffer), Vector2F::zero(), Vector2F::zero(), 10);
+        frame.update_line_cache(Invalidation::Init);
+        frame
+    }
+
+    #[test]
+    fn select_word_and_line() {
+        let mut frame = frame("let word_count = 0;\nnext\n");
+
+        frame.select_word(0, 6);
+        assert_eq!(frame.selection(), Some((Cursor::new(0, 4), Cursor::new(0, 14))));
+        assert_eq!(frame.get_selections(0), vec![4..14]);
+
+        frame.select_word(0, 15);
+        assert_eq!(frame.selection(), Some((Cursor::new(0, 15), Cursor::new(0, 16))));
+
+        frame.select_line(0);
+        assert_eq!(frame.selection(), Some((Cursor::new(0, 0), Cursor::new(1, 0))));
+        assert_eq!(frame.get_selections(0), vec![0..20]);
+        assert_eq!(frame.get_selections(1), vec![0..0]);
+    }
+
+    #[test]
+    fn extend_selection() {
+        let mut frame = frame("one\ntwo\nthree\n");
+        frame.place_cursor(1, 1, false);
+        frame.place_cursor(2, 3, true);
+        assert_eq!(frame.selection(), Some((Cursor::new(1, 1), Cursor::new(2, 3))));
+
+        // extending backwards past the anchor.
+        frame.place_cursor(0, 2, true);
+        assert_eq!(frame.selection(), Some((Cursor::new(0, 2), Cursor::new(1, 1))));
+        assert_eq!(frame.get_selections(0), vec![2..4]);
+
+        frame.handle_operation(Operation::CursorRight);
+        assert_eq!(frame.selection(), None);
+    }
+
+    #[test]
+    fn folding() {
+        let mut frame = frame("top\nif a:\n    b\n    c\nd\n");
+        let line_numbers = |frame: &Frame| -> Vec<usize> {
+            frame.lines().iter().flatten().map(|text| text.line_number).collect()
+        };
+
+        frame.set_cursor(2, 4);
+        frame.handle_operation(Operation::Fold);
+        assert_eq!(frame.cursor(), &Cursor::new(1, 4));
+        assert_eq!(line_numbers(&frame), vec![1, 2, 5]);
+        assert_eq!(frame.lines()[1].as_ref().unwrap().folded, Some(2));
+        frame.move_cursor(CursorMotion::Down);
+        assert_eq!(frame.cursor(), &Cursor::new(4, 1));
+        frame.move_cursor(CursorMotion::Up);
+        assert_eq!(frame.cursor(), &Cursor::new(1, 1));
+
+        // the fold follows the lines inserted above it, when they are undone and when the
+        // buffer is edited outside of the frame.
+        frame.set_cursor(0, 3);
+        frame.insert("\n");
+        assert_eq!(line_numbers(&frame), vec![1, 2, 3, 6]);
+        assert!(frame.undo(false));
+        assert_eq!(line_numbers(&frame), vec![1, 2, 5]);
+        frame.buffer.borrow_mut().insert(0, "\n");
+        frame.update_line_cache(Invalidation::Init);
+        assert_eq!(line_numbers(&frame), vec![1, 2, 3, 6]);
+        frame.set_cursor(2, 0);
+        frame.handle_operation(Operation::Unfold);
+        assert_eq!(line_numbers(&frame).len(), 6);
+    }
+
+    #[test]
+    fn read_only() {
+        let mut frame = frame("one\n");
+        frame.set_read_only(true);
+        frame.insert("x");
+        frame.delete_forward(1);
+        frame.select_line(0);
+        assert_eq!(frame.delete_selections(), None);
+        assert!(!frame.undo(false));
+        assert_eq!(frame.buffer().content().to_string(), "one\n");
+    }
+
+    #[test]
+    fn diff_fillers() {
+        let mut frame = frame("A\nc\n");
+        let old = ["a", "b", "b", "c", ""];
+        let new = ["A", "c", ""];
+        let (_, side) = core::diff::sides(&old, &new, &core::diff::diff(&old, &new));
+        frame.set_diff(Some(side));
+        let rows = |frame: &Frame| -> Vec<Option<String>> {
+            frame.lines()[..4]
+                .iter()
+                .flatten()
+                .map(|text| Some(text.text.clone()).filter(|_| !text.filler))
+                .collect()
+        };
+        let (a, c) = (Some("A".to_string()), Some("c".to_string()));
+        assert_eq!(rows(&frame), vec![a, None, None, c.clone()]);
+        assert_eq!(frame.lines()[0].as_ref().unwrap().diff, Some(vec![Span::new(0, 1)]));
+
+        // the view can start between the fillers.
+        frame.scroll_to_row(2);
+        assert_eq!(frame.top_row(), 2);
+        assert_eq!(rows(&frame)[..2], [None, c]);
+    }
+
+    #[test]
+    fn structural_selection() {
+        let mut buffer = core::Buffer::with_path("main.rs", 0);
+        buffer.insert(0, "fn f() {\n    g(a, b);\n}\n");
+        let mut frame = Frame::new(ptr(buffer), Vector2F::zero(), Vector2F::zero(), 10);
+        frame.update_line_cache(Invalidation::Init);
+
+        frame.place_cursor(1, 6, false);
+        frame.handle_operation(Operation::ExpandSelection);
+        assert_eq!(frame.selected_text(), Some("a".to_string()));
+        frame.handle_operation(Operation::ExpandSelection);
+        assert_eq!(frame.selected_text(), Some("a, b".to_string()));
+        frame.handle_operation(Operation::ShrinkSelection);
+        assert_eq!(frame.selected_text(), Some("a".to_string()));
+
+        frame.place_cursor(0, 7, false);
+        frame.handle_operation(Operation::MatchingBracket);
+        assert_eq!(frame.cursor(), &Cursor::new(2, 0));
+        frame.handle_operation(Operation::EnclosingFunction);
+        assert_eq!(frame.cursor(), &Cursor::new(0, 0));
+
+        let highlights = &frame.lines()[0].as_ref().unwrap().highlights;
+        assert_eq!(highlights[0].1, core::Highlight::Keyword);
+    }
+
+    #[test]
+    fn diagnostic_marks() {
+        let mut frame = frame("l\u{e9}t x = 1;\nfoo\n");
+        let diagnostic = |range, severity, message: &str| core::Diagnostic {
+            range,
+            severity,
+            message: message.to_string(),
+            source: "test".to_string(),
+        };
+        frame.buffer_mut().set_diagnostics(
+            "test",
+            vec![
+                diagnostic(1..3, Severity::Warning, "accent"),
+                diagnostic(4..4, Severity::Error, "unused\nmore"),
+                diagnostic(9..12, Severity::Hint, "spans lines"),
+            ],
+        );
+        frame.set_options(FrameOptions {
+            virtual_text: true,
+            ..FrameOptions::default()
+        });
+        frame.update_line_cache(Invalidation::Init);
+
+        let first = frame.lines()[0].as_ref().unwrap();
+        let spans: Vec<(usize, usize, Option<Severity>)> = first
+            .styles
+            .iter()
+            .filter(|span| span.style().severity().is_some())
+            .map(|span| (span.start(), span.end(), span.style().severity()))
+            .collect();
+        // the accent is two bytes, an empty diagnostic marks one character.
+        let (warning, error, hint) = (
+            Some(Severity::Warning),
+            Some(Severity::Error),
+            Some(Severity::Hint),
+        );
+        assert_eq!(spans, vec![(1, 4, warning), (5, 6, error), (10, 11, hint)]);
+        assert_eq!(first.sign, Some(Severity::Error));
+        assert_eq!(first.virtual_text, Some(("unused".to_string(), Severity::Error)));
+
+        let second = frame.lines()[1].as_ref().unwrap();
+        assert_eq!(second.sign, Some(Severity::Hint));
+        assert_eq!(second.virtual_text, None);
+    }
+
+    #[test]
+    fn wrapped_view() {
+        let mut context = RenderContext::for_tests();
+        let mut frame = frame(&format!("{}\n", "a".repeat(30)).repeat(10));
+        frame.set_options(FrameOptions {
+            wrap: true,
+            ..FrameOptions::default()
+        });
+        // ten characters fit in a row, each line takes three rows.
+        let text = frame.cache.lines_mut()[0].as_mut().unwrap();
+        let width = Frame::shaped(text, &mut context).caret(10) + 0.5;
+        frame.resize(vec2f(width, 100.0), 4);
+
+        // the rows above the line of the cursor are scrolled out of the view.
+        frame.place_cursor(1, 0, false);
+        frame.fit_wrapped(&mut context);
+        assert_eq!(frame.view().start, 1);
+
+        // scrolling by rows moves whole lines, at least one.
+        frame.scroll(1);
+        assert_eq!(frame.view().start, 1);
+        frame.fit_wrapped(&mut context);
+        assert_eq!(frame.view().start, 2);
+        frame.scroll(4);
+        frame.fit_wrapped(&mut context);
+        assert_eq!(frame.view().start, 3);
+        frame.scroll(-2);
+        frame.fit_wrapped(&mut context);
+        assert_eq!(frame.view().start, 2);
+
+        // lines out of the view are counted the same as the shown ones.
+        assert_eq!(frame.line_rows(2, width, &mut context), 3);
+        assert_eq!(frame.line_rows(9, width, &mut context), 3);
+
+        // scrolling stops at the last line.
+        frame.scroll(100);
+        frame.fit_wrapped(&mut context);
+        assert_eq!(frame.view().start, 9);
+    }
+}
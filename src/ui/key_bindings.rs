@@ -4,6 +4,7 @@ use super::edits::Operation;
 use crate::app::Config;
 use crate::glutin::event::{ModifiersState, VirtualKeyCode};
 use std::cmp::Ordering;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Hash, Eq, Ord, PartialOrd, PartialEq)]
 pub struct Key {
@@ -20,6 +21,98 @@ impl Key {
     }
 }
 
+impl FromStr for Key {
+    type Err = String;
+
+    /// parses keys like `ctrl+alt+up`, `shift+f5` or `ctrl++`, names are not case sensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mods = ModifiersState::empty();
+        // the plus key is the last '+' following a separator, or the whole string.
+        let trimmed = s.trim();
+        let (modifiers, key) = match trimmed.strip_suffix('+') {
+            Some(rest) if rest.trim_end().is_empty() || rest.trim_end().ends_with('+') => {
+                (rest.trim_end().trim_end_matches('+'), "+".to_string())
+            }
+            _ => match trimmed.rfind('+') {
+                Some(idx) => (&trimmed[..idx], trimmed[idx + 1..].trim().to_lowercase()),
+                None => ("", trimmed.to_lowercase()),
+            },
+        };
+        let parts = modifiers
+            .split_terminator('+')
+            .map(|part| part.trim().to_lowercase());
+        for modifier in parts {
+            match modifier.as_str() {
+                "ctrl" | "control" => mods |= ModifiersState::CTRL,
+                "shift" => mods |= ModifiersState::SHIFT,
+                "alt" => mods |= ModifiersState::ALT,
+                "logo" | "super" | "cmd" => mods |= ModifiersState::LOGO,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, s)),
+            }
+        }
+
+        match parse_key_code(&key) {
+            Some(key) => Ok(Self { key, mods }),
+            None => Err(format!("unknown key '{}' in '{}'", key, s)),
+        }
+    }
+}
+
+fn parse_key_code(key: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    const LETTERS: [VirtualKeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [VirtualKeyCode; 10] =
+        [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    const FUNCTION_KEYS: [VirtualKeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    let mut chars = key.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return match ch {
+            'a'..='z' => Some(LETTERS[(ch as u8 - b'a') as usize]),
+            '0'..='9' => Some(DIGITS[(ch as u8 - b'0') as usize]),
+            '=' => Some(Equals),
+            '-' => Some(Minus),
+            ',' => Some(Comma),
+            '.' => Some(Period),
+            '/' => Some(Slash),
+            ';' => Some(Semicolon),
+            '[' => Some(LBracket),
+            ']' => Some(RBracket),
+            '`' => Some(Grave),
+            '+' => Some(Plus),
+            _ => None,
+        };
+    }
+    if let Some(number) = key.strip_prefix('f').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION_KEYS.get(number.checked_sub(1)?).copied();
+    }
+
+    let key = match key {
+        "up" => Up,
+        "down" => Down,
+        "left" => Left,
+        "right" => Right,
+        "pageup" => PageUp,
+        "pagedown" => PageDown,
+        "home" => Home,
+        "end" => End,
+        "insert" => Insert,
+        "delete" => Delete,
+        "backspace" => Back,
+        "enter" | "return" => Return,
+        "tab" => Tab,
+        "escape" | "esc" => Escape,
+        "space" => Space,
+        "equals" => Equals,
+        "minus" => Minus,
+        "plus" => Plus,
+        _ => return None,
+    };
+    Some(key)
+}
+
 // #[derive(Debug, Clone, Hash, Ord, Eq, PartialEq)]
 // pub struct KeyBinding {
 //     key: Key,
@@ -44,12 +137,123 @@ pub struct BindingCollection {
 
 impl BindingCollection {
     pub fn new(config: &Config) -> Self {
-        Self {
+        let mut collection = Self {
             bindings: BTreeMap::new(),
+        };
+        collection.add_defaults();
+        // the bindings of the config replace the defaults of the same keys.
+        for (key, operation) in config.key_bindings() {
+            collection.bind(key.clone(), *operation);
         }
+        collection
+    }
+
+    fn add_defaults(&mut self) {
+        use VirtualKeyCode::*;
+        let none = ModifiersState::empty();
+        let ctrl = ModifiersState::CTRL;
+        let alt = ModifiersState::ALT;
+        let shift = ModifiersState::SHIFT;
+        let ctrl_alt = ModifiersState::CTRL | ModifiersState::ALT;
+        let alt_shift = ModifiersState::ALT | ModifiersState::SHIFT;
+        let ctrl_shift = ModifiersState::CTRL | ModifiersState::SHIFT;
+
+        let defaults = [
+            (Up, none, Operation::CursorUp),
+            (Down, none, Operation::CursorDown),
+            (Left, none, Operation::CursorLeft),
+            (Right, none, Operation::CursorRight),
+            (PageUp, none, Operation::ScrollUp),
+            (PageDown, none, Operation::ScrollDown),
+            (Equals, ctrl, Operation::ZoomIn),
+            (Minus, ctrl, Operation::ZoomOut),
+            (Key0, ctrl, Operation::ZoomReset),
+            (Numpad0, ctrl, Operation::ZoomReset),
+            (Up, ctrl_alt, Operation::AddCursorAbove),
+            (Down, ctrl_alt, Operation::AddCursorBelow),
+            (C, ctrl, Operation::Copy),
+            (X, ctrl, Operation::Cut),
+            (V, ctrl, Operation::Paste),
+            (Y, alt, Operation::CycleKillRing),
+            (Escape, none, Operation::NormalMode),
+            (K, ctrl, Operation::Hover),
+            (F12, none, Operation::GotoDefinition),
+            (F12, shift, Operation::FindReferences),
+            (F2, none, Operation::Rename),
+            (F, alt_shift, Operation::Format),
+            (Space, ctrl, Operation::Complete),
+            (F8, none, Operation::NextDiagnostic),
+            (F8, shift, Operation::PreviousDiagnostic),
+            (M, ctrl_shift, Operation::ListDiagnostics),
+            (F7, none, Operation::NextHunk),
+            (F7, shift, Operation::PreviousHunk),
+            (S, ctrl_alt, Operation::StageHunk),
+            (R, ctrl_alt, Operation::RevertHunk),
+            (D, ctrl_alt, Operation::DiffBuffers),
+            (W, ctrl_alt, Operation::DiffDisk),
+            (G, ctrl_alt, Operation::DiffGet),
+            (P, ctrl_alt, Operation::DiffPut),
+            (Grave, ctrl, Operation::Terminal),
+            (X, ctrl_alt, Operation::RunCommand),
+            (F, ctrl_alt, Operation::FilterCommand),
+            (C, ctrl_alt, Operation::CancelCommand),
+            (Z, ctrl, Operation::Undo),
+            (Z, ctrl_shift, Operation::Redo),
+            (Y, ctrl, Operation::Redo),
+            (Right, alt_shift, Operation::ExpandSelection),
+            (Left, alt_shift, Operation::ShrinkSelection),
+            (Backslash, ctrl_shift, Operation::MatchingBracket),
+            (Up, alt_shift, Operation::EnclosingFunction),
+            (LBracket, ctrl_shift, Operation::Fold),
+            (RBracket, ctrl_shift, Operation::Unfold),
+            (LBracket, ctrl_alt, Operation::ToggleFolds),
+        ];
+
+        for (key, mods, operation) in defaults.iter() {
+            self.bind(Key::new(*key, mods), *operation);
+        }
+    }
+
+    pub fn bind(&mut self, key: Key, operation: Operation) {
+        self.bindings.insert(key, operation);
     }
 
     pub fn lookup(&self, key: &Key) -> Option<&Operation> {
         self.bindings.get(key)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_keys() {
+        let key: Key = "Ctrl+Alt+Up".parse().unwrap();
+        let mods = ModifiersState::CTRL | ModifiersState::ALT;
+        assert_eq!(key, Key::new(VirtualKeyCode::Up, &mods));
+        assert_eq!(
+            "f5".parse::<Key>(),
+            Ok(Key::new(VirtualKeyCode::F5, &ModifiersState::empty()))
+        );
+        assert_eq!(
+            "shift+a".parse::<Key>(),
+            Ok(Key::new(VirtualKeyCode::A, &ModifiersState::SHIFT))
+        );
+
+        assert_eq!(
+            "ctrl++".parse::<Key>(),
+            Ok(Key::new(VirtualKeyCode::Plus, &ModifiersState::CTRL))
+        );
+        assert_eq!(
+            "+".parse::<Key>(),
+            Ok(Key::new(VirtualKeyCode::Plus, &ModifiersState::empty()))
+        );
+        assert_eq!("ctrl+plus".parse::<Key>(), "Ctrl + +".parse::<Key>());
+
+        assert!("ctrl+f13".parse::<Key>().is_err());
+        assert!("ctrl+".parse::<Key>().is_err());
+        assert!("hyper+a".parse::<Key>().is_err());
+        assert!("".parse::<Key>().is_err());
+    }
+}
@@ -1,11 +1,16 @@
 // use euclid::default::Vector2D;
 use super::frame::FrameId;
-use crate::pathfinder_geometry::vector::Vector2F;
+use crate::pathfinder_geometry::rect::RectF;
+use crate::pathfinder_geometry::vector::{vec2f, Vector2F};
 
 #[derive(Debug, Clone)]
 pub struct FrameInfo {
     pub frame: FrameId,
 }
+/// A node of the layout. A split divides its area evenly between the nodes after it, the
+/// frames side by side for a vertical split and stacked for a horizontal one. A split among
+/// those nodes takes all of the nodes after it as its part. Frames before the first split are
+/// placed side by side.
 #[derive(Debug, Clone)]
 pub enum Layout {
     Vertical,
@@ -41,6 +46,28 @@ impl FrameLayout {
         self.layout.push(Layout::Frame(frame_info));
     }
 
+    pub fn push(&mut self, layout: Layout) {
+        self.layout.push(layout);
+    }
+
+    /// takes a frame out of the layout.
+    pub fn remove_frame(&mut self, frame: FrameId) {
+        self.layout
+            .retain(|node| !matches!(node, Layout::Frame(info) if info.frame == frame));
+    }
+
+    /// the nodes of the layout, splits are followed by their frames.
+    pub fn nodes(&self) -> &[Layout] {
+        &self.layout
+    }
+
+    /// the area of each frame of the layout when the layout fills an area.
+    pub fn rects(&self, area: RectF) -> Vec<(FrameId, RectF)> {
+        let mut rects = Vec::new();
+        place(&self.layout, area, true, &mut rects);
+        rects
+    }
+
     pub fn frame_iter(&self) -> FrameIter {
         FrameIter {
             // parent: 0,
@@ -50,6 +77,51 @@ impl FrameLayout {
     }
 }
 
+/// divides an area between the frames of the nodes, side by side when vertical. The first
+/// split takes the rest of the nodes as its part.
+fn place(nodes: &[Layout], area: RectF, vertical: bool, rects: &mut Vec<(FrameId, RectF)>) {
+    let split = nodes
+        .iter()
+        .position(|node| matches!(node, Layout::Vertical | Layout::Horizontal));
+    let frames = &nodes[..split.unwrap_or(nodes.len())];
+    let parts = frames.len() + split.map_or(0, |_| 1);
+    if parts == 0 {
+        return;
+    }
+
+    let length = if vertical {
+        area.width()
+    } else {
+        area.height()
+    };
+    // the parts start at whole pixels, the last one takes what is left.
+    let start = |part: usize| (length * part as f32 / parts as f32).floor();
+    let part_rect = |part: usize| {
+        let (offset, size) = (start(part), start(part + 1) - start(part));
+        if vertical {
+            RectF::new(
+                area.origin() + vec2f(offset, 0.0),
+                vec2f(size, area.height()),
+            )
+        } else {
+            RectF::new(
+                area.origin() + vec2f(0.0, offset),
+                vec2f(area.width(), size),
+            )
+        }
+    };
+
+    for (part, node) in frames.iter().enumerate() {
+        if let Layout::Frame(info) = node {
+            rects.push((info.frame, part_rect(part)));
+        }
+    }
+    if let Some(split) = split {
+        let vertical = matches!(nodes[split], Layout::Vertical);
+        place(&nodes[split + 1..], part_rect(parts - 1), vertical, rects);
+    }
+}
+
 impl<'a> FrameIter<'a> {}
 
 impl<'a> std::iter::Iterator for FrameIter<'a> {
@@ -76,3 +148,53 @@ impl<'a> std::iter::Iterator for FrameIter<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::Buffer;
+    use crate::ui::Frame;
+    use kea::ptr;
+
+    fn frames(count: usize) -> Vec<FrameId> {
+        (0..count)
+            .map(|_| {
+                let buffer = ptr(Buffer::empty(0).unwrap());
+                Frame::new(buffer, Vector2F::zero(), Vector2F::zero(), 10).id()
+            })
+            .collect()
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> RectF {
+        RectF::new(vec2f(x, y), vec2f(width, height))
+    }
+
+    #[test]
+    fn split_area() {
+        let ids = frames(3);
+        let frame = |idx: usize| Layout::Frame(FrameInfo { frame: ids[idx] });
+        let area = rect(0.0, 10.0, 101.0, 60.0);
+
+        let mut layout = FrameLayout::new();
+        layout.push(frame(0));
+        assert_eq!(layout.rects(area), vec![(ids[0], area)]);
+
+        // frames without a split are side by side.
+        layout.push(frame(1));
+        let rects = layout.rects(area);
+        assert_eq!(rects[0], (ids[0], rect(0.0, 10.0, 50.0, 60.0)));
+        assert_eq!(rects[1], (ids[1], rect(50.0, 10.0, 51.0, 60.0)));
+
+        // the split takes the right half and stacks its frames.
+        let mut layout = FrameLayout::new();
+        for node in [frame(0), Layout::Horizontal, frame(1), frame(2)] {
+            layout.push(node);
+        }
+        let rects = layout.rects(area);
+        assert_eq!(rects[0], (ids[0], rect(0.0, 10.0, 50.0, 60.0)));
+        assert_eq!(rects[1], (ids[1], rect(50.0, 10.0, 51.0, 30.0)));
+        assert_eq!(rects[2], (ids[2], rect(50.0, 40.0, 51.0, 30.0)));
+
+        assert!(FrameLayout::new().rects(area).is_empty());
+    }
+}
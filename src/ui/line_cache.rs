@@ -1,5 +1,11 @@
-use crate::renderer::{style::StyleSpan, Renderable};
+use crate::core::{Highlight, LineChange};
+use crate::renderer::{
+    style::{Span, StyleSpan},
+    Renderable,
+};
+use kea::lsp::Severity;
 use log::error;
+use std::ops::Range;
 
 #[derive(Debug, Clone)]
 pub struct Text<T> {
@@ -13,12 +19,31 @@ pub struct Text<T> {
     pub assoc: Option<T>,
     /// the location of any cursors in this line.
     pub cursors: Vec<usize>,
+    /// the selected columns of this line, one range for each selection on the line.
+    pub selections: Vec<Range<usize>>,
     /// a list of styles to be used on text.
     /// the spans should be ordered and non overlapping. (if overlapping then the latter
-    /// style will be used).
+    /// style will be used). The spans of the diagnostic styles are the exception, they are
+    /// drawn as an undercurl over the other styles.
     // Ideally, the entire string should be represented by the spans but if parts are missing
     // then a default style will be used.
     pub styles: Vec<StyleSpan>,
+    /// the bytes colored by the syntax of the text.
+    pub highlights: Vec<(Span, Highlight)>,
+    /// the worst severity of the diagnostics of the line, shown in the gutter.
+    pub sign: Option<Severity>,
+    /// the message of a diagnostic shown after the text.
+    pub virtual_text: Option<(String, Severity)>,
+    /// the number of lines after this line hidden by a fold.
+    pub folded: Option<usize>,
+    /// how the line differs from the file in git, shown in the gutter.
+    pub change: Option<LineChange>,
+    /// the commit that last changed the line, shown after the text.
+    pub blame: Option<String>,
+    /// the line changed in a diff, with the bytes of the changed characters.
+    pub diff: Option<Vec<Span>>,
+    /// an empty row lining up the two sides of a diff, it isn't a line of the buffer.
+    pub filler: bool,
 }
 
 impl<T> Text<T> {
@@ -40,7 +65,16 @@ impl<T> Text<T> {
             visual_line,
             assoc,
             cursors,
+            selections: Vec::new(),
             styles,
+            highlights: Vec::new(),
+            sign: None,
+            virtual_text: None,
+            folded: None,
+            change: None,
+            blame: None,
+            diff: None,
+            filler: false,
         }
     }
 
@@ -1,9 +1,14 @@
+pub mod completion;
 mod edits;
 mod frame;
-mod key_bindings;
+pub mod key_bindings;
 mod layout;
 mod line_cache;
+mod prompt;
+pub mod registers;
 
+pub use edits::Operation;
 pub use frame::*;
 pub use layout::*;
 pub use line_cache::*;
+pub use prompt::*;
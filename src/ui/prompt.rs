@@ -0,0 +1,67 @@
+// Prompt.
+// A question shown at the bottom of the window. It is answered by typing the key of one of
+// its choices, other keys are ignored until it is answered.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice<T> {
+    pub key: char,
+    pub label: String,
+    pub value: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt<T> {
+    message: String,
+    choices: Vec<Choice<T>>,
+}
+
+impl<T: Clone> Prompt<T> {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            message: message.into(),
+            choices: Vec::new(),
+        }
+    }
+
+    pub fn with_choice<S: Into<String>>(mut self, key: char, label: S, value: T) -> Self {
+        self.choices.push(Choice {
+            key,
+            label: label.into(),
+            value,
+        });
+        self
+    }
+
+    /// the value of the choice of a typed character, case is ignored.
+    pub fn answer(&self, ch: char) -> Option<T> {
+        self.choices
+            .iter()
+            .find(|choice| choice.key.eq_ignore_ascii_case(&ch))
+            .map(|choice| choice.value.clone())
+    }
+
+    /// the message followed by the choices, e.g. `save changes? [y] yes [n] no`.
+    pub fn text(&self) -> String {
+        let mut text = self.message.clone();
+        for choice in &self.choices {
+            text.push_str(&format!(" [{}] {}", choice.key, choice.label));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        let prompt = Prompt::new("'a.rs' changed on disk.")
+            .with_choice('k', "keep", 1)
+            .with_choice('r', "reload", 2);
+        assert_eq!(prompt.text(), "'a.rs' changed on disk. [k] keep [r] reload");
+        assert_eq!(prompt.answer('r'), Some(2));
+        assert_eq!(prompt.answer('K'), Some(1));
+        assert_eq!(prompt.answer('x'), None);
+    }
+}
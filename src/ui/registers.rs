@@ -0,0 +1,224 @@
+// Registers.
+// Text that is yanked or deleted is stored in registers so it can be put back later. The unnamed
+// register holds the last yank or cut. Only cuts go into the kill ring, which keeps the last
+// few of them. Cuts made one after another are joined into a single entry of the ring.
+//
+// A register holds one string for each cursor that took part in the yank.
+
+use std::collections::{HashMap, VecDeque};
+
+/// the number of entries kept in the kill ring.
+const KILL_RING_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// `"`, the most recent yank or cut.
+    Unnamed,
+    /// `a` to `z`, an uppercase letter appends to the register.
+    Named(char),
+    /// `+`, the system clipboard.
+    Clipboard,
+    /// `*`, the primary selection.
+    Selection,
+}
+
+impl Register {
+    pub fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            '"' => Some(Self::Unnamed),
+            '+' => Some(Self::Clipboard),
+            '*' => Some(Self::Selection),
+            ch if ch.is_ascii_alphabetic() => Some(Self::Named(ch)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KillRing {
+    entries: VecDeque<Vec<String>>,
+    capacity: usize,
+}
+
+impl KillRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// adds an entry to the front of the ring, the oldest entry is dropped when it is full.
+    pub fn push(&mut self, texts: Vec<String>) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(texts);
+    }
+
+    /// appends to the most recent entry, used for repeated cuts. When the number of cursors
+    /// changed a new entry is added instead.
+    pub fn append(&mut self, texts: Vec<String>) {
+        match self.entries.front_mut() {
+            Some(front) if front.len() == texts.len() => {
+                for (entry, text) in front.iter_mut().zip(texts) {
+                    entry.push_str(&text);
+                }
+            }
+            _ => self.push(texts),
+        }
+    }
+
+    pub fn current(&self) -> Option<&Vec<String>> {
+        self.entries.front()
+    }
+
+    /// moves the most recent entry to the back of the ring, making the previous entry current.
+    pub fn rotate(&mut self) {
+        if let Some(front) = self.entries.pop_front() {
+            self.entries.push_back(front);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Registers {
+    named: HashMap<char, Vec<String>>,
+    /// the last yank or cut.
+    unnamed: Option<Vec<String>>,
+    kill_ring: KillRing,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self {
+            named: HashMap::new(),
+            unnamed: None,
+            kill_ring: KillRing::new(KILL_RING_SIZE),
+        }
+    }
+
+    /// stores yanked text in a register, the text is also the unnamed register. The
+    /// clipboard registers are handled by the application.
+    pub fn store(&mut self, register: Register, texts: Vec<String>) {
+        if let Register::Named(ch) = register {
+            let entry = self.named.entry(ch.to_ascii_lowercase()).or_default();
+            if ch.is_ascii_uppercase() && entry.len() == texts.len() {
+                for (entry, text) in entry.iter_mut().zip(texts.iter()) {
+                    entry.push_str(text);
+                }
+            } else {
+                *entry = texts.clone();
+            }
+        }
+        self.unnamed = Some(texts);
+    }
+
+    /// stores cut text in the kill ring and the unnamed register, repeated cuts are appended
+    /// to the last one.
+    pub fn kill(&mut self, texts: Vec<String>, repeated: bool) {
+        if repeated {
+            self.kill_ring.append(texts);
+        } else {
+            self.kill_ring.push(texts);
+        }
+        self.unnamed = self.kill_ring.current().cloned();
+    }
+
+    pub fn load(&self, register: Register) -> Option<&Vec<String>> {
+        match register {
+            Register::Named(ch) => self.named.get(&ch.to_ascii_lowercase()),
+            _ => self.unnamed.as_ref(),
+        }
+    }
+
+    pub fn kill_ring(&self) -> &KillRing {
+        &self.kill_ring
+    }
+
+    /// makes the previous cut of the kill ring the unnamed register.
+    pub fn rotate_kill_ring(&mut self) {
+        self.kill_ring.rotate();
+        self.unnamed = self.kill_ring.current().cloned();
+    }
+}
+
+/// the text each cursor pastes. When the clipboard has one line for each cursor the lines
+/// are distributed between them, otherwise every cursor pastes all of it.
+pub fn distribute(texts: &[String], cursors: usize) -> Vec<String> {
+    if texts.len() == cursors {
+        return texts.to_vec();
+    }
+
+    let text = texts.join("\n");
+    let lines: Vec<&str> = text.lines().collect();
+    if cursors > 1 && lines.len() == cursors {
+        lines.into_iter().map(String::from).collect()
+    } else {
+        vec![text; cursors]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn named_registers() {
+        let mut registers = Registers::new();
+        registers.store(Register::Named('a'), texts(&["one"]));
+        registers.store(Register::Named('A'), texts(&[" two"]));
+        registers.store(Register::Unnamed, texts(&["three"]));
+
+        assert_eq!(
+            registers.load(Register::Named('a')),
+            Some(&texts(&["one two"]))
+        );
+        assert_eq!(registers.load(Register::Unnamed), Some(&texts(&["three"])));
+        assert_eq!(registers.load(Register::Named('b')), None);
+    }
+
+    #[test]
+    fn kill_ring() {
+        let mut registers = Registers::new();
+        registers.kill(texts(&["a"]), false);
+        registers.kill(texts(&["b"]), true);
+        registers.kill(texts(&["c"]), false);
+        assert_eq!(registers.load(Register::Unnamed), Some(&texts(&["c"])));
+
+        registers.rotate_kill_ring();
+        assert_eq!(registers.load(Register::Unnamed), Some(&texts(&["ab"])));
+
+        // yanks aren't cuts, they only replace the unnamed register.
+        registers.store(Register::Named('a'), texts(&["d"]));
+        assert_eq!(registers.load(Register::Unnamed), Some(&texts(&["d"])));
+        assert_eq!(registers.kill_ring().len(), 2);
+
+        let mut ring = KillRing::new(2);
+        ring.push(texts(&["1"]));
+        ring.push(texts(&["2"]));
+        ring.push(texts(&["3"]));
+        assert_eq!(ring.len(), 2);
+        ring.rotate();
+        assert_eq!(ring.current(), Some(&texts(&["2"])));
+    }
+
+    #[test]
+    fn distribute_lines() {
+        assert_eq!(distribute(&texts(&["a\nb"]), 2), texts(&["a", "b"]));
+        assert_eq!(
+            distribute(&texts(&["a\nb"]), 3),
+            texts(&["a\nb", "a\nb", "a\nb"])
+        );
+        assert_eq!(distribute(&texts(&["a", "b"]), 2), texts(&["a", "b"]));
+        assert_eq!(distribute(&texts(&["a\nb"]), 1), texts(&["a\nb"]));
+    }
+}
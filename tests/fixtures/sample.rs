@@ -0,0 +1,50 @@
+// a small file used by the snapshot tests.
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct Counter {
+    counts: HashMap<String, usize>,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Self {
+            counts: HashMap::new(),
+        }
+    }
+
+    /// counts each word of the text.
+    pub fn add(&mut self, text: &str) {
+        for word in text.split_whitespace() {
+            *self.counts.entry(word.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn count(&self, word: &str) -> usize {
+        self.counts.get(word).cloned().unwrap_or(0)
+    }
+
+    pub fn most_common(&self) -> Option<(&str, usize)> {
+        self.counts
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(word, count)| (word.as_str(), *count))
+    }
+}
+
+fn main() {
+    let mut counter = Counter::new();
+    counter.add("the quick brown fox jumps over the lazy dog");
+    println!("the: {}", counter.count("the"));
+    println!("fox: {}", counter.count("fox"));
+
+    if let Some((word, count)) = counter.most_common() {
+        println!("most common: {} ({})", word, count);
+    }
+
+    // ligatures: -> => != == <= >=
+    let arrows = ["->", "=>", "!=", "==", "<=", ">="];
+    for arrow in arrows.iter() {
+        println!("{}", arrow);
+    }
+}
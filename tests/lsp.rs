@@ -0,0 +1,130 @@
+// Runs the language server client against the mock server in tests/support/mock-lsp.rs. Run
+// with `cargo test --features test-lsp`.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use kea::lsp::*;
+
+fn start() -> (LspClient, Receiver<LspEvent>) {
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let on_event: EventCallback = Arc::new(move |event| {
+        sender.lock().unwrap().send(event).ok();
+    });
+    let root = std::env::temp_dir();
+    let client = LspClient::start("mock", env!("CARGO_BIN_EXE_mock-lsp"), &[], &root, on_event)
+        .expect("failed to start the mock server");
+    (client, receiver)
+}
+
+fn next(receiver: &Receiver<LspEvent>) -> LspEvent {
+    receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("no event from the mock server")
+}
+
+fn response(receiver: &Receiver<LspEvent>, request: u64) -> Response {
+    loop {
+        if let LspEvent::Response { id, response } = next(receiver) {
+            assert_eq!(id, request);
+            return response;
+        }
+    }
+}
+
+fn range(line: usize, start: usize, end: usize) -> TextRange {
+    TextRange::new(Position::new(line, start), Position::new(line, end))
+}
+
+#[test]
+fn mock_server() {
+    let (mut client, events) = start();
+    let path = std::env::temp_dir().join("kea_lsp_test.txt");
+    let path = Path::new(&path);
+
+    client.did_open(
+        path,
+        "plaintext",
+        "let value = 1;\nvalue += 2;  \n".to_string(),
+    );
+    assert_eq!(
+        next(&events),
+        LspEvent::Diagnostics {
+            path: path.to_path_buf(),
+            diagnostics: Vec::new()
+        }
+    );
+
+    // an incremental change adds an error on the first line.
+    let change = TextChange {
+        range: Some(range(0, 14, 14)),
+        text: " // error".to_string(),
+    };
+    client.did_change(path, vec![change], || unreachable!());
+    match next(&events) {
+        LspEvent::Diagnostics { diagnostics, .. } => {
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].range, range(0, 18, 23));
+            assert_eq!(diagnostics[0].severity, Severity::Error);
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    let id = client.hover(path, Position::new(1, 2));
+    assert_eq!(
+        response(&events, id),
+        Response::Hover(Some("word: value".to_string()))
+    );
+
+    let id = client.references(path, Position::new(0, 6));
+    let locations = vec![
+        Location {
+            path: path.to_path_buf(),
+            range: range(0, 4, 9),
+        },
+        Location {
+            path: path.to_path_buf(),
+            range: range(1, 0, 5),
+        },
+    ];
+    assert_eq!(response(&events, id), Response::Locations(locations));
+
+    let id = client.rename(path, Position::new(1, 0), "total");
+    match response(&events, id) {
+        Response::Edits(files) => {
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].0, PathBuf::from(path));
+            assert_eq!(files[0].1.len(), 2);
+            assert_eq!(files[0].1[1].text, "total");
+        }
+        response => panic!("unexpected response {:?}", response),
+    }
+
+    let id = client.formatting(path, 4, true);
+    let edit = TextEdit {
+        range: range(1, 11, 13),
+        text: String::new(),
+    };
+    assert_eq!(
+        response(&events, id),
+        Response::Edits(vec![(path.to_path_buf(), vec![edit])])
+    );
+
+    let id = client.completion(path, Position::new(1, 0));
+    match response(&events, id) {
+        Response::Completions(items) => {
+            let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+            assert_eq!(labels, vec!["let", "value", "1", "error", "2"]);
+            assert_eq!(items[1].text, "value");
+            // the word at the position is replaced.
+            assert_eq!(items[1].range, Some(range(1, 0, 5)));
+        }
+        response => panic!("unexpected response {:?}", response),
+    }
+
+    drop(client);
+    assert_eq!(next(&events), LspEvent::Exited("mock".to_string()));
+}
@@ -0,0 +1,271 @@
+// Mock Language Server.
+// A small language server for testing the client. It keeps the text of open documents, applying
+// incremental changes, and publishes an error for each line containing "error". Its answers are
+// derived from the word at the requested position:
+//  hover:      `word: <word>`
+//  definition: the first occurrence of the word in the document
+//  references: every occurrence of the word in the document
+//  rename:     edits replacing every occurrence of the word
+//  formatting: an edit removing the trailing whitespace of each line
+//  completion: the distinct words of the document, in order, each replacing the word at the
+//              position
+
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter};
+
+use kea::lsp::rpc;
+use serde_json::{json, Value};
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut writer = BufWriter::new(stdout.lock());
+    let mut server = Server::default();
+
+    while let Ok(Some(message)) = rpc::read_message(&mut reader) {
+        let method = message["method"].as_str().unwrap_or("");
+        if method == "exit" {
+            break;
+        }
+        for reply in server.handle(method, &message) {
+            if rpc::write_message(&mut writer, &reply).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    fn handle(&mut self, method: &str, message: &Value) -> Vec<Value> {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 2,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": true,
+                    "documentFormattingProvider": true,
+                    "completionProvider": {},
+                }
+            }),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.documents.insert(uri.clone(), text.to_string());
+                return vec![self.diagnostics(&uri)];
+            }
+            "textDocument/didChange" => {
+                let text = self.documents.entry(uri.clone()).or_default();
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    apply_change(text, change);
+                }
+                return vec![self.diagnostics(&uri)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return Vec::new();
+            }
+            "textDocument/hover" => match self.word(&uri, &params["position"]) {
+                Some(word) => json!({ "contents": format!("word: {}", word) }),
+                None => Value::Null,
+            },
+            "textDocument/definition" => {
+                let ranges = self.occurrences(&uri, &params["position"]);
+                match ranges.first() {
+                    Some(range) => json!({ "uri": uri, "range": range }),
+                    None => Value::Null,
+                }
+            }
+            "textDocument/references" => {
+                let ranges = self.occurrences(&uri, &params["position"]);
+                let locations: Vec<Value> = ranges
+                    .into_iter()
+                    .map(|range| json!({ "uri": uri, "range": range }))
+                    .collect();
+                json!(locations)
+            }
+            "textDocument/rename" => {
+                let new_name = params["newName"].as_str().unwrap_or("");
+                let edits: Vec<Value> = self
+                    .occurrences(&uri, &params["position"])
+                    .into_iter()
+                    .map(|range| json!({ "range": range, "newText": new_name }))
+                    .collect();
+                json!({ "changes": { uri: edits } })
+            }
+            "textDocument/formatting" => json!(self.trailing_whitespace(&uri)),
+            "textDocument/completion" => json!(self.words(&uri, &params["position"])),
+            "shutdown" => Value::Null,
+            _ => return Vec::new(),
+        };
+        match message.get("id") {
+            Some(id) => vec![rpc::response(id.clone(), result)],
+            None => Vec::new(),
+        }
+    }
+
+    fn diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let diagnostics: Vec<Value> = text
+            .split('\n')
+            .enumerate()
+            .filter_map(|(line, text)| {
+                let start = utf16_len(&text[..text.find("error")?]);
+                Some(json!({
+                    "range": range(line, start, line, start + "error".len()),
+                    "severity": 1,
+                    "source": "mock",
+                    "message": format!("error on line {}", line + 1),
+                }))
+            })
+            .collect();
+        rpc::notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    /// the word at a position.
+    fn word(&self, uri: &str, position: &Value) -> Option<String> {
+        self.word_at(uri, position).map(|(word, _)| word)
+    }
+
+    /// the word at a position and its range.
+    fn word_at(&self, uri: &str, position: &Value) -> Option<(String, Value)> {
+        let text = self.documents.get(uri)?;
+        let line_idx = position["line"].as_u64()? as usize;
+        let line = text.split('\n').nth(line_idx)?;
+        let column = byte_offset(line, position["character"].as_u64()? as usize);
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let start = line[..column]
+            .rfind(|c: char| !is_word(c))
+            .map_or(0, |i| i + 1);
+        let end = line[column..]
+            .find(|c: char| !is_word(c))
+            .map_or(line.len(), |i| column + i);
+        if start == end {
+            None
+        } else {
+            let range = range(
+                line_idx,
+                utf16_len(&line[..start]),
+                line_idx,
+                utf16_len(&line[..end]),
+            );
+            Some((line[start..end].to_string(), range))
+        }
+    }
+
+    /// the ranges of the whole word at a position in its document.
+    fn occurrences(&self, uri: &str, position: &Value) -> Vec<Value> {
+        let word = match self.word(uri, position) {
+            Some(word) => word,
+            None => return Vec::new(),
+        };
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let mut ranges = Vec::new();
+        for (line, text) in text.split('\n').enumerate() {
+            for (start, _) in text.match_indices(word.as_str()) {
+                let end = start + word.len();
+                let bounded = |c: Option<char>| !matches!(c, Some(c) if c.is_alphanumeric());
+                if bounded(text[..start].chars().last()) && bounded(text[end..].chars().next()) {
+                    let start = utf16_len(&text[..start]);
+                    ranges.push(range(line, start, line, start + utf16_len(&word)));
+                }
+            }
+        }
+        ranges
+    }
+
+    fn words(&self, uri: &str, position: &Value) -> Vec<Value> {
+        let replaced = self.word_at(uri, position).map(|(_, range)| range);
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let mut words: Vec<&str> = Vec::new();
+        for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+            if !word.is_empty() && !words.contains(&word) {
+                words.push(word);
+            }
+        }
+        words
+            .into_iter()
+            .map(|word| match replaced.as_ref() {
+                Some(range) => json!({
+                    "label": word,
+                    "detail": "mock",
+                    "textEdit": { "range": range, "newText": word },
+                }),
+                None => json!({ "label": word, "detail": "mock" }),
+            })
+            .collect()
+    }
+
+    fn trailing_whitespace(&self, uri: &str) -> Vec<Value> {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        text.split('\n')
+            .enumerate()
+            .filter_map(|(line, text)| {
+                let trimmed = utf16_len(text.trim_end());
+                let length = utf16_len(text);
+                if trimmed == length {
+                    return None;
+                }
+                Some(json!({ "range": range(line, trimmed, line, length), "newText": "" }))
+            })
+            .collect()
+    }
+}
+
+fn apply_change(text: &mut String, change: &Value) {
+    let new_text = change["text"].as_str().unwrap_or("");
+    if change.get("range").is_none() {
+        *text = new_text.to_string();
+        return;
+    }
+    let offset = |position: &Value| {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let line_start: usize = text.split('\n').take(line).map(|l| l.len() + 1).sum();
+        let line_text = text[line_start.min(text.len())..]
+            .split('\n')
+            .next()
+            .unwrap_or("");
+        line_start + byte_offset(line_text, character)
+    };
+    let start = offset(&change["range"]["start"]);
+    let end = offset(&change["range"]["end"]);
+    text.replace_range(start..end, new_text);
+}
+
+fn range(start_line: usize, start: usize, end_line: usize, end: usize) -> Value {
+    json!({
+        "start": { "line": start_line, "character": start },
+        "end": { "line": end_line, "character": end },
+    })
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+/// the byte offset of a utf-16 column in a line.
+fn byte_offset(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= character {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}